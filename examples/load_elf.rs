@@ -5,12 +5,9 @@
 // copied, modified, or distributed except according to those terms.
 
 
-extern crate byteorder;
-extern crate elf;
-use std::path::PathBuf;
-
 extern crate rbpf;
 use rbpf::helpers;
+use rbpf::loader::Object;
 
 // The following example uses an ELF file that has been compiled from the C program available in
 // `load_elf__block_a_port.c` in the same directory.
@@ -48,25 +45,24 @@ use rbpf::helpers;
 // that you can retrieve the raw bytecode with `readelf -x .classifier load_elf__block_a_port.o` or
 // with `objdump -s -j .classifier load_elf__block_a_port.o`.
 //
-// Once the bytecode has been edited, we can load the bytecode directly from the ELF object file.
+// Once the bytecode has been edited, we can load the program directly from the ELF object file,
+// with the loader of rbpf.
 
 fn main() {
 
     let filename = "examples/load_elf__block_a_port.o";
 
-    let path = PathBuf::from(filename);
-    let file = match elf::File::open_path(&path) {
-        Ok(f) => f,
-        Err(e) => panic!("Error: {:?}", e),
+    let file = std::fs::read(filename).unwrap();
+    let object = match Object::parse(&file) {
+        Ok(o) => o,
+        Err(e) => panic!("{}", e),
     };
 
-    let text_scn = match file.get_section(".classifier") {
-        Some(s) => s,
+    let prog = match object.program(".classifier") {
+        Some(p) => p.code(),
         None => panic!("Failed to look up .classifier section"),
     };
 
-    let prog = &text_scn.data;
-
     let packet1 = &mut [
         0x01, 0x23, 0x45, 0x67, 0x89, 0xab,
         0xfe, 0xdc, 0xba, 0x98, 0x76, 0x54,
@@ -89,7 +85,7 @@ fn main() {
         0x64, 0x66, 0x0au8
     ];
 
-    let mut packet2 = &mut [
+    let packet2 = &mut [
         0x01, 0x23, 0x45, 0x67, 0x89, 0xab,
         0xfe, 0xdc, 0xba, 0x98, 0x76, 0x54,
         0x08, 0x00, // ethertype
@@ -111,16 +107,16 @@ fn main() {
         0x64, 0x66, 0x0au8
     ];
 
-    let mut vm = rbpf::EbpfVmFixedMbuff::new(prog, 0x40, 0x50);
+    let mut vm = rbpf::EbpfVmFixedMbuff::new(prog, 0x40, 0x50).unwrap();
     vm.register_helper(helpers::BPF_TRACE_PRINTK_IDX, helpers::bpf_trace_printf);
 
-    let res = vm.prog_exec(packet1);
+    let res = vm.prog_exec(packet1).unwrap();
     println!("Packet #1, program returned: {:?} ({:#x})", res, res);
     assert_eq!(res, 0xffffffff);
 
-    vm.jit_compile();
+    vm.jit_compile().unwrap();
     unsafe {
-        let res = vm.prog_exec_jit(packet2);
+        let res = vm.prog_exec_jit(packet2).unwrap();
         println!("Packet #2, program returned: {:?} ({:#x})", res, res);
         assert_eq!(res, 0);
     }
@@ -75,5 +75,5 @@ fn main() {
 
     let prog = &text_scn.data;
 
-    println!("{}", to_json(&prog));
+    println!("{}", to_json(prog));
 }
@@ -0,0 +1,1030 @@
+// Copyright 2026 agent <agent@local>
+//
+// Licensed under the Apache License, Version 2.0 <http://www.apache.org/licenses/LICENSE-2.0> or
+// the MIT license <http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+
+//! This module tracks the type of the values held by the registers of an eBPF program, by
+//! abstract interpretation, for the verifier.
+//!
+//! Each path of the program is followed from the entry point, as the verifier of the Linux kernel
+//! does. At each instruction, every register is known to be uninitialized, a number (a scalar),
+//! or a pointer to one of the memory regions available to the program: the context passed in
+//! `r1`, the packet data, the stack, or the value of a map element. Values stored on the stack
+//! with 8-byte aligned stores keep their type, so that pointers can be spilled and filled back.
+//!
+//! The analysis rejects reads of uninitialized registers, arithmetic operations that would forge
+//! pointers (or turn them into numbers that could be turned back into pointers), and memory
+//! accesses through registers that do not hold a pointer.
+//!
+//! The maps the program can use are described by `MapDef`s. Pointers to map values can only be
+//! moved by known amounts, and accesses through them must stay within the value. The map helpers
+//! must get a map handle in `r1`, and pointers to initialized stack bytes holding the key in `r2`
+//! and, for updates, the value in `r3`.
+//!
+//! Accesses to the stack must stay within the `ebpf::STACK_SIZE` bytes below `r10` of the call
+//! frame they point to, and can only read bytes that have been written before on the path. The
+//! analysis also computes the maximum number of bytes of stack used by the program, over all the
+//! frames that can be active at the same time.
+//!
+//! With `Context::FixedMbuff`, the pointers to the start and to the end of packet data are loaded
+//! from the metadata buffer, as in the kernel. The program must compare packet pointers with the
+//! end of packet data before accessing the packet: for instance, if `r2` is `data + 14` and `r3` is
+//! `data_end`, the 14 first bytes of the packet can be read on the path where `r2 <= r3`. Accesses
+//! to packet data, and to the metadata buffer itself, that cannot be proven to be in bounds are
+//! rejected, so that they cannot fail at run time.
+//!
+//! The analysis can record its state before each instruction it processes into a `Log`, to
+//! understand why a program is rejected.
+//!
+//! The program is expected to have passed `verifier::check_cfg()`, so that all paths end. Use
+//! `verifier::check_types()` to run both.
+
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+
+use cfg::Cfg;
+use disassembler;
+use ebpf;
+use error::EbpfError;
+use maps;
+
+/// Maximum number of instructions processed by the analysis, counted over all paths, before the
+/// program is rejected as too complex. Each lookup of a state among the states already explored at
+/// the start of a basic block also counts as one instruction.
+pub const MAX_PROCESSED_INSNS: usize = 1_000_000;
+
+// Maximum number of states stored for the start of each basic block. Paths reaching the block with
+// one of these states are not explored again; other paths are explored in full.
+const MAX_STATES_PER_INSN: usize = 64;
+
+// Number of 8-byte slots in the stack of a frame.
+const STACK_SLOTS: usize = ebpf::STACK_SIZE / 8;
+
+/// What the program receives in `r1`, depending on the VM it runs in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Context {
+    /// No data, for `EbpfVmNoData`: `r1` holds 0.
+    NoData,
+    /// `r1` points to a memory area with no known layout: the packet data for `EbpfVmRaw`, or the
+    /// metadata buffer for `EbpfVmMbuff`.
+    Memory,
+    /// `r1` points to the metadata buffer of `EbpfVmFixedMbuff`, which holds the addresses of the
+    /// start and of the end of packet data at the given offsets (`data_offset`,
+    /// `data_end_offset`).
+    FixedMbuff(usize, usize),
+}
+
+/// Description of a map that the program can use, as registered into the VM with
+/// `register_map()`: its file descriptor number, and the size of its keys and values.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MapDef {
+    /// File descriptor number the program uses to load the map.
+    pub fd:         u32,
+    /// Size of the keys of the map, in bytes.
+    pub key_size:   usize,
+    /// Size of the values of the map, in bytes.
+    pub value_size: usize,
+}
+
+/// Type of a value held by a register, or stored on the stack.
+///
+/// Offsets are in bytes, relative to the start of the region for the context, the packet data and
+/// the map values, and relative to `r10` for the stack. They are `None` when the analysis cannot
+/// tell their value.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum RegType {
+    /// Not initialized, cannot be read.
+    Uninit,
+    /// A number, with its value if it is known.
+    Scalar(Option<u64>),
+    /// Pointer into the context (`offset`).
+    Ctx(Option<i64>),
+    /// Pointer into packet data (`offset`). Only the bytes that have been checked against the end
+    /// of packet data can be accessed, and only through pointers with a known offset.
+    Packet(Option<i64>),
+    /// Pointer to the end of packet data. It cannot be dereferenced or moved.
+    PacketEnd,
+    /// Pointer into the stack of a call frame (`frame`, `offset`). The frame of the main program
+    /// is 0, and each local call adds one.
+    Stack(usize, Option<i64>),
+    /// Handle to the map with file descriptor `fd`, loaded with `LD_DW_IMM` (`fd`).
+    MapPtr(u32),
+    /// Pointer into the value of an element of a map (`fd`, `value_size`, `offset`). Only the
+    /// `value_size` bytes of the value can be accessed, and only through pointers with a known
+    /// offset.
+    MapValue(u32, usize, Option<i64>),
+    /// Result of a lookup in a map, either a pointer to the value of an element or 0 (`fd`,
+    /// `value_size`, `id`). It must be compared to 0 before use: the check turns all the copies of
+    /// the result, sharing the same `id`, into `MapValue` or `Scalar(Some(0))`.
+    MapValueOrNull(u32, usize, u32),
+}
+
+impl RegType {
+    /// Return `true` if the value is a pointer, or may be one.
+    pub fn is_pointer(&self) -> bool {
+        !matches!(*self, RegType::Uninit | RegType::Scalar(_))
+    }
+
+    // Move a pointer by `delta` bytes, if this type of pointer can be moved.
+    fn offset(self, delta: Option<i64>) -> Option<RegType> {
+        let add = |off: Option<i64>| match (off, delta) {
+            (Some(off), Some(delta)) => Some(off.wrapping_add(delta)),
+            _                        => None,
+        };
+        match self {
+            RegType::Ctx(off)           => Some(RegType::Ctx(add(off))),
+            RegType::Packet(off)        => Some(RegType::Packet(add(off))),
+            RegType::Stack(frame, off)  => Some(RegType::Stack(frame, add(off))),
+            // As in the kernel, pointers to map values cannot be moved by unknown amounts.
+            RegType::MapValue(fd, size, off) =>
+                add(off).map(|off| RegType::MapValue(fd, size, Some(off))),
+            _                           => None,
+        }
+    }
+
+    // Distance in bytes between two pointers into the same region, if they can be subtracted.
+    fn distance(self, other: RegType) -> Option<Option<i64>> {
+        let sub = |a: Option<i64>, b: Option<i64>| match (a, b) {
+            (Some(a), Some(b)) => Some(a.wrapping_sub(b)),
+            _                  => None,
+        };
+        match (self, other) {
+            (RegType::Ctx(a), RegType::Ctx(b))                     |
+            (RegType::Packet(a), RegType::Packet(b))               => Some(sub(a, b)),
+            (RegType::PacketEnd, RegType::Packet(_))               => Some(None),
+            (RegType::Stack(f, a), RegType::Stack(g, b)) if f == g => Some(sub(a, b)),
+            (RegType::MapValue(m, _, a), RegType::MapValue(n, _, b)) if m == n => Some(sub(a, b)),
+            _                                                      => None,
+        }
+    }
+}
+
+impl fmt::Display for RegType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let off = |off: Option<i64>| match off {
+            Some(off) => format!("{:+}", off),
+            None      => "+?".to_string(),
+        };
+        match *self {
+            RegType::Uninit                  => write!(f, "uninit"),
+            RegType::Scalar(Some(value))     => write!(f, "{:#x}", value),
+            RegType::Scalar(None)            => write!(f, "scalar"),
+            RegType::Ctx(o)                  => write!(f, "ctx{}", off(o)),
+            RegType::Packet(o)               => write!(f, "pkt{}", off(o)),
+            RegType::PacketEnd               => write!(f, "pkt_end"),
+            RegType::Stack(frame, o)         => write!(f, "fp{}{}", frame, off(o)),
+            RegType::MapPtr(fd)              => write!(f, "map_ptr(fd={})", fd),
+            RegType::MapValue(fd, size, o)   =>
+                write!(f, "map_value(fd={},size={}){}", fd, size, off(o)),
+            RegType::MapValueOrNull(fd, size, id) =>
+                write!(f, "map_value_or_null(fd={},size={},id={})", fd, size, id),
+        }
+    }
+}
+
+/// State of the analysis before it processes an instruction, as recorded in a `Log`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LogEntry {
+    /// Index of the instruction, 0 being the first instruction of the program.
+    pub insn_ptr:     usize,
+    /// The instruction, as disassembled by `disassembler::to_insn_vec()`.
+    pub desc:         String,
+    /// Types of registers r0 to r10.
+    pub regs:         [RegType; 11],
+    /// Call frame of the function the instruction belongs to, 0 for the main program.
+    pub frame:        usize,
+    /// Types of the values stored on the stack of the current frame, as pairs of offset from `r10`
+    /// and type, for each 8-byte slot that has been written, starting from the top of the stack.
+    pub stack:        Vec<(i64, RegType)>,
+    /// Number of bytes at the start of packet data known to be in the packet.
+    pub packet_range: u64,
+    /// Index in the log of the entry for the previous instruction on the same path, or `None` for
+    /// the first instruction of the program.
+    pub parent:       Option<usize>,
+}
+
+impl LogEntry {
+    fn new(insn_ptr: usize, desc: &str, state: &State, parent: Option<usize>) -> LogEntry {
+        let frame = state.frames.len() - 1;
+        let stack = state.frames[frame].slots.iter().enumerate()
+            .filter(|&(_, value)| *value != RegType::Uninit)
+            .map(|(slot, value)| (-8 * (slot as i64 + 1), *value))
+            .collect();
+        LogEntry {
+            insn_ptr,
+            desc: desc.to_string(),
+            regs: state.regs,
+            frame,
+            stack,
+            packet_range: state.packet_range,
+            parent,
+        }
+    }
+}
+
+/// Log of the analysis of a program, similar to the log of the verifier of the Linux kernel. See
+/// `analyze_with_log()`.
+///
+/// Paths are explored one after the other, so consecutive entries do not always belong to the same
+/// path: the `parent` field of each entry links it to the previous instruction on its path. The
+/// text rendering of the log, obtained with `to_string()`, shows each instruction with the types
+/// of the initialized registers and stack slots before it is processed, then the error if any, and
+/// the path that led to it, or the stack depth of the program if it is accepted and uses the
+/// stack.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Log {
+    /// One entry for each processed instruction, in the order of processing.
+    pub entries:     Vec<LogEntry>,
+    /// The problem found in the program, if any. It was found at the instruction of the last entry,
+    /// unless the program was rejected before the analysis.
+    pub error:       Option<EbpfError>,
+    /// The maximum number of bytes of stack used by the program if it is accepted (see
+    /// `stack_depth()`), or 0.
+    pub stack_depth: usize,
+}
+
+impl Log {
+    /// Return the indices of the instructions on the path from the entry point of the program to
+    /// the instruction where the error was found, or an empty vector if there is no error.
+    pub fn error_path(&self) -> Vec<usize> {
+        let mut path = vec![];
+        if self.error.is_some() {
+            let mut entry = self.entries.len().checked_sub(1);
+            while let Some(index) = entry {
+                path.push(self.entries[index].insn_ptr);
+                entry = self.entries[index].parent;
+            }
+            path.reverse();
+        }
+        path
+    }
+}
+
+impl fmt::Display for Log {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (index, entry) in self.entries.iter().enumerate() {
+            // Say where the analysis resumes when it starts over with another path.
+            if let Some(parent) = entry.parent.filter(|&parent| parent + 1 != index) {
+                writeln!(f, "from {} to {}:", self.entries[parent].insn_ptr, entry.insn_ptr)?;
+            }
+            let mut state = entry.regs.iter().enumerate()
+                .filter(|&(_, value)| *value != RegType::Uninit)
+                .map(|(reg, value)| format!("r{}={}", reg, value))
+                .collect::<Vec<String>>();
+            for &(off, value) in &entry.stack {
+                state.push(format!("fp{}{}={}", entry.frame, off, value));
+            }
+            if entry.packet_range > 0 {
+                state.push(format!("pkt_range={}", entry.packet_range));
+            }
+            writeln!(f, "{}: {}", entry.insn_ptr, entry.desc)?;
+            writeln!(f, "    {}", state.join(" "))?;
+        }
+        if let Some(ref error) = self.error {
+            writeln!(f, "{}", error)?;
+            let path = self.error_path().iter().map(|insn_ptr| insn_ptr.to_string())
+                .collect::<Vec<String>>();
+            if !path.is_empty() {
+                writeln!(f, "path: {}", path.join(" -> "))?;
+            }
+        }
+        writeln!(f, "processed {} insns", self.entries.len())?;
+        if self.stack_depth > 0 {
+            writeln!(f, "stack depth {}", self.stack_depth)?;
+        }
+        Ok(())
+    }
+}
+
+// Disassemble a program, with one description per instruction slot. The second half of
+// `LD_DW_IMM` instructions gets an empty description.
+fn disassemble(prog: &[u8]) -> Vec<String> {
+    let mut descs = vec![];
+    for hl_insn in disassembler::to_insn_vec(prog) {
+        let is_lddw = hl_insn.opc == ebpf::LD_DW_IMM;
+        descs.push(hl_insn.desc);
+        if is_lddw {
+            descs.push(String::new());
+        }
+    }
+    descs
+}
+
+// A call frame.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct Frame {
+    // Index of the first instruction of the function.
+    func:    usize,
+    // Types of the 8-byte slots of the stack, slot 0 being right below `r10`.
+    slots:   Vec<RegType>,
+    // For each slot, one bit per byte that has been written, the lowest bit being the lowest byte.
+    written: Vec<u8>,
+    // For local functions, the instruction to return to, and r6 to r9 of the caller.
+    ret:     Option<(usize, [RegType; 4])>,
+}
+
+impl Frame {
+    fn new(func: usize, ret: Option<(usize, [RegType; 4])>) -> Frame {
+        Frame {
+            func,
+            slots: vec![RegType::Uninit; STACK_SLOTS],
+            written: vec![0; STACK_SLOTS],
+            ret,
+        }
+    }
+
+    // Index of the slot holding the byte at `off` from `r10`, if it is in the stack.
+    fn slot(off: i64) -> Option<usize> {
+        if off < 0 && off >= -(ebpf::STACK_SIZE as i64) {
+            Some(((-off - 1) / 8) as usize)
+        } else {
+            None
+        }
+    }
+
+    // Bit of the byte at `off` from `r10` in the mask of its slot.
+    fn byte_mask(off: i64) -> u8 {
+        1 << off.rem_euclid(8)
+    }
+
+    // Type of the value loaded from the stack, or `None` if some of the bytes read have not been
+    // written. The access must be within the stack.
+    fn load(&self, off: i64, size: usize) -> Option<RegType> {
+        let written = (off..off + size as i64).all(|byte| {
+            Frame::slot(byte).map_or(false, |slot| self.written[slot] & Frame::byte_mask(byte) != 0)
+        });
+        if !written {
+            return None;
+        }
+        match (size, off % 8, Frame::slot(off)) {
+            (8, 0, Some(slot)) => Some(self.slots[slot]),
+            _                  => Some(RegType::Scalar(None)),
+        }
+    }
+
+    // Store a value on the stack. The access must be within the stack.
+    fn store(&mut self, off: i64, size: usize, value: RegType) {
+        for byte in off..off + size as i64 {
+            if let Some(slot) = Frame::slot(byte) {
+                self.written[slot] |= Frame::byte_mask(byte);
+                // Partial writes leave a number in the slots they touch.
+                self.slots[slot] = RegType::Scalar(None);
+            }
+        }
+        if let (8, 0, Some(slot)) = (size, off % 8, Frame::slot(off)) {
+            self.slots[slot] = value;
+        }
+    }
+
+    // Forget the types of the values on the stack, after a helper may have written anything into
+    // it: all the bytes hold numbers.
+    fn clobber(&mut self) {
+        for slot in 0..STACK_SLOTS {
+            self.slots[slot] = RegType::Scalar(None);
+            self.written[slot] = 0xff;
+        }
+    }
+}
+
+// The abstract state of the program at a given instruction.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct State {
+    regs:         [RegType; 11],
+    frames:       Vec<Frame>,
+    // Identifier for the result of the next map lookup.
+    next_id:      u32,
+    // Number of bytes at the start of packet data that are known to be in the packet, after
+    // comparisons of packet pointers with the end of packet data.
+    packet_range: u64,
+}
+
+impl State {
+    fn new(ctx: Context) -> State {
+        let mut regs = [RegType::Uninit; 11];
+        regs[1] = match ctx {
+            Context::NoData => RegType::Scalar(Some(0)),
+            _               => RegType::Ctx(Some(0)),
+        };
+        regs[10] = RegType::Stack(0, Some(0));
+        State { regs, frames: vec![Frame::new(0, None)], next_id: 0, packet_range: 0 }
+    }
+
+    fn read(&self, reg: u8, insn_ptr: usize) -> Result<RegType, EbpfError> {
+        match self.regs[reg as usize] {
+            RegType::Uninit => Err(EbpfError::UninitializedRegister(reg, insn_ptr)),
+            value           => Ok(value),
+        }
+    }
+
+    // Apply `f` to all the values held by the registers and by the stack slots of all frames.
+    fn update_all<F: Fn(RegType) -> RegType>(&mut self, f: F) {
+        for value in self.regs.iter_mut()
+            .chain(self.frames.iter_mut().flat_map(|frame| frame.slots.iter_mut())) {
+            *value = f(*value);
+        }
+    }
+
+    // Resolve the result of a map lookup, after it has been compared to 0.
+    fn resolve_lookup(&mut self, id: u32, null: bool) {
+        self.update_all(|value| match value {
+            RegType::MapValueOrNull(_, _, i) if i == id && null => RegType::Scalar(Some(0)),
+            RegType::MapValueOrNull(fd, size, i) if i == id     =>
+                RegType::MapValue(fd, size, Some(0)),
+            value                                               => value,
+        });
+    }
+}
+
+// Use of the stack by the program, collected over all paths.
+struct StackUsage {
+    // Number of bytes below `r10` used by each function, by index of its first instruction.
+    depths: HashMap<usize, usize>,
+    // Functions found on the call stack at the same time, from the main program.
+    chains: HashSet<Vec<usize>>,
+}
+
+impl StackUsage {
+    fn new() -> StackUsage {
+        let mut chains = HashSet::new();
+        chains.insert(vec![0]);
+        StackUsage { depths: HashMap::new(), chains }
+    }
+
+    // Record an access at `off` from `r10` in the stack of function `func`.
+    fn access(&mut self, func: usize, off: i64) {
+        let depth = self.depths.entry(func).or_default();
+        *depth = (*depth).max(off.unsigned_abs() as usize);
+    }
+
+    // Maximum number of bytes used by the frames active at the same time.
+    fn max_depth(&self) -> usize {
+        self.chains.iter()
+            .map(|chain| chain.iter().map(|func| self.depths.get(func).cloned().unwrap_or(0)).sum())
+            .max()
+            .unwrap_or(0)
+    }
+}
+
+// The memory region accessed by a load or a store, with the offset of the access in the context
+// or in the stack.
+enum Region {
+    Ctx(i64),
+    Packet,
+    Stack(usize, i64),
+    MapValue,
+}
+
+// Where to go on after an instruction.
+enum Outcome {
+    Next(usize),
+    // Go on with the next instruction, and with the target of a jump in another state.
+    Branch(usize, usize, Box<State>),
+    Exit,
+}
+
+fn access_size(opc: u8) -> usize {
+    match opc & 0x18 {
+        ebpf::BPF_B  => 1,
+        ebpf::BPF_H  => 2,
+        ebpf::BPF_W  => 4,
+        _            => 8,
+    }
+}
+
+// Region accessed through register `reg` with offset `off`, for `size` bytes. Accesses to the
+// stack, to the context of `EbpfVmFixedMbuff`, to packet data and to map values must be within
+// their bounds.
+fn region(state: &State, ctx: Context, reg: u8, off: i16, size: usize, insn_ptr: usize)
+          -> Result<Region, EbpfError> {
+    let in_bounds = |off: i64, len: i64| off >= 0 && off.saturating_add(size as i64) <= len;
+    let off = off as i64;
+    match state.read(reg, insn_ptr)? {
+        RegType::Ctx(Some(base))          => {
+            let off = base.wrapping_add(off);
+            match ctx {
+                Context::FixedMbuff(data, data_end)
+                    if !in_bounds(off, data.max(data_end) as i64 + 8) =>
+                    Err(EbpfError::OutOfBoundsAccess(reg, insn_ptr)),
+                _ => Ok(Region::Ctx(off)),
+            }
+        },
+        RegType::Packet(base)             => match base {
+            Some(base) if in_bounds(base.wrapping_add(off), state.packet_range as i64) =>
+                Ok(Region::Packet),
+            _ => Err(EbpfError::OutOfBoundsAccess(reg, insn_ptr)),
+        },
+        RegType::Stack(frame, Some(base)) => {
+            let off = base.wrapping_add(off);
+            let len = ebpf::STACK_SIZE as i64;
+            if !in_bounds(off.wrapping_add(len), len) {
+                return Err(EbpfError::OutOfBoundsAccess(reg, insn_ptr));
+            }
+            Ok(Region::Stack(frame, off))
+        },
+        RegType::MapValue(_, len, base)   => match base {
+            Some(base) if in_bounds(base.wrapping_add(off), len as i64) => Ok(Region::MapValue),
+            _ => Err(EbpfError::OutOfBoundsAccess(reg, insn_ptr)),
+        },
+        _                                 => Err(EbpfError::InvalidMemoryAccess(reg, insn_ptr)),
+    }
+}
+
+// Number of bytes at the start of packet data known to be in the packet after a comparison
+// between a packet pointer and the end of packet data: if the jump is taken, and if it is not.
+fn packet_bounds(opc: u8, dst: RegType, src: RegType) -> (u64, u64) {
+    // Put the packet pointer on the left: `data_end > ptr` is `ptr < data_end`.
+    let (off, opc) = match (dst, src) {
+        (RegType::Packet(Some(off)), RegType::PacketEnd) => (off, opc),
+        (RegType::PacketEnd, RegType::Packet(Some(off))) => (off, match opc {
+            ebpf::JGT_REG => ebpf::JLT_REG,
+            ebpf::JGE_REG => ebpf::JLE_REG,
+            ebpf::JLT_REG => ebpf::JGT_REG,
+            ebpf::JLE_REG => ebpf::JGE_REG,
+            opc           => opc,
+        }),
+        _ => return (0, 0),
+    };
+    // `data + off <= data_end` proves that `off` bytes are in the packet, `<` proves one more.
+    let le = off.max(0) as u64;
+    let lt = off.saturating_add(1).max(0) as u64;
+    match opc {
+        ebpf::JGT_REG                 => (0, le),
+        ebpf::JGE_REG                 => (0, lt),
+        ebpf::JLT_REG                 => (lt, 0),
+        ebpf::JLE_REG | ebpf::JEQ_REG => (le, 0),
+        ebpf::JNE_REG                 => (0, le),
+        _                             => (0, 0),
+    }
+}
+
+// Fold the operations on known 64-bit numbers that are useful to compute offsets.
+fn fold(op: u8, dst: Option<u64>, src: Option<u64>) -> Option<u64> {
+    let (dst, src) = match (dst, src) {
+        (Some(dst), Some(src)) => (dst, src),
+        _                      => return None,
+    };
+    match op {
+        ebpf::BPF_ADD => Some(dst.wrapping_add(src)),
+        ebpf::BPF_SUB => Some(dst.wrapping_sub(src)),
+        ebpf::BPF_MUL => Some(dst.wrapping_mul(src)),
+        ebpf::BPF_OR  => Some(dst | src),
+        ebpf::BPF_AND => Some(dst & src),
+        ebpf::BPF_XOR => Some(dst ^ src),
+        ebpf::BPF_NEG => Some((dst as i64).wrapping_neg() as u64),
+        _             => None,
+    }
+}
+
+fn alu(state: &mut State, insn: &ebpf::Insn, insn_ptr: usize) -> Result<(), EbpfError> {
+    let is64 = insn.opc & ebpf::BPF_CLS_MASK == ebpf::BPF_ALU64;
+    let op = insn.opc & ebpf::BPF_ALU_OP_MASK;
+    let dst = insn.dst as usize;
+
+    // Byte swaps and negations have no source operand.
+    if op == ebpf::BPF_END || op == ebpf::BPF_NEG {
+        state.regs[dst] = match state.read(insn.dst, insn_ptr)? {
+            RegType::Scalar(value) if is64 => RegType::Scalar(fold(op, value, Some(0))),
+            RegType::Scalar(_)             => RegType::Scalar(None),
+            _                              => return Err(EbpfError::PointerArithmetic(insn_ptr)),
+        };
+        return Ok(());
+    }
+
+    let src_is_reg = insn.opc & ebpf::BPF_X != 0;
+    let src = if src_is_reg {
+        state.read(insn.src, insn_ptr)?
+    } else {
+        RegType::Scalar(Some(insn.imm as u64))
+    };
+
+    if op == ebpf::BPF_MOV {
+        state.regs[dst] = match (is64, src) {
+            (true, src)                                     => src,
+            (false, RegType::Scalar(Some(v))) if src_is_reg =>
+                RegType::Scalar(Some(v as u32 as u64)),
+            (false, RegType::Scalar(_))                     => RegType::Scalar(None),
+            (false, _)                                      =>
+                return Err(EbpfError::PointerArithmetic(insn_ptr)),
+        };
+        return Ok(());
+    }
+
+    let delta = |value: Option<u64>| match op {
+        ebpf::BPF_SUB => value.map(|v| (v as i64).wrapping_neg()),
+        _             => value.map(|v| v as i64),
+    };
+    let result = match (state.read(insn.dst, insn_ptr)?, src) {
+        (RegType::Scalar(a), RegType::Scalar(b)) if is64   => Some(RegType::Scalar(fold(op, a, b))),
+        (RegType::Scalar(_), RegType::Scalar(_))           => Some(RegType::Scalar(None)),
+        _ if !is64                                         => None,
+        (ptr, RegType::Scalar(b)) if op == ebpf::BPF_ADD ||
+                                     op == ebpf::BPF_SUB   => ptr.offset(delta(b)),
+        (RegType::Scalar(a), ptr) if op == ebpf::BPF_ADD   => ptr.offset(delta(a)),
+        (a, b) if op == ebpf::BPF_SUB                      =>
+            a.distance(b).map(|dist| RegType::Scalar(dist.map(|d| d as u64))),
+        _                                                  => None,
+    };
+    match result {
+        Some(result) => {
+            state.regs[dst] = result;
+            Ok(())
+        },
+        None         => Err(EbpfError::PointerArithmetic(insn_ptr)),
+    }
+}
+
+fn load(state: &mut State, ctx: Context, usage: &mut StackUsage, insn: &ebpf::Insn,
+        insn_ptr: usize) -> Result<(), EbpfError> {
+    let size = access_size(insn.opc);
+    let value = match region(state, ctx, insn.src, insn.off, size, insn_ptr)? {
+        Region::Ctx(off) => match ctx {
+            Context::FixedMbuff(data, _) if size == 8 && off == data as i64         =>
+                RegType::Packet(Some(0)),
+            Context::FixedMbuff(_, data_end) if size == 8 && off == data_end as i64 =>
+                RegType::PacketEnd,
+            _                                                                      =>
+                RegType::Scalar(None),
+        },
+        Region::Stack(frame, off) => {
+            let frame = &state.frames[frame];
+            usage.access(frame.func, off);
+            frame.load(off, size).ok_or(EbpfError::UninitializedStackRead(off, insn_ptr))?
+        },
+        Region::Packet | Region::MapValue => RegType::Scalar(None),
+    };
+    state.regs[insn.dst as usize] = value;
+    Ok(())
+}
+
+fn store(state: &mut State, ctx: Context, usage: &mut StackUsage, insn: &ebpf::Insn,
+         insn_ptr: usize) -> Result<(), EbpfError> {
+    let size = access_size(insn.opc);
+    let value = match insn.opc & ebpf::BPF_CLS_MASK {
+        ebpf::BPF_ST => RegType::Scalar(Some(insn.imm as u64)),
+        _            => state.read(insn.src, insn_ptr)?,
+    };
+    let is_xadd = insn.opc == ebpf::ST_W_XADD || insn.opc == ebpf::ST_DW_XADD;
+    if is_xadd && value.is_pointer() {
+        return Err(EbpfError::PointerArithmetic(insn_ptr));
+    }
+    let region = region(state, ctx, insn.dst, insn.off, size, insn_ptr)?;
+    // The pointers to packet data in the context cannot be overwritten.
+    if let (Region::Ctx(off), Context::FixedMbuff(data, data_end)) = (&region, ctx) {
+        let overlaps = |ptr: usize| *off < ptr as i64 + 8 && *off + size as i64 > ptr as i64;
+        if overlaps(data) || overlaps(data_end) {
+            return Err(EbpfError::InvalidMemoryAccess(insn.dst, insn_ptr));
+        }
+    }
+    if let Region::Stack(frame, off) = region {
+        let frame = &mut state.frames[frame];
+        usage.access(frame.func, off);
+        if is_xadd {
+            match frame.load(off, size) {
+                None                              =>
+                    return Err(EbpfError::UninitializedStackRead(off, insn_ptr)),
+                Some(value) if value.is_pointer() =>
+                    return Err(EbpfError::PointerArithmetic(insn_ptr)),
+                Some(_)                           => (),
+            }
+            frame.store(off, size, RegType::Scalar(None));
+        } else if size == 8 {
+            frame.store(off, size, value);
+        } else {
+            frame.store(off, size, RegType::Scalar(None));
+        }
+    }
+    Ok(())
+}
+
+fn jump(state: &mut State, insn: &ebpf::Insn, insn_ptr: usize) -> Result<Outcome, EbpfError> {
+    let target = (insn_ptr as isize + 1 + insn.off as isize) as usize;
+    if insn.opc == ebpf::JA {
+        return Ok(Outcome::Next(target));
+    }
+
+    let dst = state.read(insn.dst, insn_ptr)?;
+    let src = if insn.opc & ebpf::BPF_X != 0 {
+        state.read(insn.src, insn_ptr)?
+    } else {
+        RegType::Scalar(Some(insn.imm as u64))
+    };
+
+    let mut taken = state.clone();
+    let (if_taken, if_not_taken) = packet_bounds(insn.opc, dst, src);
+    taken.packet_range = taken.packet_range.max(if_taken);
+    state.packet_range = state.packet_range.max(if_not_taken);
+    if let RegType::MapValueOrNull(_, _, id) = dst {
+        if insn.imm == 0 && (insn.opc == ebpf::JEQ_IMM || insn.opc == ebpf::JNE_IMM) {
+            let null_if_taken = insn.opc == ebpf::JEQ_IMM;
+            taken.resolve_lookup(id, null_if_taken);
+            state.resolve_lookup(id, !null_if_taken);
+        }
+    }
+    Ok(Outcome::Branch(insn_ptr + 1, target, Box::new(taken)))
+}
+
+// Check that register `reg`, passed to a helper, points to `size` initialized bytes of the stack.
+fn check_stack_arg(state: &State, usage: &mut StackUsage, reg: u8, size: usize, insn_ptr: usize)
+                   -> Result<(), EbpfError> {
+    let (frame, off) = match state.read(reg, insn_ptr)? {
+        RegType::Stack(frame, Some(off)) => (frame, off),
+        _                                =>
+            return Err(EbpfError::InvalidHelperArgument(reg, insn_ptr)),
+    };
+    if Frame::slot(off).is_none() || Frame::slot(off.saturating_add(size as i64 - 1)).is_none() {
+        return Err(EbpfError::OutOfBoundsAccess(reg, insn_ptr));
+    }
+    let frame = &state.frames[frame];
+    usage.access(frame.func, off);
+    match frame.load(off, size) {
+        Some(_) => Ok(()),
+        None    => Err(EbpfError::UninitializedStackRead(off, insn_ptr)),
+    }
+}
+
+// Check the arguments of a call to a map helper, as the kernel does: r1 must hold a handle on a
+// map, r2 must point to its key on the stack, and for updates, r3 to its value on the stack and
+// r4 hold the flags. Return the description of the map.
+fn check_map_helper_args(state: &State, maps: &[MapDef], usage: &mut StackUsage, id: u32,
+                         insn_ptr: usize) -> Result<MapDef, EbpfError> {
+    let def = match state.read(1, insn_ptr)? {
+        RegType::MapPtr(fd) => *map_def(maps, fd).ok_or(EbpfError::UnknownMap(fd, insn_ptr))?,
+        _                   => return Err(EbpfError::InvalidHelperArgument(1, insn_ptr)),
+    };
+    check_stack_arg(state, usage, 2, def.key_size, insn_ptr)?;
+    if id == maps::BPF_MAP_UPDATE_ELEM_IDX {
+        check_stack_arg(state, usage, 3, def.value_size, insn_ptr)?;
+        if state.read(4, insn_ptr)?.is_pointer() {
+            return Err(EbpfError::InvalidHelperArgument(4, insn_ptr));
+        }
+    }
+    Ok(def)
+}
+
+fn call(state: &mut State, maps: &[MapDef], usage: &mut StackUsage, insn: &ebpf::Insn,
+        insn_ptr: usize) -> Result<Outcome, EbpfError> {
+    if insn.src == ebpf::BPF_PSEUDO_CALL {
+        let mut saved = [RegType::Uninit; 4];
+        saved.copy_from_slice(&state.regs[6..10]);
+        let target = (insn_ptr as isize + 1 + insn.imm as isize) as usize;
+        state.frames.push(Frame::new(target, Some((insn_ptr + 1, saved))));
+        for reg in [0, 6, 7, 8, 9].iter() {
+            state.regs[*reg] = RegType::Uninit;
+        }
+        state.regs[10] = RegType::Stack(state.frames.len() - 1, Some(0));
+        return Ok(Outcome::Next(target));
+    }
+
+    // As in the VM, helpers 1 to 3 are the map helpers only if the program can use maps.
+    let id = insn.imm as u32;
+    state.regs[0] = match maps::helper(id) {
+        Some(_) if !maps.is_empty() => {
+            let def = check_map_helper_args(state, maps, usage, id, insn_ptr)?;
+            if id == maps::BPF_MAP_LOOKUP_ELEM_IDX {
+                state.next_id += 1;
+                RegType::MapValueOrNull(def.fd, def.value_size, state.next_id)
+            } else {
+                RegType::Scalar(None)
+            }
+        },
+        _                           => {
+            // Helpers can write into the stacks they get pointers to, so spilled pointers there
+            // may have been overwritten.
+            for reg in 1..6 {
+                if let RegType::Stack(frame, _) = state.regs[reg] {
+                    state.frames[frame].clobber();
+                }
+            }
+            RegType::Scalar(None)
+        },
+    };
+    for reg in 1..6 {
+        state.regs[reg] = RegType::Uninit;
+    }
+    Ok(Outcome::Next(insn_ptr + 1))
+}
+
+fn exit(state: &mut State, insn_ptr: usize) -> Result<Outcome, EbpfError> {
+    state.read(0, insn_ptr)?;
+    let (ret, saved) = match state.frames.last().and_then(|frame| frame.ret) {
+        Some(ret) => ret,
+        None      => return Ok(Outcome::Exit),
+    };
+    state.frames.pop();
+    let depth = state.frames.len();
+    // Pointers to the stack of the function are no longer valid.
+    state.update_all(|value| match value {
+        RegType::Stack(frame, _) if frame >= depth => RegType::Scalar(None),
+        value                                      => value,
+    });
+    for reg in 1..6 {
+        state.regs[reg] = RegType::Uninit;
+    }
+    state.regs[6..10].copy_from_slice(&saved);
+    state.regs[10] = RegType::Stack(depth - 1, Some(0));
+    Ok(Outcome::Next(ret))
+}
+
+// Description of the map with file descriptor `fd`, if it is known.
+fn map_def(maps: &[MapDef], fd: u32) -> Option<&MapDef> {
+    maps.iter().find(|def| def.fd == fd)
+}
+
+fn step(state: &mut State, ctx: Context, maps: &[MapDef], usage: &mut StackUsage, prog: &[u8],
+        insn_ptr: usize) -> Result<Outcome, EbpfError> {
+    let insn = ebpf::get_insn(prog, insn_ptr);
+    let class = insn.opc & ebpf::BPF_CLS_MASK;
+    let next = insn_ptr + 1;
+
+    match insn.opc {
+        ebpf::LD_DW_IMM  => {
+            state.regs[insn.dst as usize] = if insn.src == ebpf::BPF_PSEUDO_MAP_FD {
+                let fd = insn.imm as u32;
+                if map_def(maps, fd).is_none() {
+                    return Err(EbpfError::UnknownMap(fd, insn_ptr));
+                }
+                RegType::MapPtr(fd)
+            } else {
+                let next_insn = ebpf::get_insn(prog, insn_ptr + 1);
+                RegType::Scalar(Some((insn.imm as u32) as u64 + ((next_insn.imm as u64) << 32)))
+            };
+            Ok(Outcome::Next(insn_ptr + 2))
+        },
+        ebpf::LD_ABS_B | ebpf::LD_ABS_H | ebpf::LD_ABS_W | ebpf::LD_ABS_DW |
+        ebpf::LD_IND_B | ebpf::LD_IND_H | ebpf::LD_IND_W | ebpf::LD_IND_DW => {
+            if insn.opc & 0xe0 == ebpf::BPF_IND {
+                state.read(insn.src, insn_ptr)?;
+            }
+            state.regs[0] = RegType::Scalar(None);
+            Ok(Outcome::Next(next))
+        },
+        ebpf::CALL       => call(state, maps, usage, &insn, insn_ptr),
+        ebpf::TAIL_CALL  => {
+            state.read(3, insn_ptr)?;
+            Ok(Outcome::Next(next))
+        },
+        ebpf::EXIT       => exit(state, insn_ptr),
+        _                => {
+            match class {
+                ebpf::BPF_LDX                   => load(state, ctx, usage, &insn, insn_ptr)?,
+                ebpf::BPF_ST | ebpf::BPF_STX    => store(state, ctx, usage, &insn, insn_ptr)?,
+                ebpf::BPF_ALU | ebpf::BPF_ALU64 => alu(state, &insn, insn_ptr)?,
+                _                               => return jump(state, &insn, insn_ptr),
+            }
+            Ok(Outcome::Next(next))
+        },
+    }
+}
+
+/// Follow all the paths of a program, tracking the type of the values in the registers and on the
+/// stack, and check that registers are initialized before they are read, that arithmetic
+/// operations do not forge pointers, and that memory is only accessed through pointers.
+///
+/// `maps` describes the maps that the program can use: loading any other map is rejected,
+/// accesses to map values must be within their `value_size` bytes, and the arguments of the map
+/// helpers are checked. Without maps, helpers 1 to 3 are regular helpers, as in the VMs.
+///
+/// The program must have passed `verifier::check_cfg()`: see `verifier::check_types()`, which runs
+/// both. On programs with loops, the analysis goes on until it processes more than
+/// `MAX_PROCESSED_INSNS` instructions.
+///
+/// # Errors
+///
+/// Returns the first problem found, with the index of the faulty instruction.
+pub fn analyze(prog: &[u8], ctx: Context, maps: &[MapDef]) -> Result<(), EbpfError> {
+    run(prog, ctx, maps, None).map(|_| ())
+}
+
+/// Same as `analyze()`, but return the maximum number of bytes of stack used by the program: the
+/// largest sum, over the call frames that can be active at the same time, of the number of bytes
+/// below `r10` accessed by each function.
+///
+/// # Errors
+///
+/// Returns the first problem found, with the index of the faulty instruction.
+///
+/// # Examples
+///
+/// ```
+/// use rbpf::analysis::{self, Context};
+/// use rbpf::error::EbpfError;
+///
+/// let prog = &[
+///     0x7a, 0x0a, 0xf0, 0xff, 0x01, 0x00, 0x00, 0x00, // stdw [r10-16], 1
+///     0x79, 0xa0, 0xf0, 0xff, 0x00, 0x00, 0x00, 0x00, // ldxdw r0, [r10-16]
+///     0x95, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00  // exit
+/// ];
+/// assert_eq!(analysis::stack_depth(prog, Context::NoData, &[]), Ok(16));
+///
+/// let prog = &[
+///     0x79, 0xa0, 0xf8, 0xff, 0x00, 0x00, 0x00, 0x00, // ldxdw r0, [r10-8]
+///     0x95, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00  // exit
+/// ];
+/// assert_eq!(analysis::stack_depth(prog, Context::NoData, &[]),
+///            Err(EbpfError::UninitializedStackRead(-8, 0)));
+/// ```
+pub fn stack_depth(prog: &[u8], ctx: Context, maps: &[MapDef]) -> Result<usize, EbpfError> {
+    run(prog, ctx, maps, None)
+}
+
+/// Same as `analyze()`, but also record the state of the analysis before each instruction it
+/// processes into `log`, which is cleared first.
+///
+/// # Errors
+///
+/// Returns the first problem found, which is also stored in `log.error`.
+///
+/// # Examples
+///
+/// ```
+/// use rbpf::analysis::{self, Context, Log, RegType};
+///
+/// let prog = &[
+///     0xbf, 0xa2, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // mov r2, r10
+///     0x7b, 0x12, 0xf8, 0xff, 0x00, 0x00, 0x00, 0x00, // stxdw [r2-8], r1
+///     0x79, 0x20, 0xf8, 0xff, 0x00, 0x00, 0x00, 0x00, // ldxdw r0, [r2-8]
+///     0x95, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00  // exit
+/// ];
+///
+/// let mut log = Log::default();
+/// analysis::analyze_with_log(prog, Context::Memory, &[], &mut log).unwrap();
+/// assert_eq!(log.entries.len(), 4);
+/// assert_eq!(log.entries[2].regs[2], RegType::Stack(0, Some(0)));
+/// assert_eq!(log.entries[2].stack, vec![(-8, RegType::Ctx(Some(0)))]);
+/// assert_eq!(log.entries[3].regs[0], RegType::Ctx(Some(0)));
+///
+/// let text = log.to_string();
+/// assert!(text.starts_with("0: mov64 r2, r10\n    r1=ctx+0 r10=fp0+0\n"));
+/// ```
+pub fn analyze_with_log(prog: &[u8], ctx: Context, maps: &[MapDef], log: &mut Log)
+                        -> Result<(), EbpfError> {
+    *log = Log::default();
+    match run(prog, ctx, maps, Some(log)) {
+        Ok(depth) => {
+            log.stack_depth = depth;
+            Ok(())
+        },
+        Err(err)  => {
+            log.error = Some(err.clone());
+            Err(err)
+        },
+    }
+}
+
+// Run the analysis, and return the maximum stack depth of the program.
+fn run(prog: &[u8], ctx: Context, maps: &[MapDef], mut log: Option<&mut Log>)
+       -> Result<usize, EbpfError> {
+    let cfg = Cfg::new(prog);
+    let descs = match log {
+        Some(_) => disassemble(prog),
+        None    => vec![],
+    };
+    // States already explored at the first instruction of each basic block, up to
+    // `MAX_STATES_PER_INSN` for each block.
+    let mut visited: HashMap<usize, HashSet<State>> = HashMap::new();
+    // Paths to explore, with the index in the log of the entry for the jump leading to them.
+    let mut pending = vec![(0, State::new(ctx), None)];
+    let mut processed = 0;
+    let mut usage = StackUsage::new();
+
+    while let Some((mut insn_ptr, mut state, mut parent)) = pending.pop() {
+        loop {
+            if cfg.block_at(insn_ptr).is_some() {
+                // Looking up the state costs about as much as processing an instruction.
+                processed += 1;
+                let states = visited.entry(insn_ptr).or_default();
+                if states.contains(&state) {
+                    break;
+                }
+                if states.len() < MAX_STATES_PER_INSN {
+                    states.insert(state.clone());
+                }
+            }
+            processed += 1;
+            if processed > MAX_PROCESSED_INSNS {
+                return Err(EbpfError::TooComplex(insn_ptr));
+            }
+
+            if let Some(ref mut log) = log {
+                log.entries.push(LogEntry::new(insn_ptr, &descs[insn_ptr], &state, parent));
+                parent = Some(log.entries.len() - 1);
+            }
+            let depth = state.frames.len();
+            let outcome = step(&mut state, ctx, maps, &mut usage, prog, insn_ptr)?;
+            if state.frames.len() > depth {
+                usage.chains.insert(state.frames.iter().map(|frame| frame.func).collect());
+            }
+            match outcome {
+                Outcome::Next(next)                 => insn_ptr = next,
+                Outcome::Branch(next, target, taken) => {
+                    pending.push((target, *taken, parent));
+                    insn_ptr = next;
+                },
+                Outcome::Exit                       => break,
+            }
+        }
+    }
+    Ok(usage.max_depth())
+}
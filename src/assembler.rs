@@ -0,0 +1,569 @@
+// Copyright 2026 agent <agent@local>
+//
+// Licensed under the Apache License, Version 2.0 <http://www.apache.org/licenses/LICENSE-2.0> or
+// the MIT license <http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+
+//! This module translates eBPF programs written in assembly language into bytecode. It is the
+//! inverse of the `disassembler` module: it accepts the syntax produced by
+//! `disassembler::to_insn_vec()`, for example `add64 r1, 0x605` or `ldxh r0, [r1+0x2]`.
+//!
+//! In addition to the instructions, the assembler accepts:
+//!
+//! * Comments, starting with `#` or `//` and running until the end of the line.
+//! * Labels, defined as `name:` at the beginning of a line, and that can be used instead of
+//!   numerical offsets as the target of jump instructions and of local function calls
+//!   (`call_local`).
+//! * Decimal or hexadecimal (`0x`) numbers, possibly signed. Values that do not fit into the
+//!   signed operand are accepted as long as they fit into its unsigned counterpart, so that
+//!   e.g. an offset of `+0xffff` (as printed by the disassembler) stands for -1.
+//! * Map references as the operand of `lddw`, written `map_fd(N)` with `N` the file descriptor
+//!   of the map (see module `maps`).
+
+use std::collections::HashMap;
+
+use ebpf;
+use error::AsmError;
+
+// Operands expected by each kind of instruction.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum InstructionType {
+    AluBinary,
+    AluUnary,
+    Endian(i32),
+    LoadAbs,
+    LoadInd,
+    LoadDwImm,
+    LoadReg,
+    StoreImm,
+    StoreReg,
+    JumpUnconditional,
+    JumpConditional,
+    Call,
+    CallLocal,
+    NoOperand,
+}
+
+fn make_instruction_map() -> HashMap<String, (InstructionType, u8)> {
+    use self::InstructionType::*;
+
+    let mut result = HashMap::new();
+    {
+        let mut entry = |name: &str, inst_type: InstructionType, opc: u8| {
+            result.insert(name.to_string(), (inst_type, opc));
+        };
+
+        let alu_binary_ops = [
+            ("add",  ebpf::BPF_ADD),
+            ("sub",  ebpf::BPF_SUB),
+            ("mul",  ebpf::BPF_MUL),
+            ("div",  ebpf::BPF_DIV),
+            ("or",   ebpf::BPF_OR),
+            ("and",  ebpf::BPF_AND),
+            ("lsh",  ebpf::BPF_LSH),
+            ("rsh",  ebpf::BPF_RSH),
+            ("mod",  ebpf::BPF_MOD),
+            ("xor",  ebpf::BPF_XOR),
+            ("mov",  ebpf::BPF_MOV),
+            ("arsh", ebpf::BPF_ARSH),
+        ];
+        // The opcodes stored are those of the immediate variants, the register variants are
+        // obtained by setting the BPF_X bit.
+        for &(name, op) in alu_binary_ops.iter() {
+            entry(&format!("{}32", name), AluBinary, ebpf::BPF_ALU   | ebpf::BPF_K | op);
+            entry(&format!("{}64", name), AluBinary, ebpf::BPF_ALU64 | ebpf::BPF_K | op);
+        }
+        entry("neg32", AluUnary, ebpf::NEG32);
+        entry("neg64", AluUnary, ebpf::NEG64);
+
+        for &size in &[16, 32, 64] {
+            entry(&format!("le{}", size), Endian(size), ebpf::LE);
+            entry(&format!("be{}", size), Endian(size), ebpf::BE);
+        }
+
+        entry("ldabsb",  LoadAbs, ebpf::LD_ABS_B);
+        entry("ldabsh",  LoadAbs, ebpf::LD_ABS_H);
+        entry("ldabsw",  LoadAbs, ebpf::LD_ABS_W);
+        entry("ldabsdw", LoadAbs, ebpf::LD_ABS_DW);
+        entry("ldindb",  LoadInd, ebpf::LD_IND_B);
+        entry("ldindh",  LoadInd, ebpf::LD_IND_H);
+        entry("ldindw",  LoadInd, ebpf::LD_IND_W);
+        entry("ldinddw", LoadInd, ebpf::LD_IND_DW);
+
+        entry("lddw", LoadDwImm, ebpf::LD_DW_IMM);
+
+        entry("ldxb",  LoadReg, ebpf::LD_B_REG);
+        entry("ldxh",  LoadReg, ebpf::LD_H_REG);
+        entry("ldxw",  LoadReg, ebpf::LD_W_REG);
+        entry("ldxdw", LoadReg, ebpf::LD_DW_REG);
+
+        entry("stb",  StoreImm, ebpf::ST_B_IMM);
+        entry("sth",  StoreImm, ebpf::ST_H_IMM);
+        entry("stw",  StoreImm, ebpf::ST_W_IMM);
+        entry("stdw", StoreImm, ebpf::ST_DW_IMM);
+
+        entry("stxb",      StoreReg, ebpf::ST_B_REG);
+        entry("stxh",      StoreReg, ebpf::ST_H_REG);
+        entry("stxw",      StoreReg, ebpf::ST_W_REG);
+        entry("stxdw",     StoreReg, ebpf::ST_DW_REG);
+        entry("stxxaddw",  StoreReg, ebpf::ST_W_XADD);
+        entry("stxxadddw", StoreReg, ebpf::ST_DW_XADD);
+
+        entry("ja",   JumpUnconditional, ebpf::JA);
+        entry("jeq",  JumpConditional,   ebpf::JEQ_IMM);
+        entry("jgt",  JumpConditional,   ebpf::JGT_IMM);
+        entry("jge",  JumpConditional,   ebpf::JGE_IMM);
+        entry("jset", JumpConditional,   ebpf::JSET_IMM);
+        entry("jne",  JumpConditional,   ebpf::JNE_IMM);
+        entry("jsgt", JumpConditional,   ebpf::JSGT_IMM);
+        entry("jsge", JumpConditional,   ebpf::JSGE_IMM);
+        entry("jlt",  JumpConditional,   ebpf::JLT_IMM);
+        entry("jle",  JumpConditional,   ebpf::JLE_IMM);
+        entry("jslt", JumpConditional,   ebpf::JSLT_IMM);
+        entry("jsle", JumpConditional,   ebpf::JSLE_IMM);
+
+        entry("jeq32",  JumpConditional,   ebpf::JEQ_IMM32);
+        entry("jgt32",  JumpConditional,   ebpf::JGT_IMM32);
+        entry("jge32",  JumpConditional,   ebpf::JGE_IMM32);
+        entry("jset32", JumpConditional,   ebpf::JSET_IMM32);
+        entry("jne32",  JumpConditional,   ebpf::JNE_IMM32);
+        entry("jsgt32", JumpConditional,   ebpf::JSGT_IMM32);
+        entry("jsge32", JumpConditional,   ebpf::JSGE_IMM32);
+        entry("jlt32",  JumpConditional,   ebpf::JLT_IMM32);
+        entry("jle32",  JumpConditional,   ebpf::JLE_IMM32);
+        entry("jslt32", JumpConditional,   ebpf::JSLT_IMM32);
+        entry("jsle32", JumpConditional,   ebpf::JSLE_IMM32);
+
+        entry("call",       Call,      ebpf::CALL);
+        entry("call_local", CallLocal, ebpf::CALL);
+        entry("tail_call",  NoOperand, ebpf::TAIL_CALL);
+        entry("exit",       NoOperand, ebpf::EXIT);
+    }
+    result
+}
+
+// Operand of an instruction. Operands are stored along with the column at which they start.
+#[derive(Clone, Debug, PartialEq)]
+enum Operand {
+    Register(i64),
+    Integer(i64),
+    Memory(i64, i64),
+    Label(String),
+    MapFd(i64),
+}
+
+// Instruction parsed from one line of source, not encoded yet.
+struct Statement {
+    line:     usize,
+    column:   usize,
+    name:     String,
+    operands: Vec<(Operand, usize)>,
+    insn_ptr: usize,
+}
+
+// Cursor over the characters of one line of source.
+struct Parser {
+    chars: Vec<char>,
+    pos:   usize,
+    line:  usize,
+}
+
+impl Parser {
+    fn new(src: &str, line: usize) -> Parser {
+        Parser { chars: src.chars().collect(), pos: 0, line }
+    }
+
+    fn error(&self, column: usize, msg: String) -> AsmError {
+        AsmError { line: self.line, column, msg }
+    }
+
+    // Columns are counted from 1.
+    fn column(&self) -> usize {
+        self.pos + 1
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).cloned()
+    }
+
+    fn skip_whitespace(&mut self) {
+        while let Some(c) = self.peek() {
+            if !c.is_whitespace() {
+                break;
+            }
+            self.pos += 1;
+        }
+    }
+
+    fn at_end(&mut self) -> bool {
+        self.skip_whitespace();
+        self.peek().is_none()
+    }
+
+    fn expect(&mut self, expected: char) -> Result<(), AsmError> {
+        self.skip_whitespace();
+        match self.peek() {
+            Some(c) if c == expected => { self.pos += 1; Ok(()) },
+            Some(c) => Err(self.error(self.column(), format!("expected '{}', found '{}'", expected, c))),
+            None    => Err(self.error(self.column(), format!("expected '{}'", expected))),
+        }
+    }
+
+    fn is_ident_char(c: char) -> bool {
+        c.is_alphanumeric() || c == '_' || c == '.'
+    }
+
+    fn ident(&mut self) -> String {
+        let start = self.pos;
+        while let Some(c) = self.peek() {
+            if !Parser::is_ident_char(c) {
+                break;
+            }
+            self.pos += 1;
+        }
+        self.chars[start..self.pos].iter().collect()
+    }
+
+    fn register(&mut self) -> Result<i64, AsmError> {
+        self.skip_whitespace();
+        let column = self.column();
+        let name = self.ident();
+        match parse_register(&name) {
+            Some(reg) => Ok(reg),
+            None      => Err(self.error(column, format!("invalid register '{}'", name))),
+        }
+    }
+
+    fn integer(&mut self) -> Result<i64, AsmError> {
+        self.skip_whitespace();
+        let column = self.column();
+        let negative = match self.peek() {
+            Some('-') => { self.pos += 1; true },
+            Some('+') => { self.pos += 1; false },
+            _         => false,
+        };
+        let digits = self.ident();
+        let value = if digits.starts_with("0x") || digits.starts_with("0X") {
+            u64::from_str_radix(&digits[2..], 16)
+        } else {
+            digits.parse::<u64>()
+        };
+        match value {
+            Ok(v) if negative && v > 1 << 63 =>
+                Err(self.error(column, format!("integer '-{}' out of range", digits))),
+            Ok(v) if negative => Ok((v as i64).wrapping_neg()),
+            Ok(v)             => Ok(v as i64),
+            Err(_)            => Err(self.error(column, format!("invalid integer '{}'", digits))),
+        }
+    }
+
+    fn operand(&mut self) -> Result<(Operand, usize), AsmError> {
+        self.skip_whitespace();
+        let column = self.column();
+        let operand = match self.peek() {
+            Some('[') => {
+                self.pos += 1;
+                let reg = self.register()?;
+                self.skip_whitespace();
+                let off = match self.peek() {
+                    Some('+') | Some('-') => self.integer()?,
+                    _ => 0,
+                };
+                self.expect(']')?;
+                Operand::Memory(reg, off)
+            },
+            Some(c) if c.is_ascii_digit() || c == '+' || c == '-' => Operand::Integer(self.integer()?),
+            Some(c) if Parser::is_ident_char(c) => {
+                let name = self.ident();
+                match parse_register(&name) {
+                    None if name == "map_fd" && self.peek() == Some('(') => {
+                        self.pos += 1;
+                        let fd = self.integer()?;
+                        self.expect(')')?;
+                        Operand::MapFd(fd)
+                    },
+                    Some(reg) => Operand::Register(reg),
+                    None if looks_like_register(&name) =>
+                        return Err(self.error(column, format!("invalid register '{}'", name))),
+                    None      => Operand::Label(name),
+                }
+            },
+            Some(c) => return Err(self.error(column, format!("unexpected character '{}'", c))),
+            None    => return Err(self.error(column, "missing operand".to_string())),
+        };
+        Ok((operand, column))
+    }
+
+    // Parse an optional label definition, then an optional instruction.
+    fn statement(&mut self, labels: &mut HashMap<String, usize>, insn_ptr: usize)
+        -> Result<Option<Statement>, AsmError> {
+        if self.at_end() {
+            return Ok(None);
+        }
+        let column = self.column();
+        let name = self.ident();
+        if name.is_empty() {
+            let c = self.peek().unwrap_or(' ');
+            return Err(self.error(column, format!("unexpected character '{}'", c)));
+        }
+        self.skip_whitespace();
+        if self.peek() == Some(':') {
+            self.pos += 1;
+            if looks_like_register(&name) || name.starts_with(|c: char| c.is_ascii_digit()) {
+                return Err(self.error(column, format!("invalid label name '{}'", name)));
+            }
+            if labels.insert(name.clone(), insn_ptr).is_some() {
+                return Err(self.error(column, format!("duplicate label '{}'", name)));
+            }
+            return self.statement(labels, insn_ptr);
+        }
+
+        let mut operands = vec![];
+        if !self.at_end() {
+            operands.push(self.operand()?);
+            while !self.at_end() {
+                self.expect(',')?;
+                operands.push(self.operand()?);
+            }
+        }
+        Ok(Some(Statement { line: self.line, column, name, operands, insn_ptr }))
+    }
+}
+
+// Register names are `r` followed by a decimal number.
+fn looks_like_register(name: &str) -> bool {
+    name.len() >= 2 && name.starts_with('r') && name[1..].chars().all(|c| c.is_ascii_digit())
+}
+
+fn parse_register(name: &str) -> Option<i64> {
+    if !looks_like_register(name) {
+        return None;
+    }
+    match name[1..].parse::<i64>() {
+        Ok(reg) if (0..=10).contains(&reg) => Some(reg),
+        _ => None,
+    }
+}
+
+// Remove the comment, if any, at the end of a line.
+fn strip_comment(line: &str) -> &str {
+    let end = match (line.find('#'), line.find("//")) {
+        (Some(a), Some(b)) => a.min(b),
+        (Some(a), None)    => a,
+        (None,    Some(b)) => b,
+        (None,    None)    => line.len(),
+    };
+    &line[..end]
+}
+
+fn check_range(value: i64, min: i64, max: i64, what: &str, stmt: &Statement, column: usize)
+    -> Result<i64, AsmError> {
+    if value < min || value > max {
+        return Err(AsmError {
+            line:   stmt.line,
+            column,
+            msg:    format!("{} {:#x} out of range", what, value),
+        });
+    }
+    Ok(value)
+}
+
+fn imm32(value: i64, stmt: &Statement, column: usize) -> Result<i32, AsmError> {
+    check_range(value, i32::MIN as i64, u32::MAX as i64, "immediate", stmt, column)
+        .map(|v| v as u32 as i32)
+}
+
+fn off16(value: i64, stmt: &Statement, column: usize) -> Result<i16, AsmError> {
+    check_range(value, i16::MIN as i64, u16::MAX as i64, "offset", stmt, column)
+        .map(|v| v as u16 as i16)
+}
+
+// Offset from the next instruction to the label in `operand`, if it is one. `what` names the
+// numerical offset expected otherwise, for error messages.
+fn label_offset(operand: &(Operand, usize), stmt: &Statement, labels: &HashMap<String, usize>,
+                what: &str) -> Result<Option<i64>, AsmError> {
+    match *operand {
+        (Operand::Integer(_), _) => Ok(None),
+        (Operand::Label(ref name), column) => match labels.get(name) {
+            Some(&target) => Ok(Some(target as i64 - stmt.insn_ptr as i64 - 1)),
+            None => Err(AsmError {
+                line:   stmt.line,
+                column,
+                msg:    format!("undefined label '{}'", name),
+            }),
+        },
+        (_, column) => Err(AsmError {
+            line:   stmt.line,
+            column,
+            msg:    format!("expected {} or label", what),
+        }),
+    }
+}
+
+fn jump_offset(operand: &(Operand, usize), stmt: &Statement, labels: &HashMap<String, usize>)
+    -> Result<i16, AsmError> {
+    match (label_offset(operand, stmt, labels, "jump offset")?, operand) {
+        (Some(off), &(_, column)) =>
+            check_range(off, i16::MIN as i64, i16::MAX as i64, "jump offset", stmt, column)
+                .map(|v| v as i16),
+        (None, &(Operand::Integer(off), column)) => off16(off, stmt, column),
+        (None, _) => unreachable!(),
+    }
+}
+
+fn call_offset(operand: &(Operand, usize), stmt: &Statement, labels: &HashMap<String, usize>)
+    -> Result<i32, AsmError> {
+    match (label_offset(operand, stmt, labels, "call offset")?, operand) {
+        (Some(off), &(_, column)) =>
+            check_range(off, i32::MIN as i64, i32::MAX as i64, "call offset", stmt, column)
+                .map(|v| v as i32),
+        (None, &(Operand::Integer(off), column)) => imm32(off, stmt, column),
+        (None, _) => unreachable!(),
+    }
+}
+
+// Encode one statement into one instruction, or two for `lddw`.
+fn encode(stmt: &Statement, inst_type: InstructionType, opc: u8, labels: &HashMap<String, usize>)
+    -> Result<Vec<ebpf::Insn>, AsmError> {
+    use self::InstructionType::*;
+    use self::Operand::*;
+
+    let insn = |opc: u8, dst: i64, src: i64, off: i16, imm: i32| ebpf::Insn {
+        opc,
+        dst: dst as u8,
+        src: src as u8,
+        off,
+        imm,
+    };
+    let operand_error = |msg: &str| AsmError {
+        line:   stmt.line,
+        column: stmt.operands.first().map_or(stmt.column, |o| o.1),
+        msg:    format!("invalid operands for '{}', expected {}", stmt.name, msg),
+    };
+    let ops = &stmt.operands;
+
+    let res = match (inst_type, ops.as_slice()) {
+        (AluBinary, [(Register(dst), _), (Register(src), _)]) =>
+            insn(opc | ebpf::BPF_X, *dst, *src, 0, 0),
+        (AluBinary, [(Register(dst), _), (Integer(imm), c)]) =>
+            insn(opc, *dst, 0, 0, imm32(*imm, stmt, *c)?),
+        (AluBinary, _) => return Err(operand_error("register and register or immediate")),
+        (AluUnary, [(Register(dst), _)]) => insn(opc, *dst, 0, 0, 0),
+        (AluUnary, _) => return Err(operand_error("register")),
+        (Endian(size), [(Register(dst), _)]) => insn(opc, *dst, 0, 0, size),
+        (Endian(_), _) => return Err(operand_error("register")),
+        (LoadAbs, [(Integer(imm), c)]) => insn(opc, 0, 0, 0, imm32(*imm, stmt, *c)?),
+        (LoadAbs, _) => return Err(operand_error("immediate")),
+        (LoadInd, [(Register(src), _), (Integer(imm), c)]) =>
+            insn(opc, 0, *src, 0, imm32(*imm, stmt, *c)?),
+        (LoadInd, _) => return Err(operand_error("register and immediate")),
+        (LoadDwImm, [(Register(dst), _), (Integer(imm), _)]) => {
+            return Ok(vec![
+                insn(opc, *dst, 0, 0, *imm as i32),
+                insn(0, 0, 0, 0, (*imm >> 32) as i32),
+            ]);
+        },
+        (LoadDwImm, [(Register(dst), _), (MapFd(fd), c)]) => {
+            return Ok(vec![
+                insn(opc, *dst, ebpf::BPF_PSEUDO_MAP_FD as i64, 0, imm32(*fd, stmt, *c)?),
+                insn(0, 0, 0, 0, 0),
+            ]);
+        },
+        (LoadDwImm, _) => return Err(operand_error("register and immediate or map")),
+        (LoadReg, [(Register(dst), _), (Memory(src, off), c)]) =>
+            insn(opc, *dst, *src, off16(*off, stmt, *c)?, 0),
+        (LoadReg, _) => return Err(operand_error("register and memory operand")),
+        (StoreImm, [(Memory(dst, off), c), (Integer(imm), c_imm)]) =>
+            insn(opc, *dst, 0, off16(*off, stmt, *c)?, imm32(*imm, stmt, *c_imm)?),
+        (StoreImm, _) => return Err(operand_error("memory operand and immediate")),
+        (StoreReg, [(Memory(dst, off), c), (Register(src), _)]) =>
+            insn(opc, *dst, *src, off16(*off, stmt, *c)?, 0),
+        (StoreReg, _) => return Err(operand_error("memory operand and register")),
+        (JumpUnconditional, [target]) => insn(opc, 0, 0, jump_offset(target, stmt, labels)?, 0),
+        (JumpUnconditional, _) => return Err(operand_error("jump offset or label")),
+        (JumpConditional, [(Register(dst), _), (Register(src), _), target]) =>
+            insn(opc | ebpf::BPF_X, *dst, *src, jump_offset(target, stmt, labels)?, 0),
+        (JumpConditional, [(Register(dst), _), (Integer(imm), c), target]) =>
+            insn(opc, *dst, 0, jump_offset(target, stmt, labels)?, imm32(*imm, stmt, *c)?),
+        (JumpConditional, _) =>
+            return Err(operand_error("register, register or immediate, and jump offset or label")),
+        (Call, [(Integer(imm), c)]) => insn(opc, 0, 0, 0, imm32(*imm, stmt, *c)?),
+        (Call, _) => return Err(operand_error("immediate")),
+        (CallLocal, [target]) =>
+            insn(opc, 0, ebpf::BPF_PSEUDO_CALL as i64, 0, call_offset(target, stmt, labels)?),
+        (CallLocal, _) => return Err(operand_error("call offset or label")),
+        (NoOperand, []) => insn(opc, 0, 0, 0, 0),
+        (NoOperand, _) => return Err(operand_error("no operand")),
+    };
+    Ok(vec![res])
+}
+
+/// Translate an eBPF program written in assembly language into bytecode.
+///
+/// The syntax is the one produced by the `disassembler` module, with one instruction per line.
+/// Lines may also contain comments, and start with label definitions used as jump targets (see
+/// module documentation).
+///
+/// # Errors
+///
+/// Returns an `AsmError` pointing at the line and column of the first faulty token: unknown
+/// mnemonic, invalid register, wrong operands for the instruction, value out of range, undefined
+/// or duplicate label.
+///
+/// # Examples
+///
+/// ```
+/// use rbpf::assembler;
+///
+/// let prog = assembler::assemble("
+///     mov64 r0, 0             # Return value.
+///     jeq r1, 0x0, out
+///     lddw r0, 0x1122334455667788
+/// out:
+///     exit
+/// ").unwrap();
+///
+/// assert_eq!(prog, vec![
+///     0xb7, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+///     0x15, 0x01, 0x02, 0x00, 0x00, 0x00, 0x00, 0x00,
+///     0x18, 0x00, 0x00, 0x00, 0x88, 0x77, 0x66, 0x55,
+///     0x00, 0x00, 0x00, 0x00, 0x44, 0x33, 0x22, 0x11,
+///     0x95, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00
+/// ]);
+///
+/// let err = assembler::assemble("mov64 r0, 0\nadd64 r11, 1").unwrap_err();
+/// assert_eq!(err.to_string(), "[Assembler] Error: invalid register 'r11' (line 2, column 7)");
+/// ```
+pub fn assemble(src: &str) -> Result<Vec<u8>, AsmError> {
+    let instruction_map = make_instruction_map();
+
+    // First pass: parse the statements, and compute the address of each label.
+    let mut labels = HashMap::new();
+    let mut statements = vec![];
+    let mut insn_ptr = 0;
+    for (i, line) in src.lines().enumerate() {
+        let mut parser = Parser::new(strip_comment(line), i + 1);
+        if let Some(stmt) = parser.statement(&mut labels, insn_ptr)? {
+            let inst_type = match instruction_map.get(&stmt.name) {
+                Some(&(inst_type, opc)) => (inst_type, opc),
+                None => return Err(AsmError {
+                    line:   stmt.line,
+                    column: stmt.column,
+                    msg:    format!("unknown instruction '{}'", stmt.name),
+                }),
+            };
+            insn_ptr += if inst_type.0 == InstructionType::LoadDwImm { 2 } else { 1 };
+            statements.push((stmt, inst_type));
+        }
+    }
+
+    // Second pass: encode the instructions, now that labels can be resolved.
+    let mut prog = vec![];
+    for &(ref stmt, (inst_type, opc)) in &statements {
+        for insn in encode(stmt, inst_type, opc, &labels)? {
+            prog.extend_from_slice(&insn.to_array());
+        }
+    }
+    Ok(prog)
+}
@@ -0,0 +1,180 @@
+// Copyright 2026 agent <agent@local>
+//
+// Licensed under the Apache License, Version 2.0 <http://www.apache.org/licenses/LICENSE-2.0> or
+// the MIT license <http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+
+//! This module builds the control-flow graph (CFG) of eBPF programs, used by the verifier.
+//!
+//! The graph is made of basic blocks: sequences of instructions always executed in a row, and
+//! entered through their first instruction only. A block ends with a jump, an `EXIT`, a call to a
+//! local function, or right before the first instruction of another block.
+//!
+//! Local functions are part of the graph: a call to a local function has the entry block of the
+//! function and the block following the call as successors, and `EXIT` instructions have no
+//! successor. This way, a recursive call shows as a loop in the graph.
+
+use std::collections::BTreeSet;
+
+use ebpf;
+
+/// A basic block of the graph.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BasicBlock {
+    /// Index of the first instruction of the block.
+    pub start: usize,
+    /// Index of the instruction following the block.
+    pub end:   usize,
+    /// Index of the last instruction of the block. This is not `end - 1` if the block ends with a
+    /// `LD_DW_IMM` instruction, which takes two slots.
+    pub last:  usize,
+    /// Indices in the graph of the blocks that can be executed after this one: the target of the
+    /// jump or of the local call first, then the next block.
+    pub succs: Vec<usize>,
+}
+
+/// The control-flow graph of a program.
+///
+/// # Examples
+///
+/// ```
+/// use rbpf::cfg::Cfg;
+///
+/// let prog = &[
+///     0xb7, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // mov r0, 0
+///     0x15, 0x01, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, // jeq r1, 0, +1
+///     0xb7, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, // mov r0, 1
+///     0x95, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00  // exit
+/// ];
+///
+/// let cfg = Cfg::new(prog);
+/// let blocks = cfg.blocks();
+/// assert_eq!(blocks.len(), 3);
+/// assert_eq!((blocks[0].start, blocks[0].end), (0, 2));
+/// assert_eq!(blocks[0].succs, vec![2, 1]);
+/// assert_eq!(blocks[1].succs, vec![2]);
+/// assert!(blocks[2].succs.is_empty());
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Cfg {
+    blocks:    Vec<BasicBlock>,
+    functions: Vec<usize>,
+}
+
+// Where execution can go after an instruction, apart from the next one.
+enum Flow {
+    // Go on with the next instruction.
+    Next,
+    // Jump to the target, unconditionally if `true`.
+    Jump(isize, bool),
+    // Call the local function at the target, then go on with the next instruction.
+    Call(isize),
+    Exit,
+}
+
+fn flow(insn: &ebpf::Insn, insn_ptr: usize) -> Flow {
+    let class = insn.opc & ebpf::BPF_CLS_MASK;
+    match insn.opc {
+        ebpf::EXIT      => Flow::Exit,
+        ebpf::TAIL_CALL => Flow::Next,
+        ebpf::CALL if insn.src == ebpf::BPF_PSEUDO_CALL =>
+            Flow::Call(insn_ptr as isize + 1 + insn.imm as isize),
+        ebpf::CALL      => Flow::Next,
+        ebpf::JA        => Flow::Jump(insn_ptr as isize + 1 + insn.off as isize, true),
+        _ if class == ebpf::BPF_JMP || class == ebpf::BPF_JMP32 =>
+            Flow::Jump(insn_ptr as isize + 1 + insn.off as isize, false),
+        _               => Flow::Next,
+    }
+}
+
+impl Cfg {
+    /// Build the graph of a program. The program is expected to have passed the checks of
+    /// `verifier::check()`: jumps and calls out of the program are ignored.
+    pub fn new(prog: &[u8]) -> Cfg {
+        let num_insns = prog.len() / ebpf::INSN_SIZE;
+
+        // Find the first instruction of each block, and of each function.
+        let mut insns = vec![];
+        let mut leaders = BTreeSet::new();
+        let mut functions = BTreeSet::new();
+        leaders.insert(0);
+        functions.insert(0);
+        let mut insn_ptr = 0;
+        while insn_ptr < num_insns {
+            let insn = ebpf::get_insn(prog, insn_ptr);
+            let next = insn_ptr + if insn.opc == ebpf::LD_DW_IMM { 2 } else { 1 };
+            match flow(&insn, insn_ptr) {
+                Flow::Next               => (),
+                Flow::Jump(target, _)    => {
+                    leaders.insert(target);
+                    leaders.insert(next as isize);
+                },
+                Flow::Call(target)       => {
+                    leaders.insert(target);
+                    functions.insert(target);
+                    leaders.insert(next as isize);
+                },
+                Flow::Exit               => { leaders.insert(next as isize); },
+            }
+            insns.push(insn_ptr);
+            insn_ptr = next;
+        }
+        let in_prog = |target: &isize| *target >= 0 && (*target as usize) < num_insns;
+        let leaders = leaders.iter().filter(|t| in_prog(t)).map(|&t| t as usize)
+            .collect::<Vec<usize>>();
+        let functions = functions.iter().filter(|t| in_prog(t)).map(|&t| t as usize).collect();
+
+        // Cut the sequence of instructions into blocks.
+        let mut blocks: Vec<BasicBlock> = vec![];
+        for (i, &insn_ptr) in insns.iter().enumerate() {
+            if leaders.binary_search(&insn_ptr).is_ok() {
+                blocks.push(BasicBlock { start: insn_ptr, end: 0, last: 0, succs: vec![] });
+            }
+            let block = blocks.last_mut().unwrap();
+            block.last = insn_ptr;
+            block.end = insns.get(i + 1).cloned().unwrap_or(num_insns);
+        }
+
+        // Link the blocks.
+        let block_at = |insn_ptr: isize| {
+            if insn_ptr < 0 {
+                return None;
+            }
+            blocks.binary_search_by_key(&(insn_ptr as usize), |block| block.start).ok()
+        };
+        let succs = blocks.iter().map(|block| {
+            let next = block_at(block.end as isize);
+            let succs = match flow(&ebpf::get_insn(prog, block.last), block.last) {
+                Flow::Next                => vec![next],
+                Flow::Jump(target, true)  => vec![block_at(target)],
+                Flow::Jump(target, false) |
+                Flow::Call(target)        => vec![block_at(target), next],
+                Flow::Exit                => vec![],
+            };
+            succs.into_iter().flatten().collect::<Vec<usize>>()
+        }).collect::<Vec<_>>();
+        for (block, succs) in blocks.iter_mut().zip(succs) {
+            block.succs = succs;
+        }
+
+        Cfg { blocks, functions }
+    }
+
+    /// Return the basic blocks of the program, in the order of their instructions. The entry
+    /// point of the program is the first block.
+    pub fn blocks(&self) -> &[BasicBlock] {
+        &self.blocks
+    }
+
+    /// Return the index in the graph of the block starting at instruction `insn_ptr`, if any.
+    pub fn block_at(&self, insn_ptr: usize) -> Option<usize> {
+        self.blocks.binary_search_by_key(&insn_ptr, |block| block.start).ok()
+    }
+
+    /// Return the index of the first instruction of each function, in increasing order: 0 for
+    /// the main program, then the targets of local calls.
+    pub fn functions(&self) -> &[usize] {
+        &self.functions
+    }
+}
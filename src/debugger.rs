@@ -0,0 +1,265 @@
+// Copyright 2026 agent <agent@local>
+//
+// Licensed under the Apache License, Version 2.0 <http://www.apache.org/licenses/LICENSE-2.0> or
+// the MIT license <http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+
+//! This module contains a debugger for eBPF programs run by the interpreter.
+//!
+//! A debugging session is created with the `debug()` function of one of the virtual machines. It
+//! runs the program one instruction at a time, stops on breakpoints, and gives access to the
+//! registers, to the stack and to the memory regions of the program between two instructions.
+//! The instruction budget set on the VM applies to the session as well.
+
+use std::collections::BTreeSet;
+
+use ebpf;
+use error::{ExecError, ExecErrorKind};
+use {EbpfVmMbuff, ExecState};
+
+/// State of a debugging session.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Status {
+    /// Stopped before the next instruction, when the session starts or after a single step.
+    Stopped,
+    /// Stopped on a breakpoint, before the instruction at this index.
+    Breakpoint(usize),
+    /// The program exited with this return value.
+    Exited(u64),
+    /// The program was aborted on an error.
+    Faulted(ExecError),
+}
+
+/// A debugging session, executing a program with the interpreter under the control of the user.
+///
+/// Breakpoints are set on instructions of the program loaded into the VM, by index. They do not
+/// stop the programs reached with tail calls.
+///
+/// # Examples
+///
+/// ```
+/// use rbpf::debugger::Status;
+///
+/// let prog = &[
+///     0xb7, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, // mov r0, 1
+///     0x07, 0x00, 0x00, 0x00, 0x02, 0x00, 0x00, 0x00, // add r0, 2
+///     0x07, 0x00, 0x00, 0x00, 0x03, 0x00, 0x00, 0x00, // add r0, 3
+///     0x95, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00  // exit
+/// ];
+///
+/// let vm = rbpf::EbpfVmNoData::new(prog).unwrap();
+/// let mut session = vm.debug();
+///
+/// session.set_breakpoint(2);
+/// assert_eq!(session.cont(), Status::Breakpoint(2));
+/// assert_eq!(session.reg(0), 3);
+///
+/// session.set_reg(0, 10);
+/// assert_eq!(session.step(), Status::Stopped);
+/// assert_eq!(session.insn_ptr(), 3);
+/// assert_eq!(session.cont(), Status::Exited(13));
+/// ```
+pub struct Session<'a, 'b> {
+    vm:          &'b EbpfVmMbuff<'a>,
+    mem:         &'b mut [u8],
+    mbuff:       &'b mut [u8],
+    state:       ExecState<'a>,
+    breakpoints: BTreeSet<usize>,
+    status:      Status,
+}
+
+impl<'a, 'b> Session<'a, 'b> {
+    /// Create a session to debug the program loaded into `vm`, run with packet data `mem` and
+    /// metadata buffer `mbuff`. Called by the `debug()` functions of the VMs.
+    pub fn new(vm: &'b EbpfVmMbuff<'a>, mem: &'b mut [u8], mbuff: &'b mut [u8])
+               -> Session<'a, 'b> {
+        let state = ExecState::new(vm.prog, mem, mbuff);
+        Session {
+            vm,
+            mem,
+            mbuff,
+            state,
+            breakpoints: BTreeSet::new(),
+            status:      Status::Stopped,
+        }
+    }
+
+    /// Execute the next instruction. Once the program has exited or faulted, this does nothing
+    /// and returns the same status again.
+    pub fn step(&mut self) -> Status {
+        if let Status::Exited(_) | Status::Faulted(_) = self.status {
+            return self.status;
+        }
+        self.status = match self.vm.run(&mut self.state, self.mem, self.mbuff, None, true) {
+            Ok(Some(res)) => Status::Exited(res),
+            Ok(None)      => Status::Stopped,
+            Err(err)      => Status::Faulted(err),
+        };
+        self.status
+    }
+
+    /// Execute instructions until the program exits or faults, or until it reaches a breakpoint.
+    /// At least one instruction is executed, so that the program can be resumed from a
+    /// breakpoint.
+    pub fn cont(&mut self) -> Status {
+        loop {
+            if self.step() != Status::Stopped {
+                return self.status;
+            }
+            if self.state.prog_index.is_none() && self.breakpoints.contains(&self.state.insn_ptr) {
+                self.status = Status::Breakpoint(self.state.insn_ptr);
+                return self.status;
+            }
+        }
+    }
+
+    /// Set a breakpoint before the instruction at index `insn_ptr`.
+    pub fn set_breakpoint(&mut self, insn_ptr: usize) {
+        self.breakpoints.insert(insn_ptr);
+    }
+
+    /// Remove the breakpoint at index `insn_ptr`. Returns `false` if there was no such
+    /// breakpoint.
+    pub fn clear_breakpoint(&mut self, insn_ptr: usize) -> bool {
+        self.breakpoints.remove(&insn_ptr)
+    }
+
+    /// Return the indices of the instructions with a breakpoint, in increasing order.
+    pub fn breakpoints(&self) -> Vec<usize> {
+        self.breakpoints.iter().cloned().collect()
+    }
+
+    /// Return the current state of the session.
+    pub fn status(&self) -> Status {
+        self.status
+    }
+
+    /// Return the index of the next instruction to execute. After the program exited or faulted,
+    /// this is the index of the last instruction executed.
+    pub fn insn_ptr(&self) -> usize {
+        self.state.insn_ptr
+    }
+
+    /// Set the index of the next instruction to execute, in the program being run.
+    ///
+    /// # Panics
+    ///
+    /// This function panics if `insn_ptr` is out of the program.
+    pub fn set_insn_ptr(&mut self, insn_ptr: usize) {
+        if insn_ptr * ebpf::INSN_SIZE >= self.state.prog.len() {
+            panic!("[Debugger] Error: instruction {:?} is out of the program", insn_ptr);
+        }
+        self.state.insn_ptr = insn_ptr;
+    }
+
+    /// Return the program being run.
+    pub fn prog(&self) -> &'a [u8] {
+        self.state.prog
+    }
+
+    /// Return the index in the program array of the program being run, if it was reached with a
+    /// tail call, or `None` for the program loaded into the VM.
+    pub fn prog_index(&self) -> Option<usize> {
+        self.state.prog_index
+    }
+
+    /// Return the next instruction to execute.
+    pub fn next_insn(&self) -> ebpf::Insn {
+        ebpf::get_insn(self.state.prog, self.state.insn_ptr)
+    }
+
+    /// Return the number of instructions executed so far, including a faulting instruction.
+    pub fn insn_count(&self) -> u64 {
+        self.state.insn_count
+    }
+
+    /// Return the values of registers r0 to r10.
+    pub fn regs(&self) -> [u64; 11] {
+        self.state.reg
+    }
+
+    /// Return the value of register `n`.
+    ///
+    /// # Panics
+    ///
+    /// This function panics if `n` is greater than 10.
+    pub fn reg(&self, n: usize) -> u64 {
+        self.state.reg[n]
+    }
+
+    /// Set the value of register `n`. The value of r10 can be changed as well: make sure it
+    /// remains a pointer to the stack.
+    ///
+    /// # Panics
+    ///
+    /// This function panics if `n` is greater than 10.
+    pub fn set_reg(&mut self, n: usize, value: u64) {
+        self.state.reg[n] = value;
+    }
+
+    /// Return the stack of the program. It has room for all call frames, the program loaded into
+    /// the VM using the last `ebpf::STACK_SIZE` bytes.
+    pub fn stack(&self) -> &[u8] {
+        self.state.stack_bytes()
+    }
+
+    /// Return the stack of the program, for modification.
+    pub fn stack_mut(&mut self) -> &mut [u8] {
+        self.state.stack_bytes_mut()
+    }
+
+    /// Return the packet data.
+    pub fn mem(&self) -> &[u8] {
+        self.mem
+    }
+
+    /// Return the packet data, for modification.
+    pub fn mem_mut(&mut self) -> &mut [u8] {
+        self.mem
+    }
+
+    /// Return the metadata buffer.
+    pub fn mbuff(&self) -> &[u8] {
+        self.mbuff
+    }
+
+    /// Return the metadata buffer, for modification.
+    pub fn mbuff_mut(&mut self) -> &mut [u8] {
+        self.mbuff
+    }
+
+    /// Read `len` bytes at address `addr`, as seen by the program.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `OutOfBoundsLoad` error if the bytes do not all fall within the stack, the
+    /// packet data, the metadata buffer or the values of one of the maps of the VM.
+    pub fn read_memory(&self, addr: u64, len: usize) -> Result<Vec<u8>, ExecError> {
+        self.check_mem(addr, len, ExecErrorKind::OutOfBoundsLoad)?;
+        let mut res = vec![0u8; len];
+        unsafe {
+            std::ptr::copy_nonoverlapping(addr as *const u8, res.as_mut_ptr(), len);
+        }
+        Ok(res)
+    }
+
+    /// Write `data` at address `addr`, as seen by the program.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `OutOfBoundsStore` error if the bytes do not all fall within the stack, the
+    /// packet data, the metadata buffer or the values of one of the maps of the VM.
+    pub fn write_memory(&mut self, addr: u64, data: &[u8]) -> Result<(), ExecError> {
+        self.check_mem(addr, data.len(), ExecErrorKind::OutOfBoundsStore)?;
+        unsafe {
+            std::ptr::copy_nonoverlapping(data.as_ptr(), addr as *mut u8, data.len());
+        }
+        Ok(())
+    }
+
+    fn check_mem(&self, addr: u64, len: usize, kind: ExecErrorKind) -> Result<(), ExecError> {
+        EbpfVmMbuff::check_mem(addr, len, kind, self.state.insn_ptr,
+                               &[self.mbuff, self.mem, self.state.stack_bytes()], &self.vm.maps)
+    }
+}
@@ -22,11 +22,16 @@ fn alu_reg_str(name: &str, insn: &ebpf::Insn) -> String {
 
 #[inline]
 fn byteswap_str(name: &str, insn: &ebpf::Insn) -> String {
-    match insn.off {
+    match insn.imm {
         16 | 32 | 64 => {},
-        _ => println!("[Disassembler] Warning: Invalid offset value for {} insn", name)
+        _ => println!("[Disassembler] Warning: Invalid immediate value for {} insn", name)
     }
-    format!("{}{} r{}", name, insn.off, insn.dst)
+    format!("{}{} r{}", name, insn.imm, insn.dst)
+}
+
+#[inline]
+fn ld_reg_str(name: &str, insn: &ebpf::Insn) -> String {
+    format!("{} r{}, [r{}+{:#x}]", name, insn.dst, insn.src, insn.off)
 }
 
 #[inline]
@@ -149,7 +154,7 @@ pub fn to_insn_vec(prog: &[u8]) -> Vec<HLInsn> {
         panic!("[Disassembler] Error: eBPF program length must be a multiple of {:?} octets",
                ebpf::INSN_SIZE);
     }
-    if prog.len() == 0 {
+    if prog.is_empty() {
         return vec![];
     }
 
@@ -179,12 +184,16 @@ pub fn to_insn_vec(prog: &[u8]) -> Vec<HLInsn> {
                 insn_ptr += 1;
                 let next_insn = ebpf::get_insn(prog, insn_ptr);
                 imm = ((insn.imm as u32) as u64 + ((next_insn.imm as u64) << 32)) as i64;
-                name = "lddw"; desc = format!("{} r{:}, {:#x}", name, insn.dst, imm);
+                name = "lddw"; desc = if insn.src == ebpf::BPF_PSEUDO_MAP_FD {
+                    format!("{} r{:}, map_fd({:})", name, insn.dst, insn.imm)
+                } else {
+                    format!("{} r{:}, {:#x}", name, insn.dst, imm)
+                };
             },
-            ebpf::LD_B_REG   => { name = "ldxb";  desc = ld_st_reg_str(name, &insn); },
-            ebpf::LD_H_REG   => { name = "ldxh";  desc = ld_st_reg_str(name, &insn); },
-            ebpf::LD_W_REG   => { name = "ldxw";  desc = ld_st_reg_str(name, &insn); },
-            ebpf::LD_DW_REG  => { name = "ldxdw"; desc = ld_st_reg_str(name, &insn); },
+            ebpf::LD_B_REG   => { name = "ldxb";  desc = ld_reg_str(name, &insn); },
+            ebpf::LD_H_REG   => { name = "ldxh";  desc = ld_reg_str(name, &insn); },
+            ebpf::LD_W_REG   => { name = "ldxw";  desc = ld_reg_str(name, &insn); },
+            ebpf::LD_DW_REG  => { name = "ldxdw"; desc = ld_reg_str(name, &insn); },
 
             // BPF_ST class
             ebpf::ST_B_IMM   => { name = "stb";  desc = ld_st_imm_str(name, &insn); },
@@ -272,6 +281,42 @@ pub fn to_insn_vec(prog: &[u8]) -> Vec<HLInsn> {
             ebpf::JSGT_REG   => { name = "jsgt"; desc = jmp_reg_str(name, &insn); },
             ebpf::JSGE_IMM   => { name = "jsge"; desc = jmp_imm_str(name, &insn); },
             ebpf::JSGE_REG   => { name = "jsge"; desc = jmp_reg_str(name, &insn); },
+            ebpf::JLT_IMM    => { name = "jlt";  desc = jmp_imm_str(name, &insn); },
+            ebpf::JLT_REG    => { name = "jlt";  desc = jmp_reg_str(name, &insn); },
+            ebpf::JLE_IMM    => { name = "jle";  desc = jmp_imm_str(name, &insn); },
+            ebpf::JLE_REG    => { name = "jle";  desc = jmp_reg_str(name, &insn); },
+            ebpf::JSLT_IMM   => { name = "jslt"; desc = jmp_imm_str(name, &insn); },
+            ebpf::JSLT_REG   => { name = "jslt"; desc = jmp_reg_str(name, &insn); },
+            ebpf::JSLE_IMM   => { name = "jsle"; desc = jmp_imm_str(name, &insn); },
+            ebpf::JSLE_REG   => { name = "jsle"; desc = jmp_reg_str(name, &insn); },
+
+            // BPF_JMP32 class
+            ebpf::JEQ_IMM32  => { name = "jeq32";  desc = jmp_imm_str(name, &insn); },
+            ebpf::JEQ_REG32  => { name = "jeq32";  desc = jmp_reg_str(name, &insn); },
+            ebpf::JGT_IMM32  => { name = "jgt32";  desc = jmp_imm_str(name, &insn); },
+            ebpf::JGT_REG32  => { name = "jgt32";  desc = jmp_reg_str(name, &insn); },
+            ebpf::JGE_IMM32  => { name = "jge32";  desc = jmp_imm_str(name, &insn); },
+            ebpf::JGE_REG32  => { name = "jge32";  desc = jmp_reg_str(name, &insn); },
+            ebpf::JSET_IMM32 => { name = "jset32"; desc = jmp_imm_str(name, &insn); },
+            ebpf::JSET_REG32 => { name = "jset32"; desc = jmp_reg_str(name, &insn); },
+            ebpf::JNE_IMM32  => { name = "jne32";  desc = jmp_imm_str(name, &insn); },
+            ebpf::JNE_REG32  => { name = "jne32";  desc = jmp_reg_str(name, &insn); },
+            ebpf::JSGT_IMM32 => { name = "jsgt32"; desc = jmp_imm_str(name, &insn); },
+            ebpf::JSGT_REG32 => { name = "jsgt32"; desc = jmp_reg_str(name, &insn); },
+            ebpf::JSGE_IMM32 => { name = "jsge32"; desc = jmp_imm_str(name, &insn); },
+            ebpf::JSGE_REG32 => { name = "jsge32"; desc = jmp_reg_str(name, &insn); },
+            ebpf::JLT_IMM32  => { name = "jlt32";  desc = jmp_imm_str(name, &insn); },
+            ebpf::JLT_REG32  => { name = "jlt32";  desc = jmp_reg_str(name, &insn); },
+            ebpf::JLE_IMM32  => { name = "jle32";  desc = jmp_imm_str(name, &insn); },
+            ebpf::JLE_REG32  => { name = "jle32";  desc = jmp_reg_str(name, &insn); },
+            ebpf::JSLT_IMM32 => { name = "jslt32"; desc = jmp_imm_str(name, &insn); },
+            ebpf::JSLT_REG32 => { name = "jslt32"; desc = jmp_reg_str(name, &insn); },
+            ebpf::JSLE_IMM32 => { name = "jsle32"; desc = jmp_imm_str(name, &insn); },
+            ebpf::JSLE_REG32 => { name = "jsle32"; desc = jmp_reg_str(name, &insn); },
+
+            ebpf::CALL if insn.src == ebpf::BPF_PSEUDO_CALL => {
+                name = "call_local"; desc = format!("{} {:+#x}", name, insn.imm);
+            },
             ebpf::CALL       => { name = "call"; desc = format!("{} {:#x}", name, insn.imm); },
             ebpf::TAIL_CALL  => { name = "tail_call"; desc = name.to_string(); },
             ebpf::EXIT       => { name = "exit";      desc = name.to_string(); },
@@ -285,11 +330,11 @@ pub fn to_insn_vec(prog: &[u8]) -> Vec<HLInsn> {
         let hl_insn = HLInsn {
             opc:  insn.opc,
             name: name.to_string(),
-            desc: desc,
+            desc,
             dst:  insn.dst,
             src:  insn.src,
             off:  insn.off,
-            imm:  imm,
+            imm,
         };
 
         res.push(hl_insn);
@@ -311,7 +356,7 @@ pub fn to_insn_vec(prog: &[u8]) -> Vec<HLInsn> {
 ///     0x07, 0x01, 0x00, 0x00, 0x05, 0x06, 0x00, 0x00,
 ///     0xb7, 0x02, 0x00, 0x00, 0x32, 0x00, 0x00, 0x00,
 ///     0xbf, 0x01, 0x02, 0x00, 0x00, 0x00, 0x00, 0x00,
-///     0xdc, 0x00, 0x10, 0x00, 0x00, 0x00, 0x00, 0x00,
+///     0xdc, 0x00, 0x00, 0x00, 0x10, 0x00, 0x00, 0x00,
 ///     0x87, 0x08, 0x10, 0x00, 0x00, 0x00, 0x00, 0x00,
 ///     0x95, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00
 /// ];
@@ -333,7 +378,7 @@ pub fn disassemble(prog: &[u8]) {
         panic!("[Disassembler] Error: eBPF program length must be a multiple of {:?} octets",
                ebpf::INSN_SIZE);
     }
-    if prog.len() == 0 {
+    if prog.is_empty() {
         return;
     }
 
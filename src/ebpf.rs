@@ -26,6 +26,11 @@ pub const INSN_SIZE: usize = 8;
 pub const PROG_MAX_SIZE: usize = PROG_MAX_INSNS * INSN_SIZE;
 /// Stack for the eBPF stack, in bytes.
 pub const STACK_SIZE: usize = 512;
+/// Maximum number of nested call frames, including the one of the main program. Each frame has
+/// its own stack of `STACK_SIZE` bytes.
+pub const MAX_CALL_DEPTH: usize = 8;
+/// Default maximum number of tail calls in a row, same value as in the kernel.
+pub const MAX_TAIL_CALLS: usize = 33;
 
 // eBPF op codes.
 // See also https://www.kernel.org/doc/Documentation/networking/filter.txt
@@ -43,7 +48,8 @@ pub const BPF_STX   : u8 = 0x03;
 pub const BPF_ALU   : u8 = 0x04;
 /// BPF operation class: jump.
 pub const BPF_JMP   : u8 = 0x05;
-// [ class 6 unused, reserved for future use ]
+/// BPF operation class: jump, comparing the lower 32 bits of the operands.
+pub const BPF_JMP32 : u8 = 0x06;
 /// BPF operation class: 64 bits arithmetic operation.
 pub const BPF_ALU64 : u8 = 0x07;
 
@@ -138,6 +144,14 @@ pub const BPF_JNE   : u8 = 0x50;
 pub const BPF_JSGT  : u8 = 0x60;
 /// BPF JMP operation code: jump if greater or equal (signed).
 pub const BPF_JSGE  : u8 = 0x70;
+/// BPF JMP operation code: jump if lower than.
+pub const BPF_JLT   : u8 = 0xa0;
+/// BPF JMP operation code: jump if lower or equal.
+pub const BPF_JLE   : u8 = 0xb0;
+/// BPF JMP operation code: jump if lower than (signed).
+pub const BPF_JSLT  : u8 = 0xc0;
+/// BPF JMP operation code: jump if lower or equal (signed).
+pub const BPF_JSLE  : u8 = 0xd0;
 /// BPF JMP operation code: helper function call.
 pub const BPF_CALL  : u8 = 0x80;
 /// BPF JMP operation code: return from program.
@@ -164,7 +178,8 @@ pub const LD_IND_W   : u8 = BPF_LD    | BPF_IND | BPF_W;
 /// BPF opcode: `ldinddw src, dst, imm`.
 pub const LD_IND_DW  : u8 = BPF_LD    | BPF_IND | BPF_DW;
 
-/// BPF opcode: `lddw dst, imm` /// `dst = imm`.
+/// BPF opcode: `lddw dst, imm` /// `dst = imm`, or a handle to the map with file descriptor
+/// `imm` if `src` is `BPF_PSEUDO_MAP_FD`.
 pub const LD_DW_IMM  : u8 = BPF_LD    | BPF_IMM | BPF_DW;
 /// BPF opcode: `ldxb dst, [src + off]` /// `dst = (src + off) as u8`.
 pub const LD_B_REG   : u8 = BPF_LDX   | BPF_MEM | BPF_B;
@@ -341,10 +356,83 @@ pub const JSGT_REG   : u8 = BPF_JMP   | BPF_X   | BPF_JSGT;
 pub const JSGE_IMM   : u8 = BPF_JMP   | BPF_K   | BPF_JSGE;
 /// BPF opcode: `jsge dst, src, +off` /// `PC += off if dst >= src (signed)`.
 pub const JSGE_REG   : u8 = BPF_JMP   | BPF_X   | BPF_JSGE;
+/// BPF opcode: `jlt dst, imm, +off` /// `PC += off if dst < imm`.
+pub const JLT_IMM    : u8 = BPF_JMP   | BPF_K   | BPF_JLT;
+/// BPF opcode: `jlt dst, src, +off` /// `PC += off if dst < src`.
+pub const JLT_REG    : u8 = BPF_JMP   | BPF_X   | BPF_JLT;
+/// BPF opcode: `jle dst, imm, +off` /// `PC += off if dst <= imm`.
+pub const JLE_IMM    : u8 = BPF_JMP   | BPF_K   | BPF_JLE;
+/// BPF opcode: `jle dst, src, +off` /// `PC += off if dst <= src`.
+pub const JLE_REG    : u8 = BPF_JMP   | BPF_X   | BPF_JLE;
+/// BPF opcode: `jslt dst, imm, +off` /// `PC += off if dst < imm (signed)`.
+pub const JSLT_IMM   : u8 = BPF_JMP   | BPF_K   | BPF_JSLT;
+/// BPF opcode: `jslt dst, src, +off` /// `PC += off if dst < src (signed)`.
+pub const JSLT_REG   : u8 = BPF_JMP   | BPF_X   | BPF_JSLT;
+/// BPF opcode: `jsle dst, imm, +off` /// `PC += off if dst <= imm (signed)`.
+pub const JSLE_IMM   : u8 = BPF_JMP   | BPF_K   | BPF_JSLE;
+/// BPF opcode: `jsle dst, src, +off` /// `PC += off if dst <= src (signed)`.
+pub const JSLE_REG   : u8 = BPF_JMP   | BPF_X   | BPF_JSLE;
 
-/// BPF opcode: `call imm` /// helper function call to helper with key `imm`.
+// BPF_JMP32 class: same as the BPF_JMP conditional jumps, but only the lower 32 bits of the
+// operands are compared.
+/// BPF opcode: `jeq32 dst, imm, +off` /// `PC += off if (dst as u32) == (imm as u32)`.
+pub const JEQ_IMM32  : u8 = BPF_JMP32 | BPF_K   | BPF_JEQ;
+/// BPF opcode: `jeq32 dst, src, +off` /// `PC += off if (dst as u32) == (src as u32)`.
+pub const JEQ_REG32  : u8 = BPF_JMP32 | BPF_X   | BPF_JEQ;
+/// BPF opcode: `jgt32 dst, imm, +off` /// `PC += off if (dst as u32) > (imm as u32)`.
+pub const JGT_IMM32  : u8 = BPF_JMP32 | BPF_K   | BPF_JGT;
+/// BPF opcode: `jgt32 dst, src, +off` /// `PC += off if (dst as u32) > (src as u32)`.
+pub const JGT_REG32  : u8 = BPF_JMP32 | BPF_X   | BPF_JGT;
+/// BPF opcode: `jge32 dst, imm, +off` /// `PC += off if (dst as u32) >= (imm as u32)`.
+pub const JGE_IMM32  : u8 = BPF_JMP32 | BPF_K   | BPF_JGE;
+/// BPF opcode: `jge32 dst, src, +off` /// `PC += off if (dst as u32) >= (src as u32)`.
+pub const JGE_REG32  : u8 = BPF_JMP32 | BPF_X   | BPF_JGE;
+/// BPF opcode: `jset32 dst, imm, +off` /// `PC += off if (dst as u32) & (imm as u32)`.
+pub const JSET_IMM32 : u8 = BPF_JMP32 | BPF_K   | BPF_JSET;
+/// BPF opcode: `jset32 dst, src, +off` /// `PC += off if (dst as u32) & (src as u32)`.
+pub const JSET_REG32 : u8 = BPF_JMP32 | BPF_X   | BPF_JSET;
+/// BPF opcode: `jne32 dst, imm, +off` /// `PC += off if (dst as u32) != (imm as u32)`.
+pub const JNE_IMM32  : u8 = BPF_JMP32 | BPF_K   | BPF_JNE;
+/// BPF opcode: `jne32 dst, src, +off` /// `PC += off if (dst as u32) != (src as u32)`.
+pub const JNE_REG32  : u8 = BPF_JMP32 | BPF_X   | BPF_JNE;
+/// BPF opcode: `jsgt32 dst, imm, +off` /// `PC += off if (dst as i32) > (imm as i32)`.
+pub const JSGT_IMM32 : u8 = BPF_JMP32 | BPF_K   | BPF_JSGT;
+/// BPF opcode: `jsgt32 dst, src, +off` /// `PC += off if (dst as i32) > (src as i32)`.
+pub const JSGT_REG32 : u8 = BPF_JMP32 | BPF_X   | BPF_JSGT;
+/// BPF opcode: `jsge32 dst, imm, +off` /// `PC += off if (dst as i32) >= (imm as i32)`.
+pub const JSGE_IMM32 : u8 = BPF_JMP32 | BPF_K   | BPF_JSGE;
+/// BPF opcode: `jsge32 dst, src, +off` /// `PC += off if (dst as i32) >= (src as i32)`.
+pub const JSGE_REG32 : u8 = BPF_JMP32 | BPF_X   | BPF_JSGE;
+/// BPF opcode: `jlt32 dst, imm, +off` /// `PC += off if (dst as u32) < (imm as u32)`.
+pub const JLT_IMM32  : u8 = BPF_JMP32 | BPF_K   | BPF_JLT;
+/// BPF opcode: `jlt32 dst, src, +off` /// `PC += off if (dst as u32) < (src as u32)`.
+pub const JLT_REG32  : u8 = BPF_JMP32 | BPF_X   | BPF_JLT;
+/// BPF opcode: `jle32 dst, imm, +off` /// `PC += off if (dst as u32) <= (imm as u32)`.
+pub const JLE_IMM32  : u8 = BPF_JMP32 | BPF_K   | BPF_JLE;
+/// BPF opcode: `jle32 dst, src, +off` /// `PC += off if (dst as u32) <= (src as u32)`.
+pub const JLE_REG32  : u8 = BPF_JMP32 | BPF_X   | BPF_JLE;
+/// BPF opcode: `jslt32 dst, imm, +off` /// `PC += off if (dst as i32) < (imm as i32)`.
+pub const JSLT_IMM32 : u8 = BPF_JMP32 | BPF_K   | BPF_JSLT;
+/// BPF opcode: `jslt32 dst, src, +off` /// `PC += off if (dst as i32) < (src as i32)`.
+pub const JSLT_REG32 : u8 = BPF_JMP32 | BPF_X   | BPF_JSLT;
+/// BPF opcode: `jsle32 dst, imm, +off` /// `PC += off if (dst as i32) <= (imm as i32)`.
+pub const JSLE_IMM32 : u8 = BPF_JMP32 | BPF_K   | BPF_JSLE;
+/// BPF opcode: `jsle32 dst, src, +off` /// `PC += off if (dst as i32) <= (src as i32)`.
+pub const JSLE_REG32 : u8 = BPF_JMP32 | BPF_X   | BPF_JSLE;
+
+/// Value of the `src` field of a `lddw` instruction loading a handle to the map whose file
+/// descriptor is in `imm`, instead of an immediate value.
+pub const BPF_PSEUDO_MAP_FD : u8 = 1;
+
+/// Value of the `src` field of a `call` instruction calling a local function of the program
+/// instead of a helper, with `imm` the offset to the function (relative to the next instruction).
+pub const BPF_PSEUDO_CALL : u8 = 1;
+
+/// BPF opcode: `call imm` /// helper function call to helper with key `imm`, or local function
+/// call if `src` is `BPF_PSEUDO_CALL`.
 pub const CALL       : u8 = BPF_JMP   | BPF_CALL;
-/// BPF opcode: tail call.
+/// BPF opcode: tail call /// jump to the program at index `r3` in the program array, keeping the
+/// context in `r1` (the kernel also passes the array itself in `r2`).
 pub const TAIL_CALL  : u8 = BPF_JMP   | BPF_X | BPF_CALL;
 /// BPF opcode: `exit` /// `return r0`.
 pub const EXIT       : u8 = BPF_JMP   | BPF_EXIT;
@@ -488,10 +576,10 @@ pub fn get_insn(prog: &[u8], idx: usize) -> Insn {
         dst:  prog[INSN_SIZE * idx + 1] & 0x0f,
         src: (prog[INSN_SIZE * idx + 1] & 0xf0) >> 4,
         off: unsafe {
-            let x = prog.as_ptr().offset((INSN_SIZE * idx + 2) as isize) as *const i16; *x
+            let x = prog.as_ptr().add(INSN_SIZE * idx + 2) as *const i16; x.read_unaligned()
         },
         imm: unsafe {
-            let x = prog.as_ptr().offset((INSN_SIZE * idx + 4) as isize) as *const i32; *x
+            let x = prog.as_ptr().add(INSN_SIZE * idx + 4) as *const i32; x.read_unaligned()
         },
     }
 }
@@ -0,0 +1,487 @@
+// Copyright 2026 agent <agent@local>
+//
+// Licensed under the Apache License, Version 2.0 <http://www.apache.org/licenses/LICENSE-2.0> or
+// the MIT license <http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+
+//! This module contains the error types returned by the library.
+//!
+//! `EbpfError` is returned when a program is rejected at load time, for instance by the verifier
+//! when calling `new()` or `set_prog()` on one of the virtual machines.
+//!
+//! `ExecError` is returned when the interpreter encounters a fault while running a program, for
+//! instance an out of bounds memory access or a division by zero.
+//!
+//! `JitError` is returned when the JIT compiler cannot produce executable code for a program.
+//!
+//! `AsmError` is returned by the assembler when it cannot translate its input into bytecode.
+//!
+//! `MapError` is returned by the operations on eBPF maps.
+//!
+//! `ElfError` is returned by the loader when it cannot extract programs from an object file.
+
+use std::error::Error;
+use std::fmt;
+
+use ebpf;
+use libc;
+
+/// Error returned when an eBPF program cannot be loaded.
+///
+/// Most variants carry the index (number) of the faulty instruction, as `insn_ptr`. The first
+/// instruction of the program has index 0.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EbpfError {
+    /// The program length, in bytes, is not a multiple of `ebpf::INSN_SIZE`.
+    WrongLength(usize),
+    /// The program has more instructions than `ebpf::PROG_MAX_INSNS`. Contains the number of
+    /// instructions.
+    TooManyInstructions(usize),
+    /// The program does not end with an `EXIT` instruction.
+    NoExit,
+    /// Division or modulo by an immediate value of zero (`insn_ptr`).
+    DivisionByZero(usize),
+    /// Unsupported size argument for a `LE` or `BE` instruction (`insn_ptr`).
+    UnsupportedEndianSize(usize),
+    /// `LD_DW_IMM` instruction not followed by its second half (`insn_ptr`).
+    IncompleteLdDw(usize),
+    /// Jump to itself, resulting in an infinite loop (`insn_ptr`).
+    InfiniteLoop(usize),
+    /// Jump outside of the program (`target`, `insn_ptr`).
+    JumpOutOfCode(isize, usize),
+    /// Jump to the second half of a `LD_DW_IMM` instruction (`target`, `insn_ptr`).
+    JumpToMiddleOfLdDw(usize, usize),
+    /// Invalid source register (`insn_ptr`).
+    InvalidSrcRegister(usize),
+    /// Invalid destination register (`insn_ptr`).
+    InvalidDstRegister(usize),
+    /// Attempt to write into read-only register r10 (`insn_ptr`).
+    CannotWriteR10(usize),
+    /// Unknown operation code (`opc`, `insn_ptr`).
+    UnknownOpcode(u8, usize),
+    /// Jump or local call back to an instruction already on the path, creating a loop (`target`,
+    /// `insn_ptr`).
+    Loop(usize, usize),
+    /// Instruction that cannot be reached from the entry point of the program (`insn_ptr`).
+    UnreachableCode(usize),
+    /// Execution can go past the end of the program or of a local function without reaching an
+    /// `EXIT` instruction (`insn_ptr` of the last instruction of the function).
+    FallOffEnd(usize),
+    /// Read of a register that has not been initialized on some path leading to the instruction
+    /// (`reg`, `insn_ptr`).
+    UninitializedRegister(u8, usize),
+    /// Arithmetic operation that would forge a pointer, or turn a pointer into a number
+    /// (`insn_ptr`).
+    PointerArithmetic(usize),
+    /// Memory access through a register that does not hold a valid pointer (`reg`, `insn_ptr`).
+    InvalidMemoryAccess(u8, usize),
+    /// Memory access that may be out of the bounds of the region the pointer points to, for
+    /// instance to packet data that has not been checked against the end of the packet (`reg`,
+    /// `insn_ptr`).
+    OutOfBoundsAccess(u8, usize),
+    /// Read of bytes of the stack that have not been written on some path leading to the
+    /// instruction (`offset` of the access from `r10`, `insn_ptr`).
+    UninitializedStackRead(i64, usize),
+    /// Load of a map that is not known to the analysis (`fd`, `insn_ptr`).
+    UnknownMap(u32, usize),
+    /// Argument of the wrong type passed to a map helper, for instance a number instead of a
+    /// pointer to the stack (`reg`, `insn_ptr`).
+    InvalidHelperArgument(u8, usize),
+    /// The analysis of the program processed more than `analysis::MAX_PROCESSED_INSNS`
+    /// instructions (`insn_ptr` of the instruction being processed).
+    TooComplex(usize),
+}
+
+impl fmt::Display for EbpfError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            EbpfError::WrongLength(_) =>
+                write!(f, "[Verifier] Error: eBPF program length must be a multiple of {:?} octets",
+                       ebpf::INSN_SIZE),
+            EbpfError::TooManyInstructions(n) =>
+                write!(f, "[Verifier] Error: eBPF program length limited to {:?}, here {:?}",
+                       ebpf::PROG_MAX_INSNS, n),
+            EbpfError::NoExit =>
+                write!(f, "[Verifier] Error: program does not end with “EXIT” instruction"),
+            EbpfError::DivisionByZero(pc) =>
+                write!(f, "[Verifier] Error: division by 0 (insn #{:?})", pc),
+            EbpfError::UnsupportedEndianSize(pc) =>
+                write!(f, "[Verifier] Error: unsupported argument for LE/BE (insn #{:?})", pc),
+            EbpfError::IncompleteLdDw(pc) =>
+                write!(f, "[Verifier] Error: incomplete LD_DW instruction (insn #{:?})", pc),
+            EbpfError::InfiniteLoop(pc) =>
+                write!(f, "[Verifier] Error: infinite loop (insn #{:?})", pc),
+            EbpfError::JumpOutOfCode(target, pc) =>
+                write!(f, "[Verifier] Error: jump out of code to #{:?} (insn #{:?})", target, pc),
+            EbpfError::JumpToMiddleOfLdDw(target, pc) =>
+                write!(f, "[Verifier] Error: jump to middle of LD_DW at #{:?} (insn #{:?})",
+                       target, pc),
+            EbpfError::InvalidSrcRegister(pc) =>
+                write!(f, "[Verifier] Error: invalid source register (insn #{:?})", pc),
+            EbpfError::InvalidDstRegister(pc) =>
+                write!(f, "[Verifier] Error: invalid destination register (insn #{:?})", pc),
+            EbpfError::CannotWriteR10(pc) =>
+                write!(f, "[Verifier] Error: cannot write into register r10 (insn #{:?})", pc),
+            EbpfError::UnknownOpcode(opc, pc) =>
+                write!(f, "[Verifier] Error: unknown eBPF opcode {:#2x} (insn #{:?})", opc, pc),
+            EbpfError::Loop(target, pc) =>
+                write!(f, "[Verifier] Error: loop, back-edge to #{:?} (insn #{:?})", target, pc),
+            EbpfError::UnreachableCode(pc) =>
+                write!(f, "[Verifier] Error: unreachable instruction (insn #{:?})", pc),
+            EbpfError::FallOffEnd(pc) =>
+                write!(f, "[Verifier] Error: falling off the end of the function (insn #{:?})", pc),
+            EbpfError::UninitializedRegister(reg, pc) =>
+                write!(f, "[Verifier] Error: read of uninitialized register r{:?} (insn #{:?})",
+                       reg, pc),
+            EbpfError::PointerArithmetic(pc) =>
+                write!(f, "[Verifier] Error: invalid arithmetic on pointer (insn #{:?})", pc),
+            EbpfError::InvalidMemoryAccess(reg, pc) =>
+                write!(f, "[Verifier] Error: invalid memory access through r{:?} (insn #{:?})",
+                       reg, pc),
+            EbpfError::OutOfBoundsAccess(reg, pc) =>
+                write!(f, "[Verifier] Error: memory access through r{:?} may be out of bounds \
+                           (insn #{:?})", reg, pc),
+            EbpfError::UninitializedStackRead(off, pc) =>
+                write!(f, "[Verifier] Error: read of uninitialized stack at r10{:+} (insn #{:?})",
+                       off, pc),
+            EbpfError::UnknownMap(fd, pc) =>
+                write!(f, "[Verifier] Error: unknown map (fd: {:?}) (insn #{:?})", fd, pc),
+            EbpfError::InvalidHelperArgument(reg, pc) =>
+                write!(f, "[Verifier] Error: invalid argument r{:?} for helper function \
+                           (insn #{:?})", reg, pc),
+            EbpfError::TooComplex(pc) =>
+                write!(f, "[Verifier] Error: program too complex to analyze (insn #{:?})", pc),
+        }
+    }
+}
+
+impl EbpfError {
+    /// Return the index of the faulty instruction, or `None` if the error is about the program as
+    /// a whole (its length, or the lack of a final `EXIT` instruction).
+    pub fn insn_ptr(&self) -> Option<usize> {
+        match *self {
+            EbpfError::WrongLength(_)                |
+            EbpfError::TooManyInstructions(_)        |
+            EbpfError::NoExit                        => None,
+            EbpfError::DivisionByZero(pc)            |
+            EbpfError::UnsupportedEndianSize(pc)     |
+            EbpfError::IncompleteLdDw(pc)            |
+            EbpfError::InfiniteLoop(pc)              |
+            EbpfError::JumpOutOfCode(_, pc)          |
+            EbpfError::JumpToMiddleOfLdDw(_, pc)     |
+            EbpfError::InvalidSrcRegister(pc)        |
+            EbpfError::InvalidDstRegister(pc)        |
+            EbpfError::CannotWriteR10(pc)            |
+            EbpfError::UnknownOpcode(_, pc)          |
+            EbpfError::Loop(_, pc)                   |
+            EbpfError::UnreachableCode(pc)           |
+            EbpfError::FallOffEnd(pc)                |
+            EbpfError::UninitializedRegister(_, pc)  |
+            EbpfError::PointerArithmetic(pc)         |
+            EbpfError::InvalidMemoryAccess(_, pc)    |
+            EbpfError::OutOfBoundsAccess(_, pc)      |
+            EbpfError::UninitializedStackRead(_, pc) |
+            EbpfError::UnknownMap(_, pc)             |
+            EbpfError::InvalidHelperArgument(_, pc)  |
+            EbpfError::TooComplex(pc)                => Some(pc),
+        }
+    }
+}
+
+impl Error for EbpfError {}
+
+/// Kind of fault encountered by the interpreter when running an eBPF program.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExecErrorKind {
+    /// Load from memory outside of the packet data, the metadata buffer and the stack.
+    OutOfBoundsLoad,
+    /// Store to memory outside of the packet data, the metadata buffer and the stack.
+    OutOfBoundsStore,
+    /// Atomic operation on an address that is not aligned on the size of the operand.
+    UnalignedAtomic,
+    /// Division or modulo by a register containing zero.
+    DivisionByZero,
+    /// Local function call nesting more than `ebpf::MAX_CALL_DEPTH` frames.
+    CallDepthExceeded,
+    /// Call to a helper function that has not been registered (helper id).
+    UnknownHelper(u32),
+    /// Load of a map handle with a file descriptor that has not been registered (map fd).
+    UnknownMap(u32),
+    /// Call to a map helper whose first argument is not the handle of one of the registered maps.
+    InvalidMapPointer,
+    /// The metadata buffer is too small to hold the pointers to packet data at the offsets
+    /// provided by the user.
+    BufferTooSmall,
+    /// The program has executed as many instructions as allowed by the budget set with
+    /// `set_insn_budget()`, and attempted to execute one more.
+    InstructionBudgetExceeded,
+}
+
+/// Error returned when the execution of an eBPF program is aborted by the interpreter.
+///
+/// # Examples
+///
+/// ```
+/// use rbpf::error::{ExecError, ExecErrorKind};
+///
+/// let prog = &[
+///     0x72, 0x0a, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // stb [r10], 0
+///     0x95, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00  // exit
+/// ];
+///
+/// let vm = rbpf::EbpfVmNoData::new(prog).unwrap();
+///
+/// // The program writes right after the end of the stack.
+/// let err: ExecError = vm.prog_exec().unwrap_err();
+/// assert_eq!(err.kind, ExecErrorKind::OutOfBoundsStore);
+/// assert_eq!(err.insn_ptr, 0);
+/// assert_eq!(err.size, 1);
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ExecError {
+    /// The kind of fault.
+    pub kind:     ExecErrorKind,
+    /// Index of the faulting instruction, 0 being the first instruction of the program.
+    pub insn_ptr: usize,
+    /// Address of the faulting memory access, or 0 if the fault is not a memory access. For
+    /// `BufferTooSmall`, this is the offset in the metadata buffer at which a pointer could not be
+    /// stored. For `InvalidMapPointer`, this is the value passed as map handle.
+    pub addr:     u64,
+    /// Size, in bytes, of the faulting memory access, or 0 if the fault is not a memory access.
+    /// For `BufferTooSmall`, this is the length of the metadata buffer.
+    pub size:     usize,
+}
+
+impl ExecError {
+    /// Create an error that is not related to a memory access.
+    pub fn new(kind: ExecErrorKind, insn_ptr: usize) -> ExecError {
+        ExecError { kind, insn_ptr, addr: 0, size: 0 }
+    }
+
+    /// Create an error for a memory access of `size` bytes at address `addr`.
+    pub fn mem(kind: ExecErrorKind, insn_ptr: usize, addr: u64, size: usize) -> ExecError {
+        ExecError { kind, insn_ptr, addr, size }
+    }
+}
+
+impl fmt::Display for ExecError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.kind {
+            ExecErrorKind::OutOfBoundsLoad =>
+                write!(f, "Error: out of bounds memory load (insn #{:?}), addr {:#x}, size {:?}",
+                       self.insn_ptr, self.addr, self.size),
+            ExecErrorKind::OutOfBoundsStore =>
+                write!(f, "Error: out of bounds memory store (insn #{:?}), addr {:#x}, size {:?}",
+                       self.insn_ptr, self.addr, self.size),
+            ExecErrorKind::UnalignedAtomic =>
+                write!(f, "Error: unaligned atomic operation (insn #{:?}), addr {:#x}, size {:?}",
+                       self.insn_ptr, self.addr, self.size),
+            ExecErrorKind::CallDepthExceeded =>
+                write!(f, "Error: exceeded maximum call depth of {:?} (insn #{:?})",
+                       ebpf::MAX_CALL_DEPTH, self.insn_ptr),
+            ExecErrorKind::DivisionByZero =>
+                write!(f, "Error: division by 0 (insn #{:?})", self.insn_ptr),
+            ExecErrorKind::UnknownHelper(id) =>
+                write!(f, "Error: unknown helper function (id: {:#x}) (insn #{:?})",
+                       id, self.insn_ptr),
+            ExecErrorKind::UnknownMap(fd) =>
+                write!(f, "Error: unknown map (fd: {:?}) (insn #{:?})", fd, self.insn_ptr),
+            ExecErrorKind::InvalidMapPointer =>
+                write!(f, "Error: invalid map handle {:#x} passed to helper (insn #{:?})",
+                       self.addr, self.insn_ptr),
+            ExecErrorKind::BufferTooSmall =>
+                write!(f, "Error: buffer too small ({:?}), cannot store pointer at offset {:?}",
+                       self.size, self.addr),
+            ExecErrorKind::InstructionBudgetExceeded =>
+                write!(f, "Error: instruction budget exhausted (insn #{:?})", self.insn_ptr),
+        }
+    }
+}
+
+impl Error for ExecError {}
+
+/// Error returned when the JIT compiler cannot produce executable code for a program.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum JitError {
+    /// The generated code is larger than what the JIT compiler supports (size in bytes).
+    CodeTooLarge(usize),
+    /// Executable memory for the generated code could not be obtained (`errno`).
+    MemoryAllocation(i32),
+    /// There is no JIT compiler for the architecture the library was built for.
+    UnsupportedArchitecture,
+    /// A `LD_DW_IMM` instruction loads the handle of a map that has not been registered into the
+    /// VM (`fd`, `insn_ptr`).
+    UnknownMap(u32, usize),
+    /// A `CALL` instruction calls a helper function that has not been registered into the VM
+    /// (`id`, `insn_ptr`).
+    UnknownHelper(u32, usize),
+    /// Unknown operation code, in a program accepted by a custom verifier (`opc`, `insn_ptr`).
+    UnknownOpcode(u8, usize),
+}
+
+impl fmt::Display for JitError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            JitError::CodeTooLarge(size) =>
+                write!(f, "[JIT] Error: generated code too large ({:?} bytes)", size),
+            JitError::MemoryAllocation(errno) =>
+                write!(f, "[JIT] Error: cannot allocate executable memory (errno {:?})", errno),
+            JitError::UnsupportedArchitecture =>
+                write!(f, "[JIT] Error: no JIT compiler for this architecture"),
+            JitError::UnknownMap(fd, pc) =>
+                write!(f, "[JIT] Error: unknown map (fd: {:?}) (insn #{:?})", fd, pc),
+            JitError::UnknownHelper(id, pc) =>
+                write!(f, "[JIT] Error: unknown helper function (id: {:#x}) (insn #{:?})", id, pc),
+            JitError::UnknownOpcode(opc, pc) =>
+                write!(f, "[JIT] Error: unknown eBPF opcode {:#2x} (insn #{:?})", opc, pc),
+        }
+    }
+}
+
+impl Error for JitError {}
+
+/// Error returned when an eBPF program in assembly language cannot be assembled.
+///
+/// Lines and columns are counted from 1. The column points to the first character of the faulty
+/// token, or to the end of the line if a token is missing.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AsmError {
+    /// Line of the faulty token.
+    pub line:   usize,
+    /// Column of the faulty token.
+    pub column: usize,
+    /// Description of the error.
+    pub msg:    String,
+}
+
+impl fmt::Display for AsmError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "[Assembler] Error: {} (line {:?}, column {:?})", self.msg, self.line, self.column)
+    }
+}
+
+impl Error for AsmError {}
+
+/// Error returned by the operations on eBPF maps.
+///
+/// The helpers accessing the maps from eBPF programs return the opposite of `errno()` instead.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MapError {
+    /// Invalid key size (size).
+    InvalidKeySize(usize),
+    /// Invalid value size (size).
+    InvalidValueSize(usize),
+    /// Invalid maximum number of entries (number).
+    InvalidMaxEntries(usize),
+    /// Invalid flags for an update (flags).
+    InvalidFlags(u64),
+    /// No element is associated to the key.
+    KeyNotFound,
+    /// An element is already associated to the key.
+    KeyExists,
+    /// The key is out of the bounds of an array map.
+    KeyOutOfRange,
+    /// The maximum number of elements is reached.
+    MapFull,
+    /// The operation is not supported by this type of map.
+    NotSupported,
+}
+
+impl MapError {
+    /// Return the error code used by the kernel helpers for this error.
+    pub fn errno(&self) -> i64 {
+        match *self {
+            MapError::InvalidKeySize(_)    |
+            MapError::InvalidValueSize(_)  |
+            MapError::InvalidMaxEntries(_) |
+            MapError::InvalidFlags(_)      |
+            MapError::NotSupported         => libc::EINVAL as i64,
+            MapError::KeyNotFound          => libc::ENOENT as i64,
+            MapError::KeyExists            => libc::EEXIST as i64,
+            MapError::KeyOutOfRange        |
+            MapError::MapFull              => libc::E2BIG as i64,
+        }
+    }
+}
+
+impl fmt::Display for MapError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            MapError::InvalidKeySize(n) =>
+                write!(f, "[Maps] Error: invalid key size {:?}", n),
+            MapError::InvalidValueSize(n) =>
+                write!(f, "[Maps] Error: invalid value size {:?}", n),
+            MapError::InvalidMaxEntries(n) =>
+                write!(f, "[Maps] Error: invalid maximum number of entries {:?}", n),
+            MapError::InvalidFlags(flags) =>
+                write!(f, "[Maps] Error: invalid flags {:#x}", flags),
+            MapError::KeyNotFound =>
+                write!(f, "[Maps] Error: no element for this key"),
+            MapError::KeyExists =>
+                write!(f, "[Maps] Error: element already exists for this key"),
+            MapError::KeyOutOfRange =>
+                write!(f, "[Maps] Error: key out of range"),
+            MapError::MapFull =>
+                write!(f, "[Maps] Error: map is full"),
+            MapError::NotSupported =>
+                write!(f, "[Maps] Error: operation not supported for this map type"),
+        }
+    }
+}
+
+impl Error for MapError {}
+
+/// Error returned when the programs or maps of an object file cannot be loaded.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ElfError {
+    /// The file is not an ELF file.
+    NotElf,
+    /// The file is not a 64-bit little-endian ELF object for eBPF.
+    UnsupportedElf,
+    /// A header or a section lies out of the bounds of the file.
+    Truncated,
+    /// A map definition is too short or out of the `maps` section (map name).
+    InvalidMapDefinition(String),
+    /// An executable section does not contain a whole number of instructions (section name).
+    InvalidProgram(String),
+    /// A map has a type that is not supported (map name, type).
+    UnsupportedMapType(String, u32),
+    /// A map cannot be created (map name, error).
+    InvalidMap(String, MapError),
+    /// A relocation has a type that is not supported (type, section name, offset).
+    UnsupportedRelocation(u32, String, usize),
+    /// A relocation does not apply to a suitable instruction, or refers to a symbol that is not a
+    /// map or a function (section name, offset).
+    InvalidRelocation(String, usize),
+}
+
+impl fmt::Display for ElfError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ElfError::NotElf =>
+                write!(f, "[Loader] Error: not an ELF file"),
+            ElfError::UnsupportedElf =>
+                write!(f, "[Loader] Error: not a 64-bit little-endian eBPF object file"),
+            ElfError::Truncated =>
+                write!(f, "[Loader] Error: truncated or malformed file"),
+            ElfError::InvalidMapDefinition(ref name) =>
+                write!(f, "[Loader] Error: invalid definition for map {:?}", name),
+            ElfError::InvalidProgram(ref section) =>
+                write!(f, "[Loader] Error: section {:?} does not contain whole instructions",
+                       section),
+            ElfError::UnsupportedMapType(ref name, t) =>
+                write!(f, "[Loader] Error: unsupported type {:?} for map {:?}", t, name),
+            ElfError::InvalidMap(ref name, ref err) =>
+                write!(f, "[Loader] Error: cannot create map {:?}: {}", name, err),
+            ElfError::UnsupportedRelocation(t, ref section, off) =>
+                write!(f, "[Loader] Error: unsupported relocation type {:?} in section {:?} \
+                           at offset {:#x}", t, section, off),
+            ElfError::InvalidRelocation(ref section, off) =>
+                write!(f, "[Loader] Error: invalid relocation in section {:?} at offset {:#x}",
+                       section, off),
+        }
+    }
+}
+
+impl Error for ElfError {}
@@ -0,0 +1,459 @@
+// Copyright 2026 agent <agent@local>
+//
+// Licensed under the Apache License, Version 2.0 <http://www.apache.org/licenses/LICENSE-2.0> or
+// the MIT license <http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+
+//! This module contains a server for the GDB remote serial protocol, to debug eBPF programs run
+//! by the interpreter with `gdb` (built with BPF support) or `lldb`.
+//!
+//! The server drives a debugging session (see module `debugger`). It exposes twelve 64-bit
+//! registers: r0 to r10, then pc. The value of pc is the offset in bytes of the next instruction
+//! in the program being run, so that instruction `n` is at address `n * 8`. Memory reads at
+//! addresses lower than the length of the program return its bytecode, other addresses are those
+//! of the stack, the packet data, the metadata buffer and the values of maps, as seen by the
+//! program. Software breakpoints (`Z0` packets) and single-stepping are supported.
+//!
+//! When the program exits, the exit status sent to the debugger is the lowest byte of r0. Faults
+//! are reported as signals: `SIGSEGV` for out of bounds memory accesses, `SIGFPE` for divisions by
+//! zero, `SIGXCPU` when the instruction budget is exhausted, and so on.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! let prog = &[
+//!     0xb7, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, // mov r0, 1
+//!     0x95, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00  // exit
+//! ];
+//!
+//! let vm = rbpf::EbpfVmNoData::new(prog).unwrap();
+//! let mut session = vm.debug();
+//!
+//! // Wait for the debugger: `target remote localhost:1234` from gdb.
+//! let status = rbpf::gdbstub::serve_tcp(&mut session, "127.0.0.1:1234").unwrap();
+//! ```
+
+use std::io::{self, Read, Write};
+use std::net::{TcpListener, ToSocketAddrs};
+#[cfg(unix)]
+use std::os::unix::net::UnixListener;
+#[cfg(unix)]
+use std::path::Path;
+
+use debugger::{Session, Status};
+use ebpf;
+use error::ExecErrorKind;
+
+// Signals reported to the debugger, numbered as in GDB.
+const SIGILL:  u8 = 4;
+const SIGTRAP: u8 = 5;
+const SIGBUS:  u8 = 7;
+const SIGFPE:  u8 = 8;
+const SIGSEGV: u8 = 11;
+const SIGXCPU: u8 = 24;
+
+// Registers r0 to r10, then pc.
+const NUM_REGS: usize = 12;
+const PC_REG:   usize = 11;
+
+const PACKET_SIZE: usize = 0x4000;
+
+const TARGET_XML: &str = r#"<?xml version="1.0"?>
+<!DOCTYPE target SYSTEM "gdb-target.dtd">
+<target version="1.0">
+  <architecture>bpf</architecture>
+  <feature name="org.gnu.gdb.bpf.core">
+    <reg name="r0" bitsize="64" type="int64"/>
+    <reg name="r1" bitsize="64" type="int64"/>
+    <reg name="r2" bitsize="64" type="int64"/>
+    <reg name="r3" bitsize="64" type="int64"/>
+    <reg name="r4" bitsize="64" type="int64"/>
+    <reg name="r5" bitsize="64" type="int64"/>
+    <reg name="r6" bitsize="64" type="int64"/>
+    <reg name="r7" bitsize="64" type="int64"/>
+    <reg name="r8" bitsize="64" type="int64"/>
+    <reg name="r9" bitsize="64" type="int64"/>
+    <reg name="r10" bitsize="64" type="data_ptr"/>
+    <reg name="pc" bitsize="64" type="code_ptr"/>
+  </feature>
+</target>
+"#;
+
+/// Listen on TCP address `addr`, wait for a debugger to connect, and serve it with `serve()`.
+///
+/// # Errors
+///
+/// Returns an error if the address cannot be bound, or on communication errors.
+pub fn serve_tcp<A: ToSocketAddrs>(session: &mut Session, addr: A) -> io::Result<Status> {
+    let listener = TcpListener::bind(addr)?;
+    let (stream, _) = listener.accept()?;
+    serve(session, stream)
+}
+
+/// Listen on the Unix socket at `path`, wait for a debugger to connect, and serve it with
+/// `serve()`. The socket file is not removed afterwards.
+///
+/// # Errors
+///
+/// Returns an error if the socket cannot be created, or on communication errors.
+#[cfg(unix)]
+pub fn serve_unix<P: AsRef<Path>>(session: &mut Session, path: P) -> io::Result<Status> {
+    let listener = UnixListener::bind(path)?;
+    let (stream, _) = listener.accept()?;
+    serve(session, stream)
+}
+
+/// Serve the debugger connected through `stream`, until the program exits, or until the debugger
+/// detaches, kills the program or closes the connection. Returns the status of the session at
+/// that point.
+///
+/// # Errors
+///
+/// Returns an error on communication errors.
+///
+/// # Examples
+///
+/// ```
+/// use std::io::{self, Read, Write};
+/// use rbpf::debugger::Status;
+///
+/// // A fake connection, replaying the packets of a debugger.
+/// struct Client { input: io::Cursor<Vec<u8>>, output: Vec<u8> }
+/// impl Read for Client {
+///     fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> { self.input.read(buf) }
+/// }
+/// impl Write for Client {
+///     fn write(&mut self, buf: &[u8]) -> io::Result<usize> { self.output.write(buf) }
+///     fn flush(&mut self) -> io::Result<()> { Ok(()) }
+/// }
+///
+/// let prog = &[
+///     0xb7, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, // mov r0, 1
+///     0x95, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00  // exit
+/// ];
+///
+/// let vm = rbpf::EbpfVmNoData::new(prog).unwrap();
+/// let mut session = vm.debug();
+///
+/// // Single-step, then continue.
+/// let mut client = Client { input: io::Cursor::new(b"$s#73+$c#63+".to_vec()), output: vec![] };
+/// assert_eq!(rbpf::gdbstub::serve(&mut session, &mut client).unwrap(), Status::Exited(1));
+/// assert_eq!(client.output, b"+$S05#b8+$W01#b8".to_vec());
+/// ```
+pub fn serve<S: Read + Write>(session: &mut Session, stream: S) -> io::Result<Status> {
+    let mut conn = Connection { stream };
+    while let Some(packet) = conn.read_packet()? {
+        match handle(session, &packet) {
+            Reply::Packet(data) => conn.write_packet(&data)?,
+            Reply::Last(data)   => {
+                conn.write_packet(&data)?;
+                break;
+            },
+            Reply::Kill         => break,
+        }
+    }
+    Ok(session.status())
+}
+
+// Answer to a packet from the debugger.
+enum Reply {
+    // Send a reply and wait for the next packet.
+    Packet(String),
+    // Send a reply and end the connection.
+    Last(String),
+    // End the connection without replying.
+    Kill,
+}
+
+struct Connection<S: Read + Write> {
+    stream: S,
+}
+
+impl<S: Read + Write> Connection<S> {
+    fn read_byte(&mut self) -> io::Result<Option<u8>> {
+        let mut buf = [0u8];
+        loop {
+            match self.stream.read(&mut buf) {
+                Ok(0)  => return Ok(None),
+                Ok(_)  => return Ok(Some(buf[0])),
+                Err(ref e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    // Read the next packet, acknowledging it, and return its content. Acknowledgements and
+    // interrupt requests sent by the debugger are skipped. Returns `None` once the connection is
+    // closed.
+    fn read_packet(&mut self) -> io::Result<Option<String>> {
+        loop {
+            // Wait for the start of a packet.
+            loop {
+                match self.read_byte()? {
+                    Some(b'$') => break,
+                    Some(_)    => continue,
+                    None       => return Ok(None),
+                }
+            }
+            let mut data = vec![];
+            loop {
+                match self.read_byte()? {
+                    Some(b'#') => break,
+                    Some(c)    => data.push(c),
+                    None       => return Ok(None),
+                }
+            }
+            let mut checksum = [0u8; 2];
+            for c in checksum.iter_mut() {
+                match self.read_byte()? {
+                    Some(b) => *c = b,
+                    None    => return Ok(None),
+                }
+            }
+
+            let expected = std::str::from_utf8(&checksum).ok()
+                .and_then(|s| u8::from_str_radix(s, 16).ok());
+            if expected == Some(checksum_of(&data)) {
+                self.stream.write_all(b"+")?;
+                return Ok(Some(String::from_utf8_lossy(&data).into_owned()));
+            }
+            // Ask the debugger to send the packet again.
+            self.stream.write_all(b"-")?;
+        }
+    }
+
+    fn write_packet(&mut self, data: &str) -> io::Result<()> {
+        let packet = format!("${}#{:02x}", data, checksum_of(data.as_bytes()));
+        self.stream.write_all(packet.as_bytes())?;
+        self.stream.flush()
+    }
+}
+
+fn checksum_of(data: &[u8]) -> u8 {
+    data.iter().fold(0u8, |acc, &b| acc.wrapping_add(b))
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn from_hex(s: &str) -> Option<Vec<u8>> {
+    if s.len() % 2 != 0 {
+        return None;
+    }
+    (0..s.len()).step_by(2).map(|i| s.get(i..i + 2).and_then(|b| u8::from_str_radix(b, 16).ok()))
+        .collect()
+}
+
+fn parse_u64(s: &str) -> Option<u64> {
+    u64::from_str_radix(s, 16).ok()
+}
+
+// Parse an `addr,len` pair.
+fn parse_addr_len(s: &str) -> Option<(u64, usize)> {
+    let mut parts = s.splitn(2, ',');
+    let addr = parse_u64(parts.next()?)?;
+    let len = parse_u64(parts.next()?)? as usize;
+    Some((addr, len))
+}
+
+fn signal_of(kind: ExecErrorKind) -> u8 {
+    match kind {
+        ExecErrorKind::OutOfBoundsLoad           |
+        ExecErrorKind::OutOfBoundsStore          |
+        ExecErrorKind::CallDepthExceeded         |
+        ExecErrorKind::InvalidMapPointer         |
+        ExecErrorKind::BufferTooSmall            => SIGSEGV,
+        ExecErrorKind::UnalignedAtomic           => SIGBUS,
+        ExecErrorKind::DivisionByZero            => SIGFPE,
+        ExecErrorKind::InstructionBudgetExceeded => SIGXCPU,
+        ExecErrorKind::UnknownHelper(_)          |
+        ExecErrorKind::UnknownMap(_)             => SIGILL,
+    }
+}
+
+// Reply describing why the program stopped. The connection ends if the program exited.
+fn stop_reply(status: Status) -> Reply {
+    match status {
+        Status::Stopped       |
+        Status::Breakpoint(_) => Reply::Packet(format!("S{:02x}", SIGTRAP)),
+        Status::Exited(res)   => Reply::Last(format!("W{:02x}", res as u8)),
+        Status::Faulted(err)  => Reply::Packet(format!("S{:02x}", signal_of(err.kind))),
+    }
+}
+
+fn get_reg(session: &Session, n: usize) -> u64 {
+    match n {
+        PC_REG => (session.insn_ptr() * ebpf::INSN_SIZE) as u64,
+        _      => session.reg(n),
+    }
+}
+
+// Set register `n`. Returns `false` if the value is not a valid pc.
+fn set_reg(session: &mut Session, n: usize, value: u64) -> bool {
+    match n {
+        PC_REG => {
+            if value % ebpf::INSN_SIZE as u64 != 0 ||
+               value >= session.prog().len() as u64 {
+                return false;
+            }
+            session.set_insn_ptr(value as usize / ebpf::INSN_SIZE);
+        },
+        _      => session.set_reg(n, value),
+    }
+    true
+}
+
+fn read_memory(session: &Session, addr: u64, len: usize) -> Option<Vec<u8>> {
+    let prog = session.prog();
+    if addr < prog.len() as u64 {
+        let end = (addr as usize).checked_add(len)?;
+        return prog.get(addr as usize..end).map(|bytes| bytes.to_vec());
+    }
+    session.read_memory(addr, len).ok()
+}
+
+// Run the program with `s` or `c`, optionally resuming at address `args`.
+fn resume(session: &mut Session, args: &str, single_step: bool) -> Reply {
+    match session.status() {
+        Status::Exited(_)    => return stop_reply(session.status()),
+        // The program cannot go past a fault, it is terminated.
+        Status::Faulted(err) => return Reply::Last(format!("X{:02x}", signal_of(err.kind))),
+        _                    => (),
+    }
+    if !args.is_empty() {
+        match parse_u64(args) {
+            Some(addr) if set_reg(session, PC_REG, addr) => (),
+            _ => return Reply::Packet("E16".to_string()),
+        }
+    }
+    let status = if single_step { session.step() } else { session.cont() };
+    stop_reply(status)
+}
+
+fn query(query: &str) -> Reply {
+    let reply = if query.starts_with("Supported") {
+        format!("PacketSize={:x};qXfer:features:read+", PACKET_SIZE)
+    } else if let Some(args) = query.strip_prefix("Xfer:features:read:target.xml:") {
+        match parse_addr_len(args) {
+            Some((off, len)) => {
+                let off = std::cmp::min(off as usize, TARGET_XML.len());
+                let end = std::cmp::min(off.saturating_add(len), TARGET_XML.len());
+                let marker = if end == TARGET_XML.len() { "l" } else { "m" };
+                format!("{}{}", marker, &TARGET_XML[off..end])
+            },
+            None => "E16".to_string(),
+        }
+    } else {
+        match query {
+            "Attached"     => "1".to_string(),
+            "C"            => "QC1".to_string(),
+            "fThreadInfo"  => "m1".to_string(),
+            "sThreadInfo"  => "l".to_string(),
+            _              => String::new(),
+        }
+    };
+    Reply::Packet(reply)
+}
+
+fn handle(session: &mut Session, packet: &str) -> Reply {
+    // Packets are ASCII, except for binary data that the stub does not support: other packets get
+    // the reply for unsupported packets.
+    if packet.is_empty() || !packet.is_ascii() {
+        return Reply::Packet(String::new());
+    }
+    let (cmd, args) = packet.split_at(1);
+    let reply = match cmd {
+        "?" => return stop_reply(session.status()),
+        "g" => (0..NUM_REGS).map(|n| to_hex(&get_reg(session, n).to_le_bytes())).collect(),
+        "G" => match from_hex(args) {
+            Some(ref bytes) if bytes.len() == NUM_REGS * 8 => {
+                let mut ok = true;
+                for (n, value) in bytes.chunks(8).enumerate() {
+                    let mut word = [0u8; 8];
+                    word.copy_from_slice(value);
+                    ok &= set_reg(session, n, u64::from_le_bytes(word));
+                }
+                if ok { "OK".to_string() } else { "E16".to_string() }
+            },
+            _ => "E16".to_string(),
+        },
+        "p" => match parse_u64(args) {
+            Some(n) if (n as usize) < NUM_REGS =>
+                to_hex(&get_reg(session, n as usize).to_le_bytes()),
+            _ => "E16".to_string(),
+        },
+        "P" => {
+            let mut parts = args.splitn(2, '=');
+            let n = parts.next().and_then(parse_u64);
+            let value = parts.next().and_then(from_hex);
+            match (n, value) {
+                (Some(n), Some(ref value)) if (n as usize) < NUM_REGS && value.len() == 8 => {
+                    let mut word = [0u8; 8];
+                    word.copy_from_slice(value);
+                    if set_reg(session, n as usize, u64::from_le_bytes(word)) {
+                        "OK".to_string()
+                    } else {
+                        "E16".to_string()
+                    }
+                },
+                _ => "E16".to_string(),
+            }
+        },
+        "m" => match parse_addr_len(args) {
+            Some((addr, len)) => match read_memory(session, addr, len) {
+                Some(bytes) => to_hex(&bytes),
+                None        => "E0e".to_string(),
+            },
+            None => "E16".to_string(),
+        },
+        "M" => {
+            let mut parts = args.splitn(2, ':');
+            let addr_len = parts.next().and_then(parse_addr_len);
+            let data = parts.next().and_then(from_hex);
+            match (addr_len, data) {
+                (Some((addr, len)), Some(ref data)) if data.len() == len => {
+                    // The bytecode cannot be modified, breakpoints are set with `Z0` packets.
+                    if addr < session.prog().len() as u64 ||
+                       session.write_memory(addr, data).is_err() {
+                        "E0e".to_string()
+                    } else {
+                        "OK".to_string()
+                    }
+                },
+                _ => "E16".to_string(),
+            }
+        },
+        "Z" | "z" => {
+            let mut parts = args.splitn(3, ',');
+            let kind = parts.next();
+            let addr = parts.next().and_then(parse_u64);
+            match (kind, addr) {
+                // Only software breakpoints are supported.
+                (Some("0"), Some(addr)) => {
+                    if addr % ebpf::INSN_SIZE as u64 != 0 {
+                        "E16".to_string()
+                    } else if cmd == "Z" {
+                        session.set_breakpoint(addr as usize / ebpf::INSN_SIZE);
+                        "OK".to_string()
+                    } else {
+                        session.clear_breakpoint(addr as usize / ebpf::INSN_SIZE);
+                        "OK".to_string()
+                    }
+                },
+                (Some(_), Some(_)) => String::new(),
+                _                  => "E16".to_string(),
+            }
+        },
+        "s" => return resume(session, args, true),
+        "c" => return resume(session, args, false),
+        "k" => return Reply::Kill,
+        "D" => return Reply::Last("OK".to_string()),
+        "q" => return query(args),
+        // There is a single thread.
+        "H" | "T" => "OK".to_string(),
+        _   => String::new(),
+    };
+    Reply::Packet(reply)
+}
@@ -0,0 +1,1247 @@
+// Derived from uBPF <https://github.com/iovisor/ubpf>
+// Copyright 2015 Big Switch Networks, Inc
+//      (uBPF: JIT algorithm, originally in C)
+// Copyright 2016 Quentin Monnet <quentin.monnet@6wind.com>
+//      (Translation to Rust of the x86_64 backend, that this one mirrors)
+// Copyright 2026 agent <agent@local>
+//      (AArch64 backend)
+//
+// Licensed under the Apache License, Version 2.0 <http://www.apache.org/licenses/LICENSE-2.0> or
+// the MIT license <http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+// AArch64 backend of the JIT compiler. It mirrors the x86_64 backend: same runtime context and
+// stack layout, same handling of local functions, tail calls, memory checks and faults. Only the
+// instruction encoding and the calling convention (AAPCS64) differ.
+
+use std;
+use std::arch::asm;
+use std::collections::HashMap;
+use std::fmt::{Error, Formatter};
+
+use ebpf;
+use error::JitError;
+use maps::{self, Map, MapHelper};
+
+use super::{JitProgram, PAGE_SIZE, FAULT_OOB_LOAD, FAULT_OOB_STORE, FAULT_DIV_BY_ZERO,
+            FAULT_CALL_DEPTH, FAULT_INVALID_MAP, FAULT_INSN_BUDGET, FAULT_UNALIGNED};
+
+// Direct branches and calls use 26-bit offsets counted in instructions (+/-128 MiB). Conditional
+// branches only reach +/-1 MiB: this is checked when resolving jumps.
+const MAX_CODE_SIZE: usize = 1 << 27;
+
+// Special values for target_pc in struct Jump. They must not collide with the index of an
+// instruction, even when the programs reachable by tail calls are compiled along with the main one.
+const TARGET_OFFSET: isize = i32::MAX as isize;
+const TARGET_PC_EXIT:         isize = TARGET_OFFSET + 1;
+const TARGET_PC_DIV_BY_ZERO:  isize = TARGET_OFFSET + 2;
+const TARGET_PC_EXIT_ZERO:    isize = TARGET_OFFSET + 3;
+const TARGET_PC_CALL_DEPTH:   isize = TARGET_OFFSET + 4;
+const TARGET_PC_TAIL_CALL_TABLE: isize = TARGET_OFFSET + 5;
+const TARGET_PC_OOB_LOAD:     isize = TARGET_OFFSET + 6;
+const TARGET_PC_OOB_STORE:    isize = TARGET_OFFSET + 7;
+const TARGET_PC_MEM_FAULT:    isize = TARGET_OFFSET + 8;
+const TARGET_PC_INVALID_MAP:  isize = TARGET_OFFSET + 9;
+const TARGET_PC_INSN_BUDGET:  isize = TARGET_OFFSET + 10;
+const TARGET_PC_UNALIGNED:    isize = TARGET_OFFSET + 11;
+
+// Runtime context, stored on the stack below the callee-saved registers and pointed to by X26.
+// It holds data needed by some instructions while the program runs.
+const CONTEXT_MEM:     i32 = 0; // Pointer to packet data
+const CONTEXT_MEM_LEN: i32 = 8; // Length of packet data
+const CONTEXT_DEPTH:   i32 = 16; // Number of nested local function calls
+const CONTEXT_TAIL_CALLS: i32 = 24; // Number of tail calls performed so far
+const CONTEXT_MBUFF:   i32 = 32; // Pointer to metadata buffer
+const CONTEXT_MBUFF_LEN: i32 = 40; // Length of metadata buffer
+const CONTEXT_FAULT:   i32 = 48; // Pointer to the JitFault to fill on error
+const CONTEXT_INSN_BUDGET: i32 = 56; // Number of instructions the program can still execute
+// The stack pointer must always be 16-byte aligned.
+const CONTEXT_SIZE:    i32 = 64;
+
+// The eBPF stack holds the frames of all nested local functions, contiguously, below the runtime
+// context. The main program uses the top frame.
+const STACK_TOTAL_SIZE: i32 = (ebpf::STACK_SIZE * ebpf::MAX_CALL_DEPTH) as i32;
+
+#[derive(Clone, Copy)]
+enum OperandSize {
+    S8  = 0,
+    S16 = 1,
+    S32 = 2,
+    S64 = 3,
+}
+
+// Registers
+const X0:  u8 = 0;
+const X1:  u8 = 1;
+const X2:  u8 = 2;
+const X3:  u8 = 3;
+const X4:  u8 = 4;
+const X5:  u8 = 5;
+const X6:  u8 = 6;
+const X7:  u8 = 7;
+const X9:  u8 = 9;
+const X10: u8 = 10;
+const X11: u8 = 11;
+const X16: u8 = 16;
+const X17: u8 = 17;
+const X19: u8 = 19;
+const X20: u8 = 20;
+const X21: u8 = 21;
+const X22: u8 = 22;
+const X25: u8 = 25;
+const X26: u8 = 26;
+const X29: u8 = 29;
+const X30: u8 = 30;
+// Register number 31 is the stack pointer or the zero register, depending on the instruction
+const SP:  u8 = 31;
+const XZR: u8 = 31;
+
+const REGISTER_MAP_SIZE: usize = 11;
+const REGISTER_MAP: [u8;REGISTER_MAP_SIZE] = [
+    X7,  // 0  return value
+    X0,  // 1  arg 1
+    X1,  // 2  arg 2
+    X2,  // 3  arg 3
+    X3,  // 4  arg 4
+    X4,  // 5  arg 5
+    X19, // 6  callee-saved
+    X20, // 7  callee-saved
+    X21, // 8  callee-saved
+    X22, // 9  callee-saved
+    X25, // 10 stack pointer
+];
+
+// Pointer to the runtime context, callee-saved so that it survives helper calls
+const CTX: u8 = X26;
+// X9, X10 and X11 are scratch registers. X16 is used to materialize immediates that do not fit in
+// an instruction, X17 holds the end of the access during memory checks.
+
+// Condition codes
+const COND_EQ: u32 = 0x0;
+const COND_NE: u32 = 0x1;
+const COND_HS: u32 = 0x2;
+const COND_LO: u32 = 0x3;
+const COND_HI: u32 = 0x8;
+const COND_LS: u32 = 0x9;
+const COND_GE: u32 = 0xa;
+const COND_LT: u32 = 0xb;
+const COND_GT: u32 = 0xc;
+const COND_LE: u32 = 0xd;
+const COND_AL: u32 = 0xe;
+
+// Opcodes of the instructions with register operands, to be completed with size and registers
+const ADD_REG:  u32 = 0x0b000000;
+const SUB_REG:  u32 = 0x4b000000;
+const SUBS_REG: u32 = 0x6b000000;
+const AND_REG:  u32 = 0x0a000000;
+const ORR_REG:  u32 = 0x2a000000;
+const EOR_REG:  u32 = 0x4a000000;
+const ANDS_REG: u32 = 0x6a000000;
+const UDIV:     u32 = 0x1ac00800;
+const LSLV:     u32 = 0x1ac02000;
+const LSRV:     u32 = 0x1ac02400;
+const ASRV:     u32 = 0x1ac02800;
+const UBFM:     u32 = 0x53000000;
+const SBFM:     u32 = 0x13000000;
+
+const RET: u32 = 0xd65f03c0;
+
+// Return the AArch64 register for the given eBPF register
+fn map_register(r: u8) -> u8 {
+    assert!(r < REGISTER_MAP_SIZE as u8);
+    REGISTER_MAP[(r % REGISTER_MAP_SIZE as u8) as usize]
+}
+
+#[inline]
+fn sf (is64: bool) -> u32 {
+    if is64 { 1 << 31 } else { 0 }
+}
+
+#[inline]
+fn emit4 (jit: &mut JitMemory, insn: u32) {
+    jit.contents.extend_from_slice(&insn.to_le_bytes());
+    jit.offset += 4;
+}
+
+#[inline]
+fn emit8 (jit: &mut JitMemory, data: u64) {
+    jit.contents.extend_from_slice(&data.to_le_bytes());
+    jit.offset += 8;
+}
+
+// Data-processing instruction with three registers: op rd, rn, rm
+#[inline]
+fn emit_alu (jit: &mut JitMemory, is64: bool, op: u32, rd: u8, rn: u8, rm: u8) {
+    emit4(jit, op | sf(is64) | (rm as u32) << 16 | (rn as u32) << 5 | rd as u32);
+}
+
+#[inline]
+fn emit_mov (jit: &mut JitMemory, is64: bool, src: u8, dst: u8) {
+    // orr dst, xzr, src. A 32-bit move clears the upper half of dst.
+    emit_alu(jit, is64, ORR_REG, dst, XZR, src);
+}
+
+#[inline]
+fn emit_cmp (jit: &mut JitMemory, is64: bool, rn: u8, rm: u8) {
+    emit_alu(jit, is64, SUBS_REG, XZR, rn, rm);
+}
+
+// mul rd, rn, rm
+#[inline]
+fn emit_mul (jit: &mut JitMemory, is64: bool, rd: u8, rn: u8, rm: u8) {
+    emit4(jit, 0x1b007c00 | sf(is64) | (rm as u32) << 16 | (rn as u32) << 5 | rd as u32);
+}
+
+// msub rd, rn, rm, ra: rd = ra - rn * rm
+#[inline]
+fn emit_msub (jit: &mut JitMemory, is64: bool, rd: u8, rn: u8, rm: u8, ra: u8) {
+    emit4(jit, 0x1b008000 | sf(is64) | (rm as u32) << 16 | (ra as u32) << 10 |
+               (rn as u32) << 5 | rd as u32);
+}
+
+// Bitfield move (ubfm or sbfm), used for shifts by an immediate and extensions
+#[inline]
+fn emit_bfm (jit: &mut JitMemory, is64: bool, op: u32, rd: u8, rn: u8, immr: u32, imms: u32) {
+    let n = if is64 { 1 << 22 } else { 0 };
+    emit4(jit, op | sf(is64) | n | immr << 16 | imms << 10 | (rn as u32) << 5 | rd as u32);
+}
+
+// Shift by an immediate. As on x86, the amount is taken modulo the size of the operand.
+#[inline]
+fn emit_shift_imm (jit: &mut JitMemory, is64: bool, op: u32, dst: u8, imm: i32) {
+    let bits = if is64 { 64 } else { 32 };
+    let shift = imm as u32 & (bits - 1);
+    match op {
+        LSLV => emit_bfm(jit, is64, UBFM, dst, dst, (bits - shift) & (bits - 1), bits - 1 - shift),
+        LSRV => emit_bfm(jit, is64, UBFM, dst, dst, shift, bits - 1),
+        ASRV => emit_bfm(jit, is64, SBFM, dst, dst, shift, bits - 1),
+        _    => unreachable!(),
+    }
+}
+
+// add or sub with a 12-bit immediate, optionally shifted by 12 bits. With these instructions,
+// register 31 is the stack pointer.
+#[inline]
+fn emit_addsub_imm12 (jit: &mut JitMemory, is64: bool, sub: bool, rd: u8, rn: u8, imm: u32) {
+    let (sh, imm12) = if imm < 0x1000 { (0, imm) } else { (1 << 22, imm >> 12) };
+    let op = if sub { 0x51000000 } else { 0x11000000 };
+    emit4(jit, op | sf(is64) | sh | imm12 << 10 | (rn as u32) << 5 | rd as u32);
+}
+
+#[inline]
+fn fits_imm12 (imm: u64) -> bool {
+    imm < 0x1000 || (imm & 0xfff == 0 && imm < 0x1000000)
+}
+
+// rd = rn + imm. Trash X16 if the immediate does not fit in the instruction.
+fn emit_add_imm (jit: &mut JitMemory, is64: bool, rd: u8, rn: u8, imm: i64) {
+    let imm = if is64 { imm } else { imm as i32 as i64 };
+    if fits_imm12(imm.unsigned_abs()) {
+        emit_addsub_imm12(jit, is64, imm < 0, rd, rn, imm.unsigned_abs() as u32);
+    } else {
+        assert!(rd != SP && rn != SP);
+        emit_load_imm_sized(jit, is64, X16, imm as u64);
+        emit_alu(jit, is64, ADD_REG, rd, rn, X16);
+    }
+}
+
+#[inline]
+fn emit_sub_imm (jit: &mut JitMemory, is64: bool, rd: u8, rn: u8, imm: i64) {
+    emit_add_imm(jit, is64, rd, rn, imm.wrapping_neg());
+}
+
+// Compare rn with a 32-bit immediate, sign-extended for 64-bit comparisons. Trash X16 if the
+// immediate does not fit in the instruction.
+fn emit_cmp_imm (jit: &mut JitMemory, is64: bool, rn: u8, imm: i32) {
+    if (0..0x1000).contains(&imm) {
+        emit4(jit, 0x7100001f | sf(is64) | (imm as u32) << 10 | (rn as u32) << 5);
+    } else if (-0xfff..0).contains(&imm) {
+        // cmn rn, #-imm
+        emit4(jit, 0x3100001f | sf(is64) | (imm.unsigned_abs()) << 10 | (rn as u32) << 5);
+    } else {
+        emit_load_imm_sized(jit, is64, X16, imm as i64 as u64);
+        emit_cmp(jit, is64, rn, X16);
+    }
+}
+
+// Apply a data-processing instruction to dst, with an immediate operand loaded into X16.
+#[inline]
+fn emit_alu_imm (jit: &mut JitMemory, is64: bool, op: u32, dst: u8, imm: i32) {
+    emit_load_imm_sized(jit, is64, X16, imm as i64 as u64);
+    emit_alu(jit, is64, op, dst, dst, X16);
+}
+
+// Load an immediate into rd, with as few movz/movn/movk instructions as possible. A 32-bit load
+// clears the upper half of rd.
+fn emit_load_imm_sized (jit: &mut JitMemory, is64: bool, rd: u8, imm: u64) {
+    let chunks = if is64 { 4 } else { 2 };
+    let half = |i: u32| ((imm >> (16 * i)) & 0xffff) as u32;
+    let ones = (0..chunks).filter(|&i| half(i) == 0xffff).count();
+    let zeros = (0..chunks).filter(|&i| half(i) == 0).count();
+    // Start from all zeros with movz, or from all ones with movn, whichever needs fewer movk.
+    let (fill, first_op) = if ones > zeros { (0xffff, 0x12800000) } else { (0, 0x52800000) };
+    let mut chunks_to_set = (0..chunks).filter(|&i| half(i) != fill).collect::<Vec<u32>>();
+    if chunks_to_set.is_empty() {
+        chunks_to_set.push(0);
+    }
+    for (n, &i) in chunks_to_set.iter().enumerate() {
+        let (op, value) = if n == 0 {
+            (first_op, if fill == 0 { half(i) } else { !half(i) & 0xffff })
+        } else {
+            (0x72800000, half(i)) // movk
+        };
+        emit4(jit, op | sf(is64) | i << 21 | value << 5 | rd as u32);
+    }
+}
+
+#[inline]
+fn emit_load_imm (jit: &mut JitMemory, rd: u8, imm: i64) {
+    emit_load_imm_sized(jit, true, rd, imm as u64);
+}
+
+// Load or store of `size` bytes between rt and [rn + offset]. Trash X16 if the offset does not fit
+// in the instruction.
+fn emit_ldst (jit: &mut JitMemory, size: OperandSize, load: bool, rt: u8, rn: u8, offset: i32) {
+    let size = (size as u32) << 30;
+    let load = if load { 1 << 22 } else { 0 };
+    if (-256..256).contains(&offset) {
+        // ldur/stur with a 9-bit signed offset
+        emit4(jit, 0x38000000 | size | load | (offset as u32 & 0x1ff) << 12 |
+                   (rn as u32) << 5 | rt as u32);
+    } else {
+        emit_load_imm(jit, X16, offset as i64);
+        // ldr/str with a register offset
+        emit4(jit, 0x38206800 | size | load | (X16 as u32) << 16 | (rn as u32) << 5 | rt as u32);
+    }
+}
+
+// Load [src + offset] into dst.
+#[inline]
+fn emit_load (jit: &mut JitMemory, size: OperandSize, src: u8, dst: u8, offset: i32) {
+    emit_ldst(jit, size, true, dst, src, offset);
+}
+
+// Store src at [dst + offset].
+#[inline]
+fn emit_store (jit: &mut JitMemory, size: OperandSize, src: u8, dst: u8, offset: i32) {
+    emit_ldst(jit, size, false, src, dst, offset);
+}
+
+// Store a 32-bit immediate, sign-extended to 64 bits, at [dst + offset]. Trash X9.
+#[inline]
+fn emit_store_imm32 (jit: &mut JitMemory, size: OperandSize, dst: u8, offset: i32, imm: i32) {
+    emit_load_imm(jit, X9, imm as i64);
+    emit_store(jit, size, X9, dst, offset);
+}
+
+// Atomically add src to [dst + offset], with an exclusive load/store loop. Trash X9, X10 and X16.
+fn emit_atomic_add (jit: &mut JitMemory, size: OperandSize, src: u8, dst: u8, offset: i32) {
+    let is64 = matches!(size, OperandSize::S64);
+    emit_add_imm(jit, true, X16, dst, offset as i64);
+    let size = (size as u32) << 30;
+    // ldaxr x9, [x16]
+    emit4(jit, 0x085ffc00 | size | (X16 as u32) << 5 | X9 as u32);
+    emit_alu(jit, is64, ADD_REG, X9, X9, src);
+    // stlxr w10, x9, [x16]
+    emit4(jit, 0x0800fc00 | size | (X10 as u32) << 16 | (X16 as u32) << 5 | X9 as u32);
+    // cbnz w10, back to ldaxr
+    emit4(jit, 0x35000000 | (-3i32 as u32 & 0x7ffff) << 5 | X10 as u32);
+}
+
+#[inline]
+fn emit_bswap (jit: &mut JitMemory, dst: u8, size: i32) {
+    let d = dst as u32;
+    match size {
+        16 => {
+            emit4(jit, 0x5ac00400 | d << 5 | d); // rev16 wd, wd
+            emit_bfm(jit, false, UBFM, dst, dst, 0, 15); // uxth wd, wd
+        },
+        32 => emit4(jit, 0x5ac00800 | d << 5 | d), // rev wd, wd
+        64 => emit4(jit, 0xdac00c00 | d << 5 | d), // rev xd, xd
+        _  => unreachable!() // Should have been caught by verifier
+    }
+}
+
+// stp rt, rt2, [sp, #-16]!
+#[inline]
+fn emit_push_pair (jit: &mut JitMemory, rt: u8, rt2: u8) {
+    emit4(jit, 0xa9bf0000 | (rt2 as u32) << 10 | (SP as u32) << 5 | rt as u32);
+}
+
+// ldp rt, rt2, [sp], #16
+#[inline]
+fn emit_pop_pair (jit: &mut JitMemory, rt: u8, rt2: u8) {
+    emit4(jit, 0xa8c10000 | (rt2 as u32) << 10 | (SP as u32) << 5 | rt as u32);
+}
+
+// Branch with a 26-bit offset: b, or bl if link is set.
+#[inline]
+fn emit_b (jit: &mut JitMemory, link: bool, target_pc: isize) {
+    jit.jumps.push(Jump { offset_loc: jit.offset, target_pc, kind: JumpKind::Branch });
+    emit4(jit, if link { 0x94000000 } else { 0x14000000 });
+}
+
+// Conditional branch with a 19-bit offset, for jumps within the program.
+#[inline]
+fn emit_b_cond (jit: &mut JitMemory, cond: u32, target_pc: isize) {
+    jit.jumps.push(Jump { offset_loc: jit.offset, target_pc, kind: JumpKind::CondBranch });
+    emit4(jit, 0x54000000 | cond);
+}
+
+// Conditional branch to one of the handlers at the end of the code, that may be out of reach of
+// b.cond: skip an unconditional branch with the opposite condition.
+#[inline]
+fn emit_b_cond_far (jit: &mut JitMemory, cond: u32, target_pc: isize) {
+    emit4(jit, 0x54000000 | 2 << 5 | (cond ^ 1));
+    emit_b(jit, false, target_pc);
+}
+
+#[inline]
+fn set_anchor (jit: &mut JitMemory, target: isize) {
+    jit.special_targets.insert(target, jit.offset);
+}
+
+// Conditional branch to a label in the code emitted next, to be placed with set_label().
+#[inline]
+fn emit_b_cond_label (jit: &mut JitMemory, cond: u32) -> usize {
+    let loc = jit.offset;
+    emit4(jit, 0x54000000 | cond);
+    loc
+}
+
+#[inline]
+fn set_label (jit: &mut JitMemory, locs: &[usize]) {
+    for &loc in locs {
+        let rel = ((jit.offset - loc) / 4) as u32;
+        jit.patch(loc, (rel & 0x7ffff) << 5);
+    }
+}
+
+// Load the absolute address of a jump target into rd, with four instructions patched when the
+// jumps are resolved.
+#[inline]
+fn emit_load_address (jit: &mut JitMemory, rd: u8, target_pc: isize) {
+    jit.jumps.push(Jump { offset_loc: jit.offset, target_pc, kind: JumpKind::LoadAddress });
+    emit4(jit, 0xd2800000 | rd as u32); // movz
+    for hw in 1..4 {
+        emit4(jit, 0xf2800000 | hw << 21 | rd as u32); // movk
+    }
+}
+
+// Record a fault of the given kind for the instruction whose index is in the lower 32 bits of X9,
+// in the JitFault passed to the program. Leave a pointer to the JitFault in X10. Trash X11.
+fn emit_fault (jit: &mut JitMemory, kind: u64) {
+    emit_load(jit, OperandSize::S64, CTX, X10, CONTEXT_FAULT);
+    emit_load_imm(jit, X11, kind as i64);
+    emit_store(jit, OperandSize::S64, X11, X10, 0);
+    emit_mov(jit, false, X9, X11); // mov w9 into w11, zero-extended
+    emit_store(jit, OperandSize::S64, X11, X10, 8);
+}
+
+// Check that the access of `len` bytes at [base + offset] lies within the eBPF stack, the packet
+// data, the metadata buffer or the values of one of the maps, as the interpreter does. Otherwise,
+// abort the program with an out of bounds error for instruction insn_ptr. Trash X9, X10, X16 and
+// X17.
+fn emit_mem_check (jit: &mut JitMemory, kind: isize, base: u8, offset: i32, len: i32,
+                   insn_ptr: usize, maps: &HashMap<u32, &Map>) {
+    // X17 holds the end of the access. A region [start, end) is valid if start + len <= X17 <= end.
+    emit_add_imm(jit, true, X17, base, offset as i64 + len as i64);
+    let mut ok = vec![];
+
+    // Stack
+    emit_sub_imm(jit, true, X9, CTX, (STACK_TOTAL_SIZE - len) as i64);
+    emit_cmp(jit, true, X17, X9);
+    let next = emit_b_cond_label(jit, COND_LO);
+    emit_cmp(jit, true, X17, CTX);
+    ok.push(emit_b_cond_label(jit, COND_LS));
+    set_label(jit, &[next]);
+
+    // Packet data and metadata buffer
+    for &(ptr, ptr_len) in &[(CONTEXT_MEM, CONTEXT_MEM_LEN), (CONTEXT_MBUFF, CONTEXT_MBUFF_LEN)] {
+        emit_load(jit, OperandSize::S64, CTX, X9, ptr);
+        emit_add_imm(jit, true, X9, X9, len as i64);
+        emit_cmp(jit, true, X17, X9);
+        let next = emit_b_cond_label(jit, COND_LO);
+        emit_load(jit, OperandSize::S64, CTX, X9, ptr);
+        emit_load(jit, OperandSize::S64, CTX, X10, ptr_len);
+        emit_alu(jit, true, ADD_REG, X9, X9, X10);
+        emit_cmp(jit, true, X17, X9);
+        ok.push(emit_b_cond_label(jit, COND_LS));
+        set_label(jit, &[next]);
+    }
+
+    // Values of the maps, that never move
+    for map in maps.values() {
+        let (start, end) = map.value_region();
+        emit_load_imm(jit, X9, (start + len as u64) as i64);
+        emit_cmp(jit, true, X17, X9);
+        let next = emit_b_cond_label(jit, COND_LO);
+        emit_load_imm(jit, X9, end as i64);
+        emit_cmp(jit, true, X17, X9);
+        ok.push(emit_b_cond_label(jit, COND_LS));
+        set_label(jit, &[next]);
+    }
+
+    // Out of bounds: pass instruction and length in X9, end of access in X17
+    emit_load_imm(jit, X9, ((len as i64) << 32) | insn_ptr as i64);
+    emit_b(jit, false, kind);
+    set_label(jit, &ok);
+}
+
+// Check that the access of `len` bytes at [base + offset] is aligned on `len`, as exclusive loads
+// and stores require and as the interpreter checks. Otherwise, abort the program with an error for
+// instruction insn_ptr. Trash X9, X16 and X17.
+fn emit_align_check (jit: &mut JitMemory, base: u8, offset: i32, len: i32, insn_ptr: usize) {
+    // X17 holds the end of the access, as for emit_mem_check(): it is aligned if the start is.
+    emit_add_imm(jit, true, X17, base, offset as i64 + len as i64);
+    emit_load_imm(jit, X9, (len - 1) as i64);
+    emit_alu(jit, true, ANDS_REG, XZR, X17, X9); // tst x17, x9
+    let ok = emit_b_cond_label(jit, COND_EQ);
+    emit_load_imm(jit, X9, ((len as i64) << 32) | insn_ptr as i64);
+    emit_b(jit, false, TARGET_PC_UNALIGNED);
+    set_label(jit, &[ok]);
+}
+
+// Consume one instruction from the budget, or abort the program with an error for instruction
+// insn_ptr if the budget is exhausted. Trash X9.
+fn emit_insn_budget_check (jit: &mut JitMemory, insn_ptr: usize) {
+    emit_load(jit, OperandSize::S64, CTX, X9, CONTEXT_INSN_BUDGET);
+    emit_cmp_imm(jit, true, X9, 0);
+    let ok = emit_b_cond_label(jit, COND_NE);
+    emit_load_imm(jit, X9, insn_ptr as i64);
+    emit_b(jit, false, TARGET_PC_INSN_BUDGET);
+    set_label(jit, &[ok]);
+    emit_sub_imm(jit, true, X9, X9, 1);
+    emit_store(jit, OperandSize::S64, X9, CTX, CONTEXT_INSN_BUDGET);
+}
+
+// Load a value from packet data into eBPF register 0, in host byte order, for LD_ABS and LD_IND.
+// The offset in packet data is imm for LD_ABS, and src + imm (as 32-bit signed) for LD_IND. If the
+// load is out of bounds, the program exits and returns 0.
+fn emit_load_packet (jit: &mut JitMemory, size: OperandSize, src: Option<u8>, imm: i32) {
+    let len = match size {
+        OperandSize::S8  => 1,
+        OperandSize::S16 => 2,
+        OperandSize::S32 => 4,
+        OperandSize::S64 => 8,
+    };
+
+    // Offset into X9
+    match src {
+        Some(src) => {
+            emit_add_imm(jit, false, X9, src, imm as i64);
+            emit_bfm(jit, true, SBFM, X9, X9, 0, 31); // sxtw x9, w9
+        },
+        None => emit_load_imm(jit, X9, imm as i64),
+    }
+    // Negative offset: out of bounds
+    emit_cmp_imm(jit, true, X9, 0);
+    emit_b_cond_far(jit, COND_LT, TARGET_PC_EXIT_ZERO);
+    // offset > mem_len - len: out of bounds
+    emit_load(jit, OperandSize::S64, CTX, X10, CONTEXT_MEM_LEN);
+    emit_sub_imm(jit, true, X10, X10, len);
+    emit_cmp(jit, true, X9, X10);
+    emit_b_cond_far(jit, COND_GT, TARGET_PC_EXIT_ZERO);
+
+    // Load from mem + offset
+    emit_load(jit, OperandSize::S64, CTX, X10, CONTEXT_MEM);
+    emit_alu(jit, true, ADD_REG, X9, X9, X10);
+    let dst = map_register(0);
+    emit_load(jit, size, X9, dst, 0);
+    if len > 1 {
+        emit_bswap(jit, dst, len as i32 * 8);
+    }
+}
+
+// Call a helper function. Arguments are already in place, since eBPF registers 1 to 5 are mapped to
+// the registers for the first arguments.
+fn emit_call (jit: &mut JitMemory, target: i64) {
+    emit_load_imm(jit, X9, target);
+    // The link register holds the return address of local functions: preserve it.
+    emit4(jit, 0xf81f0ffe); // str x30, [sp, #-16]!
+    emit4(jit, 0xd63f0000 | (X9 as u32) << 5); // blr x9
+    emit4(jit, 0xf84107fe); // ldr x30, [sp], #16
+    emit_mov(jit, true, X0, map_register(0));
+}
+
+// Call a map helper, after checking that eBPF register 1 holds the address of one of the maps and,
+// if mem_check is set, that the key and value read by the helper are in bounds. Otherwise, abort
+// the program with an error for instruction insn_ptr.
+fn emit_map_helper_call (jit: &mut JitMemory, helper: MapHelper, id: u32, insn_ptr: usize,
+                         maps: &HashMap<u32, &Map>, mem_check: bool) {
+    let mut found = vec![];
+    for map in maps.values() {
+        emit_load_imm(jit, X9, *map as *const Map as i64);
+        emit_cmp(jit, true, map_register(1), X9);
+        let next = emit_b_cond_label(jit, COND_NE);
+        if mem_check {
+            let key_size = std::cmp::min(map.key_size(), i32::MAX as usize) as i32;
+            emit_mem_check(jit, TARGET_PC_OOB_LOAD, map_register(2), 0, key_size, insn_ptr, maps);
+            if id == maps::BPF_MAP_UPDATE_ELEM_IDX {
+                let value_size = std::cmp::min(map.value_size(), i32::MAX as usize) as i32;
+                emit_mem_check(jit, TARGET_PC_OOB_LOAD, map_register(3), 0, value_size, insn_ptr,
+                               maps);
+            }
+        }
+        found.push(emit_b_cond_label(jit, COND_AL));
+        set_label(jit, &[next]);
+    }
+
+    // Not a map: pass instruction in X9, and the value of register 1 in X17
+    emit_load_imm(jit, X9, insn_ptr as i64);
+    emit_mov(jit, true, map_register(1), X17);
+    emit_b(jit, false, TARGET_PC_INVALID_MAP);
+    set_label(jit, &found);
+
+    emit_call(jit, helper as usize as i64);
+}
+
+// Call a local function of the program, starting at target_pc. The function gets a new stack
+// frame, and r6-r9 and r10 are restored when it returns. If the maximum call depth is reached,
+// abort the program with an error for instruction insn_ptr.
+fn emit_local_call (jit: &mut JitMemory, target_pc: isize, insn_ptr: usize) {
+    emit_load_imm(jit, X9, insn_ptr as i64);
+    emit_load(jit, OperandSize::S64, CTX, X10, CONTEXT_DEPTH);
+    emit_cmp_imm(jit, true, X10, ebpf::MAX_CALL_DEPTH as i32 - 1);
+    emit_b_cond_far(jit, COND_HS, TARGET_PC_CALL_DEPTH);
+    emit_add_imm(jit, true, X10, X10, 1);
+    emit_store(jit, OperandSize::S64, X10, CTX, CONTEXT_DEPTH);
+
+    // Save r6-r10, and the link register of the caller in case it is itself a local function.
+    emit_push_pair(jit, map_register(6), map_register(7));
+    emit_push_pair(jit, map_register(8), map_register(9));
+    emit_push_pair(jit, map_register(10), X30);
+    // The frame of the callee is right below the one of the caller.
+    emit_sub_imm(jit, true, map_register(10), map_register(10), ebpf::STACK_SIZE as i64);
+
+    emit_b(jit, true, target_pc);
+
+    emit_pop_pair(jit, map_register(10), X30);
+    emit_pop_pair(jit, map_register(8), map_register(9));
+    emit_pop_pair(jit, map_register(6), map_register(7));
+
+    emit_load(jit, OperandSize::S64, CTX, X10, CONTEXT_DEPTH);
+    emit_sub_imm(jit, true, X10, X10, 1);
+    emit_store(jit, OperandSize::S64, X10, CTX, CONTEXT_DEPTH);
+}
+
+// Replace the running program with the one at index r3 of the program array, if any. Otherwise, or
+// if the maximum number of tail calls has been reached, go on with the instruction at next_pc.
+fn emit_tail_call (jit: &mut JitMemory, num_entries: usize, max_tail_calls: usize, next_pc: isize) {
+    // Index is a 32-bit unsigned value
+    emit_load_imm_sized(jit, false, X16, num_entries as u64);
+    emit_cmp(jit, false, map_register(3), X16);
+    emit_b_cond(jit, COND_HS, next_pc);
+
+    // Load the address of the program from the table, skip the call if the slot is empty
+    emit_load_address(jit, X10, TARGET_PC_TAIL_CALL_TABLE);
+    // ldr x9, [x10, w_r3, uxtw #3]
+    emit4(jit, 0xf8605800 | (map_register(3) as u32) << 16 | (X10 as u32) << 5 | X9 as u32);
+    emit_cmp_imm(jit, true, X9, 0);
+    emit_b_cond(jit, COND_EQ, next_pc);
+
+    emit_load(jit, OperandSize::S64, CTX, X10, CONTEXT_TAIL_CALLS);
+    emit_load_imm(jit, X11, max_tail_calls.min(i64::MAX as usize) as i64);
+    emit_cmp(jit, true, X10, X11);
+    emit_b_cond(jit, COND_HS, next_pc);
+    emit_add_imm(jit, true, X10, X10, 1);
+    emit_store(jit, OperandSize::S64, X10, CTX, CONTEXT_TAIL_CALLS);
+
+    // The new program starts over from the frame of the main program
+    emit_store(jit, OperandSize::S64, XZR, CTX, CONTEXT_DEPTH);
+    emit_mov(jit, true, CTX, map_register(10));
+    emit_sub_imm(jit, true, SP, CTX, STACK_TOTAL_SIZE as i64);
+
+    emit4(jit, 0xd61f0000 | (X9 as u32) << 5); // br x9
+}
+
+fn muldivmod (jit: &mut JitMemory, pc: usize, opc: u8, src: u8, dst: u8, imm: i32) {
+    let mul = (opc & ebpf::BPF_ALU_OP_MASK) == (ebpf::MUL32_IMM & ebpf::BPF_ALU_OP_MASK);
+    let div = (opc & ebpf::BPF_ALU_OP_MASK) == (ebpf::DIV32_IMM & ebpf::BPF_ALU_OP_MASK);
+    let is64 = (opc & ebpf::BPF_CLS_MASK) == ebpf::BPF_ALU64;
+    // Division by a zero immediate is rejected by the verifier
+    let is_reg = (opc & ebpf::BPF_X) != 0;
+
+    let src = if is_reg {
+        if !mul {
+            emit_load_imm(jit, X9, pc as i64);
+            emit_cmp_imm(jit, is64, src, 0);
+            emit_b_cond_far(jit, COND_EQ, TARGET_PC_DIV_BY_ZERO);
+        }
+        src
+    } else {
+        emit_load_imm_sized(jit, is64, X16, imm as i64 as u64);
+        X16
+    };
+
+    if mul {
+        emit_mul(jit, is64, dst, dst, src);
+    } else if div {
+        emit_alu(jit, is64, UDIV, dst, dst, src);
+    } else {
+        emit_alu(jit, is64, UDIV, X10, dst, src);
+        emit_msub(jit, is64, dst, X10, src, dst);
+    }
+}
+
+#[derive(Debug)]
+enum JumpKind {
+    // b or bl, 26-bit offset
+    Branch,
+    // b.cond, 19-bit offset
+    CondBranch,
+    // movz and movk instructions loading a 64-bit address
+    LoadAddress,
+    // 64-bit address in a table
+    Address,
+}
+
+#[derive(Debug)]
+struct Jump {
+    offset_loc: usize,
+    target_pc:  isize,
+    kind:       JumpKind,
+}
+
+struct JitMemory {
+    // Code is emitted into a growable buffer, then copied into executable memory once its size
+    // is known
+    contents:        Vec<u8>,
+    offset:          usize,
+    pc_locs:         Vec<usize>,
+    special_targets: HashMap<isize, usize>,
+    jumps:           Vec<Jump>,
+}
+
+impl JitMemory {
+    fn new() -> JitMemory {
+        JitMemory {
+            contents:        vec![],
+            offset:          0,
+            pc_locs:         vec![],
+            jumps:           vec![],
+            special_targets: HashMap::new(),
+        }
+    }
+
+    // Set bits in the instruction at loc.
+    fn patch(&mut self, loc: usize, bits: u32) {
+        let mut insn = [0u8; 4];
+        insn.copy_from_slice(&self.contents[loc..loc + 4]);
+        let insn = u32::from_le_bytes(insn) | bits;
+        self.contents[loc..loc + 4].copy_from_slice(&insn.to_le_bytes());
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn jit_compile(&mut self, prog: &[u8], use_mbuff: bool, update_data_ptr: bool,
+                   helpers: &HashMap<u32, ebpf::Helper>, maps: &HashMap<u32, &Map>,
+                   tail_call_progs: &[Option<&[u8]>], max_tail_calls: usize, mem_check: bool,
+                   insn_budget: Option<u64>) -> Result<(), JitError> {
+        // Programs reachable by tail calls are appended to the main program, and compiled along
+        // with it. Store the index of their first instruction.
+        let mut all_progs = prog.to_vec();
+        let tail_call_entries = tail_call_progs.iter().map(|p| p.map(|p| {
+            let entry = all_progs.len() / ebpf::INSN_SIZE;
+            all_progs.extend_from_slice(p);
+            entry
+        })).collect::<Vec<Option<usize>>>();
+        let prog = &all_progs[..];
+
+        emit_push_pair(self, X29, X30);
+        emit_addsub_imm12(self, true, false, X29, SP, 0); // mov x29, sp
+        emit_push_pair(self, X19, X20);
+        emit_push_pair(self, X21, X22);
+        emit_push_pair(self, X25, X26);
+
+        // X0: mbuff
+        // X1: mbuff_len
+        // X2: mem
+        // X3: mem_len
+        // X4: mem_offset
+        // X5: mem_end_offset
+        // X6: pointer to the JitFault
+
+        // Set up the runtime context
+        emit_sub_imm(self, true, SP, SP, CONTEXT_SIZE as i64);
+        emit_addsub_imm12(self, true, false, CTX, SP, 0); // mov x26, sp
+        emit_store(self, OperandSize::S64, X2, CTX, CONTEXT_MEM);
+        emit_store(self, OperandSize::S64, X3, CTX, CONTEXT_MEM_LEN);
+        emit_store(self, OperandSize::S64, XZR, CTX, CONTEXT_DEPTH);
+        emit_store(self, OperandSize::S64, XZR, CTX, CONTEXT_TAIL_CALLS);
+        emit_store(self, OperandSize::S64, X0, CTX, CONTEXT_MBUFF);
+        emit_store(self, OperandSize::S64, X1, CTX, CONTEXT_MBUFF_LEN);
+        emit_store(self, OperandSize::S64, X6, CTX, CONTEXT_FAULT);
+        if let Some(budget) = insn_budget {
+            emit_load_imm(self, X9, budget as i64);
+            emit_store(self, OperandSize::S64, X9, CTX, CONTEXT_INSN_BUDGET);
+        }
+
+        match (use_mbuff, update_data_ptr) {
+            (false, _) => {
+                // We do not use any mbuff. Move mem pointer into register 1.
+                emit_mov(self, true, X2, map_register(1));
+            },
+            (true, false) => {
+                // We use a mbuff already pointing to mem and mem_end: it is in register 1.
+            },
+            (true, true) => {
+                // We have a fixed (simulated) mbuff: update mem and mem_end offset values in it.
+                emit_alu(self, true, ADD_REG, X4, X4, X0);      // mbuff + mem_offset
+                emit_store(self, OperandSize::S64, X2, X4, 0);  // set mem
+                emit_alu(self, true, ADD_REG, X9, X2, X3);      // mem + mem_len (= mem_end)
+                emit_alu(self, true, ADD_REG, X5, X5, X0);      // mbuff + mem_end_offset
+                emit_store(self, OperandSize::S64, X9, X5, 0);  // set mem_end
+            }
+        }
+
+        // Copy context pointer, top of the stack, to R10
+        emit_mov(self, true, CTX, map_register(10));
+
+        // Allocate stack space
+        emit_sub_imm(self, true, SP, SP, STACK_TOTAL_SIZE as i64);
+
+        self.pc_locs = vec![0; prog.len() / ebpf::INSN_SIZE + 1];
+
+        // If the program has local functions, `exit` may return either from the program or from
+        // one of these functions.
+        let has_local_calls = (0..prog.len() / ebpf::INSN_SIZE).any(|i| {
+            let insn = ebpf::get_insn(prog, i);
+            insn.opc == ebpf::CALL && insn.src == ebpf::BPF_PSEUDO_CALL
+        });
+
+        let mut insn_ptr:usize = 0;
+        // Index of the first instruction of the program (main one, or reachable by tail calls)
+        // being compiled, so that errors report instructions relatively to it.
+        let mut prog_start = 0;
+        while insn_ptr * ebpf::INSN_SIZE < prog.len() {
+            let insn = ebpf::get_insn(prog, insn_ptr);
+
+            self.pc_locs[insn_ptr] = self.offset;
+            if tail_call_entries.contains(&Some(insn_ptr)) {
+                prog_start = insn_ptr;
+            }
+
+            let dst = map_register(insn.dst);
+            let src = map_register(insn.src);
+            let target_pc = insn_ptr as isize + insn.off as isize + 1;
+
+            if insn_budget.is_some() {
+                emit_insn_budget_check(self, insn_ptr - prog_start);
+            }
+
+            if mem_check {
+                let (target, base, len) = match insn.opc {
+                    ebpf::LD_B_REG   => (TARGET_PC_OOB_LOAD,  src, 1),
+                    ebpf::LD_H_REG   => (TARGET_PC_OOB_LOAD,  src, 2),
+                    ebpf::LD_W_REG   => (TARGET_PC_OOB_LOAD,  src, 4),
+                    ebpf::LD_DW_REG  => (TARGET_PC_OOB_LOAD,  src, 8),
+                    ebpf::ST_B_IMM   |
+                    ebpf::ST_B_REG   => (TARGET_PC_OOB_STORE, dst, 1),
+                    ebpf::ST_H_IMM   |
+                    ebpf::ST_H_REG   => (TARGET_PC_OOB_STORE, dst, 2),
+                    ebpf::ST_W_IMM   |
+                    ebpf::ST_W_REG   |
+                    ebpf::ST_W_XADD  => (TARGET_PC_OOB_STORE, dst, 4),
+                    ebpf::ST_DW_IMM  |
+                    ebpf::ST_DW_REG  |
+                    ebpf::ST_DW_XADD => (TARGET_PC_OOB_STORE, dst, 8),
+                    _                => (0, 0, 0),
+                };
+                if len > 0 {
+                    emit_mem_check(self, target, base, insn.off as i32, len,
+                                   insn_ptr - prog_start, maps);
+                }
+            }
+
+            let (is64, cond) = match insn.opc & ebpf::BPF_CLS_MASK {
+                ebpf::BPF_JMP32 => (false, jump_condition(insn.opc)),
+                ebpf::BPF_JMP   => (true,  jump_condition(insn.opc)),
+                _               => (true,  None),
+            };
+            if let Some(cond) = cond {
+                let jset = (insn.opc & ebpf::BPF_ALU_OP_MASK) == ebpf::BPF_JSET;
+                match ((insn.opc & ebpf::BPF_X) != 0, jset) {
+                    (true,  true)  => emit_alu(self, is64, ANDS_REG, XZR, dst, src),
+                    (true,  false) => emit_cmp(self, is64, dst, src),
+                    (false, true)  => {
+                        emit_load_imm_sized(self, is64, X16, insn.imm as i64 as u64);
+                        emit_alu(self, is64, ANDS_REG, XZR, dst, X16);
+                    },
+                    (false, false) => emit_cmp_imm(self, is64, dst, insn.imm),
+                }
+                emit_b_cond(self, cond, target_pc);
+                insn_ptr += 1;
+                continue;
+            }
+
+            match insn.opc {
+
+                // BPF_LD class
+                ebpf::LD_ABS_B   => emit_load_packet(self, OperandSize::S8,  None, insn.imm),
+                ebpf::LD_ABS_H   => emit_load_packet(self, OperandSize::S16, None, insn.imm),
+                ebpf::LD_ABS_W   => emit_load_packet(self, OperandSize::S32, None, insn.imm),
+                ebpf::LD_ABS_DW  => emit_load_packet(self, OperandSize::S64, None, insn.imm),
+                ebpf::LD_IND_B   => emit_load_packet(self, OperandSize::S8,  Some(src), insn.imm),
+                ebpf::LD_IND_H   => emit_load_packet(self, OperandSize::S16, Some(src), insn.imm),
+                ebpf::LD_IND_W   => emit_load_packet(self, OperandSize::S32, Some(src), insn.imm),
+                ebpf::LD_IND_DW  => emit_load_packet(self, OperandSize::S64, Some(src), insn.imm),
+
+                // BPF_LDX class
+                ebpf::LD_DW_IMM  => {
+                    insn_ptr += 1;
+                    let imm = if insn.src == ebpf::BPF_PSEUDO_MAP_FD {
+                        match maps.get(&(insn.imm as u32)) {
+                            Some(map) => *map as *const Map as u64,
+                            None      => return Err(JitError::UnknownMap(insn.imm as u32,
+                                                                         insn_ptr - 1 - prog_start)),
+                        }
+                    } else {
+                        let second_part = ebpf::get_insn(prog, insn_ptr).imm as u64;
+                        (insn.imm as u32) as u64 | second_part.wrapping_shl(32)
+                    };
+                    emit_load_imm(self, dst, imm as i64);
+                },
+                ebpf::LD_B_REG   =>
+                    emit_load(self, OperandSize::S8,  src, dst, insn.off as i32),
+                ebpf::LD_H_REG   =>
+                    emit_load(self, OperandSize::S16, src, dst, insn.off as i32),
+                ebpf::LD_W_REG   =>
+                    emit_load(self, OperandSize::S32, src, dst, insn.off as i32),
+                ebpf::LD_DW_REG  =>
+                    emit_load(self, OperandSize::S64, src, dst, insn.off as i32),
+
+                // BPF_ST class
+                ebpf::ST_B_IMM   =>
+                    emit_store_imm32(self, OperandSize::S8,  dst, insn.off as i32, insn.imm),
+                ebpf::ST_H_IMM   =>
+                    emit_store_imm32(self, OperandSize::S16, dst, insn.off as i32, insn.imm),
+                ebpf::ST_W_IMM   =>
+                    emit_store_imm32(self, OperandSize::S32, dst, insn.off as i32, insn.imm),
+                ebpf::ST_DW_IMM  =>
+                    emit_store_imm32(self, OperandSize::S64, dst, insn.off as i32, insn.imm),
+
+                // BPF_STX class
+                ebpf::ST_B_REG   =>
+                    emit_store(self, OperandSize::S8,  src, dst, insn.off as i32),
+                ebpf::ST_H_REG   =>
+                    emit_store(self, OperandSize::S16, src, dst, insn.off as i32),
+                ebpf::ST_W_REG   =>
+                    emit_store(self, OperandSize::S32, src, dst, insn.off as i32),
+                ebpf::ST_DW_REG  =>
+                    emit_store(self, OperandSize::S64, src, dst, insn.off as i32),
+                ebpf::ST_W_XADD  => {
+                    emit_align_check(self, dst, insn.off as i32, 4, insn_ptr - prog_start);
+                    emit_atomic_add(self, OperandSize::S32, src, dst, insn.off as i32);
+                },
+                ebpf::ST_DW_XADD => {
+                    emit_align_check(self, dst, insn.off as i32, 8, insn_ptr - prog_start);
+                    emit_atomic_add(self, OperandSize::S64, src, dst, insn.off as i32);
+                },
+
+                // BPF_ALU class
+                ebpf::ADD32_IMM  => emit_add_imm(self, false, dst, dst, insn.imm as i64),
+                ebpf::ADD32_REG  => emit_alu(self, false, ADD_REG, dst, dst, src),
+                ebpf::SUB32_IMM  => emit_sub_imm(self, false, dst, dst, insn.imm as i64),
+                ebpf::SUB32_REG  => emit_alu(self, false, SUB_REG, dst, dst, src),
+                ebpf::MUL32_IMM | ebpf::MUL32_REG |
+                    ebpf::DIV32_IMM | ebpf::DIV32_REG |
+                    ebpf::MOD32_IMM | ebpf::MOD32_REG =>
+                    muldivmod(self, insn_ptr - prog_start, insn.opc, src, dst, insn.imm),
+                ebpf::OR32_IMM   => emit_alu_imm(self, false, ORR_REG, dst, insn.imm),
+                ebpf::OR32_REG   => emit_alu(self, false, ORR_REG, dst, dst, src),
+                ebpf::AND32_IMM  => emit_alu_imm(self, false, AND_REG, dst, insn.imm),
+                ebpf::AND32_REG  => emit_alu(self, false, AND_REG, dst, dst, src),
+                ebpf::LSH32_IMM  => emit_shift_imm(self, false, LSLV, dst, insn.imm),
+                ebpf::LSH32_REG  => emit_alu(self, false, LSLV, dst, dst, src),
+                ebpf::RSH32_IMM  => emit_shift_imm(self, false, LSRV, dst, insn.imm),
+                ebpf::RSH32_REG  => emit_alu(self, false, LSRV, dst, dst, src),
+                ebpf::NEG32      => emit_alu(self, false, SUB_REG, dst, XZR, dst),
+                ebpf::XOR32_IMM  => emit_alu_imm(self, false, EOR_REG, dst, insn.imm),
+                ebpf::XOR32_REG  => emit_alu(self, false, EOR_REG, dst, dst, src),
+                ebpf::MOV32_IMM  => emit_load_imm_sized(self, false, dst, insn.imm as u32 as u64),
+                ebpf::MOV32_REG  => emit_mov(self, false, src, dst),
+                ebpf::ARSH32_IMM => emit_shift_imm(self, false, ASRV, dst, insn.imm),
+                ebpf::ARSH32_REG => emit_alu(self, false, ASRV, dst, dst, src),
+                ebpf::LE         => {}, // No-op
+                ebpf::BE         => emit_bswap(self, dst, insn.imm),
+
+                // BPF_ALU64 class
+                ebpf::ADD64_IMM  => emit_add_imm(self, true, dst, dst, insn.imm as i64),
+                ebpf::ADD64_REG  => emit_alu(self, true, ADD_REG, dst, dst, src),
+                ebpf::SUB64_IMM  => emit_sub_imm(self, true, dst, dst, insn.imm as i64),
+                ebpf::SUB64_REG  => emit_alu(self, true, SUB_REG, dst, dst, src),
+                ebpf::MUL64_IMM | ebpf::MUL64_REG |
+                    ebpf::DIV64_IMM | ebpf::DIV64_REG |
+                    ebpf::MOD64_IMM | ebpf::MOD64_REG  =>
+                    muldivmod(self, insn_ptr - prog_start, insn.opc, src, dst, insn.imm),
+                ebpf::OR64_IMM   => emit_alu_imm(self, true, ORR_REG, dst, insn.imm),
+                ebpf::OR64_REG   => emit_alu(self, true, ORR_REG, dst, dst, src),
+                ebpf::AND64_IMM  => emit_alu_imm(self, true, AND_REG, dst, insn.imm),
+                ebpf::AND64_REG  => emit_alu(self, true, AND_REG, dst, dst, src),
+                ebpf::LSH64_IMM  => emit_shift_imm(self, true, LSLV, dst, insn.imm),
+                ebpf::LSH64_REG  => emit_alu(self, true, LSLV, dst, dst, src),
+                ebpf::RSH64_IMM  => emit_shift_imm(self, true, LSRV, dst, insn.imm),
+                ebpf::RSH64_REG  => emit_alu(self, true, LSRV, dst, dst, src),
+                ebpf::NEG64      => emit_alu(self, true, SUB_REG, dst, XZR, dst),
+                ebpf::XOR64_IMM  => emit_alu_imm(self, true, EOR_REG, dst, insn.imm),
+                ebpf::XOR64_REG  => emit_alu(self, true, EOR_REG, dst, dst, src),
+                ebpf::MOV64_IMM  => emit_load_imm(self, dst, insn.imm as i64),
+                ebpf::MOV64_REG  => emit_mov(self, true, src, dst),
+                ebpf::ARSH64_IMM => emit_shift_imm(self, true, ASRV, dst, insn.imm),
+                ebpf::ARSH64_REG => emit_alu(self, true, ASRV, dst, dst, src),
+
+                // BPF_JMP class, conditional jumps were handled above
+                ebpf::JA         => emit_b(self, false, target_pc),
+
+                ebpf::CALL if insn.src == ebpf::BPF_PSEUDO_CALL =>
+                    emit_local_call(self, insn_ptr as isize + insn.imm as isize + 1,
+                                    insn_ptr - prog_start),
+                ebpf::CALL       => {
+                    // For JIT, helpers in use MUST be registered at compile time. They can be
+                    // updated later, but not created after compiling (we need the address of the
+                    // helper function in the JIT-compiled program).
+                    if let (Some(helper), false) = (maps::helper(insn.imm as u32),
+                                                    maps.is_empty()) {
+                        emit_map_helper_call(self, helper, insn.imm as u32,
+                                             insn_ptr - prog_start, maps, mem_check);
+                    } else if let Some(helper) = helpers.get(&(insn.imm as u32)) {
+                        emit_call(self, *helper as usize as i64);
+                    } else {
+                        return Err(JitError::UnknownHelper(insn.imm as u32,
+                                                           insn_ptr - prog_start));
+                    };
+                },
+                ebpf::TAIL_CALL  => if !tail_call_entries.is_empty() {
+                    emit_tail_call(self, tail_call_entries.len(), max_tail_calls,
+                                   insn_ptr as isize + 1);
+                },
+                ebpf::EXIT       => {
+                    if has_local_calls {
+                        // Return to the caller if we are in a local function.
+                        emit_load(self, OperandSize::S64, CTX, X10, CONTEXT_DEPTH);
+                        emit_cmp_imm(self, true, X10, 0);
+                        emit_b_cond_far(self, COND_EQ, TARGET_PC_EXIT);
+                        emit4(self, RET);
+                    } else if insn_ptr != prog.len() / ebpf::INSN_SIZE - 1 {
+                        emit_b(self, false, TARGET_PC_EXIT);
+                    };
+                },
+
+                _                => {
+                    return Err(JitError::UnknownOpcode(insn.opc, insn_ptr - prog_start));
+                },
+            }
+
+            insn_ptr += 1;
+        }
+
+        // Epilogue
+        set_anchor(self, TARGET_PC_EXIT);
+
+        // Move register 0 into x0
+        emit_mov(self, true, map_register(0), X0);
+
+        // Report the number of instructions executed: the initial budget minus what is left
+        if let Some(budget) = insn_budget {
+            emit_load_imm(self, X9, budget as i64);
+            emit_load(self, OperandSize::S64, CTX, X10, CONTEXT_INSN_BUDGET);
+            emit_alu(self, true, SUB_REG, X9, X9, X10);
+            emit_load(self, OperandSize::S64, CTX, X10, CONTEXT_FAULT);
+            emit_store(self, OperandSize::S64, X9, X10, 32);
+        }
+
+        // Deallocate stack space and runtime context. We may be exiting from a local function
+        // (on error), so restore the stack pointer from X26 rather than from its current value.
+        emit_add_imm(self, true, SP, CTX, CONTEXT_SIZE as i64);
+
+        emit_pop_pair(self, X25, X26);
+        emit_pop_pair(self, X21, X22);
+        emit_pop_pair(self, X19, X20);
+        emit_pop_pair(self, X29, X30);
+
+        emit4(self, RET);
+
+        // Division by zero handler: muldivmod stored pc in X9
+        set_anchor(self, TARGET_PC_DIV_BY_ZERO);
+        emit_fault(self, FAULT_DIV_BY_ZERO);
+        emit_b(self, false, TARGET_PC_EXIT);
+
+        // Out of bounds packet load handler: exit and return 0
+        set_anchor(self, TARGET_PC_EXIT_ZERO);
+        emit_load_imm(self, map_register(0), 0);
+        emit_b(self, false, TARGET_PC_EXIT);
+
+        // Too many nested local function calls: emit_local_call stored pc in X9
+        set_anchor(self, TARGET_PC_CALL_DEPTH);
+        emit_fault(self, FAULT_CALL_DEPTH);
+        emit_b(self, false, TARGET_PC_EXIT);
+
+        // Instruction budget exhausted: emit_insn_budget_check stored pc in X9
+        set_anchor(self, TARGET_PC_INSN_BUDGET);
+        emit_fault(self, FAULT_INSN_BUDGET);
+        emit_b(self, false, TARGET_PC_EXIT);
+
+        // Invalid map handle: emit_map_helper_call stored pc in X9, and the handle in X17
+        set_anchor(self, TARGET_PC_INVALID_MAP);
+        emit_fault(self, FAULT_INVALID_MAP);
+        emit_store(self, OperandSize::S64, X17, X10, 16);
+        emit_b(self, false, TARGET_PC_EXIT);
+
+        // Out of bounds memory accesses: emit_mem_check stored pc and length in X9, and the end
+        // of the access in X17
+        set_anchor(self, TARGET_PC_OOB_LOAD);
+        emit_fault(self, FAULT_OOB_LOAD);
+        emit_b(self, false, TARGET_PC_MEM_FAULT);
+        set_anchor(self, TARGET_PC_OOB_STORE);
+        emit_fault(self, FAULT_OOB_STORE);
+        emit_b(self, false, TARGET_PC_MEM_FAULT);
+        // Unaligned atomic operations: emit_align_check stored the same values
+        set_anchor(self, TARGET_PC_UNALIGNED);
+        emit_fault(self, FAULT_UNALIGNED);
+        set_anchor(self, TARGET_PC_MEM_FAULT);
+        emit_bfm(self, true, UBFM, X11, X9, 32, 63); // lsr x11, x9, #32: length of the access
+        emit_store(self, OperandSize::S64, X11, X10, 24);
+        emit_alu(self, true, SUB_REG, X17, X17, X11); // start of the access
+        emit_store(self, OperandSize::S64, X17, X10, 16);
+        emit_b(self, false, TARGET_PC_EXIT);
+
+        // Table of the addresses of the programs reachable by tail calls, null for empty slots
+        if !tail_call_entries.is_empty() {
+            if self.offset % 8 != 0 {
+                emit4(self, 0); // udf, never executed
+            }
+            set_anchor(self, TARGET_PC_TAIL_CALL_TABLE);
+            for entry in &tail_call_entries {
+                if let Some(entry) = *entry {
+                    self.jumps.push(Jump { offset_loc: self.offset, target_pc: entry as isize,
+                                           kind: JumpKind::Address });
+                }
+                emit8(self, 0);
+            }
+        }
+
+        Ok(())
+    }
+
+    // Patch jumps and absolute addresses, for code that will be copied at address `base`. Fail if
+    // a conditional branch cannot reach its target.
+    fn resolve_jumps(&mut self, base: u64) -> Result<(), JitError> {
+        for i in 0..self.jumps.len() {
+            let (loc, target_pc) = (self.jumps[i].offset_loc, self.jumps[i].target_pc);
+            let target_loc = match self.special_targets.get(&target_pc) {
+                Some(target) => *target,
+                None         => self.pc_locs[target_pc as usize]
+            };
+            let address = base + target_loc as u64;
+            // Offsets are counted in instructions, from the branch itself
+            let rel = (target_loc as i64 - loc as i64) / 4;
+
+            match self.jumps[i].kind {
+                JumpKind::Branch      => {
+                    if !(-(1 << 25)..1 << 25).contains(&rel) {
+                        return Err(JitError::CodeTooLarge(self.offset));
+                    }
+                    self.patch(loc, rel as u32 & 0x3ffffff);
+                },
+                JumpKind::CondBranch  => {
+                    if !(-(1 << 18)..1 << 18).contains(&rel) {
+                        return Err(JitError::CodeTooLarge(self.offset));
+                    }
+                    self.patch(loc, (rel as u32 & 0x7ffff) << 5);
+                },
+                JumpKind::LoadAddress => for hw in 0..4 {
+                    self.patch(loc + 4 * hw, ((address >> (16 * hw)) as u32 & 0xffff) << 5);
+                },
+                JumpKind::Address     =>
+                    self.contents[loc..loc + 8].copy_from_slice(&address.to_le_bytes()),
+            }
+        }
+        Ok(())
+    }
+} // struct JitMemory
+
+impl std::fmt::Debug for JitMemory {
+    fn fmt(&self, fmt: &mut Formatter) -> Result<(), Error> {
+        fmt.write_str("JIT contents: [")?;
+        for i in &self.contents {
+            fmt.write_fmt(format_args!(" {:#04x},", i))?;
+        };
+        fmt.write_str(" ] | ")?;
+        fmt.debug_struct("JIT state")
+            .field("offset", &self.offset)
+            .field("pc_locs", &self.pc_locs)
+            .field("special_targets", &self.special_targets)
+            .field("jumps", &self.jumps)
+            .finish()
+    }
+}
+
+// Condition code for the conditional jumps of classes BPF_JMP and BPF_JMP32, None for the other
+// instructions of these classes. JSET is tested as "not equal" after an AND.
+fn jump_condition (opc: u8) -> Option<u32> {
+    match opc & ebpf::BPF_ALU_OP_MASK {
+        ebpf::BPF_JEQ  => Some(COND_EQ),
+        ebpf::BPF_JGT  => Some(COND_HI),
+        ebpf::BPF_JGE  => Some(COND_HS),
+        ebpf::BPF_JSET => Some(COND_NE),
+        ebpf::BPF_JNE  => Some(COND_NE),
+        ebpf::BPF_JSGT => Some(COND_GT),
+        ebpf::BPF_JSGE => Some(COND_GE),
+        ebpf::BPF_JLT  => Some(COND_LO),
+        ebpf::BPF_JLE  => Some(COND_LS),
+        ebpf::BPF_JSLT => Some(COND_LT),
+        ebpf::BPF_JSLE => Some(COND_LE),
+        _              => None,
+    }
+}
+
+// Make the instruction cache coherent with the code written at [start, start + len): unlike x86,
+// AArch64 does not do it by itself.
+pub fn flush_icache(start: usize, len: usize) {
+    let ctr: u64;
+    unsafe {
+        asm!("mrs {}, ctr_el0", out(reg) ctr, options(nomem, nostack));
+    }
+    // Minimum line sizes of data and instruction caches, in bytes
+    let dline = 4 << ((ctr >> 16) & 0xf);
+    let iline = 4 << (ctr & 0xf);
+    let end = start + len;
+    unsafe {
+        let mut addr = start & !(dline - 1);
+        while addr < end {
+            asm!("dc cvau, {}", in(reg) addr, options(nostack));
+            addr += dline;
+        }
+        asm!("dsb ish", options(nostack));
+        let mut addr = start & !(iline - 1);
+        while addr < end {
+            asm!("ic ivau, {}", in(reg) addr, options(nostack));
+            addr += iline;
+        }
+        asm!("dsb ish", "isb", options(nostack));
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn compile(prog: &[u8],
+               helpers: &HashMap<u32, ebpf::Helper>,
+               maps: &HashMap<u32, &Map>,
+               use_mbuff: bool, update_data_ptr: bool,
+               tail_call_progs: &[Option<&[u8]>], max_tail_calls: usize,
+               mem_check: bool, insn_budget: Option<u64>)
+    -> Result<JitProgram, JitError> {
+
+    let mut jit = JitMemory::new();
+    jit.jit_compile(prog, use_mbuff, update_data_ptr, helpers, maps, tail_call_progs,
+                    max_tail_calls, mem_check, insn_budget)?;
+    if jit.offset > MAX_CODE_SIZE {
+        return Err(JitError::CodeTooLarge(jit.offset));
+    }
+
+    let mut program = JitProgram::new((jit.offset + PAGE_SIZE - 1) / PAGE_SIZE * PAGE_SIZE)?;
+    jit.resolve_jumps(program.code as u64)?;
+    // Prepopulate the rest of the memory with zeroes, that are permanently undefined instructions
+    program.load(&jit.contents, 0)?;
+    program.counts_insns = insn_budget.is_some();
+    Ok(program)
+}
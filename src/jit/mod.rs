@@ -0,0 +1,164 @@
+// Copyright 2026 agent <agent@local>
+//
+// Licensed under the Apache License, Version 2.0 <http://www.apache.org/licenses/LICENSE-2.0> or
+// the MIT license <http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+// JIT compilers. The backend is selected at build time after the target architecture; all of them
+// generate code with the same semantics, and share the layout of the fault description and the
+// management of executable memory defined here.
+
+// Without a backend, the code that manages executable memory is never used.
+#![cfg_attr(not(any(target_arch = "x86_64", target_arch = "aarch64")), allow(dead_code))]
+
+use std;
+use std::mem;
+
+use error::{ExecError, ExecErrorKind, JitError};
+
+extern crate libc;
+
+#[cfg(target_arch = "x86_64")]
+mod x86_64;
+#[cfg(target_arch = "aarch64")]
+mod aarch64;
+
+#[cfg(target_arch = "x86_64")]
+pub use self::x86_64::compile;
+#[cfg(target_arch = "aarch64")]
+pub use self::aarch64::compile;
+
+const PAGE_SIZE: usize = 4096;
+
+// Values for the `kind` field of JitFault.
+const FAULT_NONE:         u64 = 0;
+const FAULT_OOB_LOAD:     u64 = 1;
+const FAULT_OOB_STORE:    u64 = 2;
+const FAULT_DIV_BY_ZERO:  u64 = 3;
+const FAULT_CALL_DEPTH:   u64 = 4;
+const FAULT_INVALID_MAP:  u64 = 5;
+const FAULT_INSN_BUDGET:  u64 = 6;
+const FAULT_UNALIGNED:    u64 = 7;
+
+/// Description of a fault, filled by the JIT-compiled program before it aborts. Programs compiled
+/// with an instruction budget also store the number of instructions executed when they exit. The
+/// offsets of the fields are used in the generated code.
+#[repr(C)]
+#[derive(Debug, Default)]
+pub struct JitFault {
+    kind:       u64,
+    insn_ptr:   u64,
+    addr:       u64,
+    size:       u64,
+    insn_count: u64,
+}
+
+impl JitFault {
+    /// Turn the value returned by the JIT-compiled program into an error if a fault occurred.
+    pub fn into_result(self, res: u64) -> Result<u64, ExecError> {
+        let kind = match self.kind {
+            FAULT_NONE        => return Ok(res),
+            FAULT_OOB_LOAD    => ExecErrorKind::OutOfBoundsLoad,
+            FAULT_OOB_STORE   => ExecErrorKind::OutOfBoundsStore,
+            FAULT_DIV_BY_ZERO => ExecErrorKind::DivisionByZero,
+            FAULT_CALL_DEPTH  => ExecErrorKind::CallDepthExceeded,
+            FAULT_INVALID_MAP => ExecErrorKind::InvalidMapPointer,
+            FAULT_INSN_BUDGET => ExecErrorKind::InstructionBudgetExceeded,
+            FAULT_UNALIGNED   => ExecErrorKind::UnalignedAtomic,
+            _                 => unreachable!(),
+        };
+        Err(ExecError::mem(kind, self.insn_ptr as usize, self.addr, self.size as usize))
+    }
+
+    /// Number of instructions executed by a program compiled with an instruction budget.
+    pub fn insn_count(&self) -> u64 {
+        self.insn_count
+    }
+}
+
+/// Signature of JIT-compiled programs: metadata buffer and its length, packet data and its
+/// length, offsets at which to store pointers to packet data in the metadata buffer, and fault
+/// description to fill on error. Backends follow the C calling convention of the target.
+pub type JitFunction =
+    unsafe extern "C" fn (*mut u8, usize, *mut u8, usize, usize, usize, *mut JitFault) -> u64;
+
+/// JIT-compiled program. It owns the executable memory holding the generated code, and releases
+/// it when dropped. The memory is writable during compilation only: once the code is in place,
+/// it is made read-only and executable.
+#[derive(Debug)]
+pub struct JitProgram {
+    code: *mut libc::c_void,
+    size: usize,
+    // Whether the program was compiled with an instruction budget, and counts instructions.
+    counts_insns: bool,
+}
+
+// The generated code is never modified once the program has been created.
+unsafe impl Send for JitProgram {}
+unsafe impl Sync for JitProgram {}
+
+impl JitProgram {
+    // Map `size` bytes of memory (a multiple of the page size), writable but not executable.
+    fn new(size: usize) -> Result<JitProgram, JitError> {
+        let code = unsafe {
+            libc::mmap(std::ptr::null_mut(), size, libc::PROT_READ | libc::PROT_WRITE,
+                       libc::MAP_PRIVATE | libc::MAP_ANONYMOUS, -1, 0)
+        };
+        if code == libc::MAP_FAILED {
+            return Err(JitError::MemoryAllocation(errno()));
+        }
+        Ok(JitProgram { code, size, counts_insns: false })
+    }
+
+    // Copy the code into the memory, then make it read-only and executable. The rest of the
+    // memory is filled with `fill`, which should be a trapping instruction for the target.
+    fn load(&mut self, contents: &[u8], fill: u8) -> Result<(), JitError> {
+        unsafe {
+            std::ptr::write_bytes(self.code as *mut u8, fill, self.size);
+            std::ptr::copy_nonoverlapping(contents.as_ptr(), self.code as *mut u8, contents.len());
+            #[cfg(target_arch = "aarch64")]
+            aarch64::flush_icache(self.code as usize, self.size);
+            if libc::mprotect(self.code, self.size, libc::PROT_READ | libc::PROT_EXEC) != 0 {
+                return Err(JitError::MemoryAllocation(errno()));
+            }
+        }
+        Ok(())
+    }
+
+    /// Return the function to call to run the program. It must not be called once the
+    /// `JitProgram` has been dropped.
+    pub fn function(&self) -> JitFunction {
+        unsafe { mem::transmute::<*mut libc::c_void, JitFunction>(self.code) }
+    }
+
+    /// Return whether the program counts the instructions it executes, and reports their number
+    /// in the `JitFault` on exit.
+    pub fn counts_insns(&self) -> bool {
+        self.counts_insns
+    }
+}
+
+impl Drop for JitProgram {
+    fn drop(&mut self) {
+        unsafe {
+            libc::munmap(self.code, self.size);
+        }
+    }
+}
+
+fn errno() -> i32 {
+    std::io::Error::last_os_error().raw_os_error().unwrap_or(0)
+}
+
+/// Fallback for architectures without a JIT backend: always fails.
+#[cfg(not(any(target_arch = "x86_64", target_arch = "aarch64")))]
+#[allow(clippy::too_many_arguments)]
+pub fn compile(_prog: &[u8],
+               _helpers: &std::collections::HashMap<u32, ::ebpf::Helper>,
+               _maps: &std::collections::HashMap<u32, &::maps::Map>,
+               _use_mbuff: bool, _update_data_ptr: bool,
+               _tail_call_progs: &[Option<&[u8]>], _max_tail_calls: usize,
+               _mem_check: bool, _insn_budget: Option<u64>)
+    -> Result<JitProgram, JitError> {
+    Err(JitError::UnsupportedArchitecture)
+}
@@ -0,0 +1,1460 @@
+// Derived from uBPF <https://github.com/iovisor/ubpf>
+// Copyright 2015 Big Switch Networks, Inc
+//      (uBPF: JIT algorithm, originally in C)
+// Copyright 2016 Quentin Monnet <quentin.monnet@6wind.com>
+//      (Translation to Rust, MetaBuff addition)
+//
+// Licensed under the Apache License, Version 2.0 <http://www.apache.org/licenses/LICENSE-2.0> or
+// the MIT license <http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+
+use std;
+use std::mem;
+use std::collections::HashMap;
+use std::fmt::{Error, Formatter};
+use std::ops::{Index, IndexMut};
+
+use ebpf;
+use error::JitError;
+use maps::{self, Map, MapHelper};
+
+use super::{JitProgram, PAGE_SIZE, FAULT_OOB_LOAD, FAULT_OOB_STORE, FAULT_DIV_BY_ZERO,
+            FAULT_CALL_DEPTH, FAULT_INVALID_MAP, FAULT_INSN_BUDGET, FAULT_UNALIGNED};
+
+// Jumps use 32-bit relative offsets, so the code cannot be larger than this
+const MAX_CODE_SIZE: usize = i32::MAX as usize;
+
+// Special values for target_pc in struct Jump. They must not collide with the index of an
+// instruction, even when the programs reachable by tail calls are compiled along with the main one.
+const TARGET_OFFSET: isize = i32::MAX as isize;
+const TARGET_PC_EXIT:         isize = TARGET_OFFSET + 1;
+const TARGET_PC_DIV_BY_ZERO:  isize = TARGET_OFFSET + 2;
+const TARGET_PC_EXIT_ZERO:    isize = TARGET_OFFSET + 3;
+const TARGET_PC_CALL_DEPTH:   isize = TARGET_OFFSET + 4;
+const TARGET_PC_TAIL_CALL_TABLE: isize = TARGET_OFFSET + 5;
+const TARGET_PC_OOB_LOAD:     isize = TARGET_OFFSET + 6;
+const TARGET_PC_OOB_STORE:    isize = TARGET_OFFSET + 7;
+const TARGET_PC_MEM_FAULT:    isize = TARGET_OFFSET + 8;
+const TARGET_PC_INVALID_MAP:  isize = TARGET_OFFSET + 9;
+const TARGET_PC_INSN_BUDGET:  isize = TARGET_OFFSET + 10;
+const TARGET_PC_UNALIGNED:    isize = TARGET_OFFSET + 11;
+
+// Runtime context, stored on the stack below the callee-saved registers and pointed to by R12.
+// It holds data needed by some instructions while the program runs.
+const CONTEXT_MEM:     i32 = 0; // Pointer to packet data
+const CONTEXT_MEM_LEN: i32 = 8; // Length of packet data
+const CONTEXT_DEPTH:   i32 = 16; // Number of nested local function calls
+const CONTEXT_TAIL_CALLS: i32 = 24; // Number of tail calls performed so far
+const CONTEXT_MBUFF:   i32 = 32; // Pointer to metadata buffer
+const CONTEXT_MBUFF_LEN: i32 = 40; // Length of metadata buffer
+const CONTEXT_FAULT:   i32 = 48; // Pointer to the JitFault to fill on error
+const CONTEXT_INSN_BUDGET: i32 = 56; // Number of instructions the program can still execute
+// Keep the stack 16-byte aligned for helper calls.
+const CONTEXT_SIZE:    i32 = 72;
+
+// The eBPF stack holds the frames of all nested local functions, contiguously, below the runtime
+// context. The main program uses the top frame.
+const STACK_TOTAL_SIZE: i32 = (ebpf::STACK_SIZE * ebpf::MAX_CALL_DEPTH) as i32;
+
+enum OperandSize {
+    S8  = 8,
+    S16 = 16,
+    S32 = 32,
+    S64 = 64,
+}
+
+// Registers
+const RAX: u8 = 0;
+const RCX: u8 = 1;
+const RDX: u8 = 2;
+const RBX: u8 = 3;
+const RSP: u8 = 4;
+const RBP: u8 = 5;
+const RSI: u8 = 6;
+const RDI: u8 = 7;
+const R8:  u8 = 8;
+const R9:  u8 = 9;
+//const R10: u8 = 10;
+const R11: u8 = 11;
+const R12: u8 = 12;
+const R13: u8 = 13;
+const R14: u8 = 14;
+const R15: u8 = 15;
+
+const REGISTER_MAP_SIZE: usize = 11;
+const REGISTER_MAP: [u8;REGISTER_MAP_SIZE] = [
+    RAX, // 0  return value
+    RDI, // 1  arg 1
+    RSI, // 2  arg 2
+    RDX, // 3  arg 3
+    R9,  // 4  arg 4
+    R8,  // 5  arg 5
+    RBX, // 6  callee-saved
+    R13, // 7  callee-saved
+    R14, // 8  callee-saved
+    R15, // 9  callee-saved
+    RBP, // 10 stack pointer
+];
+
+// Return the x86 register for the given eBPF register
+fn map_register(r: u8) -> u8 {
+    assert!(r < REGISTER_MAP_SIZE as u8);
+    REGISTER_MAP[(r % REGISTER_MAP_SIZE as u8) as usize]
+}
+
+macro_rules! emit_bytes {
+    ( $jit:ident, $data:tt, $t:ty ) => {{
+        let size = mem::size_of::<$t>();
+        $jit.contents.extend_from_slice(&($data as $t).to_le_bytes());
+        $jit.offset += size;
+    }}
+}
+
+#[inline]
+fn emit1 (jit: &mut JitMemory, data: u8) {
+    emit_bytes!(jit, data, u8);
+}
+
+#[inline]
+fn emit2 (jit: &mut JitMemory, data: u16) {
+    emit_bytes!(jit, data, u16);
+}
+
+#[inline]
+fn emit4 (jit: &mut JitMemory, data: u32) {
+    emit_bytes!(jit, data, u32);
+}
+
+#[inline]
+fn emit8 (jit: &mut JitMemory, data: u64) {
+    emit_bytes!(jit, data, u64);
+}
+
+#[inline]
+fn emit_jump_offset (jit: &mut JitMemory, target_pc: isize) {
+    let jump = Jump { offset_loc: jit.offset, target_pc };
+    jit.jumps.push(jump);
+    emit4(jit, 0);
+}
+
+#[inline]
+fn emit_modrm (jit: &mut JitMemory, modrm: u8, r: u8, m: u8) {
+    assert!((modrm | 0xc0) == 0xc0);
+    emit1(jit, (modrm & 0xc0) | ((r & 0b111) << 3) | (m & 0b111));
+}
+
+#[inline]
+fn emit_modrm_reg2reg (jit: &mut JitMemory, r: u8, m: u8) {
+    emit_modrm(jit, 0xc0, r, m);
+}
+
+#[inline]
+fn emit_modrm_and_displacement (jit: &mut JitMemory, r: u8, m: u8, d: i32) {
+    // With RSP or R12 as base register, a SIB byte is required.
+    let emit_sib = | jit: &mut JitMemory | {
+        if (m & 0b111) == RSP {
+            emit1(jit, 0x24);
+        }
+    };
+    if d == 0 && (m & 0b111) != RBP {
+        emit_modrm(jit, 0x00, r, m);
+        emit_sib(jit);
+    } else if (-128..=127).contains(&d) {
+        emit_modrm(jit, 0x40, r, m);
+        emit_sib(jit);
+        emit1(jit, d as u8);
+    } else {
+        emit_modrm(jit, 0x80, r, m);
+        emit_sib(jit);
+        emit4(jit, d as u32);
+    }
+}
+
+#[inline]
+fn emit_rex (jit: &mut JitMemory, w: u8, r: u8, x: u8, b: u8) {
+    assert!((w | 1) == 1);
+    assert!((r | 1) == 1);
+    assert!((x | 1) == 1);
+    assert!((b | 1) == 1);
+    emit1(jit, 0x40 | (w << 3) | (r << 2) | (x << 1) | b);
+}
+
+// Emits a REX prefix with the top bit of src and dst.
+// Skipped if no bits would be set.
+#[inline]
+fn emit_basic_rex (jit: &mut JitMemory, w: u8, src: u8, dst: u8) {
+    if w != 0 || (src & 0b1000) != 0 || (dst & 0b1000) != 0 {
+        let is_masked = | val, mask | { match val & mask {
+            0 => 0,
+            _ => 1
+        }};
+        emit_rex(jit, w, is_masked(src, 8), 0, is_masked(dst, 8));
+    }
+}
+
+#[inline]
+fn emit_push (jit: &mut JitMemory, r: u8) {
+    emit_basic_rex(jit, 0, 0, r);
+    emit1(jit, 0x50 | (r & 0b111));
+}
+
+#[inline]
+fn emit_pop (jit: &mut JitMemory, r: u8) {
+    emit_basic_rex(jit, 0, 0, r);
+    emit1(jit, 0x58 | (r & 0b111));
+}
+
+// REX prefix and ModRM byte
+// We use the MR encoding when there is a choice
+// 'src' is often used as an opcode extension
+#[inline]
+fn emit_alu32 (jit: &mut JitMemory, op: u8, src: u8, dst: u8) {
+    emit_basic_rex(jit, 0, src, dst);
+    emit1(jit, op);
+    emit_modrm_reg2reg(jit, src, dst);
+}
+
+// REX prefix, ModRM byte, and 32-bit immediate
+#[inline]
+fn emit_alu32_imm32 (jit: &mut JitMemory, op: u8, src: u8, dst: u8, imm: i32) {
+    emit_alu32(jit, op, src, dst);
+    emit4(jit, imm as u32);
+}
+
+// REX prefix, ModRM byte, and 8-bit immediate
+#[inline]
+fn emit_alu32_imm8 (jit: &mut JitMemory, op: u8, src: u8, dst: u8, imm: i8) {
+    emit_alu32(jit, op, src, dst);
+    emit1(jit, imm as u8);
+}
+
+// REX.W prefix and ModRM byte
+// We use the MR encoding when there is a choice
+// 'src' is often used as an opcode extension
+#[inline]
+fn emit_alu64 (jit: &mut JitMemory, op: u8, src: u8, dst: u8) {
+    emit_basic_rex(jit, 1, src, dst);
+    emit1(jit, op);
+    emit_modrm_reg2reg(jit, src, dst);
+}
+
+// REX.W prefix, ModRM byte, and 32-bit immediate
+#[inline]
+fn emit_alu64_imm32 (jit: &mut JitMemory, op: u8, src: u8, dst: u8, imm: i32) {
+    emit_alu64(jit, op, src, dst);
+    emit4(jit, imm as u32);
+}
+
+// REX.W prefix, ModRM byte, and 8-bit immediate
+#[inline]
+fn emit_alu64_imm8 (jit: &mut JitMemory, op: u8, src: u8, dst: u8, imm: i8) {
+    emit_alu64(jit, op, src, dst);
+    emit1(jit, imm as u8);
+}
+
+// Register to register mov
+#[inline]
+fn emit_mov (jit: &mut JitMemory, src: u8, dst: u8) {
+    emit_alu64(jit, 0x89, src, dst);
+}
+
+#[inline]
+fn emit_cmp_imm32 (jit: &mut JitMemory, dst: u8, imm: i32) {
+    emit_alu64_imm32(jit, 0x81, 7, dst, imm);
+}
+
+#[inline]
+fn emit_cmp (jit: &mut JitMemory, src: u8, dst: u8) {
+    emit_alu64(jit, 0x39, src, dst);
+}
+
+#[inline]
+fn emit_cmp32_imm32 (jit: &mut JitMemory, dst: u8, imm: i32) {
+    emit_alu32_imm32(jit, 0x81, 7, dst, imm);
+}
+
+#[inline]
+fn emit_cmp32 (jit: &mut JitMemory, src: u8, dst: u8) {
+    emit_alu32(jit, 0x39, src, dst);
+}
+
+#[inline]
+fn emit_jcc (jit: &mut JitMemory, code: u8, target_pc: isize) {
+    emit1(jit, 0x0f);
+    emit1(jit, code);
+    emit_jump_offset(jit, target_pc);
+}
+
+#[inline]
+fn emit_jmp (jit: &mut JitMemory, target_pc: isize) {
+    emit1(jit, 0xe9);
+    emit_jump_offset(jit, target_pc);
+}
+
+#[inline]
+fn set_anchor(jit: &mut JitMemory, target: isize) {
+    jit.special_targets.insert(target, jit.offset);
+}
+
+// Conditional jump to a location within the sequence being emitted, to be set with set_label().
+// Return the location of the offset.
+#[inline]
+fn emit_jcc_label (jit: &mut JitMemory, code: u8) -> usize {
+    emit1(jit, 0x0f);
+    emit1(jit, code);
+    emit4(jit, 0);
+    jit.offset - 4
+}
+
+// Unconditional jump to a location within the sequence being emitted, to be set with set_label().
+// Return the location of the offset.
+#[inline]
+fn emit_jmp_label (jit: &mut JitMemory) -> usize {
+    emit1(jit, 0xe9);
+    emit4(jit, 0);
+    jit.offset - 4
+}
+
+// Make the jumps whose offsets are at locs point to the current location.
+#[inline]
+fn set_label (jit: &mut JitMemory, locs: &[usize]) {
+    for &loc in locs {
+        let rel = (jit.offset - (loc + 4)) as u32;
+        jit.contents[loc..loc + 4].copy_from_slice(&rel.to_le_bytes());
+    }
+}
+
+// Load effective address src + offset into dst
+#[inline]
+fn emit_lea (jit: &mut JitMemory, src: u8, dst: u8, offset: i32) {
+    emit_basic_rex(jit, 1, dst, src);
+    emit1(jit, 0x8d);
+    emit_modrm_and_displacement(jit, dst, src, offset);
+}
+
+// Load [src + offset] into dst
+#[inline]
+fn emit_load (jit: &mut JitMemory, size: OperandSize, src: u8, dst: u8, offset: i32) {
+    let data = match size {
+        OperandSize::S64 => 1,
+        _ => 0
+    };
+    emit_basic_rex(jit, data, dst, src);
+
+    match size {
+        OperandSize::S8 => {
+            // movzx
+            emit1(jit, 0x0f);
+            emit1(jit, 0xb6);
+        },
+        OperandSize::S16 => {
+            // movzx
+            emit1(jit, 0x0f);
+            emit1(jit, 0xb7);
+        },
+        OperandSize::S32 | OperandSize::S64 => {
+            // mov
+            emit1(jit, 0x8b);
+        }
+    }
+
+    emit_modrm_and_displacement(jit, dst, src, offset);
+}
+
+// Load sign-extended immediate into register
+#[inline]
+fn emit_load_imm (jit: &mut JitMemory, dst: u8, imm: i64) {
+    if imm >= i32::MIN as i64 && imm <= i32::MAX as i64 {
+        emit_alu64_imm32(jit, 0xc7, 0, dst, imm as i32);
+    } else {
+        // movabs $imm,dst
+        emit_basic_rex(jit, 1, 0, dst);
+        emit1(jit, 0xb8 | (dst & 0b111));
+        emit8(jit, imm as u64);
+    }
+}
+
+// Store register src to [dst + offset]
+#[inline]
+fn emit_store (jit: &mut JitMemory, size: OperandSize, src: u8, dst: u8, offset: i32) {
+    if let OperandSize::S16 = size {
+        emit1(jit, 0x66); // 16-bit override
+    }
+    let (is_s8, is_u64, rexw) = match size {
+        OperandSize::S8  => (true, false, 0),
+        OperandSize::S64 => (false, true, 1),
+        _                => (false, false, 0),
+    };
+    if is_u64 || (src & 0b1000) != 0 || (dst & 0b1000) != 0 || is_s8 {
+        let is_masked = | val, mask | {
+            match val & mask {
+                0 => 0,
+                _ => 1
+            }
+        };
+        emit_rex(jit, rexw, is_masked(src, 8), 0, is_masked(dst, 8));
+    }
+    match size {
+        OperandSize::S8 => emit1(jit, 0x88),
+        _               => emit1(jit, 0x89),
+    };
+    emit_modrm_and_displacement(jit, src, dst, offset);
+}
+
+// Atomically add register src to [dst + offset]
+#[inline]
+fn emit_atomic_add (jit: &mut JitMemory, size: OperandSize, src: u8, dst: u8, offset: i32) {
+    emit1(jit, 0xf0); // lock prefix
+    let rexw = match size {
+        OperandSize::S64 => 1,
+        _                => 0,
+    };
+    emit_basic_rex(jit, rexw, src, dst);
+    emit1(jit, 0x01);
+    emit_modrm_and_displacement(jit, src, dst, offset);
+}
+
+// Store immediate to [dst + offset]
+#[inline]
+fn emit_store_imm32 (jit: &mut JitMemory, size: OperandSize, dst: u8, offset: i32, imm: i32) {
+    if let OperandSize::S16 = size {
+        emit1(jit, 0x66); // 16-bit override
+    }
+    match size {
+        OperandSize::S64 => emit_basic_rex(jit, 1, 0, dst),
+        _                => emit_basic_rex(jit, 0, 0, dst),
+    };
+    match size {
+        OperandSize::S8 => emit1(jit, 0xc6),
+        _               => emit1(jit, 0xc7),
+    };
+    emit_modrm_and_displacement(jit, 0, dst, offset);
+    match size {
+        OperandSize::S8  => emit1(jit, imm as u8),
+        OperandSize::S16 => emit2(jit, imm as u16),
+        _                => emit4(jit, imm as u32),
+    };
+}
+
+// Convert the value in dst from network to host byte order. `size` is in bits.
+#[inline]
+fn emit_bswap (jit: &mut JitMemory, dst: u8, size: i32) {
+    match size {
+        16 => {
+            // rol
+            emit1(jit, 0x66); // 16-bit override
+            emit_alu32_imm8(jit, 0xc1, 0, dst, 8);
+            // and
+            emit_alu32_imm32(jit, 0x81, 4, dst, 0xffff);
+        }
+        32 | 64 => {
+            // bswap
+            let bit = match size { 64 => 1, _ => 0 };
+            emit_basic_rex(jit, bit, 0, dst);
+            emit1(jit, 0x0f);
+            emit1(jit, 0xc8 | (dst & 0b111));
+        }
+        _ => unreachable!() // Should have been caught by verifier
+    }
+}
+
+// Record a fault of the given kind for the instruction whose index is in the lower 32 bits of RCX,
+// in the JitFault passed to the program. Leave a pointer to the JitFault in RAX. Trash RDX.
+fn emit_fault (jit: &mut JitMemory, kind: u64) {
+    emit_load(jit, OperandSize::S64, R12, RAX, CONTEXT_FAULT);
+    emit_store_imm32(jit, OperandSize::S64, RAX, 0, kind as i32);
+    emit_alu32(jit, 0x89, RCX, RDX);             // mov ecx into edx, zero-extended
+    emit_store(jit, OperandSize::S64, RDX, RAX, 8);
+}
+
+// Check that the access of `len` bytes at [base + offset] lies within the eBPF stack, the packet
+// data, the metadata buffer or the values of one of the maps, as the interpreter does. Otherwise,
+// abort the program with an out of bounds error for instruction insn_ptr. Trash RCX and R11.
+fn emit_mem_check (jit: &mut JitMemory, kind: isize, base: u8, offset: i32, len: i32,
+                   insn_ptr: usize, maps: &HashMap<u32, &Map>) {
+    // R11 holds the end of the access. A region [start, end) is valid if start + len <= R11 <= end.
+    emit_lea(jit, base, R11, offset.wrapping_add(len));
+    let mut ok = vec![];
+
+    // Stack
+    emit_mov(jit, R12, RCX);
+    emit_alu64_imm32(jit, 0x81, 5, RCX, STACK_TOTAL_SIZE - len);
+    emit_cmp(jit, RCX, R11);
+    let next = emit_jcc_label(jit, 0x82);
+    emit_cmp(jit, R12, R11);
+    ok.push(emit_jcc_label(jit, 0x86));
+    set_label(jit, &[next]);
+
+    // Packet data and metadata buffer
+    for &(ptr, ptr_len) in &[(CONTEXT_MEM, CONTEXT_MEM_LEN), (CONTEXT_MBUFF, CONTEXT_MBUFF_LEN)] {
+        emit_load(jit, OperandSize::S64, R12, RCX, ptr);
+        emit_alu64_imm32(jit, 0x81, 0, RCX, len);
+        emit_cmp(jit, RCX, R11);
+        let next = emit_jcc_label(jit, 0x82);
+        emit_load(jit, OperandSize::S64, R12, RCX, ptr);
+        emit_basic_rex(jit, 1, RCX, R12);
+        emit1(jit, 0x03); // add [r12 + ptr_len] to rcx
+        emit_modrm_and_displacement(jit, RCX, R12, ptr_len);
+        emit_cmp(jit, RCX, R11);
+        ok.push(emit_jcc_label(jit, 0x86));
+        set_label(jit, &[next]);
+    }
+
+    // Values of the maps, that never move
+    for map in maps.values() {
+        let (start, end) = map.value_region();
+        emit_load_imm(jit, RCX, (start + len as u64) as i64);
+        emit_cmp(jit, RCX, R11);
+        let next = emit_jcc_label(jit, 0x82);
+        emit_load_imm(jit, RCX, end as i64);
+        emit_cmp(jit, RCX, R11);
+        ok.push(emit_jcc_label(jit, 0x86));
+        set_label(jit, &[next]);
+    }
+
+    // Out of bounds: pass instruction and length in RCX, end of access in R11
+    emit_load_imm(jit, RCX, ((len as i64) << 32) | insn_ptr as i64);
+    emit_jmp(jit, kind);
+    set_label(jit, &ok);
+}
+
+// Check that the access of `len` bytes at [base + offset] is aligned on `len`, as atomic
+// operations require and as the interpreter checks. Otherwise, abort the program with an error for
+// instruction insn_ptr. Trash RCX and R11.
+fn emit_align_check (jit: &mut JitMemory, base: u8, offset: i32, len: i32, insn_ptr: usize) {
+    // R11 holds the end of the access, as for emit_mem_check(): it is aligned if the start is.
+    emit_lea(jit, base, R11, offset.wrapping_add(len));
+    emit_alu32_imm32(jit, 0xf7, 0, R11, len - 1); // test r11d, len - 1
+    let ok = emit_jcc_label(jit, 0x84);
+    emit_load_imm(jit, RCX, ((len as i64) << 32) | insn_ptr as i64);
+    emit_jmp(jit, TARGET_PC_UNALIGNED);
+    set_label(jit, &[ok]);
+}
+
+// Consume one instruction from the budget, or abort the program with an error for instruction
+// insn_ptr if the budget is exhausted. Trash RCX.
+fn emit_insn_budget_check (jit: &mut JitMemory, insn_ptr: usize) {
+    // sub qword [r12 + CONTEXT_INSN_BUDGET], 1
+    emit_basic_rex(jit, 1, 0, R12);
+    emit1(jit, 0x83);
+    emit_modrm_and_displacement(jit, 5, R12, CONTEXT_INSN_BUDGET);
+    emit1(jit, 1);
+    let ok = emit_jcc_label(jit, 0x83);
+    emit_load_imm(jit, RCX, insn_ptr as i64);
+    emit_jmp(jit, TARGET_PC_INSN_BUDGET);
+    set_label(jit, &[ok]);
+}
+
+// Load a value from packet data into eBPF register 0, in host byte order, for LD_ABS and LD_IND.
+// The offset in packet data is imm for LD_ABS, and src + imm (as 32-bit signed) for LD_IND. If the
+// load is out of bounds, the program exits and returns 0.
+fn emit_load_packet (jit: &mut JitMemory, size: OperandSize, src: Option<u8>, imm: i32) {
+    let len = match size {
+        OperandSize::S8  => 1,
+        OperandSize::S16 => 2,
+        OperandSize::S32 => 4,
+        OperandSize::S64 => 8,
+    };
+
+    // Offset into RCX
+    match src {
+        Some(src) => {
+            emit_alu32(jit, 0x89, src, RCX);             // mov src32 into ecx
+            emit_alu32_imm32(jit, 0x81, 0, RCX, imm);    // add imm to ecx
+            emit_alu64(jit, 0x63, RCX, RCX);             // movsxd ecx into rcx
+        },
+        None => emit_load_imm(jit, RCX, imm as i64),
+    }
+    // Negative offset: out of bounds
+    emit_alu64(jit, 0x85, RCX, RCX);
+    emit_jcc(jit, 0x88, TARGET_PC_EXIT_ZERO);
+    // offset > mem_len - len: out of bounds
+    emit_load(jit, OperandSize::S64, R12, R11, CONTEXT_MEM_LEN);
+    emit_alu64_imm32(jit, 0x81, 5, R11, len);
+    emit_cmp(jit, R11, RCX);
+    emit_jcc(jit, 0x8f, TARGET_PC_EXIT_ZERO);
+
+    // Load from mem + offset
+    emit_load(jit, OperandSize::S64, R12, R11, CONTEXT_MEM);
+    emit_alu64(jit, 0x01, R11, RCX);
+    let dst = map_register(0);
+    match size {
+        OperandSize::S8 => emit_load(jit, OperandSize::S8, RCX, dst, 0),
+        _               => {
+            let bits = len * 8;
+            emit_load(jit, size, RCX, dst, 0);
+            emit_bswap(jit, dst, bits);
+        }
+    }
+}
+
+#[inline]
+fn emit_call (jit: &mut JitMemory, target: i64) {
+    // TODO use direct call when possible
+    emit_load_imm(jit, RAX, target);
+    // callq *%rax
+    emit1(jit, 0xff);
+    emit1(jit, 0xd0);
+}
+
+// Call a map helper, after checking that eBPF register 1 holds the address of one of the maps and,
+// if mem_check is set, that the key and value read by the helper are in bounds. Otherwise, abort
+// the program with an error for instruction insn_ptr.
+fn emit_map_helper_call (jit: &mut JitMemory, helper: MapHelper, id: u32, insn_ptr: usize,
+                         maps: &HashMap<u32, &Map>, mem_check: bool) {
+    let mut found = vec![];
+    for map in maps.values() {
+        emit_load_imm(jit, R11, *map as *const Map as i64);
+        emit_cmp(jit, R11, map_register(1));
+        let next = emit_jcc_label(jit, 0x85);
+        if mem_check {
+            let key_size = std::cmp::min(map.key_size(), i32::MAX as usize) as i32;
+            emit_mem_check(jit, TARGET_PC_OOB_LOAD, map_register(2), 0, key_size, insn_ptr, maps);
+            if id == maps::BPF_MAP_UPDATE_ELEM_IDX {
+                let value_size = std::cmp::min(map.value_size(), i32::MAX as usize) as i32;
+                emit_mem_check(jit, TARGET_PC_OOB_LOAD, map_register(3), 0, value_size, insn_ptr,
+                               maps);
+            }
+        }
+        found.push(emit_jmp_label(jit));
+        set_label(jit, &[next]);
+    }
+
+    // Not a map: pass instruction in RCX, and the value of register 1 in R11
+    emit_load_imm(jit, RCX, insn_ptr as i64);
+    emit_mov(jit, map_register(1), R11);
+    emit_jmp(jit, TARGET_PC_INVALID_MAP);
+    set_label(jit, &found);
+
+    // We reserve RCX for shifts
+    emit_mov(jit, R9, RCX);
+    emit_call(jit, helper as usize as i64);
+}
+
+// Call a local function of the program, starting at target_pc. The function gets a new stack
+// frame, and r6-r9 and r10 are restored when it returns. If the maximum call depth is reached,
+// abort the program with an error for instruction insn_ptr.
+fn emit_local_call(jit: &mut JitMemory, target_pc: isize, insn_ptr: usize) {
+    emit_load_imm(jit, RCX, insn_ptr as i64);
+    emit_load(jit, OperandSize::S64, R12, R11, CONTEXT_DEPTH);
+    emit_cmp_imm32(jit, R11, ebpf::MAX_CALL_DEPTH as i32 - 1);
+    emit_jcc(jit, 0x83, TARGET_PC_CALL_DEPTH);
+    emit_alu64_imm32(jit, 0x81, 0, R11, 1);
+    emit_store(jit, OperandSize::S64, R11, R12, CONTEXT_DEPTH);
+
+    for reg in 6..11 {
+        emit_push(jit, map_register(reg));
+    }
+    // The frame of the callee is right below the one of the caller. Five pushes, plus the return
+    // address pushed by the call: the stack is still 16-byte aligned in the callee.
+    emit_alu64_imm32(jit, 0x81, 5, map_register(10), ebpf::STACK_SIZE as i32);
+
+    emit1(jit, 0xe8); // call rel32
+    emit_jump_offset(jit, target_pc);
+
+    for reg in (6..11).rev() {
+        emit_pop(jit, map_register(reg));
+    }
+
+    emit_load(jit, OperandSize::S64, R12, R11, CONTEXT_DEPTH);
+    emit_alu64_imm32(jit, 0x81, 5, R11, 1);
+    emit_store(jit, OperandSize::S64, R11, R12, CONTEXT_DEPTH);
+}
+
+// Replace the running program with the one at index r3 of the program array, if any. Otherwise, or
+// if the maximum number of tail calls has been reached, go on with the instruction at next_pc.
+fn emit_tail_call(jit: &mut JitMemory, num_entries: usize, max_tail_calls: usize, next_pc: isize) {
+    // Index is a 32-bit unsigned value
+    emit_cmp32_imm32(jit, map_register(3), num_entries as i32);
+    emit_jcc(jit, 0x83, next_pc);
+
+    // Load the address of the program from the table, skip the call if the slot is empty
+    emit_alu32(jit, 0x89, map_register(3), RCX);
+    emit_alu64_imm8(jit, 0xc1, 4, RCX, 3);
+    emit_basic_rex(jit, 1, 0, R11);
+    emit1(jit, 0xb8 | (R11 & 0b111)); // movabs $table,%r11
+    jit.addresses.push(Jump { offset_loc: jit.offset, target_pc: TARGET_PC_TAIL_CALL_TABLE });
+    emit8(jit, 0);
+    emit_alu64(jit, 0x01, R11, RCX);
+    emit_load(jit, OperandSize::S64, RCX, RCX, 0);
+    emit_cmp_imm32(jit, RCX, 0);
+    emit_jcc(jit, 0x84, next_pc);
+
+    emit_load(jit, OperandSize::S64, R12, R11, CONTEXT_TAIL_CALLS);
+    emit_cmp_imm32(jit, R11, max_tail_calls.min(i32::MAX as usize) as i32);
+    emit_jcc(jit, 0x83, next_pc);
+    emit_alu64_imm32(jit, 0x81, 0, R11, 1);
+    emit_store(jit, OperandSize::S64, R11, R12, CONTEXT_TAIL_CALLS);
+
+    // The new program starts over from the frame of the main program
+    emit_store_imm32(jit, OperandSize::S64, R12, CONTEXT_DEPTH, 0);
+    emit_mov(jit, R12, map_register(10));
+    emit_mov(jit, R12, RSP);
+    emit_alu64_imm32(jit, 0x81, 5, RSP, STACK_TOTAL_SIZE);
+
+    // jmp *%rcx
+    emit1(jit, 0xff);
+    emit1(jit, 0xe1);
+}
+
+fn muldivmod(jit: &mut JitMemory, pc: usize, opc: u8, src: u8, dst: u8, imm: i32) {
+    let mul = (opc & ebpf::BPF_ALU_OP_MASK) == (ebpf::MUL32_IMM & ebpf::BPF_ALU_OP_MASK);
+    let div = (opc & ebpf::BPF_ALU_OP_MASK) == (ebpf::DIV32_IMM & ebpf::BPF_ALU_OP_MASK);
+    let modrm = (opc & ebpf::BPF_ALU_OP_MASK) == (ebpf::MOD32_IMM & ebpf::BPF_ALU_OP_MASK);
+    let is64 = (opc & ebpf::BPF_CLS_MASK) == ebpf::BPF_ALU64;
+    // Division by a zero immediate is rejected by the verifier
+    let is_reg = (opc & ebpf::BPF_X) != 0;
+
+    if (div || modrm) && is_reg {
+        emit_load_imm(jit, RCX, pc as i64);
+
+        // test src,src
+        if is64 {
+            emit_alu64(jit, 0x85, src, src);
+        } else {
+            emit_alu32(jit, 0x85, src, src);
+        }
+
+        // jz div_by_zero
+        emit_jcc(jit, 0x84, TARGET_PC_DIV_BY_ZERO);
+    }
+
+    if dst != RAX {
+        emit_push(jit, RAX);
+    }
+    if dst != RDX {
+        emit_push(jit, RDX);
+    }
+    if is_reg {
+        emit_mov(jit, src, RCX);
+    } else {
+        emit_load_imm(jit, RCX, imm as i64);
+    }
+
+    emit_mov(jit, dst, RAX);
+
+    if div || modrm {
+        // xor %edx,%edx
+        emit_alu32(jit, 0x31, RDX, RDX);
+    }
+
+    if is64 {
+        emit_rex(jit, 1, 0, 0, 0);
+    }
+
+    // mul %ecx or div %ecx
+    emit_alu32(jit, 0xf7, if mul { 4 } else { 6 }, RCX);
+
+    if dst != RDX {
+        if modrm {
+            emit_mov(jit, RDX, dst);
+        }
+        emit_pop(jit, RDX);
+    }
+    if dst != RAX {
+        if div || mul {
+            emit_mov(jit, RAX, dst);
+        }
+        emit_pop(jit, RAX);
+    }
+}
+
+#[derive(Debug)]
+struct Jump {
+    offset_loc: usize,
+    target_pc:  isize,
+}
+
+struct JitMemory {
+    // Code is emitted into a growable buffer, then copied into executable memory once its size
+    // is known
+    contents:        Vec<u8>,
+    offset:          usize,
+    pc_locs:         Vec<usize>,
+    special_targets: HashMap<isize, usize>,
+    jumps:           Vec<Jump>,
+    // Locations of 64-bit absolute addresses of jump targets, rather than relative offsets
+    addresses:       Vec<Jump>,
+}
+
+impl JitMemory {
+    fn new() -> JitMemory {
+        JitMemory {
+            contents:        vec![],
+            offset:          0,
+            pc_locs:         vec![],
+            jumps:           vec![],
+            special_targets: HashMap::new(),
+            addresses:       vec![],
+        }
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn jit_compile(&mut self, prog: &[u8], use_mbuff: bool, update_data_ptr: bool,
+                   helpers: &HashMap<u32, ebpf::Helper>, maps: &HashMap<u32, &Map>,
+                   tail_call_progs: &[Option<&[u8]>], max_tail_calls: usize, mem_check: bool,
+                   insn_budget: Option<u64>) -> Result<(), JitError> {
+        // Programs reachable by tail calls are appended to the main program, and compiled along
+        // with it. Store the index of their first instruction.
+        let mut all_progs = prog.to_vec();
+        let tail_call_entries = tail_call_progs.iter().map(|p| p.map(|p| {
+            let entry = all_progs.len() / ebpf::INSN_SIZE;
+            all_progs.extend_from_slice(p);
+            entry
+        })).collect::<Vec<Option<usize>>>();
+        let prog = &all_progs[..];
+
+        emit_push(self, RBP);
+        emit_push(self, RBX);
+        emit_push(self, R13);
+        emit_push(self, R14);
+        emit_push(self, R15);
+        emit_push(self, R12);
+
+        // Seventh argument, on the stack above the return address: pointer to the JitFault
+        emit_load(self, OperandSize::S64, RSP, R11, 7 * 8);
+
+        // RDI: mbuff
+        // RSI: mbuff_len
+        // RDX: mem
+        // RCX: mem_len
+        // R8:  mem_offset
+        // R9:  mem_end_offset
+        match (use_mbuff, update_data_ptr) {
+            (false, _) => {
+                // We do not use any mbuff. Move mem pointer into register 1.
+                if map_register(1) != RDX {
+                    emit_mov(self, RDX, map_register(1));
+                }
+            },
+            (true, false) => {
+                // We use a mbuff already pointing to mem and mem_end: move it to register 1.
+                if map_register(1) != RDI {
+                    emit_mov(self, RDI, map_register(1));
+                }
+            },
+            (true, true) => {
+                // We have a fixed (simulated) mbuff: update mem and mem_end offset values in it.
+                // Store mem at mbuff + mem_offset. Trash R8.
+                emit_alu64(self, 0x01, RDI, R8);                // add mbuff to mem_offset in R8
+                emit_store(self, OperandSize::S64, RDX, R8, 0); // set mem at mbuff + mem_offset
+                // Store mem_end at mbuff + mem_end_offset. Trash R9.
+                emit_load(self, OperandSize::S64, RDX, R8, 0);  // load mem into R8
+                emit_alu64(self, 0x01, RCX, R8);                // add mem_len to mem (= mem_end)
+                emit_alu64(self, 0x01, RDI, R9);                // add mbuff to mem_end_offset
+                emit_store(self, OperandSize::S64, R8, R9, 0);  // store mem_end
+
+                // Move rdi into register 1
+                if map_register(1) != RDI {
+                    emit_mov(self, RDI, map_register(1));
+                }
+            }
+        }
+
+        // Set up the runtime context
+        emit_alu64_imm32(self, 0x81, 5, RSP, CONTEXT_SIZE);
+        emit_mov(self, RSP, R12);
+        emit_store(self, OperandSize::S64, RDX, R12, CONTEXT_MEM);
+        emit_store(self, OperandSize::S64, RCX, R12, CONTEXT_MEM_LEN);
+        emit_store_imm32(self, OperandSize::S64, R12, CONTEXT_DEPTH, 0);
+        emit_store_imm32(self, OperandSize::S64, R12, CONTEXT_TAIL_CALLS, 0);
+        emit_store(self, OperandSize::S64, RDI, R12, CONTEXT_MBUFF);
+        emit_store(self, OperandSize::S64, RSI, R12, CONTEXT_MBUFF_LEN);
+        emit_store(self, OperandSize::S64, R11, R12, CONTEXT_FAULT);
+        if let Some(budget) = insn_budget {
+            emit_load_imm(self, R11, budget as i64);
+            emit_store(self, OperandSize::S64, R11, R12, CONTEXT_INSN_BUDGET);
+        }
+
+        // Copy stack pointer to R10
+        emit_mov(self, RSP, map_register(10));
+
+        // Allocate stack space
+        emit_alu64_imm32(self, 0x81, 5, RSP, STACK_TOTAL_SIZE);
+
+        self.pc_locs = vec![0; prog.len() / ebpf::INSN_SIZE + 1];
+
+        // If the program has local functions, `exit` may return either from the program or from
+        // one of these functions.
+        let has_local_calls = (0..prog.len() / ebpf::INSN_SIZE).any(|i| {
+            let insn = ebpf::get_insn(prog, i);
+            insn.opc == ebpf::CALL && insn.src == ebpf::BPF_PSEUDO_CALL
+        });
+
+        let mut insn_ptr:usize = 0;
+        // Index of the first instruction of the program (main one, or reachable by tail calls)
+        // being compiled, so that errors report instructions relatively to it.
+        let mut prog_start = 0;
+        while insn_ptr * ebpf::INSN_SIZE < prog.len() {
+            let insn = ebpf::get_insn(prog, insn_ptr);
+
+            self.pc_locs[insn_ptr] = self.offset;
+            if tail_call_entries.contains(&Some(insn_ptr)) {
+                prog_start = insn_ptr;
+            }
+
+            let dst = map_register(insn.dst);
+            let src = map_register(insn.src);
+            let target_pc = insn_ptr as isize + insn.off as isize + 1;
+
+            if insn_budget.is_some() {
+                emit_insn_budget_check(self, insn_ptr - prog_start);
+            }
+
+            if mem_check {
+                let (target, base, len) = match insn.opc {
+                    ebpf::LD_B_REG   => (TARGET_PC_OOB_LOAD,  src, 1),
+                    ebpf::LD_H_REG   => (TARGET_PC_OOB_LOAD,  src, 2),
+                    ebpf::LD_W_REG   => (TARGET_PC_OOB_LOAD,  src, 4),
+                    ebpf::LD_DW_REG  => (TARGET_PC_OOB_LOAD,  src, 8),
+                    ebpf::ST_B_IMM   |
+                    ebpf::ST_B_REG   => (TARGET_PC_OOB_STORE, dst, 1),
+                    ebpf::ST_H_IMM   |
+                    ebpf::ST_H_REG   => (TARGET_PC_OOB_STORE, dst, 2),
+                    ebpf::ST_W_IMM   |
+                    ebpf::ST_W_REG   |
+                    ebpf::ST_W_XADD  => (TARGET_PC_OOB_STORE, dst, 4),
+                    ebpf::ST_DW_IMM  |
+                    ebpf::ST_DW_REG  |
+                    ebpf::ST_DW_XADD => (TARGET_PC_OOB_STORE, dst, 8),
+                    _                => (0, 0, 0),
+                };
+                if len > 0 {
+                    emit_mem_check(self, target, base, insn.off as i32, len,
+                                   insn_ptr - prog_start, maps);
+                }
+            }
+
+            match insn.opc {
+
+                // BPF_LD class
+                ebpf::LD_ABS_B   => emit_load_packet(self, OperandSize::S8,  None, insn.imm),
+                ebpf::LD_ABS_H   => emit_load_packet(self, OperandSize::S16, None, insn.imm),
+                ebpf::LD_ABS_W   => emit_load_packet(self, OperandSize::S32, None, insn.imm),
+                ebpf::LD_ABS_DW  => emit_load_packet(self, OperandSize::S64, None, insn.imm),
+                ebpf::LD_IND_B   => emit_load_packet(self, OperandSize::S8,  Some(src), insn.imm),
+                ebpf::LD_IND_H   => emit_load_packet(self, OperandSize::S16, Some(src), insn.imm),
+                ebpf::LD_IND_W   => emit_load_packet(self, OperandSize::S32, Some(src), insn.imm),
+                ebpf::LD_IND_DW  => emit_load_packet(self, OperandSize::S64, Some(src), insn.imm),
+
+                // BPF_LDX class
+                ebpf::LD_DW_IMM  => {
+                    insn_ptr += 1;
+                    let imm = if insn.src == ebpf::BPF_PSEUDO_MAP_FD {
+                        match maps.get(&(insn.imm as u32)) {
+                            Some(map) => *map as *const Map as u64,
+                            None      => return Err(JitError::UnknownMap(insn.imm as u32,
+                                                                         insn_ptr - 1 - prog_start)),
+                        }
+                    } else {
+                        let second_part = ebpf::get_insn(prog, insn_ptr).imm as u64;
+                        (insn.imm as u32) as u64 | second_part.wrapping_shl(32)
+                    };
+                    emit_load_imm(self, dst, imm as i64);
+                },
+                ebpf::LD_B_REG   =>
+                    emit_load(self, OperandSize::S8,  src, dst, insn.off as i32),
+                ebpf::LD_H_REG   =>
+                    emit_load(self, OperandSize::S16, src, dst, insn.off as i32),
+                ebpf::LD_W_REG   =>
+                    emit_load(self, OperandSize::S32, src, dst, insn.off as i32),
+                ebpf::LD_DW_REG  =>
+                    emit_load(self, OperandSize::S64, src, dst, insn.off as i32),
+
+                // BPF_ST class
+                ebpf::ST_B_IMM   =>
+                    emit_store_imm32(self, OperandSize::S8,  dst, insn.off as i32, insn.imm),
+                ebpf::ST_H_IMM   =>
+                    emit_store_imm32(self, OperandSize::S16, dst, insn.off as i32, insn.imm),
+                ebpf::ST_W_IMM   =>
+                    emit_store_imm32(self, OperandSize::S32, dst, insn.off as i32, insn.imm),
+                ebpf::ST_DW_IMM  =>
+                    emit_store_imm32(self, OperandSize::S64, dst, insn.off as i32, insn.imm),
+
+                // BPF_STX class
+                ebpf::ST_B_REG   =>
+                    emit_store(self, OperandSize::S8,  src, dst, insn.off as i32),
+                ebpf::ST_H_REG   =>
+                    emit_store(self, OperandSize::S16, src, dst, insn.off as i32),
+                ebpf::ST_W_REG   =>
+                    emit_store(self, OperandSize::S32, src, dst, insn.off as i32),
+                ebpf::ST_DW_REG  =>
+                    emit_store(self, OperandSize::S64, src, dst, insn.off as i32),
+                ebpf::ST_W_XADD  => {
+                    emit_align_check(self, dst, insn.off as i32, 4, insn_ptr - prog_start);
+                    emit_atomic_add(self, OperandSize::S32, src, dst, insn.off as i32);
+                },
+                ebpf::ST_DW_XADD => {
+                    emit_align_check(self, dst, insn.off as i32, 8, insn_ptr - prog_start);
+                    emit_atomic_add(self, OperandSize::S64, src, dst, insn.off as i32);
+                },
+
+                // BPF_ALU class
+                ebpf::ADD32_IMM  => emit_alu32_imm32(self, 0x81, 0, dst, insn.imm),
+                ebpf::ADD32_REG  => emit_alu32(self, 0x01, src, dst),
+                ebpf::SUB32_IMM  => emit_alu32_imm32(self, 0x81, 5, dst, insn.imm),
+                ebpf::SUB32_REG  => emit_alu32(self, 0x29, src, dst),
+                ebpf::MUL32_IMM | ebpf::MUL32_REG |
+                    ebpf::DIV32_IMM | ebpf::DIV32_REG |
+                    ebpf::MOD32_IMM | ebpf::MOD32_REG =>
+                    muldivmod(self, insn_ptr - prog_start, insn.opc, src, dst, insn.imm),
+                ebpf::OR32_IMM   => emit_alu32_imm32(self, 0x81, 1, dst, insn.imm),
+                ebpf::OR32_REG   => emit_alu32(self, 0x09, src, dst),
+                ebpf::AND32_IMM  => emit_alu32_imm32(self, 0x81, 4, dst, insn.imm),
+                ebpf::AND32_REG  => emit_alu32(self, 0x21, src, dst),
+                ebpf::LSH32_IMM  => emit_alu32_imm8(self, 0xc1, 4, dst, insn.imm as i8),
+                ebpf::LSH32_REG  => {
+                    emit_mov(self, src, RCX);
+                    emit_alu32(self, 0xd3, 4, dst);
+                },
+                ebpf::RSH32_IMM  => emit_alu32_imm8(self, 0xc1, 5, dst, insn.imm as i8),
+                ebpf::RSH32_REG  => {
+                    emit_mov(self, src, RCX);
+                    emit_alu32(self, 0xd3, 5, dst);
+                },
+                ebpf::NEG32      => emit_alu32(self, 0xf7, 3, dst),
+                ebpf::XOR32_IMM  => emit_alu32_imm32(self, 0x81, 6, dst, insn.imm),
+                ebpf::XOR32_REG  => emit_alu32(self, 0x31, src, dst),
+                ebpf::MOV32_IMM  => emit_alu32_imm32(self, 0xc7, 0, dst, insn.imm),
+                ebpf::MOV32_REG  => emit_alu32(self, 0x89, src, dst),
+                ebpf::ARSH32_IMM => emit_alu32_imm8(self, 0xc1, 7, dst, insn.imm as i8),
+                ebpf::ARSH32_REG => {
+                    emit_mov(self, src, RCX);
+                    emit_alu32(self, 0xd3, 7, dst);
+                },
+                ebpf::LE         => {}, // No-op
+                ebpf::BE         => emit_bswap(self, dst, insn.imm),
+
+                // BPF_ALU64 class
+                ebpf::ADD64_IMM  => emit_alu64_imm32(self, 0x81, 0, dst, insn.imm),
+                ebpf::ADD64_REG  => emit_alu64(self, 0x01, src, dst),
+                ebpf::SUB64_IMM  => emit_alu64_imm32(self, 0x81, 5, dst, insn.imm),
+                ebpf::SUB64_REG  => emit_alu64(self, 0x29, src, dst),
+                ebpf::MUL64_IMM | ebpf::MUL64_REG |
+                    ebpf::DIV64_IMM | ebpf::DIV64_REG |
+                    ebpf::MOD64_IMM | ebpf::MOD64_REG  =>
+                    muldivmod(self, insn_ptr - prog_start, insn.opc, src, dst, insn.imm),
+                ebpf::OR64_IMM   => emit_alu64_imm32(self, 0x81, 1, dst, insn.imm),
+                ebpf::OR64_REG   => emit_alu64(self, 0x09, src, dst),
+                ebpf::AND64_IMM  => emit_alu64_imm32(self, 0x81, 4, dst, insn.imm),
+                ebpf::AND64_REG  => emit_alu64(self, 0x21, src, dst),
+                ebpf::LSH64_IMM  => emit_alu64_imm8(self, 0xc1, 4, dst, insn.imm as i8),
+                ebpf::LSH64_REG  => {
+                    emit_mov(self, src, RCX);
+                    emit_alu64(self, 0xd3, 4, dst);
+                },
+                ebpf::RSH64_IMM  =>  emit_alu64_imm8(self, 0xc1, 5, dst, insn.imm as i8),
+                ebpf::RSH64_REG  => {
+                    emit_mov(self, src, RCX);
+                    emit_alu64(self, 0xd3, 5, dst);
+                },
+                ebpf::NEG64      => emit_alu64(self, 0xf7, 3, dst),
+                ebpf::XOR64_IMM  => emit_alu64_imm32(self, 0x81, 6, dst, insn.imm),
+                ebpf::XOR64_REG  => emit_alu64(self, 0x31, src, dst),
+                ebpf::MOV64_IMM  => emit_load_imm(self, dst, insn.imm as i64),
+                ebpf::MOV64_REG  => emit_mov(self, src, dst),
+                ebpf::ARSH64_IMM => emit_alu64_imm8(self, 0xc1, 7, dst, insn.imm as i8),
+                ebpf::ARSH64_REG => {
+                    emit_mov(self, src, RCX);
+                    emit_alu64(self, 0xd3, 7, dst);
+                },
+
+                // BPF_JMP class
+                ebpf::JA         => emit_jmp(self, target_pc),
+                ebpf::JEQ_IMM    => {
+                    emit_cmp_imm32(self, dst, insn.imm);
+                    emit_jcc(self, 0x84, target_pc);
+                },
+                ebpf::JEQ_REG    => {
+                    emit_cmp(self, src, dst);
+                    emit_jcc(self, 0x84, target_pc);
+                },
+                ebpf::JGT_IMM    => {
+                    emit_cmp_imm32(self, dst, insn.imm);
+                    emit_jcc(self, 0x87, target_pc);
+                },
+                ebpf::JGT_REG    => {
+                    emit_cmp(self, src, dst);
+                    emit_jcc(self, 0x87, target_pc);
+                },
+                ebpf::JGE_IMM    => {
+                    emit_cmp_imm32(self, dst, insn.imm);
+                    emit_jcc(self, 0x83, target_pc);
+                },
+                ebpf::JGE_REG    => {
+                    emit_cmp(self, src, dst);
+                    emit_jcc(self, 0x83, target_pc);
+                },
+                ebpf::JSET_IMM   => {
+                    emit_alu64_imm32(self, 0xf7, 0, dst, insn.imm);
+                    emit_jcc(self, 0x85, target_pc);
+                },
+                ebpf::JSET_REG   => {
+                    emit_alu64(self, 0x85, src, dst);
+                    emit_jcc(self, 0x85, target_pc);
+                },
+                ebpf::JNE_IMM    => {
+                    emit_cmp_imm32(self, dst, insn.imm);
+                    emit_jcc(self, 0x85, target_pc);
+                },
+                ebpf::JNE_REG    => {
+                    emit_cmp(self, src, dst);
+                    emit_jcc(self, 0x85, target_pc);
+                },
+                ebpf::JSGT_IMM   => {
+                    emit_cmp_imm32(self, dst, insn.imm);
+                    emit_jcc(self, 0x8f, target_pc);
+                },
+                ebpf::JSGT_REG   => {
+                    emit_cmp(self, src, dst);
+                    emit_jcc(self, 0x8f, target_pc);
+                },
+                ebpf::JSGE_IMM   => {
+                    emit_cmp_imm32(self, dst, insn.imm);
+                    emit_jcc(self, 0x8d, target_pc);
+                },
+                ebpf::JSGE_REG   => {
+                    emit_cmp(self, src, dst);
+                    emit_jcc(self, 0x8d, target_pc);
+                },
+                ebpf::JLT_IMM    => {
+                    emit_cmp_imm32(self, dst, insn.imm);
+                    emit_jcc(self, 0x82, target_pc);
+                },
+                ebpf::JLT_REG    => {
+                    emit_cmp(self, src, dst);
+                    emit_jcc(self, 0x82, target_pc);
+                },
+                ebpf::JLE_IMM    => {
+                    emit_cmp_imm32(self, dst, insn.imm);
+                    emit_jcc(self, 0x86, target_pc);
+                },
+                ebpf::JLE_REG    => {
+                    emit_cmp(self, src, dst);
+                    emit_jcc(self, 0x86, target_pc);
+                },
+                ebpf::JSLT_IMM   => {
+                    emit_cmp_imm32(self, dst, insn.imm);
+                    emit_jcc(self, 0x8c, target_pc);
+                },
+                ebpf::JSLT_REG   => {
+                    emit_cmp(self, src, dst);
+                    emit_jcc(self, 0x8c, target_pc);
+                },
+                ebpf::JSLE_IMM   => {
+                    emit_cmp_imm32(self, dst, insn.imm);
+                    emit_jcc(self, 0x8e, target_pc);
+                },
+                ebpf::JSLE_REG   => {
+                    emit_cmp(self, src, dst);
+                    emit_jcc(self, 0x8e, target_pc);
+                },
+
+                // BPF_JMP32 class
+                ebpf::JEQ_IMM32  => {
+                    emit_cmp32_imm32(self, dst, insn.imm);
+                    emit_jcc(self, 0x84, target_pc);
+                },
+                ebpf::JEQ_REG32  => {
+                    emit_cmp32(self, src, dst);
+                    emit_jcc(self, 0x84, target_pc);
+                },
+                ebpf::JGT_IMM32  => {
+                    emit_cmp32_imm32(self, dst, insn.imm);
+                    emit_jcc(self, 0x87, target_pc);
+                },
+                ebpf::JGT_REG32  => {
+                    emit_cmp32(self, src, dst);
+                    emit_jcc(self, 0x87, target_pc);
+                },
+                ebpf::JGE_IMM32  => {
+                    emit_cmp32_imm32(self, dst, insn.imm);
+                    emit_jcc(self, 0x83, target_pc);
+                },
+                ebpf::JGE_REG32  => {
+                    emit_cmp32(self, src, dst);
+                    emit_jcc(self, 0x83, target_pc);
+                },
+                ebpf::JSET_IMM32 => {
+                    emit_alu32_imm32(self, 0xf7, 0, dst, insn.imm);
+                    emit_jcc(self, 0x85, target_pc);
+                },
+                ebpf::JSET_REG32 => {
+                    emit_alu32(self, 0x85, src, dst);
+                    emit_jcc(self, 0x85, target_pc);
+                },
+                ebpf::JNE_IMM32  => {
+                    emit_cmp32_imm32(self, dst, insn.imm);
+                    emit_jcc(self, 0x85, target_pc);
+                },
+                ebpf::JNE_REG32  => {
+                    emit_cmp32(self, src, dst);
+                    emit_jcc(self, 0x85, target_pc);
+                },
+                ebpf::JSGT_IMM32 => {
+                    emit_cmp32_imm32(self, dst, insn.imm);
+                    emit_jcc(self, 0x8f, target_pc);
+                },
+                ebpf::JSGT_REG32 => {
+                    emit_cmp32(self, src, dst);
+                    emit_jcc(self, 0x8f, target_pc);
+                },
+                ebpf::JSGE_IMM32 => {
+                    emit_cmp32_imm32(self, dst, insn.imm);
+                    emit_jcc(self, 0x8d, target_pc);
+                },
+                ebpf::JSGE_REG32 => {
+                    emit_cmp32(self, src, dst);
+                    emit_jcc(self, 0x8d, target_pc);
+                },
+                ebpf::JLT_IMM32  => {
+                    emit_cmp32_imm32(self, dst, insn.imm);
+                    emit_jcc(self, 0x82, target_pc);
+                },
+                ebpf::JLT_REG32  => {
+                    emit_cmp32(self, src, dst);
+                    emit_jcc(self, 0x82, target_pc);
+                },
+                ebpf::JLE_IMM32  => {
+                    emit_cmp32_imm32(self, dst, insn.imm);
+                    emit_jcc(self, 0x86, target_pc);
+                },
+                ebpf::JLE_REG32  => {
+                    emit_cmp32(self, src, dst);
+                    emit_jcc(self, 0x86, target_pc);
+                },
+                ebpf::JSLT_IMM32 => {
+                    emit_cmp32_imm32(self, dst, insn.imm);
+                    emit_jcc(self, 0x8c, target_pc);
+                },
+                ebpf::JSLT_REG32 => {
+                    emit_cmp32(self, src, dst);
+                    emit_jcc(self, 0x8c, target_pc);
+                },
+                ebpf::JSLE_IMM32 => {
+                    emit_cmp32_imm32(self, dst, insn.imm);
+                    emit_jcc(self, 0x8e, target_pc);
+                },
+                ebpf::JSLE_REG32 => {
+                    emit_cmp32(self, src, dst);
+                    emit_jcc(self, 0x8e, target_pc);
+                },
+
+                ebpf::CALL if insn.src == ebpf::BPF_PSEUDO_CALL =>
+                    emit_local_call(self, insn_ptr as isize + insn.imm as isize + 1,
+                                    insn_ptr - prog_start),
+                ebpf::CALL       => {
+                    // For JIT, helpers in use MUST be registered at compile time. They can be
+                    // updated later, but not created after compiling (we need the address of the
+                    // helper function in the JIT-compiled program).
+                    if let (Some(helper), false) = (maps::helper(insn.imm as u32),
+                                                    maps.is_empty()) {
+                        emit_map_helper_call(self, helper, insn.imm as u32,
+                                             insn_ptr - prog_start, maps, mem_check);
+                    } else if let Some(helper) = helpers.get(&(insn.imm as u32)) {
+                        // We reserve RCX for shifts
+                        emit_mov(self, R9, RCX);
+                        emit_call(self, *helper as usize as i64);
+                    } else {
+                        return Err(JitError::UnknownHelper(insn.imm as u32,
+                                                           insn_ptr - prog_start));
+                    };
+                },
+                ebpf::TAIL_CALL  => if !tail_call_entries.is_empty() {
+                    emit_tail_call(self, tail_call_entries.len(), max_tail_calls,
+                                   insn_ptr as isize + 1);
+                },
+                ebpf::EXIT       => {
+                    if has_local_calls {
+                        // Return to the caller if we are in a local function.
+                        emit_load(self, OperandSize::S64, R12, R11, CONTEXT_DEPTH);
+                        emit_cmp_imm32(self, R11, 0);
+                        emit_jcc(self, 0x84, TARGET_PC_EXIT);
+                        emit1(self, 0xc3); // ret
+                    } else if insn_ptr != prog.len() / ebpf::INSN_SIZE - 1 {
+                        emit_jmp(self, TARGET_PC_EXIT);
+                    };
+                },
+
+                _                => {
+                    return Err(JitError::UnknownOpcode(insn.opc, insn_ptr - prog_start));
+                },
+            }
+
+            insn_ptr += 1;
+        }
+
+        // Epilogue
+        set_anchor(self, TARGET_PC_EXIT);
+
+        // Move register 0 into rax
+        if map_register(0) != RAX {
+            emit_mov(self, map_register(0), RAX);
+        }
+
+        // Report the number of instructions executed: the initial budget minus what is left
+        if let Some(budget) = insn_budget {
+            emit_load_imm(self, RCX, budget as i64);
+            emit_basic_rex(self, 1, RCX, R12);
+            emit1(self, 0x2b); // sub [r12 + CONTEXT_INSN_BUDGET] from rcx
+            emit_modrm_and_displacement(self, RCX, R12, CONTEXT_INSN_BUDGET);
+            emit_load(self, OperandSize::S64, R12, R11, CONTEXT_FAULT);
+            emit_store(self, OperandSize::S64, RCX, R11, 32);
+        }
+
+        // Deallocate stack space and runtime context. We may be exiting from a local function
+        // (on error), so restore the stack pointer from R12 rather than from its current value.
+        emit_mov(self, R12, RSP);
+        emit_alu64_imm32(self, 0x81, 0, RSP, CONTEXT_SIZE);
+
+        emit_pop(self, R12);
+        emit_pop(self, R15);
+        emit_pop(self, R14);
+        emit_pop(self, R13);
+        emit_pop(self, RBX);
+        emit_pop(self, RBP);
+
+        emit1(self, 0xc3); // ret
+
+        // Division by zero handler: muldivmod stored pc in RCX
+        set_anchor(self, TARGET_PC_DIV_BY_ZERO);
+        emit_fault(self, FAULT_DIV_BY_ZERO);
+        emit_jmp(self, TARGET_PC_EXIT);
+
+        // Out of bounds packet load handler: exit and return 0
+        set_anchor(self, TARGET_PC_EXIT_ZERO);
+        emit_load_imm(self, map_register(0), 0);
+        emit_jmp(self, TARGET_PC_EXIT);
+
+        // Too many nested local function calls: emit_local_call stored pc in RCX
+        set_anchor(self, TARGET_PC_CALL_DEPTH);
+        emit_fault(self, FAULT_CALL_DEPTH);
+        emit_jmp(self, TARGET_PC_EXIT);
+
+        // Instruction budget exhausted: emit_insn_budget_check stored pc in RCX
+        set_anchor(self, TARGET_PC_INSN_BUDGET);
+        emit_fault(self, FAULT_INSN_BUDGET);
+        emit_jmp(self, TARGET_PC_EXIT);
+
+        // Invalid map handle: emit_map_helper_call stored pc in RCX, and the handle in R11
+        set_anchor(self, TARGET_PC_INVALID_MAP);
+        emit_fault(self, FAULT_INVALID_MAP);
+        emit_store(self, OperandSize::S64, R11, RAX, 16);
+        emit_jmp(self, TARGET_PC_EXIT);
+
+        // Out of bounds memory accesses: emit_mem_check stored pc and length in RCX, and the end
+        // of the access in R11
+        set_anchor(self, TARGET_PC_OOB_LOAD);
+        emit_fault(self, FAULT_OOB_LOAD);
+        emit_jmp(self, TARGET_PC_MEM_FAULT);
+        set_anchor(self, TARGET_PC_OOB_STORE);
+        emit_fault(self, FAULT_OOB_STORE);
+        emit_jmp(self, TARGET_PC_MEM_FAULT);
+        // Unaligned atomic operations: emit_align_check stored the same values
+        set_anchor(self, TARGET_PC_UNALIGNED);
+        emit_fault(self, FAULT_UNALIGNED);
+        set_anchor(self, TARGET_PC_MEM_FAULT);
+        emit_mov(self, RCX, RDX);
+        emit_alu64_imm8(self, 0xc1, 5, RDX, 32); // shr rdx, 32: length of the access
+        emit_store(self, OperandSize::S64, RDX, RAX, 24);
+        emit_alu64(self, 0x29, RDX, R11);        // sub rdx from r11: start of the access
+        emit_store(self, OperandSize::S64, R11, RAX, 16);
+        emit_jmp(self, TARGET_PC_EXIT);
+
+        // Table of the addresses of the programs reachable by tail calls, null for empty slots
+        if !tail_call_entries.is_empty() {
+            while self.offset % 8 != 0 {
+                emit1(self, 0xcc); // int3, never executed
+            }
+            set_anchor(self, TARGET_PC_TAIL_CALL_TABLE);
+            for entry in &tail_call_entries {
+                if let Some(entry) = *entry {
+                    self.addresses.push(Jump { offset_loc: self.offset, target_pc: entry as isize });
+                }
+                emit8(self, 0);
+            }
+        }
+
+        Ok(())
+    }
+
+    // Patch jumps and absolute addresses, for code that will be copied at address `base`.
+    fn resolve_jumps(&mut self, base: u64)
+    {
+        for jump in &self.addresses {
+            let target_loc = match self.special_targets.get(&jump.target_pc) {
+                Some(target) => *target,
+                None         => self.pc_locs[jump.target_pc as usize]
+            };
+            let address = base + target_loc as u64;
+            self.contents[jump.offset_loc..jump.offset_loc + 8].copy_from_slice(&address.to_le_bytes());
+        }
+
+        for jump in &self.jumps {
+            let target_loc = match self.special_targets.get(&jump.target_pc) {
+                Some(target) => *target,
+                None         => self.pc_locs[jump.target_pc as usize]
+            };
+
+            // Assumes jump offset is at end of instruction
+            let offset_loc = jump.offset_loc as i32 + std::mem::size_of::<i32>() as i32;
+            let rel = target_loc as i32 - offset_loc;
+            self.contents[jump.offset_loc..jump.offset_loc + 4].copy_from_slice(&rel.to_le_bytes());
+        }
+    }
+} // struct JitMemory
+
+impl Index<usize> for JitMemory {
+    type Output = u8;
+
+    fn index(&self, _index: usize) -> &u8 {
+        &self.contents[_index]
+    }
+}
+
+impl IndexMut<usize> for JitMemory {
+    fn index_mut(&mut self, _index: usize) -> &mut u8 {
+        &mut self.contents[_index]
+    }
+}
+
+impl std::fmt::Debug for JitMemory {
+    fn fmt(&self, fmt: &mut Formatter) -> Result<(), Error> {
+        fmt.write_str("JIT contents: [")?;
+        for i in &self.contents {
+            fmt.write_fmt(format_args!(" {:#04x},", i))?;
+        };
+        fmt.write_str(" ] | ")?;
+        fmt.debug_struct("JIT state")
+            .field("offset", &self.offset)
+            .field("pc_locs", &self.pc_locs)
+            .field("special_targets", &self.special_targets)
+            .field("jumps", &self.jumps)
+            .finish()
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn compile(prog: &[u8],
+               helpers: &HashMap<u32, ebpf::Helper>,
+               maps: &HashMap<u32, &Map>,
+               use_mbuff: bool, update_data_ptr: bool,
+               tail_call_progs: &[Option<&[u8]>], max_tail_calls: usize,
+               mem_check: bool, insn_budget: Option<u64>)
+    -> Result<JitProgram, JitError> {
+
+    let mut jit = JitMemory::new();
+    jit.jit_compile(prog, use_mbuff, update_data_ptr, helpers, maps, tail_call_progs,
+                    max_tail_calls, mem_check, insn_budget)?;
+    if jit.offset > MAX_CODE_SIZE {
+        return Err(JitError::CodeTooLarge(jit.offset));
+    }
+
+    let mut program = JitProgram::new((jit.offset + PAGE_SIZE - 1) / PAGE_SIZE * PAGE_SIZE)?;
+    jit.resolve_jumps(program.code as u64);
+    // Prepopulate the rest of the memory with 'INT3' instructions
+    program.load(&jit.contents, 0xcc)?;
+    program.counts_insns = insn_budget.is_some();
+    Ok(program)
+}
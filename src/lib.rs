@@ -15,16 +15,31 @@
 
 #![warn(missing_docs)]
 
-use std::u32;
 use std::collections::HashMap;
+use std::sync::atomic::{AtomicU32, AtomicU64, Ordering};
 
 extern crate libc;
 
+use debugger::Session;
+use error::{EbpfError, ExecError, ExecErrorKind, JitError};
+use maps::Map;
+use trace::TraceEntry;
+use verifier::Verifier;
+
+pub mod analysis;
+pub mod assembler;
+pub mod cfg;
+pub mod debugger;
 pub mod disassembler;
 pub mod ebpf;
+pub mod error;
+pub mod gdbstub;
 pub mod helpers;
 mod jit;
-mod verifier;
+pub mod loader;
+pub mod maps;
+pub mod trace;
+pub mod verifier;
 
 // A metadata buffer with two offset indications. It can be used in one kind of eBPF VM to simulate
 // the use of a metadata buffer each time the program is executed, without the user having to
@@ -36,6 +51,169 @@ struct MetaBuff {
     buffer:          Vec<u8>,
 }
 
+impl MetaBuff {
+    // Store the pointers to the start and end of packet data `mem` into the buffer.
+    fn set_data(&mut self, mem: &[u8]) -> Result<(), ExecError> {
+        let l = self.buffer.len();
+        // Can this ever happen? Probably not, should be ensured at mbuff creation.
+        for &offset in &[self.data_offset, self.data_end_offset] {
+            if offset + 8 > l {
+                return Err(ExecError::mem(ExecErrorKind::BufferTooSmall, 0, offset as u64, l));
+            }
+        }
+        unsafe {
+            let data     = self.buffer.as_mut_ptr().add(self.data_offset)     as *mut u64;
+            let data_end = self.buffer.as_mut_ptr().add(self.data_end_offset) as *mut u64;
+            data.write_unaligned(mem.as_ptr() as u64);
+            data_end.write_unaligned(mem.as_ptr() as u64 + mem.len() as u64);
+        }
+        Ok(())
+    }
+}
+
+// State of a program being run by the interpreter, updated as each instruction is executed.
+struct ExecState<'a> {
+    // Program currently running, may be replaced by tail calls.
+    prog:       &'a [u8],
+    prog_index: Option<usize>,
+    tail_calls: usize,
+    insn_ptr:   usize,
+    insn_count: u64,
+    reg:        [u64;11],
+    // Allocated as u64 words so that it is aligned for 8-byte atomic operations. Each call frame
+    // gets its own `STACK_SIZE` bytes, the main program using the top ones.
+    stack:      Vec<u64>,
+    // Saved state of the callers for local function calls: return address, r6-r9 and r10.
+    frames:     Vec<(usize, [u64;5])>,
+}
+
+impl<'a> ExecState<'a> {
+    fn new(prog: &'a [u8], mem: &[u8], mbuff: &[u8]) -> ExecState<'a> {
+        let stack = vec![0u64;ebpf::STACK_SIZE * ebpf::MAX_CALL_DEPTH / 8];
+
+        // R1 points to beginning of memory area, R10 to stack
+        let mut reg: [u64;11] = [
+            0, 0, 0, 0, 0, 0, 0, 0, 0, 0, stack.as_ptr() as u64 + stack.len() as u64 * 8
+        ];
+        if !mbuff.is_empty() {
+            reg[1] = mbuff.as_ptr() as u64;
+        }
+        else if !mem.is_empty() {
+            reg[1] = mem.as_ptr() as u64;
+        }
+
+        ExecState {
+            prog,
+            prog_index: None,
+            tail_calls: 0,
+            insn_ptr:   0,
+            insn_count: 0,
+            reg,
+            stack,
+            frames:     Vec::with_capacity(ebpf::MAX_CALL_DEPTH - 1),
+        }
+    }
+
+    // The stack as a slice of bytes.
+    fn stack_bytes(&self) -> &[u8] {
+        unsafe {
+            std::slice::from_raw_parts(self.stack.as_ptr() as *const u8, self.stack.len() * 8)
+        }
+    }
+
+    fn stack_bytes_mut(&mut self) -> &mut [u8] {
+        unsafe {
+            std::slice::from_raw_parts_mut(self.stack.as_mut_ptr() as *mut u8, self.stack.len() * 8)
+        }
+    }
+}
+
+/// An array of eBPF programs, used as the target of tail calls.
+///
+/// A tail call (`ebpf::TAIL_CALL` instruction, or `bpf_tail_call()` helper in the kernel) replaces
+/// the program currently running with the one stored at index `r3` in the array, keeping the
+/// context in `r1` and the stack. If the slot is empty or out of the array, or if the maximum
+/// number of tail calls has been reached, the program simply goes on with the next instruction.
+///
+/// The programs are checked with the simple verifier (`verifier::check()`) when they are stored
+/// into the array, and again with the verifier of the VM when the array is attached to it, so that
+/// tail calls cannot jump into a program that the VM would have rejected.
+///
+/// # Examples
+///
+/// ```
+/// let prog = &[
+///     0xb7, 0x03, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, // mov r3, 1
+///     0x8d, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // tail call to program 1
+///     0xb7, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // mov r0, 0 (tail call failed)
+///     0x95, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00  // exit
+/// ];
+/// let tail_prog = &[
+///     0xb7, 0x00, 0x00, 0x00, 0x11, 0x22, 0x00, 0x00, // mov r0, 0x2211
+///     0x95, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00  // exit
+/// ];
+///
+/// let mut prog_array = rbpf::ProgramArray::new(4);
+/// prog_array.set(1, tail_prog).unwrap();
+///
+/// let mut vm = rbpf::EbpfVmNoData::new(prog).unwrap();
+/// vm.set_prog_array(&prog_array).unwrap();
+/// assert_eq!(vm.prog_exec().unwrap(), 0x2211);
+/// ```
+pub struct ProgramArray<'a> {
+    progs: Vec<Option<&'a [u8]>>,
+}
+
+impl<'a> ProgramArray<'a> {
+
+    /// Create a new program array with `max_entries` empty slots.
+    pub fn new(max_entries: usize) -> ProgramArray<'a> {
+        ProgramArray {
+            progs: vec![None; max_entries],
+        }
+    }
+
+    /// Store a program at the given index of the array, replacing the previous one if any.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the simple verifier finds errors in the eBPF program.
+    ///
+    /// # Panics
+    ///
+    /// This function panics if `index` is out of the array.
+    pub fn set(&mut self, index: usize, prog: &'a [u8]) -> Result<(), EbpfError> {
+        verifier::check(prog)?;
+        self.progs[index] = Some(prog);
+        Ok(())
+    }
+
+    /// Remove the program stored at the given index of the array, if any.
+    ///
+    /// # Panics
+    ///
+    /// This function panics if `index` is out of the array.
+    pub fn clear(&mut self, index: usize) {
+        self.progs[index] = None;
+    }
+
+    /// Return the program stored at the given index of the array, or `None` if the slot is empty
+    /// or out of the array.
+    pub fn get(&self, index: usize) -> Option<&'a [u8]> {
+        self.progs.get(index).and_then(|prog| *prog)
+    }
+
+    /// Return the number of slots in the array.
+    pub fn len(&self) -> usize {
+        self.progs.len()
+    }
+
+    /// Return `true` if the array has no slot.
+    pub fn is_empty(&self) -> bool {
+        self.progs.is_empty()
+    }
+}
+
 /// A virtual machine to run eBPF program. This kind of VM is used for programs expecting to work
 /// on a metadata buffer containing pointers to packet data.
 ///
@@ -55,23 +233,29 @@ struct MetaBuff {
 /// // to packet data start and end in it.
 /// let mut mbuff = [0u8; 32];
 /// unsafe {
-///     let mut data     = mbuff.as_ptr().offset(8)  as *mut u64;
-///     let mut data_end = mbuff.as_ptr().offset(24) as *mut u64;
-///     *data     = mem.as_ptr() as u64;
-///     *data_end = mem.as_ptr() as u64 + mem.len() as u64;
+///     let data     = mbuff.as_mut_ptr().offset(8)  as *mut u64;
+///     let data_end = mbuff.as_mut_ptr().offset(24) as *mut u64;
+///     data.write_unaligned(mem.as_ptr() as u64);
+///     data_end.write_unaligned(mem.as_ptr() as u64 + mem.len() as u64);
 /// }
 ///
 /// // Instantiate a VM.
-/// let mut vm = rbpf::EbpfVmMbuff::new(prog);
+/// let mut vm = rbpf::EbpfVmMbuff::new(prog).unwrap();
 ///
 /// // Provide both a reference to the packet data, and to the metadata buffer.
-/// let res = vm.prog_exec(mem, &mut mbuff);
+/// let res = vm.prog_exec(mem, &mut mbuff).unwrap();
 /// assert_eq!(res, 0x2211);
 /// ```
 pub struct EbpfVmMbuff<'a> {
-    prog:    &'a [u8],
-    jit:     (unsafe fn (*mut u8, usize, *mut u8, usize, usize, usize) -> u64),
-    helpers: HashMap<u32, ebpf::Helper>,
+    prog:           &'a [u8],
+    verifier:       Verifier,
+    jit:            Option<jit::JitProgram>,
+    jit_mem_check:  bool,
+    helpers:        HashMap<u32, ebpf::Helper>,
+    prog_array:     Option<&'a ProgramArray<'a>>,
+    max_tail_calls: usize,
+    maps:           HashMap<u32, &'a Map>,
+    insn_budget:    Option<u64>,
 }
 
 impl<'a> EbpfVmMbuff<'a> {
@@ -79,9 +263,9 @@ impl<'a> EbpfVmMbuff<'a> {
     /// Create a new virtual machine instance, and load an eBPF program into that instance.
     /// When attempting to load the program, it passes through a simple verifier.
     ///
-    /// # Panics
+    /// # Errors
     ///
-    /// The simple verifier may panic if it finds errors in the eBPF program at load time.
+    /// Returns an error if the simple verifier finds errors in the eBPF program at load time.
     ///
     /// # Examples
     ///
@@ -93,28 +277,32 @@ impl<'a> EbpfVmMbuff<'a> {
     /// ];
     ///
     /// // Instantiate a VM.
-    /// let mut vm = rbpf::EbpfVmMbuff::new(prog);
+    /// let mut vm = rbpf::EbpfVmMbuff::new(prog).unwrap();
     /// ```
-    pub fn new(prog: &'a [u8]) -> EbpfVmMbuff<'a> {
-        verifier::check(prog);
+    pub fn new(prog: &'a [u8]) -> Result<EbpfVmMbuff<'a>, EbpfError> {
+        verifier::check(prog)?;
 
-        fn no_jit(_mbuff: *mut u8, _len: usize, _mem: *mut u8, _mem_len: usize,
-                  _nodata_offset: usize, _nodata_end_offset: usize) -> u64 {
-            panic!("Error: program has not been JIT-compiled");
-        }
-
-        EbpfVmMbuff {
-            prog:    prog,
-            jit:     no_jit,
-            helpers: HashMap::new(),
-        }
+        Ok(EbpfVmMbuff {
+            prog,
+            verifier:       verifier::check,
+            jit:            None,
+            jit_mem_check:  false,
+            helpers:        HashMap::new(),
+            prog_array:     None,
+            max_tail_calls: ebpf::MAX_TAIL_CALLS,
+            maps:           HashMap::new(),
+            insn_budget:    None,
+        })
     }
 
     /// Load a new eBPF program into the virtual machine instance.
     ///
-    /// # Panics
+    /// If the previous program was JIT-compiled, the compiled program is discarded: call
+    /// `jit_compile()` again before running the new one with `prog_exec_jit()`.
     ///
-    /// The simple verifier may panic if it finds errors in the eBPF program at load time.
+    /// # Errors
+    ///
+    /// Returns an error if the simple verifier finds errors in the eBPF program at load time.
     ///
     /// # Examples
     ///
@@ -130,12 +318,54 @@ impl<'a> EbpfVmMbuff<'a> {
     /// ];
     ///
     /// // Instantiate a VM.
-    /// let mut vm = rbpf::EbpfVmMbuff::new(prog1);
-    /// vm.set_prog(prog2);
+    /// let mut vm = rbpf::EbpfVmMbuff::new(prog1).unwrap();
+    /// vm.set_prog(prog2).unwrap();
     /// ```
-    pub fn set_prog(&mut self, prog: &'a [u8]) {
-        verifier::check(prog);
+    pub fn set_prog(&mut self, prog: &'a [u8]) -> Result<(), EbpfError> {
+        (self.verifier)(prog)?;
         self.prog = prog;
+        self.jit = None;
+        Ok(())
+    }
+
+    /// Set a new verifier function, used instead of `verifier::check()` to check the programs
+    /// loaded into the VM with `set_prog()`, and the programs of the array attached with
+    /// `set_prog_array()`. The program currently loaded, and the programs of the array currently
+    /// attached, are checked right away.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the verifier rejects the loaded program, or one of the programs of the
+    /// array. The previous verifier is kept in that case.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rbpf::verifier;
+    ///
+    /// let prog1 = &[
+    ///     0xb7, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // mov r0, 0
+    ///     0x95, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00  // exit
+    /// ];
+    /// let prog2 = &[
+    ///     0x05, 0x00, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, // ja +1
+    ///     0xb7, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // mov r0, 0
+    ///     0x95, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00  // exit
+    /// ];
+    ///
+    /// let mut vm = rbpf::EbpfVmMbuff::new(prog1).unwrap();
+    /// vm.set_verifier(verifier::check_cfg).unwrap();
+    ///
+    /// // The second instruction cannot be reached.
+    /// assert!(vm.set_prog(prog2).is_err());
+    /// ```
+    pub fn set_verifier(&mut self, verifier: Verifier) -> Result<(), EbpfError> {
+        verifier(self.prog)?;
+        if let Some(prog_array) = self.prog_array {
+            EbpfVmMbuff::check_prog_array(prog_array, verifier)?;
+        }
+        self.verifier = verifier;
+        Ok(())
     }
 
     /// Register a built-in or user-defined helper function in order to use it later from within
@@ -166,7 +396,7 @@ impl<'a> EbpfVmMbuff<'a> {
     /// ];
     ///
     /// // Instantiate a VM.
-    /// let mut vm = rbpf::EbpfVmMbuff::new(prog);
+    /// let mut vm = rbpf::EbpfVmMbuff::new(prog).unwrap();
     ///
     /// // Register a helper.
     /// // On running the program this helper will print the content of registers r3, r4 and r5 to
@@ -177,6 +407,150 @@ impl<'a> EbpfVmMbuff<'a> {
         self.helpers.insert(key, function);
     }
 
+    /// Attach a program array to the virtual machine, to be used as the target of tail calls (see
+    /// `ProgramArray`).
+    ///
+    /// If using JIT-compiled eBPF programs, the programs of the array are compiled along with the
+    /// loaded program: be sure to fill the array and to attach it before compiling the program.
+    /// Attaching an array discards the program compiled earlier, if any.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the verifier of the VM rejects one of the programs of the array.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let prog = &[
+    ///     0x8d, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // tail call to program r3 (0)
+    ///     0x95, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00  // exit
+    /// ];
+    ///
+    /// let mut prog_array = rbpf::ProgramArray::new(1);
+    /// prog_array.set(0, prog).unwrap();
+    ///
+    /// // Instantiate a VM.
+    /// let mut vm = rbpf::EbpfVmMbuff::new(prog).unwrap();
+    /// vm.set_prog_array(&prog_array).unwrap();
+    /// ```
+    pub fn set_prog_array(&mut self, prog_array: &'a ProgramArray<'a>) -> Result<(), EbpfError> {
+        EbpfVmMbuff::check_prog_array(prog_array, self.verifier)?;
+        self.prog_array = Some(prog_array);
+        self.jit = None;
+        Ok(())
+    }
+
+    /// Set the maximum number of tail calls that a program can perform in a row, 33 by default
+    /// (`ebpf::MAX_TAIL_CALLS`). Once the limit is reached, tail calls fail and the program goes
+    /// on with the instruction following the tail call.
+    ///
+    /// If using JIT-compiled eBPF programs, be sure to set the limit before compiling the program.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let prog = &[
+    ///     0x95, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00  // exit
+    /// ];
+    ///
+    /// // Instantiate a VM.
+    /// let mut vm = rbpf::EbpfVmMbuff::new(prog).unwrap();
+    /// vm.set_max_tail_calls(8);
+    /// ```
+    pub fn set_max_tail_calls(&mut self, max: usize) {
+        self.max_tail_calls = max;
+    }
+
+    /// Make the JIT-compiler emit runtime checks for memory accesses, disabled by default. As with
+    /// the interpreter, each load or store must then fall within the metadata buffer, the packet
+    /// data, the stack or the values of a registered map. An out of bounds access aborts the
+    /// program and `prog_exec_jit()` returns an error indicating the faulting instruction,
+    /// instead of possibly crashing the process.
+    ///
+    /// The checks slow the program down. Be sure to enable them before compiling the program.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let prog = &[
+    ///     0x95, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00  // exit
+    /// ];
+    ///
+    /// // Instantiate a VM.
+    /// let mut vm = rbpf::EbpfVmMbuff::new(prog).unwrap();
+    /// vm.set_jit_mem_check(true);
+    /// ```
+    pub fn set_jit_mem_check(&mut self, check: bool) {
+        self.jit_mem_check = check;
+    }
+
+    /// Set the maximum number of instructions that a run of the program can execute, or `None`
+    /// for no limit (the default). Instructions executed by local functions and by programs
+    /// reached with tail calls count toward the budget. Once it is exhausted, the program is
+    /// aborted with an `InstructionBudgetExceeded` error, which notably prevents programs with
+    /// loops from running forever.
+    ///
+    /// If using JIT-compiled eBPF programs, be sure to set the budget before compiling the
+    /// program: setting it discards the program compiled earlier, if any. Metering instructions
+    /// slows JIT-compiled programs down.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rbpf::error::ExecErrorKind;
+    ///
+    /// let prog = &[
+    ///     0xb7, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // mov r0, 0
+    ///     0x05, 0x00, 0xfe, 0xff, 0x00, 0x00, 0x00, 0x00, // ja -2
+    ///     0x95, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00  // exit
+    /// ];
+    ///
+    /// // Instantiate a VM.
+    /// let mut vm = rbpf::EbpfVmMbuff::new(prog).unwrap();
+    /// vm.set_insn_budget(Some(1000));
+    ///
+    /// let err = vm.prog_exec(&[], &[]).unwrap_err();
+    /// assert_eq!(err.kind, ExecErrorKind::InstructionBudgetExceeded);
+    /// ```
+    pub fn set_insn_budget(&mut self, budget: Option<u64>) {
+        self.insn_budget = budget;
+        self.jit = None;
+    }
+
+    /// Register an eBPF map with file descriptor number `fd`, so that the program can obtain a
+    /// handle on it with a `lddw` instruction whose `src` field is `ebpf::BPF_PSEUDO_MAP_FD` and
+    /// whose immediate value is `fd` (see module `maps`).
+    ///
+    /// Once a map is registered, calls to helpers 1, 2 and 3 go to the `bpf_map_lookup_elem()`,
+    /// `bpf_map_update_elem()` and `bpf_map_delete_elem()` map helpers, as in the kernel, rather
+    /// than to the helpers registered with these keys, if any. The program is aborted if it passes
+    /// them something else than a handle on a registered map, or a key or value out of bounds.
+    ///
+    /// If using JIT-compiled eBPF programs, be sure to register all maps before compiling the
+    /// program: registering a map discards the program compiled earlier, if any.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rbpf::maps::{Map, MapType};
+    ///
+    /// let prog = &[
+    ///     0x18, 0x11, 0x00, 0x00, 0x03, 0x00, 0x00, 0x00, // lddw r1, map_fd(3)
+    ///     0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+    ///     0x95, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00  // exit
+    /// ];
+    ///
+    /// let map = Map::new(MapType::Hash, 4, 8, 16).unwrap();
+    ///
+    /// // Instantiate a VM.
+    /// let mut vm = rbpf::EbpfVmMbuff::new(prog).unwrap();
+    /// vm.register_map(3, &map);
+    /// ```
+    pub fn register_map(&mut self, fd: u32, map: &'a Map) {
+        self.maps.insert(fd, map);
+        self.jit = None;
+    }
+
     /// Execute the program loaded, with the given packet data and metadata buffer.
     ///
     /// If the program is made to be compatible with Linux kernel, it is expected to load the
@@ -184,11 +558,11 @@ impl<'a> EbpfVmMbuff<'a> {
     /// metadata buffer, at some appointed offsets. It is up to the user to ensure that these
     /// pointers are correctly stored in the buffer.
     ///
-    /// # Panics
+    /// # Errors
     ///
-    /// This function is currently expected to panic if it encounters any error during the program
-    /// execution, such as out of bounds accesses or division by zero attempts. This may be changed
-    /// in the future (we could raise errors instead).
+    /// Returns an `ExecError` if the interpreter encounters a fault during the program execution,
+    /// such as out of bounds accesses or division by zero attempts. The error indicates the kind of
+    /// fault and the index of the faulting instruction.
     ///
     /// # Examples
     ///
@@ -206,46 +580,176 @@ impl<'a> EbpfVmMbuff<'a> {
     /// // pointers to packet data start and end in it.
     /// let mut mbuff = [0u8; 32];
     /// unsafe {
-    ///     let mut data     = mbuff.as_ptr().offset(8)  as *mut u64;
-    ///     let mut data_end = mbuff.as_ptr().offset(24) as *mut u64;
-    ///     *data     = mem.as_ptr() as u64;
-    ///     *data_end = mem.as_ptr() as u64 + mem.len() as u64;
+    ///     let data     = mbuff.as_mut_ptr().offset(8)  as *mut u64;
+    ///     let data_end = mbuff.as_mut_ptr().offset(24) as *mut u64;
+    ///     data.write_unaligned(mem.as_ptr() as u64);
+    ///     data_end.write_unaligned(mem.as_ptr() as u64 + mem.len() as u64);
     /// }
     ///
     /// // Instantiate a VM.
-    /// let mut vm = rbpf::EbpfVmMbuff::new(prog);
+    /// let mut vm = rbpf::EbpfVmMbuff::new(prog).unwrap();
     ///
     /// // Provide both a reference to the packet data, and to the metadata buffer.
-    /// let res = vm.prog_exec(mem, &mut mbuff);
+    /// let res = vm.prog_exec(mem, &mut mbuff).unwrap();
     /// assert_eq!(res, 0x2211);
     /// ```
-    pub fn prog_exec(&self, mem: &[u8], mbuff: &[u8]) -> u64 {
-        const U32MAX: u64 = u32::MAX as u64;
+    pub fn prog_exec(&self, mem: &[u8], mbuff: &[u8]) -> Result<u64, ExecError> {
+        self.interpret(mem, mbuff, None).map(|(res, _)| res)
+    }
 
-        let stack = vec![0u8;ebpf::STACK_SIZE];
+    /// Execute the program loaded, with the given packet data and metadata buffer, as
+    /// `prog_exec()` does. Return the value of r0 along with the number of instructions executed,
+    /// including those of local functions and of programs reached with tail calls.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `ExecError` if the interpreter encounters a fault during the program execution,
+    /// as `prog_exec()` does.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let prog = &[
+    ///     0xb7, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, // mov r0, 1
+    ///     0x95, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00  // exit
+    /// ];
+    ///
+    /// // Instantiate a VM.
+    /// let vm = rbpf::EbpfVmMbuff::new(prog).unwrap();
+    /// assert_eq!(vm.prog_exec_metered(&[], &[]).unwrap(), (1, 2));
+    /// ```
+    pub fn prog_exec_metered(&self, mem: &[u8], mbuff: &[u8]) -> Result<(u64, u64), ExecError> {
+        self.interpret(mem, mbuff, None)
+    }
 
-        // R1 points to beginning of memory area, R10 to stack
-        let mut reg: [u64;11] = [
-            0, 0, 0, 0, 0, 0, 0, 0, 0, 0, stack.as_ptr() as u64 + stack.len() as u64
-        ];
-        if mbuff.len() > 0 {
-            reg[1] = mbuff.as_ptr() as u64;
-        }
-        else if mem.len() > 0 {
-            reg[1] = mem.as_ptr() as u64;
+    /// Execute the program loaded, with the given packet data and metadata buffer, as
+    /// `prog_exec()` does, and call `tracer` before executing each instruction, with the index and
+    /// content of the instruction and the values of the registers (see module `trace`).
+    ///
+    /// # Errors
+    ///
+    /// Returns an `ExecError` if the interpreter encounters a fault during the program execution,
+    /// as `prog_exec()` does. The tracer has been called for the faulting instruction.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let prog = &[
+    ///     0xb7, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, // mov r0, 1
+    ///     0x95, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00  // exit
+    /// ];
+    ///
+    /// // Instantiate a VM.
+    /// let vm = rbpf::EbpfVmMbuff::new(prog).unwrap();
+    ///
+    /// let mut trace = vec![];
+    /// vm.prog_exec_traced(&[], &[], &mut |entry| trace.push(entry.clone())).unwrap();
+    /// assert_eq!(trace.len(), 2);
+    /// assert_eq!(trace[1].insn_ptr, 1);
+    /// assert_eq!(trace[1].regs[0], 1);
+    /// ```
+    pub fn prog_exec_traced(&self, mem: &[u8], mbuff: &[u8], tracer: &mut dyn FnMut(&TraceEntry))
+                            -> Result<u64, ExecError> {
+        self.interpret(mem, mbuff, Some(tracer)).map(|(res, _)| res)
+    }
+
+    // Run the program with the interpreter. Returns the value of r0 and the number of instructions
+    // executed.
+    fn interpret(&self, mem: &[u8], mbuff: &[u8], tracer: Option<&mut dyn FnMut(&TraceEntry)>)
+                 -> Result<(u64, u64), ExecError> {
+        let mut state = ExecState::new(self.prog, mem, mbuff);
+        // Unless single stepping, the interpreter only returns once the program exited.
+        match self.run(&mut state, mem, mbuff, tracer, false)? {
+            Some(res) => Ok((res, state.insn_count)),
+            None      => unreachable!(),
         }
+    }
+
+    /// Start a debugging session for the loaded program, with the interpreter. The session takes
+    /// a reference to the packet data `mem` and to the metadata buffer `mbuff`, as for
+    /// `prog_exec()`, and lets the caller run the program step by step. See module `debugger`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rbpf::debugger::Status;
+    ///
+    /// let prog = &[
+    ///     0x79, 0x11, 0x08, 0x00, 0x00, 0x00, 0x00, 0x00, // Load mem from mbuff into R1.
+    ///     0x69, 0x10, 0x02, 0x00, 0x00, 0x00, 0x00, 0x00, // ldhx r1[2], r0
+    ///     0x95, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00  // exit
+    /// ];
+    /// let mem = &mut [
+    ///     0xaa, 0xbb, 0x11, 0x22, 0xcc, 0xdd
+    /// ];
+    ///
+    /// // Just for the example we create our metadata buffer from scratch, and we store the
+    /// // pointers to packet data start and end in it.
+    /// let mut mbuff = [0u8; 32];
+    /// unsafe {
+    ///     let data     = mbuff.as_mut_ptr().offset(8)  as *mut u64;
+    ///     let data_end = mbuff.as_mut_ptr().offset(24) as *mut u64;
+    ///     data.write_unaligned(mem.as_ptr() as u64);
+    ///     data_end.write_unaligned(mem.as_ptr() as u64 + mem.len() as u64);
+    /// }
+    ///
+    /// let vm = rbpf::EbpfVmMbuff::new(prog).unwrap();
+    /// let mut session = vm.debug(mem, &mut mbuff);
+    ///
+    /// session.set_breakpoint(1);
+    /// assert_eq!(session.cont(), Status::Breakpoint(1));
+    /// session.mem_mut()[2] = 0x33;
+    /// assert_eq!(session.cont(), Status::Exited(0x2233));
+    /// ```
+    pub fn debug<'b>(&'b self, mem: &'b mut [u8], mbuff: &'b mut [u8]) -> Session<'a, 'b> {
+        Session::new(self, mem, mbuff)
+    }
+
+    // Execute the program with the interpreter from `state`, until it exits, or for one instruction
+    // only if `single_step` is set. Returns the value of r0 if the program exited.
+    fn run(&self, state: &mut ExecState<'a>, mem: &[u8], mbuff: &[u8],
+           mut tracer: Option<&mut dyn FnMut(&TraceEntry)>, single_step: bool)
+           -> Result<Option<u64>, ExecError> {
+        const U32MAX: u64 = u32::MAX as u64;
+
+        let stack = unsafe {
+            std::slice::from_raw_parts(state.stack.as_ptr() as *const u8, state.stack.len() * 8)
+        };
+        let reg = &mut state.reg;
+        let frames = &mut state.frames;
 
         let check_mem_load = | addr: u64, len: usize, insn_ptr: usize | {
-            EbpfVmMbuff::check_mem(addr, len, "load", insn_ptr, mbuff, mem, &stack);
+            EbpfVmMbuff::check_mem(addr, len, ExecErrorKind::OutOfBoundsLoad, insn_ptr,
+                                   &[mbuff, mem, stack], &self.maps)
         };
         let check_mem_store = | addr: u64, len: usize, insn_ptr: usize | {
-            EbpfVmMbuff::check_mem(addr, len, "store", insn_ptr, mbuff, mem, &stack);
+            EbpfVmMbuff::check_mem(addr, len, ExecErrorKind::OutOfBoundsStore, insn_ptr,
+                                   &[mbuff, mem, stack], &self.maps)
+        };
+        // Read a big-endian value of `len` bytes at offset `off` in packet data, if in bounds.
+        let load_packet = | off: i32, len: usize | {
+            if off < 0 || off as usize + len > mem.len() {
+                return None;
+            }
+            Some(mem[off as usize..off as usize + len].iter().fold(0u64, |acc, &b| (acc << 8) | b as u64))
         };
 
-        // Loop on instructions
-        let mut insn_ptr:usize = 0;
-        while insn_ptr * ebpf::INSN_SIZE < self.prog.len() {
-            let insn = ebpf::get_insn(self.prog, insn_ptr);
+        // Program currently running, may be replaced by tail calls
+        let mut prog = state.prog;
+        let mut prog_index = state.prog_index;
+        let mut tail_calls = state.tail_calls;
+        let mut insn_ptr = state.insn_ptr;
+
+        loop {
+            if self.insn_budget == Some(state.insn_count) {
+                return Err(ExecError::new(ExecErrorKind::InstructionBudgetExceeded, insn_ptr));
+            }
+            state.insn_count += 1;
+
+            let insn = ebpf::get_insn(prog, insn_ptr);
+            if let Some(ref mut tracer) = tracer {
+                tracer(&TraceEntry { prog_index, insn_ptr, insn: insn.clone(), regs: *reg });
+            }
             insn_ptr += 1;
             let _dst    = insn.dst as usize;
             let _src    = insn.src as usize;
@@ -253,87 +757,121 @@ impl<'a> EbpfVmMbuff<'a> {
             match insn.opc {
 
                 // BPF_LD class
-                ebpf::LD_ABS_B   => unimplemented!(),
-                ebpf::LD_ABS_H   => unimplemented!(),
-                ebpf::LD_ABS_W   => unimplemented!(),
-                ebpf::LD_ABS_DW  => unimplemented!(),
-                ebpf::LD_IND_B   => unimplemented!(),
-                ebpf::LD_IND_H   => unimplemented!(),
-                ebpf::LD_IND_W   => unimplemented!(),
-                ebpf::LD_IND_DW  => unimplemented!(),
+                // The packet data is the implicit base for these loads, and the value is converted
+                // from network byte order. As in the kernel, out of bounds loads abort the program,
+                // which then returns 0.
+                ebpf::LD_ABS_B   => match load_packet(insn.imm, 1) { Some(v) => reg[0] = v, None => return Ok(Some(0)) },
+                ebpf::LD_ABS_H   => match load_packet(insn.imm, 2) { Some(v) => reg[0] = v, None => return Ok(Some(0)) },
+                ebpf::LD_ABS_W   => match load_packet(insn.imm, 4) { Some(v) => reg[0] = v, None => return Ok(Some(0)) },
+                ebpf::LD_ABS_DW  => match load_packet(insn.imm, 8) { Some(v) => reg[0] = v, None => return Ok(Some(0)) },
+                ebpf::LD_IND_B   => match load_packet((reg[_src] as i32).wrapping_add(insn.imm), 1) {
+                    Some(v) => reg[0] = v, None => return Ok(Some(0))
+                },
+                ebpf::LD_IND_H   => match load_packet((reg[_src] as i32).wrapping_add(insn.imm), 2) {
+                    Some(v) => reg[0] = v, None => return Ok(Some(0))
+                },
+                ebpf::LD_IND_W   => match load_packet((reg[_src] as i32).wrapping_add(insn.imm), 4) {
+                    Some(v) => reg[0] = v, None => return Ok(Some(0))
+                },
+                ebpf::LD_IND_DW  => match load_packet((reg[_src] as i32).wrapping_add(insn.imm), 8) {
+                    Some(v) => reg[0] = v, None => return Ok(Some(0))
+                },
 
                 // BPF_LDX class
                 ebpf::LD_DW_IMM  => {
-                    let next_insn = ebpf::get_insn(self.prog, insn_ptr);
+                    let next_insn = ebpf::get_insn(prog, insn_ptr);
                     insn_ptr += 1;
-                    reg[_dst] = ((insn.imm as u32) as u64) + ((next_insn.imm as u64) << 32);
+                    reg[_dst] = if insn.src == ebpf::BPF_PSEUDO_MAP_FD {
+                        match self.maps.get(&(insn.imm as u32)) {
+                            Some(map) => *map as *const Map as u64,
+                            None      => return Err(ExecError::new(
+                                ExecErrorKind::UnknownMap(insn.imm as u32), insn_ptr - 2)),
+                        }
+                    } else {
+                        ((insn.imm as u32) as u64) + ((next_insn.imm as u64) << 32)
+                    };
                 },
                 ebpf::LD_B_REG   => reg[_dst] = unsafe {
-                    let x = (reg[_src] as *const u8).offset(insn.off as isize) as *const u8;
-                    check_mem_load(x as u64, 1, insn_ptr);
-                    *x as u64
+                    let x = (reg[_src] as *const u8).offset(insn.off as isize);
+                    check_mem_load(x as u64, 1, insn_ptr - 1)?;
+                    x.read_unaligned() as u64
                 },
                 ebpf::LD_H_REG   => reg[_dst] = unsafe {
                     let x = (reg[_src] as *const u8).offset(insn.off as isize) as *const u16;
-                    check_mem_load(x as u64, 2, insn_ptr);
-                    *x as u64
+                    check_mem_load(x as u64, 2, insn_ptr - 1)?;
+                    x.read_unaligned() as u64
                 },
                 ebpf::LD_W_REG   => reg[_dst] = unsafe {
                     let x = (reg[_src] as *const u8).offset(insn.off as isize) as *const u32;
-                    check_mem_load(x as u64, 4, insn_ptr);
-                    *x as u64
+                    check_mem_load(x as u64, 4, insn_ptr - 1)?;
+                    x.read_unaligned() as u64
                 },
                 ebpf::LD_DW_REG  => reg[_dst] = unsafe {
                     let x = (reg[_src] as *const u8).offset(insn.off as isize) as *const u64;
-                    check_mem_load(x as u64, 8, insn_ptr);
-                    *x as u64
+                    check_mem_load(x as u64, 8, insn_ptr - 1)?;
+                    x.read_unaligned()
                 },
 
                 // BPF_ST class
                 ebpf::ST_B_IMM   => unsafe {
                     let x = (reg[_dst] as *const u8).offset(insn.off as isize) as *mut u8;
-                    check_mem_store(x as u64, 1, insn_ptr);
-                    *x = insn.imm as u8;
+                    check_mem_store(x as u64, 1, insn_ptr - 1)?;
+                    x.write_unaligned(insn.imm as u8);
                 },
                 ebpf::ST_H_IMM   => unsafe {
                     let x = (reg[_dst] as *const u8).offset(insn.off as isize) as *mut u16;
-                    check_mem_store(x as u64, 2, insn_ptr);
-                    *x = insn.imm as u16;
+                    check_mem_store(x as u64, 2, insn_ptr - 1)?;
+                    x.write_unaligned(insn.imm as u16);
                 },
                 ebpf::ST_W_IMM   => unsafe {
                     let x = (reg[_dst] as *const u8).offset(insn.off as isize) as *mut u32;
-                    check_mem_store(x as u64, 4, insn_ptr);
-                    *x = insn.imm as u32;
+                    check_mem_store(x as u64, 4, insn_ptr - 1)?;
+                    x.write_unaligned(insn.imm as u32);
                 },
                 ebpf::ST_DW_IMM  => unsafe {
                     let x = (reg[_dst] as *const u8).offset(insn.off as isize) as *mut u64;
-                    check_mem_store(x as u64, 8, insn_ptr);
-                    *x = insn.imm as u64;
+                    check_mem_store(x as u64, 8, insn_ptr - 1)?;
+                    x.write_unaligned(insn.imm as u64);
                 },
 
                 // BPF_STX class
                 ebpf::ST_B_REG   => unsafe {
                     let x = (reg[_dst] as *const u8).offset(insn.off as isize) as *mut u8;
-                    check_mem_store(x as u64, 1, insn_ptr);
-                    *x = reg[_src] as u8;
+                    check_mem_store(x as u64, 1, insn_ptr - 1)?;
+                    x.write_unaligned(reg[_src] as u8);
                 },
                 ebpf::ST_H_REG   => unsafe {
                     let x = (reg[_dst] as *const u8).offset(insn.off as isize) as *mut u16;
-                    check_mem_store(x as u64, 2, insn_ptr);
-                    *x = reg[_src] as u16;
+                    check_mem_store(x as u64, 2, insn_ptr - 1)?;
+                    x.write_unaligned(reg[_src] as u16);
                 },
                 ebpf::ST_W_REG   => unsafe {
                     let x = (reg[_dst] as *const u8).offset(insn.off as isize) as *mut u32;
-                    check_mem_store(x as u64, 4, insn_ptr);
-                    *x = reg[_src] as u32;
+                    check_mem_store(x as u64, 4, insn_ptr - 1)?;
+                    x.write_unaligned(reg[_src] as u32);
                 },
                 ebpf::ST_DW_REG  => unsafe {
                     let x = (reg[_dst] as *const u8).offset(insn.off as isize) as *mut u64;
-                    check_mem_store(x as u64, 8, insn_ptr);
-                    *x = reg[_src] as u64;
+                    check_mem_store(x as u64, 8, insn_ptr - 1)?;
+                    x.write_unaligned(reg[_src]);
+                },
+                // Atomic operations require the address to be aligned on the size of the operand.
+                ebpf::ST_W_XADD  => unsafe {
+                    let x = (reg[_dst] as *const u8).offset(insn.off as isize) as *const AtomicU32;
+                    check_mem_store(x as u64, 4, insn_ptr - 1)?;
+                    if x as u64 % 4 != 0 {
+                        return Err(ExecError::mem(ExecErrorKind::UnalignedAtomic, insn_ptr - 1, x as u64, 4));
+                    }
+                    (*x).fetch_add(reg[_src] as u32, Ordering::SeqCst);
+                },
+                ebpf::ST_DW_XADD => unsafe {
+                    let x = (reg[_dst] as *const u8).offset(insn.off as isize) as *const AtomicU64;
+                    check_mem_store(x as u64, 8, insn_ptr - 1)?;
+                    if x as u64 % 8 != 0 {
+                        return Err(ExecError::mem(ExecErrorKind::UnalignedAtomic, insn_ptr - 1, x as u64, 8));
+                    }
+                    (*x).fetch_add(reg[_src], Ordering::SeqCst);
                 },
-                ebpf::ST_W_XADD  => unimplemented!(),
-                ebpf::ST_DW_XADD => unimplemented!(),
 
                 // BPF_ALU class
                 // TODO Check how overflow works in kernel. Should we &= U32MAX all src register value
@@ -347,8 +885,8 @@ impl<'a> EbpfVmMbuff<'a> {
                 ebpf::MUL32_REG  => reg[_dst] = (reg[_dst] as i32).wrapping_mul(reg[_src] as i32) as u64,
                 ebpf::DIV32_IMM  => reg[_dst] = (reg[_dst] as u32 / insn.imm              as u32) as u64,
                 ebpf::DIV32_REG  => {
-                    if reg[_src] == 0 {
-                        panic!("Error: division by 0");
+                    if reg[_src] as u32 == 0 {
+                        return Err(ExecError::new(ExecErrorKind::DivisionByZero, insn_ptr - 1));
                     }
                     reg[_dst] = (reg[_dst] as u32 / reg[_src] as u32) as u64;
                 },
@@ -363,8 +901,8 @@ impl<'a> EbpfVmMbuff<'a> {
                 ebpf::NEG32      => { reg[_dst] = (reg[_dst] as i32).wrapping_neg()                 as u64; reg[_dst] &= U32MAX; },
                 ebpf::MOD32_IMM  =>   reg[_dst] = (reg[_dst] as u32             % insn.imm  as u32) as u64,
                 ebpf::MOD32_REG  => {
-                    if reg[_src] == 0 {
-                        panic!("Error: division by 0");
+                    if reg[_src] as u32 == 0 {
+                        return Err(ExecError::new(ExecErrorKind::DivisionByZero, insn_ptr - 1));
                     }
                     reg[_dst] = (reg[_dst] as u32 % reg[_src] as u32) as u64;
                 },
@@ -401,7 +939,7 @@ impl<'a> EbpfVmMbuff<'a> {
                 ebpf::DIV64_IMM  => reg[_dst]                       /= insn.imm as u64,
                 ebpf::DIV64_REG  => {
                     if reg[_src] == 0 {
-                        panic!("Error: division by 0");
+                        return Err(ExecError::new(ExecErrorKind::DivisionByZero, insn_ptr - 1));
                     }
                     reg[_dst] /= reg[_src];
                 },
@@ -417,7 +955,7 @@ impl<'a> EbpfVmMbuff<'a> {
                 ebpf::MOD64_IMM  => reg[_dst] %=  insn.imm as u64,
                 ebpf::MOD64_REG  => {
                     if reg[_src] == 0 {
-                        panic!("Error: division by 0");
+                        return Err(ExecError::new(ExecErrorKind::DivisionByZero, insn_ptr - 1));
                     }
                     reg[_dst] %= reg[_src];
                 },
@@ -445,42 +983,138 @@ impl<'a> EbpfVmMbuff<'a> {
                 ebpf::JSGT_REG   => if reg[_dst] as i64 >  reg[_src] as i64 { insn_ptr = (insn_ptr as i16 + insn.off) as usize; },
                 ebpf::JSGE_IMM   => if reg[_dst] as i64 >= insn.imm  as i64 { insn_ptr = (insn_ptr as i16 + insn.off) as usize; },
                 ebpf::JSGE_REG   => if reg[_dst] as i64 >= reg[_src] as i64 { insn_ptr = (insn_ptr as i16 + insn.off) as usize; },
-                // Do not delegate the check to the verifier, since registered functions can be
+                ebpf::JLT_IMM    => if reg[_dst] <  insn.imm as u64         { insn_ptr = (insn_ptr as i16 + insn.off) as usize; },
+                ebpf::JLT_REG    => if reg[_dst] <  reg[_src]               { insn_ptr = (insn_ptr as i16 + insn.off) as usize; },
+                ebpf::JLE_IMM    => if reg[_dst] <= insn.imm as u64         { insn_ptr = (insn_ptr as i16 + insn.off) as usize; },
+                ebpf::JLE_REG    => if reg[_dst] <= reg[_src]               { insn_ptr = (insn_ptr as i16 + insn.off) as usize; },
+                ebpf::JSLT_IMM   => if (reg[_dst] as i64) <  insn.imm  as i64 { insn_ptr = (insn_ptr as i16 + insn.off) as usize; },
+                ebpf::JSLT_REG   => if (reg[_dst] as i64) <  reg[_src] as i64 { insn_ptr = (insn_ptr as i16 + insn.off) as usize; },
+                ebpf::JSLE_IMM   => if reg[_dst] as i64 <= insn.imm  as i64 { insn_ptr = (insn_ptr as i16 + insn.off) as usize; },
+                ebpf::JSLE_REG   => if reg[_dst] as i64 <= reg[_src] as i64 { insn_ptr = (insn_ptr as i16 + insn.off) as usize; },
+
+                // BPF_JMP32 class
+                ebpf::JEQ_IMM32  => if reg[_dst] as u32 == insn.imm as u32      { insn_ptr = (insn_ptr as i16 + insn.off) as usize; },
+                ebpf::JEQ_REG32  => if reg[_dst] as u32 == reg[_src] as u32     { insn_ptr = (insn_ptr as i16 + insn.off) as usize; },
+                ebpf::JGT_IMM32  => if reg[_dst] as u32 >  insn.imm as u32      { insn_ptr = (insn_ptr as i16 + insn.off) as usize; },
+                ebpf::JGT_REG32  => if reg[_dst] as u32 >  reg[_src] as u32     { insn_ptr = (insn_ptr as i16 + insn.off) as usize; },
+                ebpf::JGE_IMM32  => if reg[_dst] as u32 >= insn.imm as u32      { insn_ptr = (insn_ptr as i16 + insn.off) as usize; },
+                ebpf::JGE_REG32  => if reg[_dst] as u32 >= reg[_src] as u32     { insn_ptr = (insn_ptr as i16 + insn.off) as usize; },
+                ebpf::JSET_IMM32 => if reg[_dst] as u32 & insn.imm as u32 != 0  { insn_ptr = (insn_ptr as i16 + insn.off) as usize; },
+                ebpf::JSET_REG32 => if reg[_dst] as u32 & reg[_src] as u32 != 0 { insn_ptr = (insn_ptr as i16 + insn.off) as usize; },
+                ebpf::JNE_IMM32  => if reg[_dst] as u32 != insn.imm as u32      { insn_ptr = (insn_ptr as i16 + insn.off) as usize; },
+                ebpf::JNE_REG32  => if reg[_dst] as u32 != reg[_src] as u32     { insn_ptr = (insn_ptr as i16 + insn.off) as usize; },
+                ebpf::JSGT_IMM32 => if reg[_dst] as i32 >  insn.imm             { insn_ptr = (insn_ptr as i16 + insn.off) as usize; },
+                ebpf::JSGT_REG32 => if reg[_dst] as i32 >  reg[_src] as i32     { insn_ptr = (insn_ptr as i16 + insn.off) as usize; },
+                ebpf::JSGE_IMM32 => if reg[_dst] as i32 >= insn.imm             { insn_ptr = (insn_ptr as i16 + insn.off) as usize; },
+                ebpf::JSGE_REG32 => if reg[_dst] as i32 >= reg[_src] as i32     { insn_ptr = (insn_ptr as i16 + insn.off) as usize; },
+                ebpf::JLT_IMM32  => if (reg[_dst] as u32) <  insn.imm as u32    { insn_ptr = (insn_ptr as i16 + insn.off) as usize; },
+                ebpf::JLT_REG32  => if (reg[_dst] as u32) <  reg[_src] as u32   { insn_ptr = (insn_ptr as i16 + insn.off) as usize; },
+                ebpf::JLE_IMM32  => if (reg[_dst] as u32) <= insn.imm as u32    { insn_ptr = (insn_ptr as i16 + insn.off) as usize; },
+                ebpf::JLE_REG32  => if (reg[_dst] as u32) <= reg[_src] as u32   { insn_ptr = (insn_ptr as i16 + insn.off) as usize; },
+                ebpf::JSLT_IMM32 => if (reg[_dst] as i32) <  insn.imm           { insn_ptr = (insn_ptr as i16 + insn.off) as usize; },
+                ebpf::JSLT_REG32 => if (reg[_dst] as i32) <  reg[_src] as i32   { insn_ptr = (insn_ptr as i16 + insn.off) as usize; },
+                ebpf::JSLE_IMM32 => if (reg[_dst] as i32) <= insn.imm           { insn_ptr = (insn_ptr as i16 + insn.off) as usize; },
+                ebpf::JSLE_REG32 => if (reg[_dst] as i32) <= reg[_src] as i32   { insn_ptr = (insn_ptr as i16 + insn.off) as usize; },
+
+                // Do not delegate the check to the verifier, since registered functions can be
                 // changed after the program has been verified.
-                ebpf::CALL       => if let Some(function) = self.helpers.get(&(insn.imm as u32)) {
+                ebpf::CALL if insn.src == ebpf::BPF_PSEUDO_CALL => {
+                    if frames.len() + 1 >= ebpf::MAX_CALL_DEPTH {
+                        return Err(ExecError::new(ExecErrorKind::CallDepthExceeded, insn_ptr - 1));
+                    }
+                    let mut saved = [0u64;5];
+                    saved.copy_from_slice(&reg[6..11]);
+                    frames.push((insn_ptr, saved));
+                    reg[10] -= ebpf::STACK_SIZE as u64;
+                    insn_ptr = (insn_ptr as isize + insn.imm as isize) as usize;
+                },
+                ebpf::CALL       => if let (Some(helper), false) = (maps::helper(insn.imm as u32),
+                                                                   self.maps.is_empty()) {
+                    reg[0] = EbpfVmMbuff::call_map_helper(helper, insn.imm as u32, reg, insn_ptr - 1,
+                                                          &[mbuff, mem, stack], &self.maps)?;
+                } else if let Some(function) = self.helpers.get(&(insn.imm as u32)) {
                     reg[0] = function(reg[1], reg[2], reg[3], reg[4], reg[5]);
                 } else {
-                    panic!("Error: unknown helper function (id: {:#x})", insn.imm as u32);
+                    return Err(ExecError::new(ExecErrorKind::UnknownHelper(insn.imm as u32),
+                                              insn_ptr - 1));
+                },
+                // On failure, the tail call does nothing and we go on with the next instruction.
+                ebpf::TAIL_CALL  => {
+                    let index = reg[3] as u32 as usize;
+                    let target = self.prog_array.and_then(|array| array.get(index));
+                    if let (Some(target), true) = (target, tail_calls < self.max_tail_calls) {
+                        tail_calls += 1;
+                        prog = target;
+                        prog_index = Some(index);
+                        insn_ptr = 0;
+                        // The new program starts over from the frame of the main program.
+                        frames.clear();
+                        reg[10] = stack.as_ptr() as u64 + stack.len() as u64;
+                    }
+                },
+                ebpf::EXIT       => match frames.pop() {
+                    // Return from a local function: restore the state of the caller.
+                    Some((return_ptr, saved)) => {
+                        reg[6..11].copy_from_slice(&saved);
+                        insn_ptr = return_ptr;
+                    },
+                    None => return Ok(Some(reg[0])),
                 },
-                ebpf::TAIL_CALL  => unimplemented!(),
-                ebpf::EXIT       => return reg[0],
 
                 _                => unreachable!()
             }
-        }
 
-        unreachable!()
+            // Only the debugger executes a single instruction at a time, and needs the state to
+            // resume from.
+            if single_step {
+                state.prog       = prog;
+                state.prog_index = prog_index;
+                state.tail_calls = tail_calls;
+                state.insn_ptr   = insn_ptr;
+                return Ok(None);
+            }
+        }
     }
 
-    fn check_mem(addr: u64, len: usize, access_type: &str, insn_ptr: usize,
-                 mbuff: &[u8], mem: &[u8], stack: &[u8]) {
-        if mbuff.as_ptr() as u64 <= addr && addr + len as u64 <= mbuff.as_ptr() as u64 + mbuff.len() as u64 {
-            return
+    // Call a map helper, after checking that r1 holds a handle on one of the maps, and that the key
+    // pointed to by r2, and the value pointed to by r3 for updates, are in bounds.
+    fn call_map_helper(helper: maps::MapHelper, id: u32, reg: &[u64], insn_ptr: usize,
+                       regions: &[&[u8]], maps: &HashMap<u32, &Map>) -> Result<u64, ExecError> {
+        let map = match maps.values().find(|map| **map as *const Map as u64 == reg[1]) {
+            Some(map) => map,
+            None      => return Err(ExecError::mem(ExecErrorKind::InvalidMapPointer, insn_ptr,
+                                                   reg[1], 0)),
+        };
+        EbpfVmMbuff::check_mem(reg[2], map.key_size(), ExecErrorKind::OutOfBoundsLoad, insn_ptr,
+                               regions, maps)?;
+        if id == maps::BPF_MAP_UPDATE_ELEM_IDX {
+            EbpfVmMbuff::check_mem(reg[3], map.value_size(), ExecErrorKind::OutOfBoundsLoad,
+                                   insn_ptr, regions, maps)?;
         }
-        if mem.as_ptr() as u64 <= addr && addr + len as u64 <= mem.as_ptr() as u64 + mem.len() as u64 {
-            return
+        // The map is alive for as long as the VM, and the key and value are in bounds.
+        Ok(unsafe { helper(reg[1], reg[2], reg[3], reg[4], reg[5]) })
+    }
+
+    // Check that an access of `len` bytes at `addr` falls within one of the memory regions
+    // (metadata buffer, packet data, stack) or within the values of one of the maps.
+    fn check_mem(addr: u64, len: usize, kind: ExecErrorKind, insn_ptr: usize,
+                 regions: &[&[u8]], maps: &HashMap<u32, &Map>) -> Result<(), ExecError> {
+        // An access wrapping around the address space is out of bounds.
+        let end = match addr.checked_add(len as u64) {
+            Some(end) => end,
+            None      => return Err(ExecError::mem(kind, insn_ptr, addr, len)),
+        };
+        for region in regions {
+            if region.as_ptr() as u64 <= addr &&
+               end <= region.as_ptr() as u64 + region.len() as u64 {
+                return Ok(())
+            }
         }
-        if stack.as_ptr() as u64 <= addr && addr + len as u64 <= stack.as_ptr() as u64 + stack.len() as u64 {
-            return
+        if maps.values().any(|map| map.contains(addr, len)) {
+            return Ok(())
         }
 
-        panic!(
-            "Error: out of bounds memory {} (insn #{:?}), addr {:#x}, size {:?}\nmbuff: {:#x}/{:#x}, mem: {:#x}/{:#x}, stack: {:#x}/{:#x}",
-            access_type, insn_ptr, addr, len,
-            mbuff.as_ptr() as u64, mbuff.len(),
-            mem.as_ptr() as u64, mem.len(),
-            stack.as_ptr() as u64, stack.len()
-        );
+        Err(ExecError::mem(kind, insn_ptr, addr, len))
     }
 
     /// JIT-compile the loaded program. No argument required for this.
@@ -488,10 +1122,12 @@ impl<'a> EbpfVmMbuff<'a> {
     /// If using helper functions, be sure to register them into the VM before calling this
     /// function.
     ///
-    /// # Panics
+    /// # Errors
     ///
-    /// This function panics if an error occurs during JIT-compiling, such as the occurrence of an
-    /// unknown eBPF operation code.
+    /// Returns an error if the program loads a map or calls a helper function that has not been
+    /// registered into the VM, if it contains an unknown eBPF operation code, if the generated code
+    /// is too large, if executable memory cannot be allocated for it, or if there is no JIT
+    /// compiler for the target architecture (only x86_64 and AArch64 are supported).
     ///
     /// # Examples
     ///
@@ -503,12 +1139,50 @@ impl<'a> EbpfVmMbuff<'a> {
     /// ];
     ///
     /// // Instantiate a VM.
-    /// let mut vm = rbpf::EbpfVmMbuff::new(prog);
+    /// let mut vm = rbpf::EbpfVmMbuff::new(prog).unwrap();
     ///
-    /// vm.jit_compile();
+    /// vm.jit_compile().unwrap();
     /// ```
-    pub fn jit_compile(&mut self) {
-        self.jit = jit::compile(self.prog, &self.helpers, true, false);
+    pub fn jit_compile(&mut self) -> Result<(), JitError> {
+        self.jit = Some(jit::compile(self.prog, &self.helpers, &self.maps, true, false,
+                                     &self.tail_call_progs(), self.max_tail_calls,
+                                     self.jit_mem_check, self.insn_budget)?);
+        Ok(())
+    }
+
+    // Function of the JIT-compiled program, valid as long as the VM holds the program.
+    fn jit_function(&self) -> jit::JitFunction {
+        match self.jit {
+            Some(ref program) => program.function(),
+            None              => panic!("Error: program has not been JIT-compiled"),
+        }
+    }
+
+    // Turn the outcome of a run of the JIT-compiled program into a result, with the number of
+    // instructions executed if the program counts them.
+    fn jit_result(&self, fault: jit::JitFault, res: u64) -> Result<(u64, Option<u64>), ExecError> {
+        let counted = self.jit.as_ref().map_or(false, |program| program.counts_insns());
+        let insn_count = fault.insn_count();
+        let res = fault.into_result(res)?;
+        Ok((res, if counted { Some(insn_count) } else { None }))
+    }
+
+    // Check all the programs of a program array with the given verifier.
+    fn check_prog_array(prog_array: &ProgramArray, verifier: Verifier) -> Result<(), EbpfError> {
+        for index in 0..prog_array.len() {
+            if let Some(prog) = prog_array.get(index) {
+                verifier(prog)?;
+            }
+        }
+        Ok(())
+    }
+
+    // Programs that can be reached with tail calls, indexed as in the program array.
+    fn tail_call_progs(&self) -> Vec<Option<&'a [u8]>> {
+        match self.prog_array {
+            Some(array) => (0..array.len()).map(|i| array.get(i)).collect(),
+            None        => vec![],
+        }
     }
 
     /// Execute the previously JIT-compiled program, with the given packet data and metadata
@@ -519,16 +1193,19 @@ impl<'a> EbpfVmMbuff<'a> {
     /// metadata buffer, at some appointed offsets. It is up to the user to ensure that these
     /// pointers are correctly stored in the buffer.
     ///
-    /// # Panics
+    /// # Errors
     ///
-    /// This function panics if an error occurs during the execution of the program.
+    /// Returns an `ExecError` if the program faults during its execution, such as on division by
+    /// zero attempts, or on out of bounds memory accesses if the program was compiled with
+    /// `set_jit_mem_check()` enabled.
     ///
     /// # Safety
     ///
     /// **WARNING:** JIT-compiled assembly code is not safe, in particular there is no runtime
-    /// check for memory access; so if the eBPF program attempts erroneous accesses, this may end
-    /// very bad (program may segfault). It may be wise to check that the program works with the
-    /// interpreter before running the JIT-compiled version of it.
+    /// check for memory access unless `set_jit_mem_check()` was enabled before compiling; so if
+    /// the eBPF program attempts erroneous accesses, this may end very bad (program may segfault).
+    /// It may be wise to check that the program works with the interpreter before running the
+    /// JIT-compiled version of it.
     ///
     /// For this reason the function should be called from within an `unsafe` bloc.
     ///
@@ -548,24 +1225,59 @@ impl<'a> EbpfVmMbuff<'a> {
     /// // pointers to packet data start and end in it.
     /// let mut mbuff = [0u8; 32];
     /// unsafe {
-    ///     let mut data     = mbuff.as_ptr().offset(8)  as *mut u64;
-    ///     let mut data_end = mbuff.as_ptr().offset(24) as *mut u64;
-    ///     *data     = mem.as_ptr() as u64;
-    ///     *data_end = mem.as_ptr() as u64 + mem.len() as u64;
+    ///     let data     = mbuff.as_mut_ptr().offset(8)  as *mut u64;
+    ///     let data_end = mbuff.as_mut_ptr().offset(24) as *mut u64;
+    ///     data.write_unaligned(mem.as_ptr() as u64);
+    ///     data_end.write_unaligned(mem.as_ptr() as u64 + mem.len() as u64);
     /// }
     ///
     /// // Instantiate a VM.
-    /// let mut vm = rbpf::EbpfVmMbuff::new(prog);
+    /// let mut vm = rbpf::EbpfVmMbuff::new(prog).unwrap();
     ///
-    /// vm.jit_compile();
+    /// vm.jit_compile().unwrap();
     ///
     /// // Provide both a reference to the packet data, and to the metadata buffer.
     /// unsafe {
-    ///     let res = vm.prog_exec_jit(mem, &mut mbuff);
+    ///     let res = vm.prog_exec_jit(mem, &mut mbuff).unwrap();
     ///     assert_eq!(res, 0x2211);
     /// }
     /// ```
-    pub unsafe fn prog_exec_jit(&self, mem: &mut [u8], mbuff: &'a mut [u8]) -> u64 {
+    pub unsafe fn prog_exec_jit(&self, mem: &mut [u8], mbuff: &'a mut [u8]) -> Result<u64, ExecError> {
+        self.prog_exec_jit_metered(mem, mbuff).map(|(res, _)| res)
+    }
+
+    /// Execute the previously JIT-compiled program, with the given packet data and metadata
+    /// buffer, as `prog_exec_jit()` does. Return the value of r0 along with the number of
+    /// instructions executed, which is only counted if the program was compiled with an
+    /// instruction budget (see `set_insn_budget()`), and is `None` otherwise.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `ExecError` as `prog_exec_jit()` does.
+    ///
+    /// # Safety
+    ///
+    /// See `prog_exec_jit()`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let prog = &[
+    ///     0xb7, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, // mov r0, 1
+    ///     0x95, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00  // exit
+    /// ];
+    ///
+    /// // Instantiate a VM.
+    /// let mut vm = rbpf::EbpfVmMbuff::new(prog).unwrap();
+    /// vm.set_insn_budget(Some(100));
+    /// vm.jit_compile().unwrap();
+    ///
+    /// unsafe {
+    ///     assert_eq!(vm.prog_exec_jit_metered(&mut [], &mut []).unwrap(), (1, Some(2)));
+    /// }
+    /// ```
+    pub unsafe fn prog_exec_jit_metered(&self, mem: &mut [u8], mbuff: &'a mut [u8])
+                                        -> Result<(u64, Option<u64>), ExecError> {
         // If packet data is empty, do not send the address of an empty slice; send a null pointer
         //  as first argument instead, as this is uBPF's behavior (empty packet should not happen
         //  in the kernel; anyway the verifier would prevent the use of uninitialized registers).
@@ -577,7 +1289,10 @@ impl<'a> EbpfVmMbuff<'a> {
         // The last two arguments are not used in this function. They would be used if there was a
         // need to indicate to the JIT at which offset in the mbuff mem_ptr and mem_ptr + mem.len()
         // should be stored; this is what happens with struct EbpfVmFixedMbuff.
-        (self.jit)(mbuff.as_ptr() as *mut u8, mbuff.len(), mem_ptr, mem.len(), 0, 0)
+        let mut fault = jit::JitFault::default();
+        let res = self.jit_function()(mbuff.as_ptr() as *mut u8, mbuff.len(), mem_ptr, mem.len(),
+                                      0, 0, &mut fault);
+        self.jit_result(fault, res)
     }
 }
 
@@ -639,13 +1354,13 @@ impl<'a> EbpfVmMbuff<'a> {
 /// ];
 ///
 /// // Instantiate a VM. Note that we provide the start and end offsets for mem pointers.
-/// let mut vm = rbpf::EbpfVmFixedMbuff::new(prog, 0x40, 0x50);
+/// let mut vm = rbpf::EbpfVmFixedMbuff::new(prog, 0x40, 0x50).unwrap();
 ///
 /// // Provide only a reference to the packet data. We do not manage the metadata buffer.
-/// let res = vm.prog_exec(mem1);
+/// let res = vm.prog_exec(mem1).unwrap();
 /// assert_eq!(res, 0xffffffffffffffdd);
 ///
-/// let res = vm.prog_exec(mem2);
+/// let res = vm.prog_exec(mem2).unwrap();
 /// assert_eq!(res, 0x27);
 /// ```
 pub struct EbpfVmFixedMbuff<'a> {
@@ -658,9 +1373,9 @@ impl<'a> EbpfVmFixedMbuff<'a> {
     /// Create a new virtual machine instance, and load an eBPF program into that instance.
     /// When attempting to load the program, it passes through a simple verifier.
     ///
-    /// # Panics
+    /// # Errors
     ///
-    /// The simple verifier may panic if it finds errors in the eBPF program at load time.
+    /// Returns an error if the simple verifier finds errors in the eBPF program at load time.
     ///
     /// # Examples
     ///
@@ -676,31 +1391,35 @@ impl<'a> EbpfVmFixedMbuff<'a> {
     /// ];
     ///
     /// // Instantiate a VM. Note that we provide the start and end offsets for mem pointers.
-    /// let mut vm = rbpf::EbpfVmFixedMbuff::new(prog, 0x40, 0x50);
+    /// let mut vm = rbpf::EbpfVmFixedMbuff::new(prog, 0x40, 0x50).unwrap();
     /// ```
-    pub fn new(prog: &'a [u8], data_offset: usize, data_end_offset: usize) -> EbpfVmFixedMbuff<'a> {
-        let parent = EbpfVmMbuff::new(prog);
+    pub fn new(prog: &'a [u8], data_offset: usize, data_end_offset: usize)
+        -> Result<EbpfVmFixedMbuff<'a>, EbpfError> {
+        let parent = EbpfVmMbuff::new(prog)?;
         let get_buff_len = | x: usize, y: usize | if x >= y { x + 8 } else { y + 8 };
         let buffer = vec![0u8; get_buff_len(data_offset, data_end_offset)];
         let mbuff = MetaBuff {
-            data_offset:     data_offset,
-            data_end_offset: data_end_offset,
-            buffer:          buffer,
+            data_offset,
+            data_end_offset,
+            buffer,
         };
-        EbpfVmFixedMbuff {
-            parent: parent,
-            mbuff:  mbuff,
-        }
+        Ok(EbpfVmFixedMbuff {
+            parent,
+            mbuff,
+        })
     }
 
     /// Load a new eBPF program into the virtual machine instance.
     ///
+    /// If the previous program was JIT-compiled, the compiled program is discarded: call
+    /// `jit_compile()` again before running the new one with `prog_exec_jit()`.
+    ///
     /// At the same time, load new offsets for storing pointers to start and end of packet data in
     /// the internal metadata buffer.
     ///
-    /// # Panics
+    /// # Errors
     ///
-    /// The simple verifier may panic if it finds errors in the eBPF program at load time.
+    /// Returns an error if the simple verifier finds errors in the eBPF program at load time.
     ///
     /// # Examples
     ///
@@ -723,19 +1442,57 @@ impl<'a> EbpfVmFixedMbuff<'a> {
     ///     0xaa, 0xbb, 0x11, 0x22, 0xcc, 0x27,
     /// ];
     ///
-    /// let mut vm = rbpf::EbpfVmFixedMbuff::new(prog1, 0, 0);
-    /// vm.set_prog(prog2, 0x40, 0x50);
+    /// let mut vm = rbpf::EbpfVmFixedMbuff::new(prog1, 0, 0).unwrap();
+    /// vm.set_prog(prog2, 0x40, 0x50).unwrap();
     ///
-    /// let res = vm.prog_exec(mem);
+    /// let res = vm.prog_exec(mem).unwrap();
     /// assert_eq!(res, 0x27);
     /// ```
-    pub fn set_prog(&mut self, prog: &'a [u8], data_offset: usize, data_end_offset: usize) {
+    pub fn set_prog(&mut self, prog: &'a [u8], data_offset: usize, data_end_offset: usize)
+        -> Result<(), EbpfError> {
+        // Verify the program first, so that the VM is left untouched if it is rejected.
+        self.parent.set_prog(prog)?;
         let get_buff_len = | x: usize, y: usize | if x >= y { x + 8 } else { y + 8 };
         let buffer = vec![0u8; get_buff_len(data_offset, data_end_offset)];
         self.mbuff.buffer = buffer;
         self.mbuff.data_offset = data_offset;
         self.mbuff.data_end_offset = data_end_offset;
-        self.parent.set_prog(prog)
+        Ok(())
+    }
+
+    /// Set a new verifier function, used instead of `verifier::check()` to check the programs
+    /// loaded into the VM with `set_prog()`, and the programs of the array attached with
+    /// `set_prog_array()`. The program currently loaded, and the programs of the array currently
+    /// attached, are checked right away.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the verifier rejects the loaded program, or one of the programs of the
+    /// array. The previous verifier is kept in that case.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rbpf::verifier;
+    ///
+    /// let prog1 = &[
+    ///     0xb7, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // mov r0, 0
+    ///     0x95, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00  // exit
+    /// ];
+    /// let prog2 = &[
+    ///     0x05, 0x00, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, // ja +1
+    ///     0xb7, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // mov r0, 0
+    ///     0x95, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00  // exit
+    /// ];
+    ///
+    /// let mut vm = rbpf::EbpfVmFixedMbuff::new(prog1, 0x40, 0x50).unwrap();
+    /// vm.set_verifier(verifier::check_cfg).unwrap();
+    ///
+    /// // The second instruction cannot be reached.
+    /// assert!(vm.set_prog(prog2, 0x40, 0x50).is_err());
+    /// ```
+    pub fn set_verifier(&mut self, verifier: Verifier) -> Result<(), EbpfError> {
+        self.parent.set_verifier(verifier)
     }
 
     /// Register a built-in or user-defined helper function in order to use it later from within
@@ -772,18 +1529,157 @@ impl<'a> EbpfVmFixedMbuff<'a> {
     /// ];
     ///
     /// // Instantiate a VM.
-    /// let mut vm = rbpf::EbpfVmFixedMbuff::new(prog, 0x40, 0x50);
+    /// let mut vm = rbpf::EbpfVmFixedMbuff::new(prog, 0x40, 0x50).unwrap();
     ///
     /// // Register a helper. This helper will store the result of the square root of r1 into r0.
     /// vm.register_helper(1, helpers::sqrti);
     ///
-    /// let res = vm.prog_exec(mem);
+    /// let res = vm.prog_exec(mem).unwrap();
     /// assert_eq!(res, 3);
     /// ```
     pub fn register_helper(&mut self, key: u32, function: fn (u64, u64, u64, u64, u64) -> u64) {
         self.parent.register_helper(key, function);
     }
 
+    /// Attach a program array to the virtual machine, to be used as the target of tail calls (see
+    /// `ProgramArray`).
+    ///
+    /// If using JIT-compiled eBPF programs, the programs of the array are compiled along with the
+    /// loaded program: be sure to fill the array and to attach it before compiling the program.
+    /// Attaching an array discards the program compiled earlier, if any.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the verifier of the VM rejects one of the programs of the array.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let prog = &[
+    ///     0x8d, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // tail call to program r3 (0)
+    ///     0x95, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00  // exit
+    /// ];
+    ///
+    /// let mut prog_array = rbpf::ProgramArray::new(1);
+    /// prog_array.set(0, prog).unwrap();
+    ///
+    /// // Instantiate a VM.
+    /// let mut vm = rbpf::EbpfVmFixedMbuff::new(prog, 0x40, 0x50).unwrap();
+    /// vm.set_prog_array(&prog_array).unwrap();
+    /// ```
+    pub fn set_prog_array(&mut self, prog_array: &'a ProgramArray<'a>) -> Result<(), EbpfError> {
+        self.parent.set_prog_array(prog_array)
+    }
+
+    /// Set the maximum number of tail calls that a program can perform in a row, 33 by default
+    /// (`ebpf::MAX_TAIL_CALLS`). Once the limit is reached, tail calls fail and the program goes
+    /// on with the instruction following the tail call.
+    ///
+    /// If using JIT-compiled eBPF programs, be sure to set the limit before compiling the program.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let prog = &[
+    ///     0x95, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00  // exit
+    /// ];
+    ///
+    /// // Instantiate a VM.
+    /// let mut vm = rbpf::EbpfVmFixedMbuff::new(prog, 0x40, 0x50).unwrap();
+    /// vm.set_max_tail_calls(8);
+    /// ```
+    pub fn set_max_tail_calls(&mut self, max: usize) {
+        self.parent.set_max_tail_calls(max);
+    }
+
+    /// Make the JIT-compiler emit runtime checks for memory accesses, disabled by default. As with
+    /// the interpreter, each load or store must then fall within the metadata buffer, the packet
+    /// data, the stack or the values of a registered map. An out of bounds access aborts the
+    /// program and `prog_exec_jit()` returns an error indicating the faulting instruction,
+    /// instead of possibly crashing the process.
+    ///
+    /// The checks slow the program down. Be sure to enable them before compiling the program.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let prog = &[
+    ///     0x95, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00  // exit
+    /// ];
+    ///
+    /// // Instantiate a VM.
+    /// let mut vm = rbpf::EbpfVmFixedMbuff::new(prog, 0x40, 0x50).unwrap();
+    /// vm.set_jit_mem_check(true);
+    /// ```
+    pub fn set_jit_mem_check(&mut self, check: bool) {
+        self.parent.set_jit_mem_check(check);
+    }
+
+    /// Set the maximum number of instructions that a run of the program can execute, or `None`
+    /// for no limit (the default). Instructions executed by local functions and by programs
+    /// reached with tail calls count toward the budget. Once it is exhausted, the program is
+    /// aborted with an `InstructionBudgetExceeded` error, which notably prevents programs with
+    /// loops from running forever.
+    ///
+    /// If using JIT-compiled eBPF programs, be sure to set the budget before compiling the
+    /// program: setting it discards the program compiled earlier, if any. Metering instructions
+    /// slows JIT-compiled programs down.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rbpf::error::ExecErrorKind;
+    ///
+    /// let prog = &[
+    ///     0xb7, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // mov r0, 0
+    ///     0x05, 0x00, 0xfe, 0xff, 0x00, 0x00, 0x00, 0x00, // ja -2
+    ///     0x95, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00  // exit
+    /// ];
+    ///
+    /// // Instantiate a VM.
+    /// let mut vm = rbpf::EbpfVmFixedMbuff::new(prog, 0x40, 0x50).unwrap();
+    /// vm.set_insn_budget(Some(1000));
+    ///
+    /// let err = vm.prog_exec(&mut []).unwrap_err();
+    /// assert_eq!(err.kind, ExecErrorKind::InstructionBudgetExceeded);
+    /// ```
+    pub fn set_insn_budget(&mut self, budget: Option<u64>) {
+        self.parent.set_insn_budget(budget);
+    }
+
+    /// Register an eBPF map with file descriptor number `fd`, so that the program can obtain a
+    /// handle on it with a `lddw` instruction whose `src` field is `ebpf::BPF_PSEUDO_MAP_FD` and
+    /// whose immediate value is `fd` (see module `maps`).
+    ///
+    /// Once a map is registered, calls to helpers 1, 2 and 3 go to the `bpf_map_lookup_elem()`,
+    /// `bpf_map_update_elem()` and `bpf_map_delete_elem()` map helpers, as in the kernel, rather
+    /// than to the helpers registered with these keys, if any. The program is aborted if it passes
+    /// them something else than a handle on a registered map, or a key or value out of bounds.
+    ///
+    /// If using JIT-compiled eBPF programs, be sure to register all maps before compiling the
+    /// program: registering a map discards the program compiled earlier, if any.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rbpf::maps::{Map, MapType};
+    ///
+    /// let prog = &[
+    ///     0x18, 0x11, 0x00, 0x00, 0x03, 0x00, 0x00, 0x00, // lddw r1, map_fd(3)
+    ///     0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+    ///     0x95, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00  // exit
+    /// ];
+    ///
+    /// let map = Map::new(MapType::Hash, 4, 8, 16).unwrap();
+    ///
+    /// // Instantiate a VM.
+    /// let mut vm = rbpf::EbpfVmFixedMbuff::new(prog, 0x40, 0x50).unwrap();
+    /// vm.register_map(3, &map);
+    /// ```
+    pub fn register_map(&mut self, fd: u32, map: &'a Map) {
+        self.parent.register_map(fd, map);
+    }
+
     /// Execute the program loaded, with the given packet data.
     ///
     /// If the program is made to be compatible with Linux kernel, it is expected to load the
@@ -791,11 +1687,11 @@ impl<'a> EbpfVmFixedMbuff<'a> {
     /// metadata buffer, which in the case of this VM is handled internally. The offsets at which
     /// the addresses should be placed should have be set at the creation of the VM.
     ///
-    /// # Panics
+    /// # Errors
     ///
-    /// This function is currently expected to panic if it encounters any error during the program
-    /// execution, such as out of bounds accesses or division by zero attempts. This may be changed
-    /// in the future (we could raise errors instead).
+    /// Returns an `ExecError` if the interpreter encounters a fault during the program execution,
+    /// such as out of bounds accesses or division by zero attempts, or if the internal metadata
+    /// buffer is too small to hold the pointers to packet data at the offsets given by the user.
     ///
     /// # Examples
     ///
@@ -814,37 +1710,115 @@ impl<'a> EbpfVmFixedMbuff<'a> {
     /// ];
     ///
     /// // Instantiate a VM. Note that we provide the start and end offsets for mem pointers.
-    /// let mut vm = rbpf::EbpfVmFixedMbuff::new(prog, 0x40, 0x50);
+    /// let mut vm = rbpf::EbpfVmFixedMbuff::new(prog, 0x40, 0x50).unwrap();
     ///
     /// // Provide only a reference to the packet data. We do not manage the metadata buffer.
-    /// let res = vm.prog_exec(mem);
+    /// let res = vm.prog_exec(mem).unwrap();
     /// assert_eq!(res, 0xdd);
     /// ```
-    pub fn prog_exec(&mut self, mem: &'a mut [u8]) -> u64 {
-        let l = self.mbuff.buffer.len();
-        // Can this ever happen? Probably not, should be ensured at mbuff creation.
-        if self.mbuff.data_offset + 8 > l || self.mbuff.data_end_offset + 8 > l {
-            panic!("Error: buffer too small ({:?}), cannot use data_offset {:?} and data_end_offset {:?}",
-            l, self.mbuff.data_offset, self.mbuff.data_end_offset);
-        }
-        unsafe {
-            let mut data     = self.mbuff.buffer.as_ptr().offset(self.mbuff.data_offset as isize)     as *mut u64;
-            let mut data_end = self.mbuff.buffer.as_ptr().offset(self.mbuff.data_end_offset as isize) as *mut u64;
-            *data     = mem.as_ptr() as u64;
-            *data_end = mem.as_ptr() as u64 + mem.len() as u64;
-        }
+    pub fn prog_exec(&mut self, mem: &'a mut [u8]) -> Result<u64, ExecError> {
+        self.mbuff.set_data(mem)?;
         self.parent.prog_exec(mem, &self.mbuff.buffer)
     }
 
+    /// Execute the program loaded, with the given packet data, as `prog_exec()` does. Return the
+    /// value of r0 along with the number of instructions executed, including those of local
+    /// functions and of programs reached with tail calls.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `ExecError` as `prog_exec()` does.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let prog = &[
+    ///     0xb7, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, // mov r0, 1
+    ///     0x95, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00  // exit
+    /// ];
+    ///
+    /// // Instantiate a VM.
+    /// let mut vm = rbpf::EbpfVmFixedMbuff::new(prog, 0x40, 0x50).unwrap();
+    /// assert_eq!(vm.prog_exec_metered(&mut []).unwrap(), (1, 2));
+    /// ```
+    pub fn prog_exec_metered(&mut self, mem: &'a mut [u8]) -> Result<(u64, u64), ExecError> {
+        self.mbuff.set_data(mem)?;
+        self.parent.prog_exec_metered(mem, &self.mbuff.buffer)
+    }
+
+    /// Execute the program loaded, with the given packet data, as `prog_exec()` does, and call
+    /// `tracer` before executing each instruction (see module `trace`).
+    ///
+    /// # Errors
+    ///
+    /// Returns an `ExecError` as `prog_exec()` does.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let prog = &[
+    ///     0xb7, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, // mov r0, 1
+    ///     0x95, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00  // exit
+    /// ];
+    ///
+    /// // Instantiate a VM.
+    /// let mut vm = rbpf::EbpfVmFixedMbuff::new(prog, 0x40, 0x50).unwrap();
+    ///
+    /// let mut executed = vec![];
+    /// vm.prog_exec_traced(&mut [], &mut |entry| executed.push(entry.insn_ptr)).unwrap();
+    /// assert_eq!(executed, vec![0, 1]);
+    /// ```
+    pub fn prog_exec_traced(&mut self, mem: &'a mut [u8], tracer: &mut dyn FnMut(&TraceEntry))
+                            -> Result<u64, ExecError> {
+        self.mbuff.set_data(mem)?;
+        self.parent.prog_exec_traced(mem, &self.mbuff.buffer, tracer)
+    }
+
+    /// Start a debugging session for the loaded program, with the interpreter, on packet data
+    /// `mem`. The pointers to the start and end of packet data are stored into the metadata
+    /// buffer as for `prog_exec()`. See module `debugger`.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `BufferTooSmall` error if the pointers cannot be stored into the metadata buffer.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rbpf::debugger::Status;
+    ///
+    /// let prog = &[
+    ///     0x79, 0x11, 0x40, 0x00, 0x00, 0x00, 0x00, 0x00, // Load mem from mbuff into r1.
+    ///     0x69, 0x10, 0x02, 0x00, 0x00, 0x00, 0x00, 0x00, // ldhx r1[2], r0
+    ///     0x95, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00  // exit
+    /// ];
+    /// let mem = &mut [
+    ///     0xaa, 0xbb, 0x11, 0x22, 0xcc, 0xdd
+    /// ];
+    ///
+    /// let mut vm = rbpf::EbpfVmFixedMbuff::new(prog, 0x40, 0x50).unwrap();
+    /// let mut session = vm.debug(mem).unwrap();
+    ///
+    /// assert_eq!(session.step(), Status::Stopped);
+    /// assert_eq!(session.reg(1), session.mem().as_ptr() as u64);
+    /// assert_eq!(session.cont(), Status::Exited(0x2211));
+    /// ```
+    pub fn debug<'b>(&'b mut self, mem: &'b mut [u8]) -> Result<Session<'a, 'b>, ExecError> {
+        self.mbuff.set_data(mem)?;
+        Ok(self.parent.debug(mem, &mut self.mbuff.buffer))
+    }
+
     /// JIT-compile the loaded program. No argument required for this.
     ///
     /// If using helper functions, be sure to register them into the VM before calling this
     /// function.
     ///
-    /// # Panics
+    /// # Errors
     ///
-    /// This function panics if an error occurs during JIT-compiling, such as the occurrence of an
-    /// unknown eBPF operation code.
+    /// Returns an error if the program loads a map or calls a helper function that has not been
+    /// registered into the VM, if it contains an unknown eBPF operation code, if the generated code
+    /// is too large, if executable memory cannot be allocated for it, or if there is no JIT
+    /// compiler for the target architecture (only x86_64 and AArch64 are supported).
     ///
     /// # Examples
     ///
@@ -860,12 +1834,18 @@ impl<'a> EbpfVmFixedMbuff<'a> {
     /// ];
     ///
     /// // Instantiate a VM. Note that we provide the start and end offsets for mem pointers.
-    /// let mut vm = rbpf::EbpfVmFixedMbuff::new(prog, 0x40, 0x50);
+    /// let mut vm = rbpf::EbpfVmFixedMbuff::new(prog, 0x40, 0x50).unwrap();
     ///
-    /// vm.jit_compile();
+    /// vm.jit_compile().unwrap();
     /// ```
-    pub fn jit_compile(&mut self) {
-        self.parent.jit = jit::compile(self.parent.prog, &self.parent.helpers, true, true);
+    pub fn jit_compile(&mut self) -> Result<(), JitError> {
+        self.parent.jit = Some(jit::compile(self.parent.prog, &self.parent.helpers,
+                                            &self.parent.maps, true, true,
+                                            &self.parent.tail_call_progs(),
+                                            self.parent.max_tail_calls,
+                                            self.parent.jit_mem_check,
+                                            self.parent.insn_budget)?);
+        Ok(())
     }
 
     /// Execute the previously JIT-compiled program, with the given packet data, in a manner very
@@ -876,16 +1856,19 @@ impl<'a> EbpfVmFixedMbuff<'a> {
     /// metadata buffer, which in the case of this VM is handled internally. The offsets at which
     /// the addresses should be placed should have be set at the creation of the VM.
     ///
-    /// # Panics
+    /// # Errors
     ///
-    /// This function panics if an error occurs during the execution of the program.
+    /// Returns an `ExecError` if the program faults during its execution, such as on division by
+    /// zero attempts, or on out of bounds memory accesses if the program was compiled with
+    /// `set_jit_mem_check()` enabled.
     ///
     /// # Safety
     ///
     /// **WARNING:** JIT-compiled assembly code is not safe, in particular there is no runtime
-    /// check for memory access; so if the eBPF program attempts erroneous accesses, this may end
-    /// very bad (program may segfault). It may be wise to check that the program works with the
-    /// interpreter before running the JIT-compiled version of it.
+    /// check for memory access unless `set_jit_mem_check()` was enabled before compiling; so if
+    /// the eBPF program attempts erroneous accesses, this may end very bad (program may segfault).
+    /// It may be wise to check that the program works with the interpreter before running the
+    /// JIT-compiled version of it.
     ///
     /// For this reason the function should be called from within an `unsafe` bloc.
     ///
@@ -906,19 +1889,57 @@ impl<'a> EbpfVmFixedMbuff<'a> {
     /// ];
     ///
     /// // Instantiate a VM. Note that we provide the start and end offsets for mem pointers.
-    /// let mut vm = rbpf::EbpfVmFixedMbuff::new(prog, 0x40, 0x50);
+    /// let mut vm = rbpf::EbpfVmFixedMbuff::new(prog, 0x40, 0x50).unwrap();
     ///
-    /// vm.jit_compile();
+    /// vm.jit_compile().unwrap();
     ///
     /// // Provide only a reference to the packet data. We do not manage the metadata buffer.
     /// unsafe {
-    ///     let res = vm.prog_exec_jit(mem);
+    ///     let res = vm.prog_exec_jit(mem).unwrap();
     ///     assert_eq!(res, 0xdd);
     /// }
     /// ```
     // This struct redefines the `prog_exec_jit()` function, in order to pass the offsets
     // associated with the fixed mbuff.
-    pub unsafe fn prog_exec_jit(&mut self, mem: &'a mut [u8]) -> u64 {
+    pub unsafe fn prog_exec_jit(&mut self, mem: &'a mut [u8]) -> Result<u64, ExecError> {
+        self.prog_exec_jit_metered(mem).map(|(res, _)| res)
+    }
+
+    /// Execute the previously JIT-compiled program, with the given packet data, as
+    /// `prog_exec_jit()` does. Return the value of r0 along with the number of instructions
+    /// executed, which is only counted if the program was compiled with an instruction budget
+    /// (see `set_insn_budget()`), and is `None` otherwise.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `ExecError` as `prog_exec_jit()` does.
+    ///
+    /// # Safety
+    ///
+    /// See `prog_exec_jit()`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let prog = &[
+    ///     0xb7, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, // mov r0, 1
+    ///     0x95, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00  // exit
+    /// ];
+    /// let mem = &mut [
+    ///     0xaa, 0xbb, 0x11, 0x22, 0xcc, 0xdd
+    /// ];
+    ///
+    /// // Instantiate a VM.
+    /// let mut vm = rbpf::EbpfVmFixedMbuff::new(prog, 0x40, 0x50).unwrap();
+    /// vm.set_insn_budget(Some(100));
+    /// vm.jit_compile().unwrap();
+    ///
+    /// unsafe {
+    ///     assert_eq!(vm.prog_exec_jit_metered(mem).unwrap(), (1, Some(2)));
+    /// }
+    /// ```
+    pub unsafe fn prog_exec_jit_metered(&mut self, mem: &'a mut [u8])
+                                        -> Result<(u64, Option<u64>), ExecError> {
         // If packet data is empty, do not send the address of an empty slice; send a null pointer
         //  as first argument instead, as this is uBPF's behavior (empty packet should not happen
         //  in the kernel; anyway the verifier would prevent the use of uninitialized registers).
@@ -927,8 +1948,12 @@ impl<'a> EbpfVmFixedMbuff<'a> {
             0 => std::ptr::null_mut(),
             _ => mem.as_ptr() as *mut u8
         };
-        (self.parent.jit)(self.mbuff.buffer.as_ptr() as *mut u8, self.mbuff.buffer.len(),
-                          mem_ptr, mem.len(), self.mbuff.data_offset, self.mbuff.data_end_offset)
+        let mut fault = jit::JitFault::default();
+        let res = self.parent.jit_function()(self.mbuff.buffer.as_ptr() as *mut u8,
+                                             self.mbuff.buffer.len(), mem_ptr, mem.len(),
+                                             self.mbuff.data_offset, self.mbuff.data_end_offset,
+                                             &mut fault);
+        self.parent.jit_result(fault, res)
     }
 }
 
@@ -949,10 +1974,10 @@ impl<'a> EbpfVmFixedMbuff<'a> {
 /// ];
 ///
 /// // Instantiate a VM.
-/// let vm = rbpf::EbpfVmRaw::new(prog);
+/// let vm = rbpf::EbpfVmRaw::new(prog).unwrap();
 ///
 /// // Provide only a reference to the packet data.
-/// let res = vm.prog_exec(mem);
+/// let res = vm.prog_exec(mem).unwrap();
 /// assert_eq!(res, 0x22cc);
 /// ```
 pub struct EbpfVmRaw<'a> {
@@ -964,9 +1989,9 @@ impl<'a> EbpfVmRaw<'a> {
     /// Create a new virtual machine instance, and load an eBPF program into that instance.
     /// When attempting to load the program, it passes through a simple verifier.
     ///
-    /// # Panics
+    /// # Errors
     ///
-    /// The simple verifier may panic if it finds errors in the eBPF program at load time.
+    /// Returns an error if the simple verifier finds errors in the eBPF program at load time.
     ///
     /// # Examples
     ///
@@ -979,20 +2004,23 @@ impl<'a> EbpfVmRaw<'a> {
     /// ];
     ///
     /// // Instantiate a VM.
-    /// let vm = rbpf::EbpfVmRaw::new(prog);
+    /// let vm = rbpf::EbpfVmRaw::new(prog).unwrap();
     /// ```
-    pub fn new(prog: &'a [u8]) -> EbpfVmRaw<'a> {
-        let parent = EbpfVmMbuff::new(prog);
-        EbpfVmRaw {
-            parent: parent,
-        }
+    pub fn new(prog: &'a [u8]) -> Result<EbpfVmRaw<'a>, EbpfError> {
+        let parent = EbpfVmMbuff::new(prog)?;
+        Ok(EbpfVmRaw {
+            parent,
+        })
     }
 
     /// Load a new eBPF program into the virtual machine instance.
     ///
-    /// # Panics
+    /// If the previous program was JIT-compiled, the compiled program is discarded: call
+    /// `jit_compile()` again before running the new one with `prog_exec_jit()`.
+    ///
+    /// # Errors
     ///
-    /// The simple verifier may panic if it finds errors in the eBPF program at load time.
+    /// Returns an error if the simple verifier finds errors in the eBPF program at load time.
     ///
     /// # Examples
     ///
@@ -1012,16 +2040,51 @@ impl<'a> EbpfVmRaw<'a> {
     ///     0xaa, 0xbb, 0x11, 0x22, 0xcc, 0x27,
     /// ];
     ///
-    /// let mut vm = rbpf::EbpfVmRaw::new(prog1);
-    /// vm.set_prog(prog2);
+    /// let mut vm = rbpf::EbpfVmRaw::new(prog1).unwrap();
+    /// vm.set_prog(prog2).unwrap();
     ///
-    /// let res = vm.prog_exec(mem);
+    /// let res = vm.prog_exec(mem).unwrap();
     /// assert_eq!(res, 0x22cc);
     /// ```
-    pub fn set_prog(&mut self, prog: &'a [u8]) {
+    pub fn set_prog(&mut self, prog: &'a [u8]) -> Result<(), EbpfError> {
         self.parent.set_prog(prog)
     }
 
+    /// Set a new verifier function, used instead of `verifier::check()` to check the programs
+    /// loaded into the VM with `set_prog()`, and the programs of the array attached with
+    /// `set_prog_array()`. The program currently loaded, and the programs of the array currently
+    /// attached, are checked right away.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the verifier rejects the loaded program, or one of the programs of the
+    /// array. The previous verifier is kept in that case.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rbpf::verifier;
+    ///
+    /// let prog1 = &[
+    ///     0xb7, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // mov r0, 0
+    ///     0x95, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00  // exit
+    /// ];
+    /// let prog2 = &[
+    ///     0x05, 0x00, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, // ja +1
+    ///     0xb7, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // mov r0, 0
+    ///     0x95, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00  // exit
+    /// ];
+    ///
+    /// let mut vm = rbpf::EbpfVmRaw::new(prog1).unwrap();
+    /// vm.set_verifier(verifier::check_cfg).unwrap();
+    ///
+    /// // The second instruction cannot be reached.
+    /// assert!(vm.set_prog(prog2).is_err());
+    /// ```
+    pub fn set_verifier(&mut self, verifier: Verifier) -> Result<(), EbpfError> {
+        self.parent.set_verifier(verifier)
+    }
+
     /// Register a built-in or user-defined helper function in order to use it later from within
     /// the eBPF program. The helper is registered into a hashmap, so the `key` can be any `u32`.
     ///
@@ -1049,31 +2112,170 @@ impl<'a> EbpfVmRaw<'a> {
     /// ];
     ///
     /// // Instantiate a VM.
-    /// let mut vm = rbpf::EbpfVmRaw::new(prog);
+    /// let mut vm = rbpf::EbpfVmRaw::new(prog).unwrap();
     ///
     /// // Register a helper. This helper will store the result of the square root of r1 into r0.
     /// vm.register_helper(1, helpers::sqrti);
     ///
-    /// let res = vm.prog_exec(mem);
+    /// let res = vm.prog_exec(mem).unwrap();
     /// assert_eq!(res, 0x10000000);
     /// ```
     pub fn register_helper(&mut self, key: u32, function: fn (u64, u64, u64, u64, u64) -> u64) {
         self.parent.register_helper(key, function);
     }
 
-    /// Execute the program loaded, with the given packet data.
+    /// Attach a program array to the virtual machine, to be used as the target of tail calls (see
+    /// `ProgramArray`).
     ///
-    /// # Panics
+    /// If using JIT-compiled eBPF programs, the programs of the array are compiled along with the
+    /// loaded program: be sure to fill the array and to attach it before compiling the program.
+    /// Attaching an array discards the program compiled earlier, if any.
     ///
-    /// This function is currently expected to panic if it encounters any error during the program
-    /// execution, such as out of bounds accesses or division by zero attempts. This may be changed
-    /// in the future (we could raise errors instead).
+    /// # Errors
+    ///
+    /// Returns an error if the verifier of the VM rejects one of the programs of the array.
     ///
     /// # Examples
     ///
     /// ```
     /// let prog = &[
-    ///     0x71, 0x11, 0x04, 0x00, 0x00, 0x00, 0x00, 0x00, // ldxb r1[0x04], r1
+    ///     0x8d, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // tail call to program r3 (0)
+    ///     0x95, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00  // exit
+    /// ];
+    ///
+    /// let mut prog_array = rbpf::ProgramArray::new(1);
+    /// prog_array.set(0, prog).unwrap();
+    ///
+    /// // Instantiate a VM.
+    /// let mut vm = rbpf::EbpfVmRaw::new(prog).unwrap();
+    /// vm.set_prog_array(&prog_array).unwrap();
+    /// ```
+    pub fn set_prog_array(&mut self, prog_array: &'a ProgramArray<'a>) -> Result<(), EbpfError> {
+        self.parent.set_prog_array(prog_array)
+    }
+
+    /// Set the maximum number of tail calls that a program can perform in a row, 33 by default
+    /// (`ebpf::MAX_TAIL_CALLS`). Once the limit is reached, tail calls fail and the program goes
+    /// on with the instruction following the tail call.
+    ///
+    /// If using JIT-compiled eBPF programs, be sure to set the limit before compiling the program.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let prog = &[
+    ///     0x95, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00  // exit
+    /// ];
+    ///
+    /// // Instantiate a VM.
+    /// let mut vm = rbpf::EbpfVmRaw::new(prog).unwrap();
+    /// vm.set_max_tail_calls(8);
+    /// ```
+    pub fn set_max_tail_calls(&mut self, max: usize) {
+        self.parent.set_max_tail_calls(max);
+    }
+
+    /// Make the JIT-compiler emit runtime checks for memory accesses, disabled by default. As with
+    /// the interpreter, each load or store must then fall within the metadata buffer, the packet
+    /// data, the stack or the values of a registered map. An out of bounds access aborts the
+    /// program and `prog_exec_jit()` returns an error indicating the faulting instruction,
+    /// instead of possibly crashing the process.
+    ///
+    /// The checks slow the program down. Be sure to enable them before compiling the program.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let prog = &[
+    ///     0x95, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00  // exit
+    /// ];
+    ///
+    /// // Instantiate a VM.
+    /// let mut vm = rbpf::EbpfVmRaw::new(prog).unwrap();
+    /// vm.set_jit_mem_check(true);
+    /// ```
+    pub fn set_jit_mem_check(&mut self, check: bool) {
+        self.parent.set_jit_mem_check(check);
+    }
+
+    /// Set the maximum number of instructions that a run of the program can execute, or `None`
+    /// for no limit (the default). Instructions executed by local functions and by programs
+    /// reached with tail calls count toward the budget. Once it is exhausted, the program is
+    /// aborted with an `InstructionBudgetExceeded` error, which notably prevents programs with
+    /// loops from running forever.
+    ///
+    /// If using JIT-compiled eBPF programs, be sure to set the budget before compiling the
+    /// program: setting it discards the program compiled earlier, if any. Metering instructions
+    /// slows JIT-compiled programs down.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rbpf::error::ExecErrorKind;
+    ///
+    /// let prog = &[
+    ///     0xb7, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // mov r0, 0
+    ///     0x05, 0x00, 0xfe, 0xff, 0x00, 0x00, 0x00, 0x00, // ja -2
+    ///     0x95, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00  // exit
+    /// ];
+    ///
+    /// // Instantiate a VM.
+    /// let mut vm = rbpf::EbpfVmRaw::new(prog).unwrap();
+    /// vm.set_insn_budget(Some(1000));
+    ///
+    /// let err = vm.prog_exec(&mut []).unwrap_err();
+    /// assert_eq!(err.kind, ExecErrorKind::InstructionBudgetExceeded);
+    /// ```
+    pub fn set_insn_budget(&mut self, budget: Option<u64>) {
+        self.parent.set_insn_budget(budget);
+    }
+
+    /// Register an eBPF map with file descriptor number `fd`, so that the program can obtain a
+    /// handle on it with a `lddw` instruction whose `src` field is `ebpf::BPF_PSEUDO_MAP_FD` and
+    /// whose immediate value is `fd` (see module `maps`).
+    ///
+    /// Once a map is registered, calls to helpers 1, 2 and 3 go to the `bpf_map_lookup_elem()`,
+    /// `bpf_map_update_elem()` and `bpf_map_delete_elem()` map helpers, as in the kernel, rather
+    /// than to the helpers registered with these keys, if any. The program is aborted if it passes
+    /// them something else than a handle on a registered map, or a key or value out of bounds.
+    ///
+    /// If using JIT-compiled eBPF programs, be sure to register all maps before compiling the
+    /// program: registering a map discards the program compiled earlier, if any.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rbpf::maps::{Map, MapType};
+    ///
+    /// let prog = &[
+    ///     0x18, 0x11, 0x00, 0x00, 0x03, 0x00, 0x00, 0x00, // lddw r1, map_fd(3)
+    ///     0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+    ///     0x95, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00  // exit
+    /// ];
+    ///
+    /// let map = Map::new(MapType::Hash, 4, 8, 16).unwrap();
+    ///
+    /// // Instantiate a VM.
+    /// let mut vm = rbpf::EbpfVmRaw::new(prog).unwrap();
+    /// vm.register_map(3, &map);
+    /// ```
+    pub fn register_map(&mut self, fd: u32, map: &'a Map) {
+        self.parent.register_map(fd, map);
+    }
+
+    /// Execute the program loaded, with the given packet data.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `ExecError` if the interpreter encounters a fault during the program execution,
+    /// such as out of bounds accesses or division by zero attempts. The error indicates the kind of
+    /// fault and the index of the faulting instruction.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let prog = &[
+    ///     0x71, 0x11, 0x04, 0x00, 0x00, 0x00, 0x00, 0x00, // ldxb r1[0x04], r1
     ///     0x07, 0x01, 0x00, 0x00, 0x00, 0x22, 0x00, 0x00, // add r1, 0x22
     ///     0xbf, 0x10, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // mov r0, r1
     ///     0x95, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00  // exit
@@ -1083,24 +2285,104 @@ impl<'a> EbpfVmRaw<'a> {
     ///     0xaa, 0xbb, 0x11, 0x22, 0xcc, 0x27
     /// ];
     ///
-    /// let mut vm = rbpf::EbpfVmRaw::new(prog);
+    /// let mut vm = rbpf::EbpfVmRaw::new(prog).unwrap();
     ///
-    /// let res = vm.prog_exec(mem);
+    /// let res = vm.prog_exec(mem).unwrap();
     /// assert_eq!(res, 0x22cc);
     /// ```
-    pub fn prog_exec(&self, mem: &'a mut [u8]) -> u64 {
+    pub fn prog_exec(&self, mem: &'a mut [u8]) -> Result<u64, ExecError> {
         self.parent.prog_exec(mem, &[])
     }
 
+    /// Execute the program loaded, with the given packet data, as `prog_exec()` does. Return the
+    /// value of r0 along with the number of instructions executed, including those of local
+    /// functions and of programs reached with tail calls.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `ExecError` as `prog_exec()` does.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let prog = &[
+    ///     0xb7, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, // mov r0, 1
+    ///     0x95, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00  // exit
+    /// ];
+    ///
+    /// let vm = rbpf::EbpfVmRaw::new(prog).unwrap();
+    /// assert_eq!(vm.prog_exec_metered(&mut []).unwrap(), (1, 2));
+    /// ```
+    pub fn prog_exec_metered(&self, mem: &'a mut [u8]) -> Result<(u64, u64), ExecError> {
+        self.parent.prog_exec_metered(mem, &[])
+    }
+
+    /// Execute the program loaded, with the given packet data, as `prog_exec()` does, and call
+    /// `tracer` before executing each instruction (see module `trace`).
+    ///
+    /// # Errors
+    ///
+    /// Returns an `ExecError` as `prog_exec()` does.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let prog = &[
+    ///     0xb7, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, // mov r0, 1
+    ///     0x95, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00  // exit
+    /// ];
+    ///
+    /// let vm = rbpf::EbpfVmRaw::new(prog).unwrap();
+    ///
+    /// let mut executed = vec![];
+    /// vm.prog_exec_traced(&mut [], &mut |entry| executed.push(entry.insn_ptr)).unwrap();
+    /// assert_eq!(executed, vec![0, 1]);
+    /// ```
+    pub fn prog_exec_traced(&self, mem: &'a mut [u8], tracer: &mut dyn FnMut(&TraceEntry))
+                            -> Result<u64, ExecError> {
+        self.parent.prog_exec_traced(mem, &[], tracer)
+    }
+
+    /// Start a debugging session for the loaded program, with the interpreter, on memory `mem`.
+    /// See module `debugger`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rbpf::debugger::Status;
+    ///
+    /// let prog = &[
+    ///     0x71, 0x11, 0x04, 0x00, 0x00, 0x00, 0x00, 0x00, // ldxb r1[0x04], r1
+    ///     0x07, 0x01, 0x00, 0x00, 0x00, 0x22, 0x00, 0x00, // add r1, 0x22
+    ///     0xbf, 0x10, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // mov r0, r1
+    ///     0x95, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00  // exit
+    /// ];
+    /// let mem = &mut [
+    ///     0xaa, 0xbb, 0x11, 0x22, 0xcc, 0xdd
+    /// ];
+    ///
+    /// let vm = rbpf::EbpfVmRaw::new(prog).unwrap();
+    /// let mut session = vm.debug(mem);
+    ///
+    /// let addr = session.reg(1) + 4;
+    /// session.write_memory(addr, &[0x01]).unwrap();
+    /// assert_eq!(session.cont(), Status::Exited(0x2201));
+    /// ```
+    pub fn debug<'b>(&'b self, mem: &'b mut [u8]) -> Session<'a, 'b> {
+        self.parent.debug(mem, &mut [])
+    }
+
     /// JIT-compile the loaded program. No argument required for this.
     ///
     /// If using helper functions, be sure to register them into the VM before calling this
     /// function.
     ///
-    /// # Panics
+    /// # Errors
     ///
-    /// This function panics if an error occurs during JIT-compiling, such as the occurrence of an
-    /// unknown eBPF operation code.
+    /// Returns an error if the program loads a map or calls a helper function that has not been
+    /// registered into the VM, if it contains an unknown eBPF operation code, if the generated code
+    /// is too large, if executable memory cannot be allocated for it, or if there is no JIT
+    /// compiler for the target architecture (only x86_64 and AArch64 are supported).
     ///
     /// # Examples
     ///
@@ -1112,27 +2394,36 @@ impl<'a> EbpfVmRaw<'a> {
     ///     0x95, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00  // exit
     /// ];
     ///
-    /// let mut vm = rbpf::EbpfVmRaw::new(prog);
+    /// let mut vm = rbpf::EbpfVmRaw::new(prog).unwrap();
     ///
-    /// vm.jit_compile();
+    /// vm.jit_compile().unwrap();
     /// ```
-    pub fn jit_compile(&mut self) {
-        self.parent.jit = jit::compile(self.parent.prog, &self.parent.helpers, false, false);
+    pub fn jit_compile(&mut self) -> Result<(), JitError> {
+        self.parent.jit = Some(jit::compile(self.parent.prog, &self.parent.helpers,
+                                            &self.parent.maps, false, false,
+                                            &self.parent.tail_call_progs(),
+                                            self.parent.max_tail_calls,
+                                            self.parent.jit_mem_check,
+                                            self.parent.insn_budget)?);
+        Ok(())
     }
 
     /// Execute the previously JIT-compiled program, with the given packet data, in a manner very
     /// similar to `prog_exec()`.
     ///
-    /// # Panics
+    /// # Errors
     ///
-    /// This function panics if an error occurs during the execution of the program.
+    /// Returns an `ExecError` if the program faults during its execution, such as on division by
+    /// zero attempts, or on out of bounds memory accesses if the program was compiled with
+    /// `set_jit_mem_check()` enabled.
     ///
     /// # Safety
     ///
     /// **WARNING:** JIT-compiled assembly code is not safe, in particular there is no runtime
-    /// check for memory access; so if the eBPF program attempts erroneous accesses, this may end
-    /// very bad (program may segfault). It may be wise to check that the program works with the
-    /// interpreter before running the JIT-compiled version of it.
+    /// check for memory access unless `set_jit_mem_check()` was enabled before compiling; so if
+    /// the eBPF program attempts erroneous accesses, this may end very bad (program may segfault).
+    /// It may be wise to check that the program works with the interpreter before running the
+    /// JIT-compiled version of it.
     ///
     /// For this reason the function should be called from within an `unsafe` bloc.
     ///
@@ -1150,19 +2441,54 @@ impl<'a> EbpfVmRaw<'a> {
     ///     0xaa, 0xbb, 0x11, 0x22, 0xcc, 0x27
     /// ];
     ///
-    /// let mut vm = rbpf::EbpfVmRaw::new(prog);
+    /// let mut vm = rbpf::EbpfVmRaw::new(prog).unwrap();
     ///
-    /// vm.jit_compile();
+    /// vm.jit_compile().unwrap();
     ///
     /// unsafe {
-    ///     let res = vm.prog_exec_jit(mem);
+    ///     let res = vm.prog_exec_jit(mem).unwrap();
     ///     assert_eq!(res, 0x22cc);
     /// }
     /// ```
-    pub unsafe fn prog_exec_jit(&self, mem: &'a mut [u8]) -> u64 {
+    pub unsafe fn prog_exec_jit(&self, mem: &'a mut [u8]) -> Result<u64, ExecError> {
         let mut mbuff = vec![];
         self.parent.prog_exec_jit(mem, &mut mbuff)
     }
+
+    /// Execute the previously JIT-compiled program, with the given packet data, as
+    /// `prog_exec_jit()` does. Return the value of r0 along with the number of instructions
+    /// executed, which is only counted if the program was compiled with an instruction budget
+    /// (see `set_insn_budget()`), and is `None` otherwise.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `ExecError` as `prog_exec_jit()` does.
+    ///
+    /// # Safety
+    ///
+    /// See `prog_exec_jit()`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let prog = &[
+    ///     0xb7, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, // mov r0, 1
+    ///     0x95, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00  // exit
+    /// ];
+    ///
+    /// let mut vm = rbpf::EbpfVmRaw::new(prog).unwrap();
+    /// vm.set_insn_budget(Some(100));
+    /// vm.jit_compile().unwrap();
+    ///
+    /// unsafe {
+    ///     assert_eq!(vm.prog_exec_jit_metered(&mut []).unwrap(), (1, Some(2)));
+    /// }
+    /// ```
+    pub unsafe fn prog_exec_jit_metered(&self, mem: &'a mut [u8])
+                                        -> Result<(u64, Option<u64>), ExecError> {
+        let mut mbuff = vec![];
+        self.parent.prog_exec_jit_metered(mem, &mut mbuff)
+    }
 }
 
 /// A virtual machine to run eBPF program. This kind of VM is used for programs that do not work
@@ -1198,10 +2524,10 @@ impl<'a> EbpfVmRaw<'a> {
 /// ];
 ///
 /// // Instantiate a VM.
-/// let vm = rbpf::EbpfVmNoData::new(prog);
+/// let vm = rbpf::EbpfVmNoData::new(prog).unwrap();
 ///
 /// // Provide only a reference to the packet data.
-/// let res = vm.prog_exec();
+/// let res = vm.prog_exec().unwrap();
 /// assert_eq!(res, 0x11);
 /// ```
 pub struct EbpfVmNoData<'a> {
@@ -1213,9 +2539,9 @@ impl<'a> EbpfVmNoData<'a> {
     /// Create a new virtual machine instance, and load an eBPF program into that instance.
     /// When attempting to load the program, it passes through a simple verifier.
     ///
-    /// # Panics
+    /// # Errors
     ///
-    /// The simple verifier may panic if it finds errors in the eBPF program at load time.
+    /// Returns an error if the simple verifier finds errors in the eBPF program at load time.
     ///
     /// # Examples
     ///
@@ -1227,20 +2553,23 @@ impl<'a> EbpfVmNoData<'a> {
     /// ];
     ///
     /// // Instantiate a VM.
-    /// let vm = rbpf::EbpfVmNoData::new(prog);
+    /// let vm = rbpf::EbpfVmNoData::new(prog).unwrap();
     /// ```
-    pub fn new(prog: &'a [u8]) -> EbpfVmNoData<'a> {
-        let parent = EbpfVmRaw::new(prog);
-        EbpfVmNoData {
-            parent: parent,
-        }
+    pub fn new(prog: &'a [u8]) -> Result<EbpfVmNoData<'a>, EbpfError> {
+        let parent = EbpfVmRaw::new(prog)?;
+        Ok(EbpfVmNoData {
+            parent,
+        })
     }
 
     /// Load a new eBPF program into the virtual machine instance.
     ///
-    /// # Panics
+    /// If the previous program was JIT-compiled, the compiled program is discarded: call
+    /// `jit_compile()` again before running the new one with `prog_exec_jit()`.
+    ///
+    /// # Errors
     ///
-    /// The simple verifier may panic if it finds errors in the eBPF program at load time.
+    /// Returns an error if the simple verifier finds errors in the eBPF program at load time.
     ///
     /// # Examples
     ///
@@ -1255,20 +2584,55 @@ impl<'a> EbpfVmNoData<'a> {
     ///     0x95, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00  // exit
     /// ];
     ///
-    /// let mut vm = rbpf::EbpfVmNoData::new(prog1);
+    /// let mut vm = rbpf::EbpfVmNoData::new(prog1).unwrap();
     ///
-    /// let res = vm.prog_exec();
+    /// let res = vm.prog_exec().unwrap();
     /// assert_eq!(res, 0x2211);
     ///
-    /// vm.set_prog(prog2);
+    /// vm.set_prog(prog2).unwrap();
     ///
-    /// let res = vm.prog_exec();
+    /// let res = vm.prog_exec().unwrap();
     /// assert_eq!(res, 0x1122);
     /// ```
-    pub fn set_prog(&mut self, prog: &'a [u8]) {
+    pub fn set_prog(&mut self, prog: &'a [u8]) -> Result<(), EbpfError> {
         self.parent.set_prog(prog)
     }
 
+    /// Set a new verifier function, used instead of `verifier::check()` to check the programs
+    /// loaded into the VM with `set_prog()`, and the programs of the array attached with
+    /// `set_prog_array()`. The program currently loaded, and the programs of the array currently
+    /// attached, are checked right away.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the verifier rejects the loaded program, or one of the programs of the
+    /// array. The previous verifier is kept in that case.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rbpf::verifier;
+    ///
+    /// let prog1 = &[
+    ///     0xb7, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // mov r0, 0
+    ///     0x95, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00  // exit
+    /// ];
+    /// let prog2 = &[
+    ///     0x05, 0x00, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, // ja +1
+    ///     0xb7, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // mov r0, 0
+    ///     0x95, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00  // exit
+    /// ];
+    ///
+    /// let mut vm = rbpf::EbpfVmNoData::new(prog1).unwrap();
+    /// vm.set_verifier(verifier::check_cfg).unwrap();
+    ///
+    /// // The second instruction cannot be reached.
+    /// assert!(vm.set_prog(prog2).is_err());
+    /// ```
+    pub fn set_verifier(&mut self, verifier: Verifier) -> Result<(), EbpfError> {
+        self.parent.set_verifier(verifier)
+    }
+
     /// Register a built-in or user-defined helper function in order to use it later from within
     /// the eBPF program. The helper is registered into a hashmap, so the `key` can be any `u32`.
     ///
@@ -1291,27 +2655,168 @@ impl<'a> EbpfVmNoData<'a> {
     ///     0x95, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00  // exit
     /// ];
     ///
-    /// let mut vm = rbpf::EbpfVmNoData::new(prog);
+    /// let mut vm = rbpf::EbpfVmNoData::new(prog).unwrap();
     ///
     /// // Register a helper. This helper will store the result of the square root of r1 into r0.
     /// vm.register_helper(1, helpers::sqrti);
     ///
-    /// let res = vm.prog_exec();
+    /// let res = vm.prog_exec().unwrap();
     /// assert_eq!(res, 0x1000);
     /// ```
     pub fn register_helper(&mut self, key: u32, function: fn (u64, u64, u64, u64, u64) -> u64) {
         self.parent.register_helper(key, function);
     }
 
+    /// Attach a program array to the virtual machine, to be used as the target of tail calls (see
+    /// `ProgramArray`).
+    ///
+    /// If using JIT-compiled eBPF programs, the programs of the array are compiled along with the
+    /// loaded program: be sure to fill the array and to attach it before compiling the program.
+    /// Attaching an array discards the program compiled earlier, if any.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the verifier of the VM rejects one of the programs of the array.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let prog = &[
+    ///     0x8d, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // tail call to program r3 (0)
+    ///     0x95, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00  // exit
+    /// ];
+    ///
+    /// let mut prog_array = rbpf::ProgramArray::new(1);
+    /// prog_array.set(0, prog).unwrap();
+    ///
+    /// // Instantiate a VM.
+    /// let mut vm = rbpf::EbpfVmNoData::new(prog).unwrap();
+    /// vm.set_prog_array(&prog_array).unwrap();
+    /// ```
+    pub fn set_prog_array(&mut self, prog_array: &'a ProgramArray<'a>) -> Result<(), EbpfError> {
+        self.parent.set_prog_array(prog_array)
+    }
+
+    /// Set the maximum number of tail calls that a program can perform in a row, 33 by default
+    /// (`ebpf::MAX_TAIL_CALLS`). Once the limit is reached, tail calls fail and the program goes
+    /// on with the instruction following the tail call.
+    ///
+    /// If using JIT-compiled eBPF programs, be sure to set the limit before compiling the program.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let prog = &[
+    ///     0x95, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00  // exit
+    /// ];
+    ///
+    /// // Instantiate a VM.
+    /// let mut vm = rbpf::EbpfVmNoData::new(prog).unwrap();
+    /// vm.set_max_tail_calls(8);
+    /// ```
+    pub fn set_max_tail_calls(&mut self, max: usize) {
+        self.parent.set_max_tail_calls(max);
+    }
+
+    /// Make the JIT-compiler emit runtime checks for memory accesses, disabled by default. As with
+    /// the interpreter, each load or store must then fall within the metadata buffer, the packet
+    /// data, the stack or the values of a registered map. An out of bounds access aborts the
+    /// program and `prog_exec_jit()` returns an error indicating the faulting instruction,
+    /// instead of possibly crashing the process.
+    ///
+    /// The checks slow the program down. Be sure to enable them before compiling the program.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let prog = &[
+    ///     0x95, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00  // exit
+    /// ];
+    ///
+    /// // Instantiate a VM.
+    /// let mut vm = rbpf::EbpfVmNoData::new(prog).unwrap();
+    /// vm.set_jit_mem_check(true);
+    /// ```
+    pub fn set_jit_mem_check(&mut self, check: bool) {
+        self.parent.set_jit_mem_check(check);
+    }
+
+    /// Set the maximum number of instructions that a run of the program can execute, or `None`
+    /// for no limit (the default). Instructions executed by local functions and by programs
+    /// reached with tail calls count toward the budget. Once it is exhausted, the program is
+    /// aborted with an `InstructionBudgetExceeded` error, which notably prevents programs with
+    /// loops from running forever.
+    ///
+    /// If using JIT-compiled eBPF programs, be sure to set the budget before compiling the
+    /// program: setting it discards the program compiled earlier, if any. Metering instructions
+    /// slows JIT-compiled programs down.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rbpf::error::ExecErrorKind;
+    ///
+    /// let prog = &[
+    ///     0xb7, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // mov r0, 0
+    ///     0x05, 0x00, 0xfe, 0xff, 0x00, 0x00, 0x00, 0x00, // ja -2
+    ///     0x95, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00  // exit
+    /// ];
+    ///
+    /// // Instantiate a VM.
+    /// let mut vm = rbpf::EbpfVmNoData::new(prog).unwrap();
+    /// vm.set_insn_budget(Some(1000));
+    ///
+    /// let err = vm.prog_exec().unwrap_err();
+    /// assert_eq!(err.kind, ExecErrorKind::InstructionBudgetExceeded);
+    /// ```
+    pub fn set_insn_budget(&mut self, budget: Option<u64>) {
+        self.parent.set_insn_budget(budget);
+    }
+
+    /// Register an eBPF map with file descriptor number `fd`, so that the program can obtain a
+    /// handle on it with a `lddw` instruction whose `src` field is `ebpf::BPF_PSEUDO_MAP_FD` and
+    /// whose immediate value is `fd` (see module `maps`).
+    ///
+    /// Once a map is registered, calls to helpers 1, 2 and 3 go to the `bpf_map_lookup_elem()`,
+    /// `bpf_map_update_elem()` and `bpf_map_delete_elem()` map helpers, as in the kernel, rather
+    /// than to the helpers registered with these keys, if any. The program is aborted if it passes
+    /// them something else than a handle on a registered map, or a key or value out of bounds.
+    ///
+    /// If using JIT-compiled eBPF programs, be sure to register all maps before compiling the
+    /// program: registering a map discards the program compiled earlier, if any.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rbpf::maps::{Map, MapType};
+    ///
+    /// let prog = &[
+    ///     0x18, 0x11, 0x00, 0x00, 0x03, 0x00, 0x00, 0x00, // lddw r1, map_fd(3)
+    ///     0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+    ///     0x95, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00  // exit
+    /// ];
+    ///
+    /// let map = Map::new(MapType::Hash, 4, 8, 16).unwrap();
+    ///
+    /// // Instantiate a VM.
+    /// let mut vm = rbpf::EbpfVmNoData::new(prog).unwrap();
+    /// vm.register_map(3, &map);
+    /// ```
+    pub fn register_map(&mut self, fd: u32, map: &'a Map) {
+        self.parent.register_map(fd, map);
+    }
+
     /// JIT-compile the loaded program. No argument required for this.
     ///
     /// If using helper functions, be sure to register them into the VM before calling this
     /// function.
     ///
-    /// # Panics
+    /// # Errors
     ///
-    /// This function panics if an error occurs during JIT-compiling, such as the occurrence of an
-    /// unknown eBPF operation code.
+    /// Returns an error if the program loads a map or calls a helper function that has not been
+    /// registered into the VM, if it contains an unknown eBPF operation code, if the generated code
+    /// is too large, if executable memory cannot be allocated for it, or if there is no JIT
+    /// compiler for the target architecture (only x86_64 and AArch64 are supported).
     ///
     /// # Examples
     ///
@@ -1322,22 +2827,21 @@ impl<'a> EbpfVmNoData<'a> {
     ///     0x95, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00  // exit
     /// ];
     ///
-    /// let mut vm = rbpf::EbpfVmNoData::new(prog);
+    /// let mut vm = rbpf::EbpfVmNoData::new(prog).unwrap();
     ///
     ///
-    /// vm.jit_compile();
+    /// vm.jit_compile().unwrap();
     /// ```
-    pub fn jit_compile(&mut self) {
-        self.parent.jit_compile();
+    pub fn jit_compile(&mut self) -> Result<(), JitError> {
+        self.parent.jit_compile()
     }
 
     /// Execute the program loaded, without providing pointers to any memory area whatsoever.
     ///
-    /// # Panics
+    /// # Errors
     ///
-    /// This function is currently expected to panic if it encounters any error during the program
-    /// execution, such as memory accesses or division by zero attempts. This may be changed in the
-    /// future (we could raise errors instead).
+    /// Returns an `ExecError` if the interpreter encounters a fault during the program execution,
+    /// such as memory accesses or division by zero attempts.
     ///
     /// # Examples
     ///
@@ -1348,29 +2852,105 @@ impl<'a> EbpfVmNoData<'a> {
     ///     0x95, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00  // exit
     /// ];
     ///
-    /// let vm = rbpf::EbpfVmNoData::new(prog);
+    /// let vm = rbpf::EbpfVmNoData::new(prog).unwrap();
     ///
     /// // For this kind of VM, the `prog_exec()` function needs no argument.
-    /// let res = vm.prog_exec();
+    /// let res = vm.prog_exec().unwrap();
     /// assert_eq!(res, 0x1122);
     /// ```
-    pub fn prog_exec(&self) -> u64 {
+    pub fn prog_exec(&self) -> Result<u64, ExecError> {
         self.parent.prog_exec(&mut [])
     }
 
+    /// Execute the program loaded, as `prog_exec()` does. Return the value of r0 along with the
+    /// number of instructions executed, including those of local functions and of programs
+    /// reached with tail calls.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `ExecError` as `prog_exec()` does.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let prog = &[
+    ///     0xb7, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, // mov r0, 1
+    ///     0x95, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00  // exit
+    /// ];
+    ///
+    /// let vm = rbpf::EbpfVmNoData::new(prog).unwrap();
+    /// assert_eq!(vm.prog_exec_metered().unwrap(), (1, 2));
+    /// ```
+    pub fn prog_exec_metered(&self) -> Result<(u64, u64), ExecError> {
+        self.parent.prog_exec_metered(&mut [])
+    }
+
+    /// Execute the program loaded, as `prog_exec()` does, and call `tracer` before executing each
+    /// instruction (see module `trace`).
+    ///
+    /// # Errors
+    ///
+    /// Returns an `ExecError` as `prog_exec()` does.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let prog = &[
+    ///     0xb7, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, // mov r0, 1
+    ///     0x95, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00  // exit
+    /// ];
+    ///
+    /// let vm = rbpf::EbpfVmNoData::new(prog).unwrap();
+    ///
+    /// let mut executed = vec![];
+    /// vm.prog_exec_traced(&mut |entry| executed.push(entry.insn_ptr)).unwrap();
+    /// assert_eq!(executed, vec![0, 1]);
+    /// ```
+    pub fn prog_exec_traced(&self, tracer: &mut dyn FnMut(&TraceEntry)) -> Result<u64, ExecError> {
+        self.parent.prog_exec_traced(&mut [], tracer)
+    }
+
+    /// Start a debugging session for the loaded program, with the interpreter, without providing
+    /// pointers to any memory area. See module `debugger`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rbpf::debugger::Status;
+    ///
+    /// let prog = &[
+    ///     0xb7, 0x00, 0x00, 0x00, 0x11, 0x22, 0x00, 0x00, // mov r0, 0x2211
+    ///     0xdc, 0x00, 0x00, 0x00, 0x10, 0x00, 0x00, 0x00, // be16 r0
+    ///     0x95, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00  // exit
+    /// ];
+    ///
+    /// let vm = rbpf::EbpfVmNoData::new(prog).unwrap();
+    /// let mut session = vm.debug();
+    ///
+    /// assert_eq!(session.step(), Status::Stopped);
+    /// assert_eq!(session.reg(0), 0x2211);
+    /// assert_eq!(session.cont(), Status::Exited(0x1122));
+    /// ```
+    pub fn debug<'b>(&'b self) -> Session<'a, 'b> {
+        self.parent.debug(&mut [])
+    }
+
     /// Execute the previously JIT-compiled program, without providing pointers to any memory area
     /// whatsoever, in a manner very similar to `prog_exec()`.
     ///
-    /// # Panics
+    /// # Errors
     ///
-    /// This function panics if an error occurs during the execution of the program.
+    /// Returns an `ExecError` if the program faults during its execution, such as on division by
+    /// zero attempts, or on out of bounds memory accesses if the program was compiled with
+    /// `set_jit_mem_check()` enabled.
     ///
     /// # Safety
     ///
     /// **WARNING:** JIT-compiled assembly code is not safe, in particular there is no runtime
-    /// check for memory access; so if the eBPF program attempts erroneous accesses, this may end
-    /// very bad (program may segfault). It may be wise to check that the program works with the
-    /// interpreter before running the JIT-compiled version of it.
+    /// check for memory access unless `set_jit_mem_check()` was enabled before compiling; so if
+    /// the eBPF program attempts erroneous accesses, this may end very bad (program may segfault).
+    /// It may be wise to check that the program works with the interpreter before running the
+    /// JIT-compiled version of it.
     ///
     /// For this reason the function should be called from within an `unsafe` bloc.
     ///
@@ -1383,16 +2963,49 @@ impl<'a> EbpfVmNoData<'a> {
     ///     0x95, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00  // exit
     /// ];
     ///
-    /// let mut vm = rbpf::EbpfVmNoData::new(prog);
+    /// let mut vm = rbpf::EbpfVmNoData::new(prog).unwrap();
     ///
-    /// vm.jit_compile();
+    /// vm.jit_compile().unwrap();
     ///
     /// unsafe {
-    ///     let res = vm.prog_exec_jit();
+    ///     let res = vm.prog_exec_jit().unwrap();
     ///     assert_eq!(res, 0x1122);
     /// }
     /// ```
-    pub unsafe fn prog_exec_jit(&self) -> u64 {
+    pub unsafe fn prog_exec_jit(&self) -> Result<u64, ExecError> {
         self.parent.prog_exec_jit(&mut [])
     }
+
+    /// Execute the previously JIT-compiled program, as `prog_exec_jit()` does. Return the value
+    /// of r0 along with the number of instructions executed, which is only counted if the program
+    /// was compiled with an instruction budget (see `set_insn_budget()`), and is `None`
+    /// otherwise.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `ExecError` as `prog_exec_jit()` does.
+    ///
+    /// # Safety
+    ///
+    /// See `prog_exec_jit()`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let prog = &[
+    ///     0xb7, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, // mov r0, 1
+    ///     0x95, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00  // exit
+    /// ];
+    ///
+    /// let mut vm = rbpf::EbpfVmNoData::new(prog).unwrap();
+    /// vm.set_insn_budget(Some(100));
+    /// vm.jit_compile().unwrap();
+    ///
+    /// unsafe {
+    ///     assert_eq!(vm.prog_exec_jit_metered().unwrap(), (1, Some(2)));
+    /// }
+    /// ```
+    pub unsafe fn prog_exec_jit_metered(&self) -> Result<(u64, Option<u64>), ExecError> {
+        self.parent.prog_exec_jit_metered(&mut [])
+    }
 }
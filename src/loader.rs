@@ -0,0 +1,504 @@
+// Copyright 2026 agent <agent@local>
+//
+// Licensed under the Apache License, Version 2.0 <http://www.apache.org/licenses/LICENSE-2.0> or
+// the MIT license <http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+
+//! This module loads eBPF programs from object files produced by clang/LLVM (for example with
+//! `clang -O2 -target bpf -c prog.c -o prog.o`).
+//!
+//! The object is parsed as follows:
+//!
+//! * Each non-empty executable section is a program, named after the section (`.classifier`,
+//!   `xdp`, `socket`…). The `.text` section is also returned as a program if it is not empty,
+//!   although it usually contains the functions called from the other sections.
+//! * The `license` section, if present, contains the license of the programs.
+//! * The `maps` section contains the definitions of the maps (legacy `struct bpf_map_def`: type,
+//!   key size, value size, max entries and flags, as 32-bit integers). Maps are numbered in the
+//!   order of their definitions, starting from 0, and that number is the file descriptor used by
+//!   the programs to refer to them.
+//!
+//! Relocations are applied to the programs:
+//!
+//! * `R_BPF_64_64` on `lddw` instructions referencing a map: the instruction is turned into a
+//!   `lddw` with `src` set to `ebpf::BPF_PSEUDO_MAP_FD`, and the number of the map as immediate.
+//! * `R_BPF_64_32` on local function calls (`call` with `src` set to `ebpf::BPF_PSEUDO_CALL`):
+//!   the sections containing the called functions (usually `.text`) are appended to the program,
+//!   and the offset of the call is updated accordingly.
+//!
+//! The resulting programs can be loaded into any virtual machine, once the maps created with
+//! `Object::create_maps()` have been registered with `register_map()`.
+//!
+//! # Examples
+//!
+//! ```
+//! use rbpf::loader::Object;
+//!
+//! let file = std::fs::read("tests/elfs/map_counter.o").unwrap();
+//! let object = Object::parse(&file).unwrap();
+//! assert_eq!(object.license(), Some("GPL"));
+//!
+//! let maps = object.create_maps().unwrap();
+//! let prog = object.program("xdp").unwrap();
+//!
+//! let mut vm = rbpf::EbpfVmNoData::new(prog.code()).unwrap();
+//! for (fd, map) in maps.iter().enumerate() {
+//!     vm.register_map(fd as u32, map);
+//! }
+//! assert_eq!(vm.prog_exec().unwrap(), 1);
+//! assert_eq!(vm.prog_exec().unwrap(), 2);
+//! ```
+
+use std::collections::HashMap;
+
+use ebpf;
+use error::ElfError;
+use maps::{Map, MapType};
+
+const EM_BPF:          u16   = 247;
+
+const SHT_SYMTAB:      u32   = 2;
+const SHT_NOBITS:      u32   = 8;
+const SHT_REL:         u32   = 9;
+const SHF_EXECINSTR:   u64   = 0x4;
+
+const STT_SECTION:     u8    = 3;
+
+const ELF_HEADER_SIZE: usize = 64;
+const SHDR_SIZE:       usize = 64;
+const SYM_SIZE:        usize = 24;
+const REL_SIZE:        usize = 16;
+
+/// Relocation type for `lddw` instructions, with the 64-bit address of the symbol.
+pub const R_BPF_64_64: u32 = 1;
+/// Relocation type for local function calls, with the 32-bit offset to the symbol.
+pub const R_BPF_64_32: u32 = 10;
+
+/// Map type of hash maps in the `maps` section (`BPF_MAP_TYPE_HASH` in the kernel).
+pub const BPF_MAP_TYPE_HASH:  u32 = 1;
+/// Map type of array maps in the `maps` section (`BPF_MAP_TYPE_ARRAY` in the kernel).
+pub const BPF_MAP_TYPE_ARRAY: u32 = 2;
+
+// Size of the mandatory fields of `struct bpf_map_def`: type, key_size, value_size, max_entries.
+const MAP_DEF_MIN_SIZE: usize = 16;
+
+/// Definition of a map, as found in the `maps` section of an object file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MapDef {
+    /// Name of the symbol of the map.
+    pub name:        String,
+    /// Type of the map, `BPF_MAP_TYPE_HASH` or `BPF_MAP_TYPE_ARRAY` for the supported types.
+    pub map_type:    u32,
+    /// Size of the keys, in bytes.
+    pub key_size:    u32,
+    /// Size of the values, in bytes.
+    pub value_size:  u32,
+    /// Maximum number of elements in the map.
+    pub max_entries: u32,
+    /// Flags of the map, or 0 if not present in the definition.
+    pub map_flags:   u32,
+}
+
+impl MapDef {
+    /// Create a map from this definition.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the map type is not supported, or if the sizes are rejected by
+    /// `Map::new()`.
+    pub fn create(&self) -> Result<Map, ElfError> {
+        let map_type = match self.map_type {
+            BPF_MAP_TYPE_HASH  => MapType::Hash,
+            BPF_MAP_TYPE_ARRAY => MapType::Array,
+            t                  => return Err(ElfError::UnsupportedMapType(self.name.clone(), t)),
+        };
+        Map::new(map_type, self.key_size as usize, self.value_size as usize,
+                 self.max_entries as usize)
+            .map_err(|e| ElfError::InvalidMap(self.name.clone(), e))
+    }
+}
+
+/// A program extracted from an object file, with its relocations applied.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Program {
+    name: String,
+    code: Vec<u8>,
+}
+
+impl Program {
+    /// Return the name of the program, that is to say, the name of its section.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Return the bytecode of the program, ready to be loaded into a virtual machine.
+    pub fn code(&self) -> &[u8] {
+        &self.code
+    }
+}
+
+/// The programs, maps and license parsed from an object file, see module documentation.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Object {
+    license:  Option<String>,
+    maps:     Vec<MapDef>,
+    programs: Vec<Program>,
+}
+
+// Section header, with its name resolved.
+struct Section {
+    name:   String,
+    stype:  u32,
+    flags:  u64,
+    offset: usize,
+    size:   usize,
+    link:   usize,
+    info:   usize,
+}
+
+struct Symbol {
+    name:  String,
+    stype: u8,
+    shndx: usize,
+    value: u64,
+    size:  u64,
+}
+
+struct Rel {
+    offset: usize,
+    rtype:  u32,
+    sym:    usize,
+}
+
+// Return `len` bytes at offset `off` in the file, checking bounds. Integers are little-endian.
+fn read(file: &[u8], off: usize, len: usize) -> Result<&[u8], ElfError> {
+    match off.checked_add(len) {
+        Some(end) if end <= file.len() => Ok(&file[off..end]),
+        _                              => Err(ElfError::Truncated),
+    }
+}
+
+fn read_u16(file: &[u8], off: usize) -> Result<u16, ElfError> {
+    let b = read(file, off, 2)?;
+    Ok(u16::from_le_bytes([b[0], b[1]]))
+}
+
+fn read_u32(file: &[u8], off: usize) -> Result<u32, ElfError> {
+    let b = read(file, off, 4)?;
+    Ok(u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+}
+
+fn read_u64(file: &[u8], off: usize) -> Result<u64, ElfError> {
+    let b = read(file, off, 8)?;
+    Ok(u64::from_le_bytes([b[0], b[1], b[2], b[3], b[4], b[5], b[6], b[7]]))
+}
+
+// Read the NUL-terminated string at offset `off` in the string table `strtab`.
+fn read_str(file: &[u8], strtab: &Section, off: usize) -> Result<String, ElfError> {
+    let table = read(file, strtab.offset, strtab.size)?;
+    let bytes = table.get(off..).ok_or(ElfError::Truncated)?;
+    let len = bytes.iter().position(|&b| b == 0).ok_or(ElfError::Truncated)?;
+    Ok(String::from_utf8_lossy(&bytes[..len]).into_owned())
+}
+
+fn parse_sections(file: &[u8]) -> Result<Vec<Section>, ElfError> {
+    let shoff = read_u64(file, 0x28)? as usize;
+    let shentsize = read_u16(file, 0x3a)? as usize;
+    let shnum = read_u16(file, 0x3c)? as usize;
+    let shstrndx = read_u16(file, 0x3e)? as usize;
+    if shentsize != SHDR_SIZE || shstrndx >= shnum {
+        return Err(ElfError::UnsupportedElf);
+    }
+
+    let mut sections = Vec::with_capacity(shnum);
+    let mut names = Vec::with_capacity(shnum);
+    for i in 0..shnum {
+        let hdr = shoff.checked_add(i * SHDR_SIZE).ok_or(ElfError::Truncated)?;
+        names.push(read_u32(file, hdr)? as usize);
+        let section = Section {
+            name:   String::new(),
+            stype:  read_u32(file, hdr + 4)?,
+            flags:  read_u64(file, hdr + 8)?,
+            offset: read_u64(file, hdr + 24)? as usize,
+            size:   read_u64(file, hdr + 32)? as usize,
+            link:   read_u32(file, hdr + 40)? as usize,
+            info:   read_u32(file, hdr + 44)? as usize,
+        };
+        if section.stype != SHT_NOBITS {
+            read(file, section.offset, section.size)?;
+        }
+        sections.push(section);
+    }
+    for (i, name) in names.into_iter().enumerate() {
+        sections[i].name = read_str(file, &sections[shstrndx], name)?;
+    }
+    Ok(sections)
+}
+
+fn parse_symbols(file: &[u8], sections: &[Section]) -> Result<Vec<Symbol>, ElfError> {
+    let symtab = match sections.iter().find(|s| s.stype == SHT_SYMTAB) {
+        Some(s) => s,
+        None    => return Ok(vec![]),
+    };
+    let strtab = sections.get(symtab.link).ok_or(ElfError::Truncated)?;
+    (0..symtab.size / SYM_SIZE).map(|i| {
+        let off = symtab.offset + i * SYM_SIZE;
+        Ok(Symbol {
+            name:  read_str(file, strtab, read_u32(file, off)? as usize)?,
+            stype: read(file, off + 4, 1)?[0] & 0xf,
+            shndx: read_u16(file, off + 6)? as usize,
+            value: read_u64(file, off + 8)?,
+            size:  read_u64(file, off + 16)?,
+        })
+    }).collect()
+}
+
+// Relocations applying to each section, indexed by section.
+fn parse_relocations(file: &[u8], sections: &[Section])
+    -> Result<HashMap<usize, Vec<Rel>>, ElfError> {
+    let mut relocations = HashMap::new();
+    for section in sections.iter().filter(|s| s.stype == SHT_REL) {
+        let rels = (0..section.size / REL_SIZE).map(|i| {
+            let off = section.offset + i * REL_SIZE;
+            let info = read_u64(file, off + 8)?;
+            Ok(Rel {
+                offset: read_u64(file, off)? as usize,
+                rtype:  info as u32,
+                sym:    (info >> 32) as usize,
+            })
+        }).collect::<Result<Vec<Rel>, ElfError>>()?;
+        relocations.insert(section.info, rels);
+    }
+    Ok(relocations)
+}
+
+// Parse the map definitions, and return them along with their offsets in the `maps` section.
+fn parse_maps(file: &[u8], sections: &[Section], symbols: &[Symbol])
+    -> Result<Vec<(u64, MapDef)>, ElfError> {
+    let shndx = match sections.iter().position(|s| s.name == "maps") {
+        Some(i) => i,
+        None    => return Ok(vec![]),
+    };
+    let section = &sections[shndx];
+    let mut syms = symbols.iter()
+        .filter(|s| s.shndx == shndx && s.stype != STT_SECTION)
+        .collect::<Vec<&Symbol>>();
+    syms.sort_by_key(|s| s.value);
+
+    syms.iter().map(|sym| {
+        let size = match sym.size {
+            0 => section.size / syms.len(),
+            n => n as usize,
+        };
+        let invalid = || ElfError::InvalidMapDefinition(sym.name.clone());
+        let end = (sym.value as usize).checked_add(size).ok_or_else(invalid)?;
+        if size < MAP_DEF_MIN_SIZE || end > section.size {
+            return Err(invalid());
+        }
+        let off = section.offset.checked_add(sym.value as usize).ok_or_else(invalid)?;
+        Ok((sym.value, MapDef {
+            name:        sym.name.clone(),
+            map_type:    read_u32(file, off)?,
+            key_size:    read_u32(file, off + 4)?,
+            value_size:  read_u32(file, off + 8)?,
+            max_entries: read_u32(file, off + 12)?,
+            map_flags:   if size >= 20 { read_u32(file, off + 16)? } else { 0 },
+        }))
+    }).collect()
+}
+
+impl Object {
+
+    /// Parse an object file, and apply the relocations to the programs it contains.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file is not a 64-bit little-endian eBPF ELF object, if it is
+    /// malformed, or if it contains relocations that cannot be applied.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rbpf::error::ElfError;
+    /// use rbpf::loader::Object;
+    ///
+    /// assert_eq!(Object::parse(b"not an object file").err(), Some(ElfError::NotElf));
+    /// ```
+    pub fn parse(file: &[u8]) -> Result<Object, ElfError> {
+        if file.len() < ELF_HEADER_SIZE || file[0..4] != [0x7f, b'E', b'L', b'F'] {
+            return Err(ElfError::NotElf);
+        }
+        // 64-bit, little-endian, eBPF.
+        if file[4] != 2 || file[5] != 1 || read_u16(file, 0x12)? != EM_BPF {
+            return Err(ElfError::UnsupportedElf);
+        }
+
+        let sections = parse_sections(file)?;
+        let symbols = parse_symbols(file, &sections)?;
+        let relocations = parse_relocations(file, &sections)?;
+        let maps = parse_maps(file, &sections, &symbols)?;
+
+        let license = match sections.iter().find(|s| s.name == "license") {
+            Some(s) => {
+                let bytes = read(file, s.offset, s.size)?;
+                let len = bytes.iter().position(|&b| b == 0).unwrap_or(bytes.len());
+                Some(String::from_utf8_lossy(&bytes[..len]).into_owned())
+            },
+            None    => None,
+        };
+
+        let loader = Loader { file, sections: &sections, symbols: &symbols, relocations,
+                              maps: &maps };
+        let programs = sections.iter().enumerate()
+            .filter(|&(_, s)| s.flags & SHF_EXECINSTR != 0 && s.size > 0)
+            .map(|(i, s)| Ok(Program {
+                name: s.name.clone(),
+                code: loader.link(i)?,
+            }))
+            .collect::<Result<Vec<Program>, ElfError>>()?;
+
+        Ok(Object {
+            license,
+            maps: maps.into_iter().map(|(_, def)| def).collect(),
+            programs,
+        })
+    }
+
+    /// Return the content of the `license` section, if any.
+    pub fn license(&self) -> Option<&str> {
+        self.license.as_deref()
+    }
+
+    /// Return the definitions of the maps. The file descriptor used by the programs to refer to
+    /// each map is its index in this slice.
+    pub fn maps(&self) -> &[MapDef] {
+        &self.maps
+    }
+
+    /// Return the programs, in the order of their sections in the file.
+    pub fn programs(&self) -> &[Program] {
+        &self.programs
+    }
+
+    /// Return the program in section `name`, if any.
+    pub fn program(&self, name: &str) -> Option<&Program> {
+        self.programs.iter().find(|p| p.name == name)
+    }
+
+    /// Create the maps defined in the object. The file descriptor used by the programs to refer to
+    /// each map is its index in the returned vector: register each map into the virtual machine
+    /// with this number (see the module documentation for an example).
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if one of the maps cannot be created (see `MapDef::create()`).
+    pub fn create_maps(&self) -> Result<Vec<Map>, ElfError> {
+        self.maps.iter().map(|def| def.create()).collect()
+    }
+}
+
+// State used to apply the relocations to the programs.
+struct Loader<'a> {
+    file:        &'a [u8],
+    sections:    &'a [Section],
+    symbols:     &'a [Symbol],
+    relocations: HashMap<usize, Vec<Rel>>,
+    maps:        &'a [(u64, MapDef)],
+}
+
+impl<'a> Loader<'a> {
+
+    fn rels(&self, shndx: usize) -> &[Rel] {
+        self.relocations.get(&shndx).map_or(&[], |rels| rels.as_slice())
+    }
+
+    // Return the code of the program in section `shndx`, with the sections containing the
+    // functions it calls appended, and all relocations applied.
+    fn link(&self, shndx: usize) -> Result<Vec<u8>, ElfError> {
+        // Collect the sections reachable through local function calls.
+        let mut layout = vec![shndx];
+        let mut i = 0;
+        while i < layout.len() {
+            for rel in self.rels(layout[i]).iter().filter(|rel| rel.rtype == R_BPF_64_32) {
+                if let Some(sym) = self.symbols.get(rel.sym) {
+                    let executable = self.sections.get(sym.shndx)
+                        .map_or(false, |s| s.flags & SHF_EXECINSTR != 0);
+                    if executable && !layout.contains(&sym.shndx) {
+                        layout.push(sym.shndx);
+                    }
+                }
+            }
+            i += 1;
+        }
+
+        // Concatenate them, and store the index of the first instruction of each one.
+        let mut code = vec![];
+        let mut bases = HashMap::new();
+        for &i in &layout {
+            let section = &self.sections[i];
+            if section.size % ebpf::INSN_SIZE != 0 {
+                return Err(ElfError::InvalidProgram(section.name.clone()));
+            }
+            bases.insert(i, code.len() / ebpf::INSN_SIZE);
+            code.extend_from_slice(read(self.file, section.offset, section.size)?);
+        }
+
+        for &i in &layout {
+            for rel in self.rels(i) {
+                self.relocate(&mut code, &bases, i, rel)?;
+            }
+        }
+        Ok(code)
+    }
+
+    fn relocate(&self, code: &mut [u8], bases: &HashMap<usize, usize>, shndx: usize, rel: &Rel)
+        -> Result<(), ElfError> {
+        let section = &self.sections[shndx];
+        let error = || ElfError::InvalidRelocation(section.name.clone(), rel.offset);
+        if rel.offset % ebpf::INSN_SIZE != 0 || rel.offset >= section.size {
+            return Err(error());
+        }
+        let insn_ptr = bases[&shndx] + rel.offset / ebpf::INSN_SIZE;
+        let insn = ebpf::get_insn(code, insn_ptr);
+        let sym = self.symbols.get(rel.sym).ok_or_else(error)?;
+
+        match rel.rtype {
+            R_BPF_64_64 => {
+                let maps_shndx = self.sections.iter().position(|s| s.name == "maps");
+                if insn.opc != ebpf::LD_DW_IMM || Some(sym.shndx) != maps_shndx {
+                    return Err(error());
+                }
+                // With section symbols, the offset of the map is in the immediate.
+                let off = sym.value.checked_add(insn.imm as u32 as u64).ok_or_else(error)?;
+                let fd = self.maps.iter().position(|&(o, _)| o == off).ok_or_else(error)?;
+                set_insn(code, insn_ptr, ebpf::BPF_PSEUDO_MAP_FD, fd as i32);
+            },
+            R_BPF_64_32 => {
+                let target_base = match bases.get(&sym.shndx) {
+                    Some(&b) if insn.opc == ebpf::CALL && insn.src == ebpf::BPF_PSEUDO_CALL => b,
+                    _ => return Err(error()),
+                };
+                // With section symbols, the offset of the function is in the immediate, relative
+                // to the next instruction.
+                let target = if sym.stype == STT_SECTION {
+                    sym.value as i64 / ebpf::INSN_SIZE as i64 + insn.imm as i64 + 1
+                } else {
+                    sym.value as i64 / ebpf::INSN_SIZE as i64
+                };
+                let imm = target_base as i64 + target - insn_ptr as i64 - 1;
+                set_insn(code, insn_ptr, ebpf::BPF_PSEUDO_CALL, imm as i32);
+            },
+            t           =>
+                return Err(ElfError::UnsupportedRelocation(t, section.name.clone(), rel.offset)),
+        }
+        Ok(())
+    }
+}
+
+// Set the `src` register and the immediate of the instruction at `insn_ptr`.
+fn set_insn(code: &mut [u8], insn_ptr: usize, src: u8, imm: i32) {
+    let off = insn_ptr * ebpf::INSN_SIZE;
+    code[off + 1] = (code[off + 1] & 0x0f) | (src << 4);
+    code[off + 4..off + 8].copy_from_slice(&imm.to_le_bytes());
+}
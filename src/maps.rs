@@ -0,0 +1,404 @@
+// Copyright 2026 agent <agent@local>
+//
+// Licensed under the Apache License, Version 2.0 <http://www.apache.org/licenses/LICENSE-2.0> or
+// the MIT license <http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+
+//! This module implements eBPF maps in user space, and the helpers used by eBPF programs to access
+//! them.
+//!
+//! Maps are key/value stores shared between eBPF programs and the application running them. They
+//! have fixed key and value sizes, and a maximum number of entries. Two types are supported:
+//!
+//! * Hash maps, with arbitrary keys.
+//! * Array maps, with `u32` keys ranging from 0 to `max_entries - 1`. All elements of an array
+//!   always exist (they are zero-initialized), and they cannot be deleted.
+//!
+//! Maps are registered into a virtual machine with a file descriptor number. Programs obtain a
+//! handle on a map with a `lddw` instruction whose `src` field is `ebpf::BPF_PSEUDO_MAP_FD`, and
+//! whose immediate is that number, as with the kernel. Once a map is registered, the VM also
+//! provides the `bpf_map_lookup_elem()`, `bpf_map_update_elem()` and `bpf_map_delete_elem()`
+//! helpers at their kernel indexes, so that programs compiled for the kernel run unmodified.
+//! Before calling them, the VM checks that their first argument is the handle of one of its maps,
+//! and that the key and value they read are in bounds of the memory the program can access.
+//!
+//! Storage for the values is allocated once and for all when the map is created, so that pointers
+//! to values returned to the program by `bpf_map_lookup_elem()` remain valid for the lifetime of
+//! the map.
+//!
+//! # Examples
+//!
+//! ```
+//! use rbpf::assembler::assemble;
+//! use rbpf::maps::{Map, MapType};
+//!
+//! // Increment the value associated to key 0 in the array map with fd 3.
+//! let prog = assemble("
+//!     stw [r10-0x4], 0x0
+//!     lddw r1, map_fd(3)
+//!     mov64 r2, r10
+//!     add64 r2, -4
+//!     call 0x1
+//!     jeq r0, 0x0, out
+//!     ldxdw r1, [r0+0x0]
+//!     add64 r1, 0x1
+//!     stxdw [r0+0x0], r1
+//! out:
+//!     exit").unwrap();
+//!
+//! let map = Map::new(MapType::Array, 4, 8, 1).unwrap();
+//!
+//! let mut vm = rbpf::EbpfVmNoData::new(&prog).unwrap();
+//! vm.register_map(3, &map);
+//! vm.prog_exec().unwrap();
+//! vm.prog_exec().unwrap();
+//!
+//! assert_eq!(map.lookup(&0u32.to_le_bytes()), Some(2u64.to_le_bytes().to_vec()));
+//! ```
+
+use std::cell::UnsafeCell;
+use std::collections::HashMap;
+use std::sync::{Mutex, MutexGuard};
+
+use error::MapError;
+
+/// Index of helper `bpf_map_lookup_elem()` in Linux kernel.
+pub const BPF_MAP_LOOKUP_ELEM_IDX: u32 = 1;
+/// Index of helper `bpf_map_update_elem()` in Linux kernel.
+pub const BPF_MAP_UPDATE_ELEM_IDX: u32 = 2;
+/// Index of helper `bpf_map_delete_elem()` in Linux kernel.
+pub const BPF_MAP_DELETE_ELEM_IDX: u32 = 3;
+
+/// Flag for map updates: create a new element or update an existing one.
+pub const BPF_ANY:     u64 = 0;
+/// Flag for map updates: create a new element only if it did not exist.
+pub const BPF_NOEXIST: u64 = 1;
+/// Flag for map updates: update an existing element only.
+pub const BPF_EXIST:   u64 = 2;
+
+/// Maximum size of the keys, in bytes: as in the kernel, keys must fit in the stack of a program.
+pub const MAX_KEY_SIZE:   usize = 512;
+/// Maximum size of the values, in bytes: the largest allocation the kernel can make
+/// (`KMALLOC_MAX_SIZE`).
+pub const MAX_VALUE_SIZE: usize = 1 << 22;
+/// Maximum size of the storage for all values of a map, in bytes, as for array maps in the kernel.
+pub const MAX_MAP_SIZE:   usize = u32::MAX as usize;
+
+/// Type of an eBPF map.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MapType {
+    /// Hash map (`BPF_MAP_TYPE_HASH` in the kernel).
+    Hash,
+    /// Array map (`BPF_MAP_TYPE_ARRAY` in the kernel).
+    Array,
+}
+
+/// An eBPF map, see module documentation.
+pub struct Map {
+    map_type:    MapType,
+    key_size:    usize,
+    value_size:  usize,
+    max_entries: usize,
+    // Preallocated storage for `max_entries` values. Programs may write to it through the pointers
+    // returned by `bpf_map_lookup_elem()`, hence the `UnsafeCell`.
+    values:      UnsafeCell<Box<[u8]>>,
+    // For hash maps: slot in `values` for each key, and slots not in use.
+    slots:       Mutex<Slots>,
+}
+
+struct Slots {
+    used: HashMap<Vec<u8>, usize>,
+    free: Vec<usize>,
+}
+
+// Elements are created and deleted under the lock of the slots, so that maps can be shared
+// between threads. As in the kernel, values themselves are accessed through raw pointers without
+// synchronization, and concurrent accesses to the same element race.
+unsafe impl Sync for Map {}
+
+impl Map {
+
+    /// Create a new map, with all values zero-initialized for array maps, or no element for hash
+    /// maps.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if one of the sizes is null or above its maximum (`MAX_KEY_SIZE`,
+    /// `MAX_VALUE_SIZE`), if the key size of an array map is not 4, or if the storage for the
+    /// values would exceed `MAX_MAP_SIZE`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rbpf::maps::{Map, MapType};
+    ///
+    /// let map = Map::new(MapType::Hash, 8, 16, 1024).unwrap();
+    /// assert_eq!(map.value_size(), 16);
+    /// ```
+    pub fn new(map_type: MapType, key_size: usize, value_size: usize, max_entries: usize)
+        -> Result<Map, MapError> {
+        if key_size == 0 || key_size > MAX_KEY_SIZE ||
+           (map_type == MapType::Array && key_size != 4) {
+            return Err(MapError::InvalidKeySize(key_size));
+        }
+        if value_size == 0 || value_size > MAX_VALUE_SIZE {
+            return Err(MapError::InvalidValueSize(value_size));
+        }
+        let size = match value_size.checked_mul(max_entries) {
+            Some(size) if max_entries > 0 && size <= MAX_MAP_SIZE => size,
+            _ => return Err(MapError::InvalidMaxEntries(max_entries)),
+        };
+        Ok(Map {
+            map_type,
+            key_size,
+            value_size,
+            max_entries,
+            values:     UnsafeCell::new(vec![0u8; size].into_boxed_slice()),
+            slots:      Mutex::new(Slots {
+                used: HashMap::new(),
+                free: (0..max_entries).rev().collect(),
+            }),
+        })
+    }
+
+    /// Return the type of the map.
+    pub fn map_type(&self) -> MapType {
+        self.map_type
+    }
+
+    /// Return the size of the keys of the map, in bytes.
+    pub fn key_size(&self) -> usize {
+        self.key_size
+    }
+
+    /// Return the size of the values of the map, in bytes.
+    pub fn value_size(&self) -> usize {
+        self.value_size
+    }
+
+    /// Return the maximum number of elements in the map.
+    pub fn max_entries(&self) -> usize {
+        self.max_entries
+    }
+
+    // The slots are never left inconsistent, so the lock can be taken again after a panic.
+    fn slots<'b>(&'b self) -> MutexGuard<'b, Slots> {
+        self.slots.lock().unwrap_or_else(|err| err.into_inner())
+    }
+
+    // Slot of the value associated to `key`, if any.
+    fn slot(&self, slots: &Slots, key: &[u8]) -> Option<usize> {
+        match self.map_type {
+            MapType::Hash  => slots.used.get(key).cloned(),
+            MapType::Array => {
+                let index = u32::from_le_bytes([key[0], key[1], key[2], key[3]]) as usize;
+                if index < self.max_entries { Some(index) } else { None }
+            },
+        }
+    }
+
+    fn value_ptr(&self, slot: usize) -> *mut u8 {
+        unsafe { (*self.values.get()).as_mut_ptr().add(slot * self.value_size) }
+    }
+
+    fn check_key(&self, key: &[u8]) -> Result<(), MapError> {
+        if key.len() != self.key_size {
+            return Err(MapError::InvalidKeySize(key.len()));
+        }
+        Ok(())
+    }
+
+    /// Return a copy of the value associated to `key`, or `None` if there is no such element.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rbpf::maps::{Map, MapType};
+    ///
+    /// let map = Map::new(MapType::Array, 4, 2, 4).unwrap();
+    /// assert_eq!(map.lookup(&[0, 0, 0, 0]), Some(vec![0, 0]));
+    /// assert_eq!(map.lookup(&[4, 0, 0, 0]), None);
+    /// ```
+    pub fn lookup(&self, key: &[u8]) -> Option<Vec<u8>> {
+        if self.check_key(key).is_err() {
+            return None;
+        }
+        self.slot(&self.slots(), key).map(|slot| unsafe {
+            std::slice::from_raw_parts(self.value_ptr(slot), self.value_size).to_vec()
+        })
+    }
+
+    /// Create or update the element associated to `key`. `flags` is one of `BPF_ANY`,
+    /// `BPF_NOEXIST` or `BPF_EXIST`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the key or value size is wrong, if the flags are invalid or prevent the
+    /// update, if the key is out of an array map, or if a hash map is full.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rbpf::error::MapError;
+    /// use rbpf::maps::{Map, MapType, BPF_ANY, BPF_NOEXIST};
+    ///
+    /// let map = Map::new(MapType::Hash, 1, 1, 4).unwrap();
+    /// map.update(&[1], &[0x11], BPF_ANY).unwrap();
+    /// assert_eq!(map.update(&[1], &[0x22], BPF_NOEXIST), Err(MapError::KeyExists));
+    /// assert_eq!(map.lookup(&[1]), Some(vec![0x11]));
+    /// ```
+    pub fn update(&self, key: &[u8], value: &[u8], flags: u64) -> Result<(), MapError> {
+        self.check_key(key)?;
+        if value.len() != self.value_size {
+            return Err(MapError::InvalidValueSize(value.len()));
+        }
+        if flags > BPF_EXIST {
+            return Err(MapError::InvalidFlags(flags));
+        }
+
+        let mut slots = self.slots();
+        let slot = match (self.slot(&slots, key), self.map_type, flags) {
+            (Some(_), _, BPF_NOEXIST)       => return Err(MapError::KeyExists),
+            (Some(slot), _, _)              => slot,
+            (None, MapType::Array, _)       => return Err(MapError::KeyOutOfRange),
+            (None, MapType::Hash, BPF_EXIST) => return Err(MapError::KeyNotFound),
+            (None, MapType::Hash, _)        => {
+                let slot = slots.free.pop().ok_or(MapError::MapFull)?;
+                slots.used.insert(key.to_vec(), slot);
+                slot
+            },
+        };
+        // The value may come from the storage of the map itself, when passed by a program.
+        unsafe {
+            std::ptr::copy(value.as_ptr(), self.value_ptr(slot), self.value_size);
+        }
+        Ok(())
+    }
+
+    /// Delete the element associated to `key`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the key size is wrong, if there is no such element, or if the map is
+    /// an array map (elements of array maps cannot be deleted).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rbpf::error::MapError;
+    /// use rbpf::maps::{Map, MapType, BPF_ANY};
+    ///
+    /// let map = Map::new(MapType::Hash, 1, 1, 4).unwrap();
+    /// map.update(&[1], &[0x11], BPF_ANY).unwrap();
+    /// map.delete(&[1]).unwrap();
+    /// assert_eq!(map.delete(&[1]), Err(MapError::KeyNotFound));
+    /// ```
+    pub fn delete(&self, key: &[u8]) -> Result<(), MapError> {
+        self.check_key(key)?;
+        if self.map_type == MapType::Array {
+            return Err(MapError::NotSupported);
+        }
+        let mut slots = self.slots();
+        let slot = slots.used.remove(key).ok_or(MapError::KeyNotFound)?;
+        unsafe {
+            std::ptr::write_bytes(self.value_ptr(slot), 0, self.value_size);
+        }
+        slots.free.push(slot);
+        Ok(())
+    }
+
+    /// Return `true` if the `len` bytes at address `addr` are in the storage of the values of the
+    /// map. Used by the interpreter to check memory accesses.
+    pub fn contains(&self, addr: u64, len: usize) -> bool {
+        let (start, end) = self.value_region();
+        match addr.checked_add(len as u64) {
+            Some(last) => start <= addr && last <= end,
+            None       => false,
+        }
+    }
+
+    /// Return the start and end addresses of the storage of the values of the map. Used by the
+    /// JIT compiler to check memory accesses.
+    pub fn value_region(&self) -> (u64, u64) {
+        let start = self.value_ptr(0) as u64;
+        (start, start + (self.value_size * self.max_entries) as u64)
+    }
+}
+
+/// Signature of the map helpers. They are `unsafe`, since they dereference the pointers passed by
+/// the program: the virtual machine checks their arguments before calling them, and they cannot be
+/// registered as regular helpers.
+pub type MapHelper = unsafe fn (u64, u64, u64, u64, u64) -> u64;
+
+/// Return the map helper with kernel index `id`, if any.
+pub fn helper(id: u32) -> Option<MapHelper> {
+    match id {
+        BPF_MAP_LOOKUP_ELEM_IDX => Some(bpf_map_lookup_elem),
+        BPF_MAP_UPDATE_ELEM_IDX => Some(bpf_map_update_elem),
+        BPF_MAP_DELETE_ELEM_IDX => Some(bpf_map_delete_elem),
+        _                       => None,
+    }
+}
+
+// Read the key pointed to by `key` for the map at address `map`.
+unsafe fn map_and_key<'a>(map: u64, key: u64) -> (&'a Map, &'a [u8]) {
+    let map = &*(map as *const Map);
+    (map, std::slice::from_raw_parts(key as *const u8, map.key_size))
+}
+
+/// Look up the element associated to the key pointed to by `key`, in the map at address `map`
+/// (as loaded by a `lddw` instruction with `ebpf::BPF_PSEUDO_MAP_FD`). Return a pointer to the
+/// value, or 0 if there is no such element. Arguments 3 to 5 are unused.
+///
+/// Same as the kernel helper, the program can read and modify the value through the pointer.
+///
+/// # Safety
+///
+/// `map` must be the address of a live `Map`, and `key` the address of `key_size` readable bytes.
+#[allow(unused_variables)]
+pub unsafe fn bpf_map_lookup_elem (map: u64, key: u64, unused3: u64, unused4: u64, unused5: u64)
+    -> u64 {
+    let (map, key) = map_and_key(map, key);
+    match map.slot(&map.slots(), key) {
+        Some(slot) => map.value_ptr(slot) as u64,
+        None       => 0,
+    }
+}
+
+/// Create or update the element associated to the key pointed to by `key`, in the map at address
+/// `map`, with the value pointed to by `value`. `flags` is one of `BPF_ANY`, `BPF_NOEXIST` or
+/// `BPF_EXIST`. Return 0 on success, or a negative error code as the kernel helper. Argument 5 is
+/// unused.
+///
+/// # Safety
+///
+/// `map` must be the address of a live `Map`, `key` the address of `key_size` readable bytes, and
+/// `value` the address of `value_size` readable bytes.
+#[allow(unused_variables)]
+pub unsafe fn bpf_map_update_elem (map: u64, key: u64, value: u64, flags: u64, unused5: u64)
+    -> u64 {
+    let (map, key) = map_and_key(map, key);
+    let value = std::slice::from_raw_parts(value as *const u8, map.value_size);
+    match map.update(key, value, flags) {
+        Ok(())   => 0,
+        Err(err) => -err.errno() as u64,
+    }
+}
+
+/// Delete the element associated to the key pointed to by `key`, in the map at address `map`.
+/// Return 0 on success, or a negative error code as the kernel helper. Arguments 3 to 5 are
+/// unused.
+///
+/// # Safety
+///
+/// `map` must be the address of a live `Map`, and `key` the address of `key_size` readable bytes.
+#[allow(unused_variables)]
+pub unsafe fn bpf_map_delete_elem (map: u64, key: u64, unused3: u64, unused4: u64, unused5: u64)
+    -> u64 {
+    let (map, key) = map_and_key(map, key);
+    match map.delete(key) {
+        Ok(())   => 0,
+        Err(err) => -err.errno() as u64,
+    }
+}
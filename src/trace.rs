@@ -0,0 +1,101 @@
+// Copyright 2026 agent <agent@local>
+//
+// Licensed under the Apache License, Version 2.0 <http://www.apache.org/licenses/LICENSE-2.0> or
+// the MIT license <http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+
+//! This module contains the types used to trace the execution of eBPF programs by the interpreter,
+//! and a function to render a trace along with the disassembled instructions.
+//!
+//! Programs are traced by running them with the `prog_exec_traced()` functions of the virtual
+//! machines, which call the given callback with the state of the program before each instruction
+//! is executed. JIT-compiled programs are not traced.
+
+use std::collections::HashMap;
+
+use disassembler;
+use ebpf;
+use ProgramArray;
+
+/// State of the program before the interpreter executes one of its instructions.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TraceEntry {
+    /// Index in the program array of the program being run, if it was reached with a tail call,
+    /// or `None` for the program loaded into the VM.
+    pub prog_index: Option<usize>,
+    /// Index of the instruction in the program being run, 0 being the first instruction.
+    pub insn_ptr:   usize,
+    /// The instruction about to be executed. For `LD_DW_IMM`, this is the first half only.
+    pub insn:       ebpf::Insn,
+    /// Values of registers r0 to r10.
+    pub regs:       [u64; 11],
+}
+
+/// Render a trace as text, one line per executed instruction: its index, the instruction as
+/// disassembled by `disassembler::to_insn_vec()`, and the values of the registers before it is
+/// executed. `prog` is the program loaded into the VM, and `prog_array` the program array attached
+/// to it, if any, to disassemble the programs reached with tail calls. Instructions from programs
+/// that cannot be found are rendered with their opcode only.
+///
+/// # Examples
+///
+/// ```
+/// use rbpf::trace;
+///
+/// let prog = &[
+///     0xb7, 0x00, 0x00, 0x00, 0x02, 0x00, 0x00, 0x00, // mov r0, 2
+///     0x27, 0x00, 0x00, 0x00, 0x03, 0x00, 0x00, 0x00, // mul r0, 3
+///     0x95, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00  // exit
+/// ];
+///
+/// let vm = rbpf::EbpfVmNoData::new(prog).unwrap();
+/// let mut entries = vec![];
+/// vm.prog_exec_traced(&mut |entry| entries.push(entry.clone())).unwrap();
+///
+/// let text = trace::render(prog, None, &entries);
+/// let lines = text.lines().collect::<Vec<_>>();
+/// assert_eq!(lines.len(), 3);
+/// assert!(lines[1].starts_with("    1  mul64 r0, 0x3  r0=0x2 r1=0x0"));
+/// ```
+pub fn render(prog: &[u8], prog_array: Option<&ProgramArray>, trace: &[TraceEntry]) -> String {
+    // Disassemble each program once, and index the descriptions by instruction.
+    let mut descs: HashMap<Option<usize>, Vec<String>> = HashMap::new();
+    for entry in trace {
+        descs.entry(entry.prog_index).or_insert_with(|| {
+            let prog = match entry.prog_index {
+                None        => Some(prog),
+                Some(index) => prog_array.and_then(|array| array.get(index)),
+            };
+            let mut prog_descs = vec![];
+            for hl_insn in prog.map(disassembler::to_insn_vec).unwrap_or_default() {
+                let is_lddw = hl_insn.opc == ebpf::LD_DW_IMM;
+                prog_descs.push(hl_insn.desc);
+                // Placeholder for the second half of LD_DW_IMM, never executed.
+                if is_lddw {
+                    prog_descs.push(String::new());
+                }
+            }
+            prog_descs
+        });
+    }
+
+    let lines = trace.iter().map(|entry| {
+        let desc = match descs[&entry.prog_index].get(entry.insn_ptr) {
+            Some(desc) => desc.clone(),
+            None       => format!("opcode {:#04x}", entry.insn.opc),
+        };
+        (entry, desc)
+    }).collect::<Vec<_>>();
+    let width = lines.iter().map(|(_, desc)| desc.len()).max().unwrap_or(0);
+
+    let mut res = String::new();
+    for (entry, desc) in lines {
+        let regs = entry.regs.iter().enumerate()
+            .map(|(i, value)| format!("r{}={:#x}", i, value))
+            .collect::<Vec<String>>();
+        res.push_str(&format!("{:5}  {:width$}  {}\n", entry.insn_ptr, desc, regs.join(" "),
+                              width = width));
+    }
+    res
+}
@@ -9,233 +9,750 @@
 // copied, modified, or distributed except according to those terms.
 
 
-// This “verifier” performs simple checks when the eBPF program is loaded into the VM (before it is
-// interpreted or JIT-compiled). It has nothing to do with the much more elaborated verifier inside
-// Linux kernel. There is no verification regarding the program flow control (should be a Direct
-// Acyclic Graph) or the consistency for registers usage (the verifier of the kernel assigns types
-// to the registers and is much stricter).
-//
-// On the other hand, rbpf is not expected to run in kernel space.
-//
-// Improving the verifier would be nice, but this is not trivial (and Linux kernel is under GPL
-// license, so we cannot copy it).
-//
-// Contrary to the verifier of the Linux kernel, this one does not modify the bytecode at all.
+//! This “verifier” performs simple checks when the eBPF program is loaded into the VM (before it is
+//! interpreted or JIT-compiled). It has nothing to do with the much more elaborated verifier inside
+//! Linux kernel. By default, there is no verification regarding the program flow control (should
+//! be a Direct Acyclic Graph) or the consistency for registers usage (the verifier of the kernel
+//! assigns types to the registers and is much stricter).
+//!
+//! Stricter checks are available as separate functions, and can be used by the VMs instead of
+//! `check()` with their `set_verifier()` function. `check_cfg()` builds the control-flow graph of
+//! the program, and rejects loops, unreachable instructions and paths falling off the end of the
+//! program or of a local function. `check_types()` also tracks the type of the values held by the
+//! registers, and rejects reads of uninitialized registers, arithmetic operations forging
+//! pointers, memory accesses through registers that do not hold pointers, accesses to the stack
+//! or to map values out of their bounds, reads of stack bytes never written, and invalid
+//! arguments to map helpers. `stack_depth()` runs the same checks, and returns the maximum number
+//! of bytes of stack used by the program.
+//!
+//! `diagnose()` runs the same checks as `check()`, but reports all the problems found in the
+//! program instead of the first one, and `render_diagnostics()` displays them next to the
+//! disassembled program.
+//!
+//! On the other hand, rbpf is not expected to run in kernel space.
+//!
+//! Improving the verifier would be nice, but this is not trivial (and Linux kernel is under GPL
+//! license, so we cannot copy it).
+//!
+//! Contrary to the verifier of the Linux kernel, this one does not modify the bytecode at all.
 
 
+use analysis::{self, Context, Log, MapDef};
+use cfg::Cfg;
+use disassembler;
 use ebpf;
+use error::EbpfError;
 
-fn check_prog_len(prog: &[u8]) {
+/// Function checking a program when it is loaded into a VM. See `set_verifier()` on the VMs.
+pub type Verifier = fn(prog: &[u8]) -> Result<(), EbpfError>;
+
+fn check_prog_len<F>(prog: &[u8], report: &mut F) -> Result<(), EbpfError>
+    where F: FnMut(EbpfError) -> Result<(), EbpfError> {
     if prog.len() % ebpf::INSN_SIZE != 0 {
-        panic!("[Verifier] Error: eBPF program length must be a multiple of {:?} octets",
-               ebpf::INSN_SIZE);
+        report(EbpfError::WrongLength(prog.len()))?;
     }
     if prog.len() > ebpf::PROG_MAX_SIZE {
-        panic!("[Verifier] Error: eBPF program length limited to {:?}, here {:?}",
-               ebpf::PROG_MAX_INSNS, prog.len() / ebpf::INSN_SIZE);
+        report(EbpfError::TooManyInstructions(prog.len() / ebpf::INSN_SIZE))?;
     }
 
-    if prog.len() == 0 {
-        panic!("[Verifier] Error: program does not end with “EXIT” instruction");
-    }
-    let last_insn = ebpf::get_insn(prog, (prog.len() / ebpf::INSN_SIZE) - 1);
-    if last_insn.opc != ebpf::EXIT {
-        panic!("[Verifier] Error: program does not end with “EXIT” instruction");
+    let num_insns = prog.len() / ebpf::INSN_SIZE;
+    if num_insns == 0 || ebpf::get_insn(prog, num_insns - 1).opc != ebpf::EXIT {
+        report(EbpfError::NoExit)?;
     }
+
+    Ok(())
 }
 
-fn check_imm_nonzero(insn: &ebpf::Insn, insn_ptr: usize) {
+fn check_imm_nonzero(insn: &ebpf::Insn, insn_ptr: usize) -> Result<(), EbpfError> {
     if insn.imm == 0 {
-        panic!("[Verifier] Error: division by 0 (insn #{:?})", insn_ptr);
+        return Err(EbpfError::DivisionByZero(insn_ptr));
     }
+    Ok(())
 }
 
-fn check_imm_endian(insn: &ebpf::Insn, insn_ptr: usize) {
+fn check_imm_endian(insn: &ebpf::Insn, insn_ptr: usize) -> Result<(), EbpfError> {
     match insn.imm {
-        16 | 32 | 64 => return,
-        _ => panic!("[Verifier] Error: unsupported argument for LE/BE (insn #{:?})", insn_ptr)
+        16 | 32 | 64 => Ok(()),
+        _ => Err(EbpfError::UnsupportedEndianSize(insn_ptr)),
     }
 }
 
-fn check_load_dw(prog: &[u8], insn_ptr: usize) {
-    // We know we can reach next insn since we enforce an EXIT insn at the end of program, while
-    // this function should be called only for LD_DW insn, that cannot be last in program.
+fn check_load_dw(prog: &[u8], insn_ptr: usize) -> Result<(), EbpfError> {
+    // The program may not end with an EXIT insn when collecting all diagnostics.
+    if (insn_ptr + 2) * ebpf::INSN_SIZE > prog.len() {
+        return Err(EbpfError::IncompleteLdDw(insn_ptr));
+    }
     let next_insn = ebpf::get_insn(prog, insn_ptr + 1);
     if next_insn.opc != 0 {
-        panic!("[Verifier] Error: incomplete LD_DW instruction (insn #{:?})", insn_ptr);
+        return Err(EbpfError::IncompleteLdDw(insn_ptr));
     }
-
+    Ok(())
 }
 
-fn check_jmp_offset(prog: &[u8], insn_ptr: usize) {
+fn check_jmp_offset(prog: &[u8], insn_ptr: usize) -> Result<(), EbpfError> {
     let insn = ebpf::get_insn(prog, insn_ptr);
     if insn.off == -1 {
-        panic!("[Verifier] Error: infinite loop (insn #{:?})", insn_ptr);
+        return Err(EbpfError::InfiniteLoop(insn_ptr));
     }
 
-    let dst_insn_ptr = insn_ptr as isize + 1 + insn.off as isize;
+    check_jmp_target(prog, insn_ptr as isize + 1 + insn.off as isize, insn_ptr)
+}
+
+// Local function calls hold the offset to the called function in `imm`.
+fn check_call_target(prog: &[u8], insn_ptr: usize) -> Result<(), EbpfError> {
+    let insn = ebpf::get_insn(prog, insn_ptr);
+    check_jmp_target(prog, insn_ptr as isize + 1 + insn.imm as isize, insn_ptr)
+}
+
+// The target of a jump or call must be an instruction of the program, and not the second half of
+// a `lddw`.
+fn check_jmp_target(prog: &[u8], dst_insn_ptr: isize, insn_ptr: usize) -> Result<(), EbpfError> {
     if dst_insn_ptr < 0 || dst_insn_ptr as usize >= (prog.len() / ebpf::INSN_SIZE) {
-        panic!("[Verifier] Error: jump out of code to #{:?} (insn #{:?})",
-               dst_insn_ptr, insn_ptr);
+        return Err(EbpfError::JumpOutOfCode(dst_insn_ptr, insn_ptr));
     }
 
     let dst_insn = ebpf::get_insn(prog, dst_insn_ptr as usize);
     if dst_insn.opc == 0 {
-        panic!("[Verifier] Error: jump to middle of LD_DW at #{:?} (insn #{:?})",
-               dst_insn_ptr, insn_ptr);
+        return Err(EbpfError::JumpToMiddleOfLdDw(dst_insn_ptr as usize, insn_ptr));
     }
+    Ok(())
 }
 
-fn check_registers(insn: &ebpf::Insn, store: bool, insn_ptr: usize) {
+fn check_registers(insn: &ebpf::Insn, store: bool, insn_ptr: usize) -> Result<(), EbpfError> {
     if insn.src > 10 {
-        panic!("[Verifier] Error: invalid source register (insn #{:?})", insn_ptr);
+        return Err(EbpfError::InvalidSrcRegister(insn_ptr));
     }
 
     match (insn.dst, store) {
-        (0 ... 9, _) | (10, true) => {},
-        (10, false) => panic!("[Verifier] Error: cannot write into register r10 (insn #{:?})",
-                              insn_ptr),
-        (_, _)      => panic!("[Verifier] Error: invalid destination register (insn #{:?})",
-                              insn_ptr)
+        (0 ..= 9, _) | (10, true) => Ok(()),
+        (10, false) => Err(EbpfError::CannotWriteR10(insn_ptr)),
+        (_, _)      => Err(EbpfError::InvalidDstRegister(insn_ptr)),
     }
 }
 
-pub fn check(prog: &[u8]) -> bool {
-    check_prog_len(prog);
+/// Check that an eBPF program is valid, as far as this simple verifier is concerned.
+///
+/// This is called by the virtual machines each time a program is loaded, but it can also be used
+/// on its own, to validate some bytecode before handing it over to a VM.
+///
+/// # Errors
+///
+/// Returns the first problem found in the program, with the index of the faulty instruction when
+/// relevant.
+///
+/// # Examples
+///
+/// ```
+/// use rbpf::verifier;
+/// use rbpf::error::EbpfError;
+///
+/// let prog = &[
+///     0xb7, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // mov r0, 0
+///     0x95, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00  // exit
+/// ];
+/// assert_eq!(verifier::check(prog), Ok(()));
+///
+/// let prog = &[
+///     0xb7, 0x0a, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // mov r10, 0
+///     0x95, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00  // exit
+/// ];
+/// assert_eq!(verifier::check(prog), Err(EbpfError::CannotWriteR10(0)));
+/// ```
+pub fn check(prog: &[u8]) -> Result<(), EbpfError> {
+    check_insns(prog, &mut |err| Err(err))
+}
+
+// Check the instructions of a program, and pass each problem found to `report`. Stop at the first
+// error returned by `report`.
+fn check_insns<F>(prog: &[u8], report: &mut F) -> Result<(), EbpfError>
+    where F: FnMut(EbpfError) -> Result<(), EbpfError> {
+    check_prog_len(prog, report)?;
 
+    let num_insns = prog.len() / ebpf::INSN_SIZE;
     let mut insn_ptr:usize = 0;
-    while insn_ptr * ebpf::INSN_SIZE < prog.len() {
+    while insn_ptr < num_insns {
         let insn = ebpf::get_insn(prog, insn_ptr);
-        let mut store = false;
-
-        match insn.opc {
-
-            // BPF_LD class
-            ebpf::LD_ABS_B   => { unimplemented!(); },
-            ebpf::LD_ABS_H   => { unimplemented!(); },
-            ebpf::LD_ABS_W   => { unimplemented!(); },
-            ebpf::LD_ABS_DW  => { unimplemented!(); },
-            ebpf::LD_IND_B   => { unimplemented!(); },
-            ebpf::LD_IND_H   => { unimplemented!(); },
-            ebpf::LD_IND_W   => { unimplemented!(); },
-            ebpf::LD_IND_DW  => { unimplemented!(); },
-
-            // BPF_LDX class
-            ebpf::LD_DW_IMM  => {
-                store = true;
-                check_load_dw(prog, insn_ptr);
-                insn_ptr += 1;
+        if let Err(err) = check_opcode(prog, &insn, insn_ptr) {
+            report(err)?;
+        }
+
+        let store = match insn.opc & ebpf::BPF_CLS_MASK {
+            ebpf::BPF_ST | ebpf::BPF_STX => true,
+            _                            => insn.opc == ebpf::LD_DW_IMM,
+        };
+        if let Err(err) = check_registers(&insn, store, insn_ptr) {
+            report(err)?;
+        }
+
+        insn_ptr = next_insn_ptr(prog, &insn, insn_ptr);
+    }
+
+    Ok(())
+}
+
+// Check the operation code of an instruction, and the operands specific to this operation.
+fn check_opcode(prog: &[u8], insn: &ebpf::Insn, insn_ptr: usize) -> Result<(), EbpfError> {
+    match insn.opc {
+
+        // BPF_LD class
+        ebpf::LD_ABS_B   => {},
+        ebpf::LD_ABS_H   => {},
+        ebpf::LD_ABS_W   => {},
+        ebpf::LD_ABS_DW  => {},
+        ebpf::LD_IND_B   => {},
+        ebpf::LD_IND_H   => {},
+        ebpf::LD_IND_W   => {},
+        ebpf::LD_IND_DW  => {},
+
+        // BPF_LDX class
+        ebpf::LD_DW_IMM  => {
+            if insn.src != 0 && insn.src != ebpf::BPF_PSEUDO_MAP_FD {
+                return Err(EbpfError::InvalidSrcRegister(insn_ptr));
+            }
+            check_load_dw(prog, insn_ptr)?;
+        },
+        ebpf::LD_B_REG   => {},
+        ebpf::LD_H_REG   => {},
+        ebpf::LD_W_REG   => {},
+        ebpf::LD_DW_REG  => {},
+
+        // BPF_ST class
+        ebpf::ST_B_IMM   => {},
+        ebpf::ST_H_IMM   => {},
+        ebpf::ST_W_IMM   => {},
+        ebpf::ST_DW_IMM  => {},
+
+        // BPF_STX class
+        ebpf::ST_B_REG   => {},
+        ebpf::ST_H_REG   => {},
+        ebpf::ST_W_REG   => {},
+        ebpf::ST_DW_REG  => {},
+        ebpf::ST_W_XADD  => {},
+        ebpf::ST_DW_XADD => {},
+
+        // BPF_ALU class
+        ebpf::ADD32_IMM  => {},
+        ebpf::ADD32_REG  => {},
+        ebpf::SUB32_IMM  => {},
+        ebpf::SUB32_REG  => {},
+        ebpf::MUL32_IMM  => {},
+        ebpf::MUL32_REG  => {},
+        ebpf::DIV32_IMM  => { check_imm_nonzero(insn, insn_ptr)?; },
+        ebpf::DIV32_REG  => {},
+        ebpf::OR32_IMM   => {},
+        ebpf::OR32_REG   => {},
+        ebpf::AND32_IMM  => {},
+        ebpf::AND32_REG  => {},
+        ebpf::LSH32_IMM  => {},
+        ebpf::LSH32_REG  => {},
+        ebpf::RSH32_IMM  => {},
+        ebpf::RSH32_REG  => {},
+        ebpf::NEG32      => {},
+        ebpf::MOD32_IMM  => { check_imm_nonzero(insn, insn_ptr)?; },
+        ebpf::MOD32_REG  => {},
+        ebpf::XOR32_IMM  => {},
+        ebpf::XOR32_REG  => {},
+        ebpf::MOV32_IMM  => {},
+        ebpf::MOV32_REG  => {},
+        ebpf::ARSH32_IMM => {},
+        ebpf::ARSH32_REG => {},
+        ebpf::LE         => { check_imm_endian(insn, insn_ptr)?; },
+        ebpf::BE         => { check_imm_endian(insn, insn_ptr)?; },
+
+        // BPF_ALU64 class
+        ebpf::ADD64_IMM  => {},
+        ebpf::ADD64_REG  => {},
+        ebpf::SUB64_IMM  => {},
+        ebpf::SUB64_REG  => {},
+        ebpf::MUL64_IMM  => {},
+        ebpf::MUL64_REG  => {},
+        ebpf::DIV64_IMM  => { check_imm_nonzero(insn, insn_ptr)?; },
+        ebpf::DIV64_REG  => {},
+        ebpf::OR64_IMM   => {},
+        ebpf::OR64_REG   => {},
+        ebpf::AND64_IMM  => {},
+        ebpf::AND64_REG  => {},
+        ebpf::LSH64_IMM  => {},
+        ebpf::LSH64_REG  => {},
+        ebpf::RSH64_IMM  => {},
+        ebpf::RSH64_REG  => {},
+        ebpf::NEG64      => {},
+        ebpf::MOD64_IMM  => { check_imm_nonzero(insn, insn_ptr)?; },
+        ebpf::MOD64_REG  => {},
+        ebpf::XOR64_IMM  => {},
+        ebpf::XOR64_REG  => {},
+        ebpf::MOV64_IMM  => {},
+        ebpf::MOV64_REG  => {},
+        ebpf::ARSH64_IMM => {},
+        ebpf::ARSH64_REG => {},
+
+        // BPF_JMP class
+        ebpf::JA         => { check_jmp_offset(prog, insn_ptr)?; },
+        ebpf::JEQ_IMM    => { check_jmp_offset(prog, insn_ptr)?; },
+        ebpf::JEQ_REG    => { check_jmp_offset(prog, insn_ptr)?; },
+        ebpf::JGT_IMM    => { check_jmp_offset(prog, insn_ptr)?; },
+        ebpf::JGT_REG    => { check_jmp_offset(prog, insn_ptr)?; },
+        ebpf::JGE_IMM    => { check_jmp_offset(prog, insn_ptr)?; },
+        ebpf::JGE_REG    => { check_jmp_offset(prog, insn_ptr)?; },
+        ebpf::JSET_IMM   => { check_jmp_offset(prog, insn_ptr)?; },
+        ebpf::JSET_REG   => { check_jmp_offset(prog, insn_ptr)?; },
+        ebpf::JNE_IMM    => { check_jmp_offset(prog, insn_ptr)?; },
+        ebpf::JNE_REG    => { check_jmp_offset(prog, insn_ptr)?; },
+        ebpf::JSGT_IMM   => { check_jmp_offset(prog, insn_ptr)?; },
+        ebpf::JSGT_REG   => { check_jmp_offset(prog, insn_ptr)?; },
+        ebpf::JSGE_IMM   => { check_jmp_offset(prog, insn_ptr)?; },
+        ebpf::JSGE_REG   => { check_jmp_offset(prog, insn_ptr)?; },
+        ebpf::JLT_IMM    => { check_jmp_offset(prog, insn_ptr)?; },
+        ebpf::JLT_REG    => { check_jmp_offset(prog, insn_ptr)?; },
+        ebpf::JLE_IMM    => { check_jmp_offset(prog, insn_ptr)?; },
+        ebpf::JLE_REG    => { check_jmp_offset(prog, insn_ptr)?; },
+        ebpf::JSLT_IMM   => { check_jmp_offset(prog, insn_ptr)?; },
+        ebpf::JSLT_REG   => { check_jmp_offset(prog, insn_ptr)?; },
+        ebpf::JSLE_IMM   => { check_jmp_offset(prog, insn_ptr)?; },
+        ebpf::JSLE_REG   => { check_jmp_offset(prog, insn_ptr)?; },
+
+        // BPF_JMP32 class
+        ebpf::JEQ_IMM32  => { check_jmp_offset(prog, insn_ptr)?; },
+        ebpf::JEQ_REG32  => { check_jmp_offset(prog, insn_ptr)?; },
+        ebpf::JGT_IMM32  => { check_jmp_offset(prog, insn_ptr)?; },
+        ebpf::JGT_REG32  => { check_jmp_offset(prog, insn_ptr)?; },
+        ebpf::JGE_IMM32  => { check_jmp_offset(prog, insn_ptr)?; },
+        ebpf::JGE_REG32  => { check_jmp_offset(prog, insn_ptr)?; },
+        ebpf::JSET_IMM32 => { check_jmp_offset(prog, insn_ptr)?; },
+        ebpf::JSET_REG32 => { check_jmp_offset(prog, insn_ptr)?; },
+        ebpf::JNE_IMM32  => { check_jmp_offset(prog, insn_ptr)?; },
+        ebpf::JNE_REG32  => { check_jmp_offset(prog, insn_ptr)?; },
+        ebpf::JSGT_IMM32 => { check_jmp_offset(prog, insn_ptr)?; },
+        ebpf::JSGT_REG32 => { check_jmp_offset(prog, insn_ptr)?; },
+        ebpf::JSGE_IMM32 => { check_jmp_offset(prog, insn_ptr)?; },
+        ebpf::JSGE_REG32 => { check_jmp_offset(prog, insn_ptr)?; },
+        ebpf::JLT_IMM32  => { check_jmp_offset(prog, insn_ptr)?; },
+        ebpf::JLT_REG32  => { check_jmp_offset(prog, insn_ptr)?; },
+        ebpf::JLE_IMM32  => { check_jmp_offset(prog, insn_ptr)?; },
+        ebpf::JLE_REG32  => { check_jmp_offset(prog, insn_ptr)?; },
+        ebpf::JSLT_IMM32 => { check_jmp_offset(prog, insn_ptr)?; },
+        ebpf::JSLT_REG32 => { check_jmp_offset(prog, insn_ptr)?; },
+        ebpf::JSLE_IMM32 => { check_jmp_offset(prog, insn_ptr)?; },
+        ebpf::JSLE_REG32 => { check_jmp_offset(prog, insn_ptr)?; },
+
+        ebpf::CALL       => match insn.src {
+            0                     => {},
+            ebpf::BPF_PSEUDO_CALL => { check_call_target(prog, insn_ptr)?; },
+            _                     => { return Err(EbpfError::InvalidSrcRegister(insn_ptr)); },
+        },
+        ebpf::TAIL_CALL  => {},
+        ebpf::EXIT       => {},
+
+        _                => {
+            return Err(EbpfError::UnknownOpcode(insn.opc, insn_ptr));
+        },
+    }
+
+    Ok(())
+}
+
+/// Check a program with `check()`, then check its control-flow graph: reject loops (including
+/// recursive local calls), instructions that cannot be reached, and paths that fall off the end of
+/// the program or of a local function without reaching an `EXIT` instruction.
+///
+/// Loops are not rejected by `check()`: programs with loops can be run with an instruction budget
+/// instead (see `set_insn_budget()` on the VMs).
+///
+/// # Errors
+///
+/// Returns the first problem found in the program. Loops are reported with the target and the
+/// index of the jump or call closing them.
+///
+/// # Examples
+///
+/// ```
+/// use rbpf::verifier;
+/// use rbpf::error::EbpfError;
+///
+/// let prog = &[
+///     0xb7, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // mov r0, 0
+///     0x07, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, // add r0, 1
+///     0x55, 0x00, 0xfe, 0xff, 0x0a, 0x00, 0x00, 0x00, // jne r0, 10, -2
+///     0x95, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00  // exit
+/// ];
+/// assert_eq!(verifier::check(prog), Ok(()));
+/// assert_eq!(verifier::check_cfg(prog), Err(EbpfError::Loop(1, 2)));
+///
+/// // The VMs can use this verifier instead of the default one.
+/// let mut vm = rbpf::EbpfVmNoData::new(prog).unwrap();
+/// assert!(vm.set_verifier(verifier::check_cfg).is_err());
+/// ```
+pub fn check_cfg(prog: &[u8]) -> Result<(), EbpfError> {
+    check(prog)?;
+
+    let cfg = Cfg::new(prog);
+    let blocks = cfg.blocks();
+
+    // Depth-first search from the entry point: an edge to a block being explored closes a loop.
+    #[derive(Clone, Copy, PartialEq)]
+    enum Mark { New, OnPath, Done }
+    let mut marks = vec![Mark::New; blocks.len()];
+    let mut path = vec![(0, 0)];
+    marks[0] = Mark::OnPath;
+    while let Some(&mut (block, ref mut next_succ)) = path.last_mut() {
+        match blocks[block].succs.get(*next_succ) {
+            Some(&succ) => {
+                *next_succ += 1;
+                match marks[succ] {
+                    Mark::New    => {
+                        marks[succ] = Mark::OnPath;
+                        path.push((succ, 0));
+                    },
+                    Mark::OnPath => return Err(EbpfError::Loop(blocks[succ].start,
+                                                                blocks[block].last)),
+                    Mark::Done   => (),
+                }
             },
-            ebpf::LD_B_REG   => {},
-            ebpf::LD_H_REG   => {},
-            ebpf::LD_W_REG   => {},
-            ebpf::LD_DW_REG  => {},
-
-            // BPF_ST class
-            ebpf::ST_B_IMM   => store = true,
-            ebpf::ST_H_IMM   => store = true,
-            ebpf::ST_W_IMM   => store = true,
-            ebpf::ST_DW_IMM  => store = true,
-
-            // BPF_STX class
-            ebpf::ST_B_REG   => store = true,
-            ebpf::ST_H_REG   => store = true,
-            ebpf::ST_W_REG   => store = true,
-            ebpf::ST_DW_REG  => store = true,
-            ebpf::ST_W_XADD  => { unimplemented!(); },
-            ebpf::ST_DW_XADD => { unimplemented!(); },
-
-            // BPF_ALU class
-            ebpf::ADD32_IMM  => {},
-            ebpf::ADD32_REG  => {},
-            ebpf::SUB32_IMM  => {},
-            ebpf::SUB32_REG  => {},
-            ebpf::MUL32_IMM  => {},
-            ebpf::MUL32_REG  => {},
-            ebpf::DIV32_IMM  => { check_imm_nonzero(&insn, insn_ptr); },
-            ebpf::DIV32_REG  => {},
-            ebpf::OR32_IMM   => {},
-            ebpf::OR32_REG   => {},
-            ebpf::AND32_IMM  => {},
-            ebpf::AND32_REG  => {},
-            ebpf::LSH32_IMM  => {},
-            ebpf::LSH32_REG  => {},
-            ebpf::RSH32_IMM  => {},
-            ebpf::RSH32_REG  => {},
-            ebpf::NEG32      => {},
-            ebpf::MOD32_IMM  => { check_imm_nonzero(&insn, insn_ptr); },
-            ebpf::MOD32_REG  => {},
-            ebpf::XOR32_IMM  => {},
-            ebpf::XOR32_REG  => {},
-            ebpf::MOV32_IMM  => {},
-            ebpf::MOV32_REG  => {},
-            ebpf::ARSH32_IMM => {},
-            ebpf::ARSH32_REG => {},
-            ebpf::LE         => { check_imm_endian(&insn, insn_ptr); },
-            ebpf::BE         => { check_imm_endian(&insn, insn_ptr); },
-
-            // BPF_ALU64 class
-            ebpf::ADD64_IMM  => {},
-            ebpf::ADD64_REG  => {},
-            ebpf::SUB64_IMM  => {},
-            ebpf::SUB64_REG  => {},
-            ebpf::MUL64_IMM  => { check_imm_nonzero(&insn, insn_ptr); },
-            ebpf::MUL64_REG  => {},
-            ebpf::DIV64_IMM  => { check_imm_nonzero(&insn, insn_ptr); },
-            ebpf::DIV64_REG  => {},
-            ebpf::OR64_IMM   => {},
-            ebpf::OR64_REG   => {},
-            ebpf::AND64_IMM  => {},
-            ebpf::AND64_REG  => {},
-            ebpf::LSH64_IMM  => {},
-            ebpf::LSH64_REG  => {},
-            ebpf::RSH64_IMM  => {},
-            ebpf::RSH64_REG  => {},
-            ebpf::NEG64      => {},
-            ebpf::MOD64_IMM  => {},
-            ebpf::MOD64_REG  => {},
-            ebpf::XOR64_IMM  => {},
-            ebpf::XOR64_REG  => {},
-            ebpf::MOV64_IMM  => {},
-            ebpf::MOV64_REG  => {},
-            ebpf::ARSH64_IMM => {},
-            ebpf::ARSH64_REG => {},
-
-            // BPF_JMP class
-            ebpf::JA         => { check_jmp_offset(prog, insn_ptr); },
-            ebpf::JEQ_IMM    => { check_jmp_offset(prog, insn_ptr); },
-            ebpf::JEQ_REG    => { check_jmp_offset(prog, insn_ptr); },
-            ebpf::JGT_IMM    => { check_jmp_offset(prog, insn_ptr); },
-            ebpf::JGT_REG    => { check_jmp_offset(prog, insn_ptr); },
-            ebpf::JGE_IMM    => { check_jmp_offset(prog, insn_ptr); },
-            ebpf::JGE_REG    => { check_jmp_offset(prog, insn_ptr); },
-            ebpf::JSET_IMM   => { check_jmp_offset(prog, insn_ptr); },
-            ebpf::JSET_REG   => { check_jmp_offset(prog, insn_ptr); },
-            ebpf::JNE_IMM    => { check_jmp_offset(prog, insn_ptr); },
-            ebpf::JNE_REG    => { check_jmp_offset(prog, insn_ptr); },
-            ebpf::JSGT_IMM   => { check_jmp_offset(prog, insn_ptr); },
-            ebpf::JSGT_REG   => { check_jmp_offset(prog, insn_ptr); },
-            ebpf::JSGE_IMM   => { check_jmp_offset(prog, insn_ptr); },
-            ebpf::JSGE_REG   => { check_jmp_offset(prog, insn_ptr); },
-            ebpf::CALL       => {},
-            ebpf::TAIL_CALL  => { unimplemented!() },
-            ebpf::EXIT       => {},
-
-            _                => {
-                panic!("[Verifier] Error: unknown eBPF opcode {:#2x} (insn #{:?})",
-                       insn.opc, insn_ptr);
+            None => {
+                marks[block] = Mark::Done;
+                path.pop();
             },
         }
+    }
+
+    if let Some(block) = blocks.iter().zip(&marks).find(|(_, &mark)| mark == Mark::New) {
+        return Err(EbpfError::UnreachableCode(block.0.start));
+    }
+
+    // A block that does not end with an unconditional jump or `EXIT` goes on with the next
+    // instruction, which must not be the first one of another function, or past the program.
+    let num_insns = prog.len() / ebpf::INSN_SIZE;
+    for block in blocks {
+        let opc = ebpf::get_insn(prog, block.last).opc;
+        if opc != ebpf::JA && opc != ebpf::EXIT &&
+           (block.end == num_insns || cfg.functions().binary_search(&block.end).is_ok()) {
+            return Err(EbpfError::FallOffEnd(block.last));
+        }
+    }
+
+    Ok(())
+}
+
+/// Check a program with `check_cfg()`, then follow all its paths to track the type of the values
+/// held by the registers: uninitialized, number, or pointer to the context, to packet data, to the
+/// stack or to a map value. With `Context::FixedMbuff`, accesses to packet data must also be
+/// preceded by a comparison with the end of packet data. See the `analysis` module for details.
+///
+/// `ctx` tells what the program receives in `r1`, and depends on the VM the program runs in.
+/// `maps` describes the maps registered into the VM: the program cannot use any other map. To
+/// use this function as the verifier of a VM, wrap it into a closure that does not capture
+/// anything.
+///
+/// # Errors
+///
+/// Returns the first problem found in the program, such as a read of an uninitialized register,
+/// an arithmetic operation that would forge a pointer, a memory access through a register that
+/// does not hold a pointer, a memory access that may be out of bounds, including accesses to the
+/// stack outside of the `ebpf::STACK_SIZE` bytes below `r10`, a read of stack bytes that have
+/// not been written, the use of an unknown map, or an argument of the wrong type passed to a map
+/// helper.
+///
+/// # Examples
+///
+/// ```
+/// use rbpf::analysis::{Context, MapDef};
+/// use rbpf::verifier;
+/// use rbpf::error::EbpfError;
+///
+/// let prog = &[
+///     0x71, 0x10, 0x02, 0x00, 0x00, 0x00, 0x00, 0x00, // ldxb r0, [r1+2]
+///     0x95, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00  // exit
+/// ];
+/// assert_eq!(verifier::check_types(prog, Context::Memory, &[]), Ok(()));
+/// // There is no pointer in r1 without data.
+/// assert_eq!(verifier::check_types(prog, Context::NoData, &[]),
+///            Err(EbpfError::InvalidMemoryAccess(1, 0)));
+///
+/// let prog = &[
+///     0x18, 0x11, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, // lddw r1, map_fd(1)
+///     0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+///     0xb7, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // mov r0, 0
+///     0x95, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00  // exit
+/// ];
+/// let maps = &[MapDef { fd: 1, key_size: 4, value_size: 8 }];
+/// assert_eq!(verifier::check_types(prog, Context::NoData, maps), Ok(()));
+/// assert_eq!(verifier::check_types(prog, Context::NoData, &[]),
+///            Err(EbpfError::UnknownMap(1, 0)));
+///
+/// let prog = &[
+///     0xbf, 0x20, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // mov r0, r2
+///     0x95, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00  // exit
+/// ];
+/// assert_eq!(verifier::check_types(prog, Context::Memory, &[]),
+///            Err(EbpfError::UninitializedRegister(2, 0)));
+///
+/// // The VMs can use this verifier instead of the default one.
+/// let mut vm = rbpf::EbpfVmNoData::new(prog).unwrap();
+/// assert!(vm.set_verifier(|prog| verifier::check_types(prog, Context::NoData, &[])).is_err());
+/// ```
+pub fn check_types(prog: &[u8], ctx: Context, maps: &[MapDef]) -> Result<(), EbpfError> {
+    check_cfg(prog)?;
+    analysis::analyze(prog, ctx, maps)
+}
+
+/// Same as `check_types()`, but also record the log of the analysis into `log`, which is cleared
+/// first: the types of the registers and stack slots before each processed instruction, and the
+/// path leading to the problem found, if any. See `analysis::Log`.
+///
+/// # Errors
+///
+/// Returns the first problem found in the program, which is also stored in `log.error`. If the
+/// program is rejected by `check_cfg()`, the log has no entries.
+///
+/// # Examples
+///
+/// ```
+/// use rbpf::analysis::{Context, Log};
+/// use rbpf::verifier;
+/// use rbpf::error::EbpfError;
+///
+/// let prog = &[
+///     0x15, 0x01, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, // jeq r1, 0, +1
+///     0xb7, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // mov r0, 0
+///     0x95, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00  // exit
+/// ];
+///
+/// let mut log = Log::default();
+/// let err = verifier::check_types_with_log(prog, Context::NoData, &[], &mut log).unwrap_err();
+/// assert_eq!(err, EbpfError::UninitializedRegister(0, 2));
+/// // r0 is not set when the jump is taken.
+/// assert_eq!(log.error_path(), vec![0, 2]);
+/// assert!(log.to_string().contains("from 0 to 2:\n2: exit\n    r1=0x0 r10=fp0+0\n"));
+/// ```
+pub fn check_types_with_log(prog: &[u8], ctx: Context, maps: &[MapDef], log: &mut Log)
+                            -> Result<(), EbpfError> {
+    if let Err(err) = check_cfg(prog) {
+        *log = Log::default();
+        log.error = Some(err.clone());
+        return Err(err);
+    }
+    analysis::analyze_with_log(prog, ctx, maps, log)
+}
+
+/// Check a program with `check_types()`, and return the maximum number of bytes of stack it uses,
+/// adding up the stacks of the local functions that can be called from one another. Each call
+/// frame can use up to `ebpf::STACK_SIZE` bytes.
+///
+/// # Errors
+///
+/// Returns the first problem found in the program, as `check_types()`.
+///
+/// # Examples
+///
+/// ```
+/// use rbpf::analysis::Context;
+/// use rbpf::verifier;
+/// use rbpf::error::EbpfError;
+///
+/// let prog = &[
+///     0x7a, 0x0a, 0xf8, 0xff, 0x00, 0x00, 0x00, 0x00, // stdw [r10-8], 0
+///     0x85, 0x10, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, // call_local +1
+///     0x95, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // exit
+///     0x62, 0x0a, 0xe0, 0xff, 0x00, 0x00, 0x00, 0x00, // stw [r10-32], 0
+///     0xb7, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // mov r0, 0
+///     0x95, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00  // exit
+/// ];
+/// assert_eq!(verifier::stack_depth(prog, Context::NoData, &[]), Ok(8 + 32));
+///
+/// let prog = &[
+///     0x7a, 0x0a, 0xfc, 0xff, 0x00, 0x00, 0x00, 0x00, // stdw [r10-4], 0
+///     0xb7, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // mov r0, 0
+///     0x95, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00  // exit
+/// ];
+/// assert_eq!(verifier::stack_depth(prog, Context::NoData, &[]),
+///            Err(EbpfError::OutOfBoundsAccess(10, 0)));
+/// ```
+pub fn stack_depth(prog: &[u8], ctx: Context, maps: &[MapDef]) -> Result<usize, EbpfError> {
+    check_cfg(prog)?;
+    analysis::stack_depth(prog, ctx, maps)
+}
+
+/// Severity of a problem reported by `diagnose()`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    /// The program is valid, but the instruction is probably not what the author meant.
+    Warning,
+    /// The program is rejected by `check()`.
+    Error,
+}
+
+/// A problem found in a program by `diagnose()`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    /// Index of the faulty instruction, or `None` if the problem is about the program as a whole.
+    pub insn_ptr: Option<usize>,
+    /// Whether the problem makes the program invalid.
+    pub severity: Severity,
+    /// Description of the problem. For errors, this is the message of the `EbpfError` that
+    /// `check()` would return.
+    pub message:  String,
+}
+
+// Index of the instruction following the one at `insn_ptr`. `LD_DW_IMM` takes two slots, unless
+// its second half is missing.
+fn next_insn_ptr(prog: &[u8], insn: &ebpf::Insn, insn_ptr: usize) -> usize {
+    if insn.opc == ebpf::LD_DW_IMM && check_load_dw(prog, insn_ptr).is_ok() {
+        insn_ptr + 2
+    } else {
+        insn_ptr + 1
+    }
+}
+
+// Names of the fields of an instruction that are not used by its operation, but are not zero.
+fn unused_fields(insn: &ebpf::Insn) -> Vec<&'static str> {
+    let reg_src = insn.opc & ebpf::BPF_X != 0;
+    // Whether dst, src, off and imm are used.
+    let used = match insn.opc & ebpf::BPF_CLS_MASK {
+        ebpf::BPF_ALU | ebpf::BPF_ALU64 => match insn.opc & ebpf::BPF_ALU_OP_MASK {
+            ebpf::BPF_NEG => (true, false, false, false),
+            ebpf::BPF_END => (true, false, false, true),
+            _             => (true, reg_src, false, !reg_src),
+        },
+        ebpf::BPF_JMP | ebpf::BPF_JMP32 => match insn.opc {
+            ebpf::JA                     => (false, false, true, false),
+            ebpf::CALL                   => (false, true, false, true),
+            ebpf::TAIL_CALL | ebpf::EXIT => (false, false, false, false),
+            _                            => (true, reg_src, true, !reg_src),
+        },
+        ebpf::BPF_LD if insn.opc == ebpf::LD_DW_IMM => (true, true, false, true),
+        ebpf::BPF_LD if insn.opc & ebpf::BPF_IND != 0 => (false, true, false, true),
+        ebpf::BPF_LD  => (false, false, false, true),
+        ebpf::BPF_LDX => (true, true, true, false),
+        ebpf::BPF_ST  => (true, false, true, true),
+        _             => (true, true, true, false),
+    };
+
+    let fields = [
+        ("dst", used.0, insn.dst != 0),
+        ("src", used.1, insn.src != 0),
+        ("off", used.2, insn.off != 0),
+        ("imm", used.3, insn.imm != 0),
+    ];
+    fields.iter().filter(|&&(_, used, set)| !used && set).map(|&(name, _, _)| name).collect()
+}
 
-        check_registers(&insn, store, insn_ptr);
+/// Check a program like `check()` does, but report all the problems found instead of stopping at
+/// the first one: errors for everything `check()` rejects, such as invalid registers, jumps out
+/// of the program, invalid immediate values and unknown operation codes, and warnings for fields
+/// of instructions that are not used by the operation but are not zero.
+///
+/// The program is valid for `check()` if, and only if, no diagnostic has `Severity::Error`.
+/// Diagnostics about the program as a whole come first, then diagnostics are sorted by
+/// instruction. See `render_diagnostics()` to display them with the disassembled program.
+///
+/// # Examples
+///
+/// ```
+/// use rbpf::verifier::{self, Severity};
+///
+/// let prog = &[
+///     0xb7, 0x0b, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // mov r11, 0
+///     0x37, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // div r0, 0
+///     0x05, 0x00, 0x08, 0x00, 0x00, 0x00, 0x00, 0x00, // ja +8
+///     0xb7, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00  // mov r0, 0
+/// ];
+///
+/// let diags = verifier::diagnose(prog);
+/// let summary = diags.iter().map(|d| (d.insn_ptr, d.severity)).collect::<Vec<_>>();
+/// assert_eq!(summary, vec![(None, Severity::Error),
+///                          (Some(0), Severity::Error),
+///                          (Some(1), Severity::Error),
+///                          (Some(2), Severity::Error)]);
+/// assert_eq!(diags[0].message, "[Verifier] Error: program does not end with “EXIT” instruction");
+/// assert_eq!(diags[2].message, "[Verifier] Error: division by 0 (insn #1)");
+/// ```
+pub fn diagnose(prog: &[u8]) -> Vec<Diagnostic> {
+    let mut diags = vec![];
+    {
+        let mut report = |err: EbpfError| {
+            let diag = Diagnostic {
+                insn_ptr: err.insn_ptr(),
+                severity: Severity::Error,
+                message:  err.to_string(),
+            };
+            // The same error can be found by several checks.
+            if !diags.contains(&diag) {
+                diags.push(diag);
+            }
+            Ok(())
+        };
+        // Never fails, since `report` does not.
+        let _ = check_insns(prog, &mut report);
+    }
 
-        insn_ptr += 1;
+    let num_insns = prog.len() / ebpf::INSN_SIZE;
+    let mut insn_ptr = 0;
+    while insn_ptr < num_insns {
+        let insn = ebpf::get_insn(prog, insn_ptr);
+        if !matches!(check_opcode(prog, &insn, insn_ptr), Err(EbpfError::UnknownOpcode(..))) {
+            for field in unused_fields(&insn) {
+                diags.push(Diagnostic {
+                    insn_ptr: Some(insn_ptr),
+                    severity: Severity::Warning,
+                    message:  format!("[Verifier] Warning: unused field {} is not zero \
+                                       (insn #{:?})", field, insn_ptr),
+                });
+            }
+        }
+        insn_ptr = next_insn_ptr(prog, &insn, insn_ptr);
     }
 
-    // insn_ptr should now be equal to number of instructions.
-    if insn_ptr != prog.len() / ebpf::INSN_SIZE {
-        panic!("[Verifier] Error: jumped out of code to #{:?}", insn_ptr);
+    diags.sort_by_key(|diag| diag.insn_ptr);
+    diags
+}
+
+/// Render the diagnostics returned by `diagnose()` next to the disassembled program: one line per
+/// instruction, with its index and description, followed by the messages of the diagnostics for
+/// this instruction. Diagnostics about the program as a whole are rendered first. Instructions
+/// that cannot be disassembled are shown by their operation code.
+///
+/// # Examples
+///
+/// ```
+/// use rbpf::verifier;
+///
+/// let prog = &[
+///     0xb7, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // mov r0, 0
+///     0x8f, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // (unknown opcode)
+///     0x95, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00  // exit
+/// ];
+///
+/// let diags = verifier::diagnose(prog);
+/// assert_eq!(verifier::render_diagnostics(prog, &diags),
+///            "    0  mov64 r0, 0x0\n\
+///             \x20   1  opcode 0x8f\n\
+///             \x20      ^ [Verifier] Error: unknown eBPF opcode 0x8f (insn #1)\n\
+///             \x20   2  exit\n");
+/// ```
+pub fn render_diagnostics(prog: &[u8], diags: &[Diagnostic]) -> String {
+    let mut res = String::new();
+    for diag in diags.iter().filter(|diag| diag.insn_ptr.is_none()) {
+        res.push_str(&format!("{}\n", diag.message));
     }
 
-    true
+    let num_insns = prog.len() / ebpf::INSN_SIZE;
+    let mut insn_ptr = 0;
+    while insn_ptr < num_insns {
+        let insn = ebpf::get_insn(prog, insn_ptr);
+        let next = next_insn_ptr(prog, &insn, insn_ptr);
+        // The disassembler panics on the instructions rejected here.
+        let desc = match check_opcode(prog, &insn, insn_ptr) {
+            Ok(()) => {
+                let bytes = &prog[insn_ptr * ebpf::INSN_SIZE..next * ebpf::INSN_SIZE];
+                disassembler::to_insn_vec(bytes).remove(0).desc
+            },
+            Err(_) => format!("opcode {:#04x}", insn.opc),
+        };
+        res.push_str(&format!("{:5}  {}\n", insn_ptr, desc));
+        for diag in diags.iter().filter(|diag| diag.insn_ptr == Some(insn_ptr)) {
+            res.push_str(&format!("       ^ {}\n", diag.message));
+        }
+        insn_ptr = next;
+    }
+    res
 }
@@ -0,0 +1,198 @@
+// Copyright 2026 agent <agent@local>
+//
+// Licensed under the Apache License, Version 2.0 <http://www.apache.org/licenses/LICENSE-2.0> or
+// the MIT license <http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+
+extern crate rbpf;
+use rbpf::assembler::assemble;
+use rbpf::disassembler;
+use rbpf::error::AsmError;
+
+// Assemble each line produced by the disassembler, and check we get the original bytecode back.
+fn check_round_trip(prog: &[u8]) {
+    let src = disassembler::to_insn_vec(prog).iter()
+        .map(|insn| insn.desc.clone())
+        .collect::<Vec<String>>()
+        .join("\n");
+    assert_eq!(assemble(&src).unwrap(), prog, "\n{}", src);
+}
+
+fn asm_err(line: usize, column: usize, msg: &str) -> Result<Vec<u8>, AsmError> {
+    Err(AsmError { line, column, msg: msg.to_string() })
+}
+
+#[test]
+fn test_asm_empty() {
+    assert_eq!(assemble(""), Ok(vec![]));
+    assert_eq!(assemble("\n  # Nothing to see here.\n\n"), Ok(vec![]));
+}
+
+#[test]
+fn test_asm_exit() {
+    assert_eq!(assemble("exit"), Ok(vec![0x95, 0, 0, 0, 0, 0, 0, 0]));
+}
+
+#[test]
+fn test_asm_round_trip_alu() {
+    check_round_trip(&[
+        0x07, 0x01, 0x00, 0x00, 0x05, 0x06, 0x00, 0x00, // add64 r1, 0x605
+        0x0f, 0x21, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // add64 r1, r2
+        0x04, 0x03, 0x00, 0x00, 0xff, 0xff, 0xff, 0xff, // add32 r3, -1
+        0x1c, 0x54, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // sub32 r4, r5
+        0x27, 0x06, 0x00, 0x00, 0x07, 0x00, 0x00, 0x00, // mul64 r6, 7
+        0x3f, 0x87, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // div64 r7, r8
+        0x44, 0x09, 0x00, 0x00, 0x10, 0x00, 0x00, 0x00, // or32 r9, 0x10
+        0x5f, 0x10, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // and64 r0, r1
+        0x67, 0x01, 0x00, 0x00, 0x20, 0x00, 0x00, 0x00, // lsh64 r1, 0x20
+        0x7c, 0x21, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // rsh32 r1, r2
+        0x84, 0x03, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // neg32 r3
+        0x87, 0x08, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // neg64 r8
+        0x97, 0x01, 0x00, 0x00, 0x03, 0x00, 0x00, 0x00, // mod64 r1, 3
+        0xac, 0x21, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // xor32 r1, r2
+        0xb7, 0x02, 0x00, 0x00, 0x32, 0x00, 0x00, 0x00, // mov64 r2, 0x32
+        0xbc, 0x10, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // mov32 r0, r1
+        0xc7, 0x01, 0x00, 0x00, 0x38, 0x00, 0x00, 0x00, // arsh64 r1, 0x38
+        0xd4, 0x01, 0x00, 0x00, 0x20, 0x00, 0x00, 0x00, // le32 r1
+        0xdc, 0x00, 0x00, 0x00, 0x10, 0x00, 0x00, 0x00, // be16 r0
+        0x95, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00  // exit
+    ]);
+}
+
+#[test]
+fn test_asm_round_trip_ld_st() {
+    check_round_trip(&[
+        0x18, 0x01, 0x00, 0x00, 0x88, 0x77, 0x66, 0x55, // lddw r1, 0x1122334455667788
+        0x00, 0x00, 0x00, 0x00, 0x44, 0x33, 0x22, 0x11,
+        0x18, 0x02, 0x00, 0x00, 0xff, 0xff, 0xff, 0xff, // lddw r2, -1
+        0x00, 0x00, 0x00, 0x00, 0xff, 0xff, 0xff, 0xff,
+        0x18, 0x13, 0x00, 0x00, 0x04, 0x00, 0x00, 0x00, // lddw r3, map_fd(4)
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x30, 0x00, 0x00, 0x00, 0x03, 0x00, 0x00, 0x00, // ldabsb 0x3
+        0x48, 0x10, 0x00, 0x00, 0x04, 0x00, 0x00, 0x00, // ldindh r1, 0x4
+        0x61, 0x10, 0x02, 0x00, 0x00, 0x00, 0x00, 0x00, // ldxw r0, [r1+0x2]
+        0x79, 0xa1, 0xf8, 0xff, 0x00, 0x00, 0x00, 0x00, // ldxdw r1, [r10-0x8]
+        0x72, 0x0a, 0xff, 0xff, 0x11, 0x00, 0x00, 0x00, // stb [r10-0x1], 0x11
+        0x7a, 0x01, 0x08, 0x00, 0x22, 0x00, 0x00, 0x00, // stdw [r1+0x8], 0x22
+        0x6b, 0x21, 0x04, 0x00, 0x00, 0x00, 0x00, 0x00, // stxh [r1+0x4], r2
+        0xdb, 0x21, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // stxxadddw [r1+0x0], r2
+        0x95, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00  // exit
+    ]);
+}
+
+#[test]
+fn test_asm_round_trip_jmp() {
+    check_round_trip(&[
+        0x05, 0x00, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, // ja +1
+        0x15, 0x01, 0x02, 0x00, 0x00, 0x00, 0x00, 0x00, // jeq r1, 0, +2
+        0x2d, 0x21, 0xfe, 0xff, 0x00, 0x00, 0x00, 0x00, // jgt r1, r2, -2
+        0x45, 0x01, 0x00, 0x00, 0x08, 0x00, 0x00, 0x00, // jset r1, 8, +0
+        0x6d, 0x21, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // jsgt r1, r2, +0
+        0xa5, 0x01, 0x01, 0x00, 0x05, 0x00, 0x00, 0x00, // jlt r1, 0x5, +1
+        0xbd, 0x21, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // jle r1, r2, +0
+        0xc5, 0x01, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, // jslt r1, -1, -1
+        0xdd, 0x21, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // jsle r1, r2, +0
+        0x16, 0x01, 0x01, 0x00, 0x05, 0x00, 0x00, 0x00, // jeq32 r1, 0x5, +1
+        0x4e, 0x21, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // jset32 r1, r2, +0
+        0xd6, 0x01, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, // jsle32 r1, -1, -1
+        0x85, 0x00, 0x00, 0x00, 0x06, 0x00, 0x00, 0x00, // call 6
+        0x85, 0x10, 0x00, 0x00, 0xfe, 0xff, 0xff, 0xff, // call_local -2
+        0x8d, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // tail_call
+        0x95, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00  // exit
+    ]);
+}
+
+#[test]
+fn test_asm_labels_and_comments() {
+    let prog = assemble("
+        // Compute 7 * 7 * ... in a loop, as in test_vm_mul_loop.
+        mov64 r0, 0x7
+        add64 r1, 0xa
+        lsh64 r1, 0x20
+        rsh64 r1, 0x20
+        jeq r1, 0x0, end    # Skip the loop if r1 is null.
+        mov64 r0, 0x7
+    loop:
+        mul64 r0, 0x7
+        add64 r1, -1
+        jne r1, 0x0, loop
+    end: exit
+    ").unwrap();
+    assert_eq!(prog, vec![
+        0xb7, 0x00, 0x00, 0x00, 0x07, 0x00, 0x00, 0x00,
+        0x07, 0x01, 0x00, 0x00, 0x0a, 0x00, 0x00, 0x00,
+        0x67, 0x01, 0x00, 0x00, 0x20, 0x00, 0x00, 0x00,
+        0x77, 0x01, 0x00, 0x00, 0x20, 0x00, 0x00, 0x00,
+        0x15, 0x01, 0x04, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0xb7, 0x00, 0x00, 0x00, 0x07, 0x00, 0x00, 0x00,
+        0x27, 0x00, 0x00, 0x00, 0x07, 0x00, 0x00, 0x00,
+        0x07, 0x01, 0x00, 0x00, 0xff, 0xff, 0xff, 0xff,
+        0x55, 0x01, 0xfd, 0xff, 0x00, 0x00, 0x00, 0x00,
+        0x95, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00
+    ]);
+
+    let vm = rbpf::EbpfVmNoData::new(&prog).unwrap();
+    assert_eq!(vm.prog_exec().unwrap(), 0x75db9c97);
+}
+
+#[test]
+fn test_asm_label_after_lddw() {
+    // lddw takes two slots, labels after it must account for this.
+    let prog = assemble("
+        ja skip
+        lddw r0, 0x1
+    skip:
+        exit
+    ").unwrap();
+    assert_eq!(&prog[..8], &[0x05, 0x00, 0x02, 0x00, 0x00, 0x00, 0x00, 0x00]);
+}
+
+#[test]
+fn test_asm_err_unknown_instruction() {
+    assert_eq!(assemble("exit\n  foo r1, 2"), asm_err(2, 3, "unknown instruction 'foo'"));
+}
+
+#[test]
+fn test_asm_err_invalid_register() {
+    assert_eq!(assemble("add64 r11, 1"), asm_err(1, 7, "invalid register 'r11'"));
+    assert_eq!(assemble("ldxb r0, [rx+1]"), asm_err(1, 11, "invalid register 'rx'"));
+}
+
+#[test]
+fn test_asm_err_operands() {
+    assert_eq!(assemble("neg64 1"),
+               asm_err(1, 7, "invalid operands for 'neg64', expected register"));
+    assert_eq!(assemble("exit r0"),
+               asm_err(1, 6, "invalid operands for 'exit', expected no operand"));
+    assert_eq!(assemble("add64 r0,"), asm_err(1, 10, "missing operand"));
+    assert_eq!(assemble("ldxb r0, [r1+1"), asm_err(1, 15, "expected ']'"));
+    assert_eq!(assemble("mov64 r0 1"), asm_err(1, 10, "expected ',', found '1'"));
+}
+
+#[test]
+fn test_asm_err_out_of_range() {
+    assert_eq!(assemble("mov64 r0, 0x100000000"), asm_err(1, 11, "immediate 0x100000000 out of range"));
+    assert_eq!(assemble("stb [r1+0x10000], 0"), asm_err(1, 5, "offset 0x10000 out of range"));
+    assert_eq!(assemble("add64 r0, zz"),
+               asm_err(1, 7, "invalid operands for 'add64', expected register and register or immediate"));
+}
+
+#[test]
+fn test_asm_err_labels() {
+    assert_eq!(assemble("ja nowhere\nexit"), asm_err(1, 4, "undefined label 'nowhere'"));
+    assert_eq!(assemble("a:\na: exit"), asm_err(2, 1, "duplicate label 'a'"));
+}
+
+#[test]
+fn test_asm_call_local_label() {
+    let prog = assemble("
+        call_local fn
+        exit
+    fn:
+        mov64 r0, 0x1
+        exit
+    ").unwrap();
+    assert_eq!(&prog[..8], &[0x85, 0x10, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00]);
+    assert_eq!(assemble("call_local r1"), asm_err(1, 12, "expected call offset or label"));
+}
@@ -0,0 +1,77 @@
+; Object file used to test the loader (module `loader`), with two maps, a license, a program
+; calling a function in `.text`, and a program using a map directly.
+;
+; Compiled with:
+;
+;     llc -march=bpf -filetype=obj map_counter.ll -o map_counter.o
+;
+; Roughly equivalent C code:
+;
+;     struct bpf_map_def SEC("maps") counters = { BPF_MAP_TYPE_ARRAY, 4, 8, 16, 0 };
+;     struct bpf_map_def SEC("maps") seen = { BPF_MAP_TYPE_HASH, 8, 8, 4, 0 };
+;     char _license[] SEC("license") = "GPL";
+;
+;     __attribute__((noinline)) long count(int k)
+;     {
+;         long *value = bpf_map_lookup_elem(&counters, &k);
+;         if (!value)
+;             return 0;
+;         return ++*value;
+;     }
+;
+;     SEC("xdp") int prog(void *ctx) { return count(3); }
+;
+;     SEC("socket") long mark(long *ctx)
+;     {
+;         long key = *ctx, value = 1;
+;         return bpf_map_update_elem(&seen, &key, &value, BPF_NOEXIST);
+;     }
+
+target datalayout = "e-m:e-p:64:64-i64:64-i128:128-n32:64-S128"
+target triple = "bpf"
+
+%struct.bpf_map_def = type { i32, i32, i32, i32, i32 }
+
+@counters = global %struct.bpf_map_def { i32 2, i32 4, i32 8, i32 16, i32 0 }, section "maps", align 4
+@seen = global %struct.bpf_map_def { i32 1, i32 8, i32 8, i32 4, i32 0 }, section "maps", align 4
+@_license = global [4 x i8] c"GPL\00", section "license", align 1
+
+define dso_local i64 @count(i32 %k) noinline nounwind section ".text" {
+entry:
+  %key = alloca i32, align 4
+  store i32 %k, i32* %key, align 4
+  %m = bitcast %struct.bpf_map_def* @counters to i8*
+  %kp = bitcast i32* %key to i8*
+  %v = call i8* inttoptr (i64 1 to i8* (i8*, i8*)*)(i8* %m, i8* %kp)
+  %isnull = icmp eq i8* %v, null
+  br i1 %isnull, label %out, label %inc
+inc:
+  %vp = bitcast i8* %v to i64*
+  %old = load i64, i64* %vp, align 8
+  %new = add i64 %old, 1
+  store i64 %new, i64* %vp, align 8
+  ret i64 %new
+out:
+  ret i64 0
+}
+
+define dso_local i32 @prog(i8* %ctx) nounwind section "xdp" {
+entry:
+  %r = call i64 @count(i32 3)
+  %t = trunc i64 %r to i32
+  ret i32 %t
+}
+
+define dso_local i64 @mark(i64* %ctx) nounwind section "socket" {
+entry:
+  %key = alloca i64, align 8
+  %value = alloca i64, align 8
+  %k = load i64, i64* %ctx, align 8
+  store i64 %k, i64* %key, align 8
+  store i64 1, i64* %value, align 8
+  %m = bitcast %struct.bpf_map_def* @seen to i8*
+  %kp = bitcast i64* %key to i8*
+  %vp = bitcast i64* %value to i8*
+  %r = call i64 inttoptr (i64 2 to i64 (i8*, i8*, i8*, i64)*)(i8* %m, i8* %kp, i8* %vp, i64 1)
+  ret i64 %r
+}
@@ -0,0 +1,17 @@
+; Object file used to test the loader (module `loader`): the program loads the address of a
+; global variable, which is not supported.
+;
+; Compiled with:
+;
+;     llc -march=bpf -filetype=obj rodata.ll -o rodata.o
+
+target datalayout = "e-m:e-p:64:64-i64:64-i128:128-n32:64-S128"
+target triple = "bpf"
+
+@value = constant i64 42, section ".rodata", align 8
+
+define dso_local i64 @prog(i8* %ctx) nounwind section "socket" {
+entry:
+  %v = load volatile i64, i64* @value, align 8
+  ret i64 %v
+}
@@ -0,0 +1,290 @@
+// Copyright 2026 agent <agent@local>
+//
+// Licensed under the Apache License, Version 2.0 <http://www.apache.org/licenses/LICENSE-2.0> or
+// the MIT license <http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+
+// These tests replay the packets a debugger would send, and check the replies of the stub.
+
+extern crate rbpf;
+use std::io::{self, Read, Write};
+use rbpf::assembler::assemble;
+use rbpf::debugger::{Session, Status};
+use rbpf::gdbstub;
+
+// A fake connection: packets from the debugger are all available from the start.
+struct Client {
+    input:  io::Cursor<Vec<u8>>,
+    output: Vec<u8>,
+}
+
+impl Read for Client {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.input.read(buf)
+    }
+}
+
+impl Write for Client {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.output.write(buf)
+    }
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+fn packet(data: &str) -> String {
+    let checksum = data.bytes().fold(0u8, |acc, b| acc.wrapping_add(b));
+    format!("${}#{:02x}", data, checksum)
+}
+
+// Send the packets to the stub, and return its replies along with the final status.
+fn run(session: &mut Session, packets: &[&str]) -> (Vec<String>, Status) {
+    let input = packets.iter().map(|p| packet(p) + "+").collect::<String>();
+    let mut client = Client { input: io::Cursor::new(input.into_bytes()), output: vec![] };
+    let status = gdbstub::serve(session, &mut client).unwrap();
+    (parse_replies(&client.output), status)
+}
+
+fn parse_replies(output: &[u8]) -> Vec<String> {
+    let output = String::from_utf8(output.to_vec()).unwrap();
+    let mut replies = vec![];
+    for chunk in output.split('$').skip(1) {
+        let (data, checksum) = chunk.split_at(chunk.find('#').unwrap());
+        assert_eq!(&checksum[1..3], &packet(data)[data.len() + 2..]);
+        replies.push(data.to_string());
+    }
+    replies
+}
+
+fn reg_hex(value: u64) -> String {
+    value.to_le_bytes().iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+const LOOP_PROG: &str = "
+    mov64 r0, 0x0
+    mov64 r1, 0x3
+    add64 r0, 0x2
+    add64 r1, -1
+    jne r1, 0x0, -3
+    exit";
+
+#[test]
+fn test_gdbstub_registers() {
+    let prog = assemble(LOOP_PROG).unwrap();
+    let vm = rbpf::EbpfVmNoData::new(&prog).unwrap();
+    let mut session = vm.debug();
+    let r10 = session.reg(10);
+
+    let (replies, status) = run(&mut session, &[
+        "?", "s", "s", "g", "pb", "P0=1100000000000000", "p0", "Pb=1000000000000000", "pb",
+        "Pb=0900000000000000", "Pb=3000000000000000", "pc",
+    ]);
+    assert_eq!(status, Status::Stopped);
+    let regs = [0, 3, 0, 0, 0, 0, 0, 0, 0, 0, r10, 0x10].iter()
+        .map(|&v| reg_hex(v)).collect::<String>();
+    assert_eq!(replies, vec![
+        "S05".to_string(), "S05".to_string(), "S05".to_string(), regs, reg_hex(0x10),
+        "OK".to_string(), reg_hex(0x11), "OK".to_string(), reg_hex(0x10),
+        // Unaligned pc, pc out of the program, unknown register.
+        "E16".to_string(), "E16".to_string(), "E16".to_string(),
+    ]);
+    assert_eq!(session.reg(0), 0x11);
+
+    // Write all registers at once, moving back to the first instruction of the loop.
+    let mut regs = [0u64; 12];
+    regs[1] = 1;
+    regs[10] = r10;
+    regs[11] = 0x10;
+    let regs = regs.iter().map(|&v| reg_hex(v)).collect::<String>();
+    let (replies, status) = run(&mut session, &[&format!("G{}", regs), "c"]);
+    assert_eq!(replies, vec!["OK", "W02"]);
+    assert_eq!(status, Status::Exited(2));
+}
+
+#[test]
+fn test_gdbstub_memory() {
+    let prog = assemble("
+        stdw [r10-0x8], 0x11
+        ldxdw r0, [r10-0x8]
+        ldxb r2, [r1+0x1]
+        add64 r0, r2
+        exit").unwrap();
+    let mem = &mut [0x01, 0x02, 0x03, 0x04];
+    let vm = rbpf::EbpfVmRaw::new(&prog).unwrap();
+    let mut session = vm.debug(mem);
+    let r1 = session.reg(1);
+    let r10 = session.reg(10);
+
+    let (replies, status) = run(&mut session, &[
+        // Bytecode.
+        "m8,8", "m20,10",
+        // Stack.
+        "s", &format!("m{:x},8", r10 - 8), &format!("M{:x},1:22", r10 - 8),
+        // Packet data.
+        &format!("m{:x},4", r1), &format!("M{:x},1:10", r1 + 1),
+        // Out of bounds, and read-only bytecode.
+        &format!("m{:x},4", r1 + 2), &format!("M{:x},1:00", r10), "M0,1:00",
+        "c",
+    ]);
+    assert_eq!(replies, vec![
+        "79a0f8ff00000000", "E0e",
+        "S05", "1100000000000000", "OK",
+        "01020304", "OK",
+        "E0e", "E0e", "E0e",
+        "W32",
+    ]);
+    assert_eq!(status, Status::Exited(0x32));
+}
+
+#[test]
+fn test_gdbstub_memory_wrapping_address() {
+    let prog = assemble("
+        mov64 r0, 0x0
+        exit").unwrap();
+    let vm = rbpf::EbpfVmNoData::new(&prog).unwrap();
+    let mut session = vm.debug();
+
+    // Accesses wrapping around the address space are out of bounds.
+    let (replies, _) = run(&mut session, &[
+        "mfffffffffffffffc,8", "Mfffffffffffffffc,8:0000000000000000", "m8,ffffffffffffffff",
+        "qXfer:features:read:target.xml:10,ffffffffffffffff",
+    ]);
+    assert_eq!(&replies[..3], &["E0e", "E0e", "E0e"]);
+    assert!(replies[3].starts_with('l'));
+    assert!(session.read_memory(0xfffffffffffffffc, 8).is_err());
+    assert!(session.write_memory(0xffffffffffffffff, &[0]).is_err());
+}
+
+#[test]
+fn test_gdbstub_breakpoints() {
+    let prog = assemble(LOOP_PROG).unwrap();
+    let vm = rbpf::EbpfVmNoData::new(&prog).unwrap();
+    let mut session = vm.debug();
+
+    let (replies, status) = run(&mut session, &[
+        "Z0,18,8", "Z0,28,8", "Z0,19,8", "Z1,18,8", "c", "p1", "c", "p1", "z0,18,8", "c", "pb",
+        "c",
+    ]);
+    assert_eq!(replies, vec![
+        "OK", "OK", "E16", "", "S05", reg_hex(3).as_str(), "S05", reg_hex(2).as_str(), "OK", "S05",
+        reg_hex(0x28).as_str(), "W06",
+    ]);
+    assert_eq!(status, Status::Exited(6));
+}
+
+#[test]
+fn test_gdbstub_fault() {
+    let prog = assemble("
+        mov32 r0, 0x1
+        mov32 r1, 0x0
+        div32 r0, r1
+        exit").unwrap();
+    let vm = rbpf::EbpfVmNoData::new(&prog).unwrap();
+    let mut session = vm.debug();
+
+    // The program stops on the fault, and is terminated if resumed.
+    let (replies, status) = run(&mut session, &["c", "pb", "c", "?"]);
+    assert_eq!(replies, vec!["S08".to_string(), reg_hex(0x10), "X08".to_string()]);
+    match status {
+        Status::Faulted(err) => assert_eq!(err.insn_ptr, 2),
+        status               => panic!("unexpected status {:?}", status),
+    }
+}
+
+#[test]
+fn test_gdbstub_queries() {
+    let prog = assemble(LOOP_PROG).unwrap();
+    let vm = rbpf::EbpfVmNoData::new(&prog).unwrap();
+    let mut session = vm.debug();
+
+    let (replies, status) = run(&mut session, &[
+        "qSupported:multiprocess+;swbreak+", "qAttached", "Hg0",
+        "qXfer:features:read:target.xml:0,20", "vMustReplyEmpty", "D",
+    ]);
+    assert_eq!(replies, vec![
+        "PacketSize=4000;qXfer:features:read+", "1", "OK",
+        "m<?xml version=\"1.0\"?>\n<!DOCTYPE ", "", "OK",
+    ]);
+    assert_eq!(status, Status::Stopped);
+
+    let (replies, _) = run(&mut session, &["qXfer:features:read:target.xml:0,1000", "k", "?"]);
+    assert_eq!(replies.len(), 1);
+    assert!(replies[0].starts_with("l<?xml"));
+    assert!(replies[0].contains("<architecture>bpf</architecture>"));
+}
+
+#[test]
+fn test_gdbstub_bad_checksum() {
+    let prog = assemble(LOOP_PROG).unwrap();
+    let vm = rbpf::EbpfVmNoData::new(&prog).unwrap();
+    let mut session = vm.debug();
+
+    let input = format!("$?#00{}+", packet("?"));
+    let mut client = Client { input: io::Cursor::new(input.into_bytes()), output: vec![] };
+    gdbstub::serve(&mut session, &mut client).unwrap();
+    assert_eq!(client.output, b"-+$S05#b8".to_vec());
+}
+
+#[test]
+fn test_gdbstub_non_ascii() {
+    let prog = assemble(LOOP_PROG).unwrap();
+    let vm = rbpf::EbpfVmNoData::new(&prog).unwrap();
+    let mut session = vm.debug();
+
+    // Packets with valid checksums, but bytes that are not ASCII, as the command or in arguments.
+    let mut input = b"$\xff#ff+$m\xff#6c+".to_vec();
+    input.extend_from_slice(format!("{}+", packet("?")).as_bytes());
+    let mut client = Client { input: io::Cursor::new(input), output: vec![] };
+    gdbstub::serve(&mut session, &mut client).unwrap();
+    assert_eq!(client.output, b"+$#00+$#00+$S05#b8".to_vec());
+}
+
+// Client for the socket tests: single-step, then continue to the end of the program. The stub
+// closes the connection after its last reply, which is not acknowledged.
+fn socket_client<S: Read + Write>(mut stream: S) -> Vec<String> {
+    stream.write_all(format!("{}+{}", packet("s"), packet("c")).as_bytes()).unwrap();
+    let mut output = vec![];
+    stream.read_to_end(&mut output).unwrap();
+    parse_replies(&output)
+}
+
+#[test]
+fn test_gdbstub_tcp() {
+    let prog = assemble(LOOP_PROG).unwrap();
+    let vm = rbpf::EbpfVmNoData::new(&prog).unwrap();
+    let mut session = vm.debug();
+
+    // Find a free port.
+    let port = std::net::TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port();
+    let client = std::thread::spawn(move || loop {
+        if let Ok(stream) = std::net::TcpStream::connect(("127.0.0.1", port)) {
+            return socket_client(stream);
+        }
+        std::thread::sleep(std::time::Duration::from_millis(10));
+    });
+    assert_eq!(gdbstub::serve_tcp(&mut session, ("127.0.0.1", port)).unwrap(), Status::Exited(6));
+    assert_eq!(client.join().unwrap(), vec!["S05", "W06"]);
+}
+
+#[cfg(unix)]
+#[test]
+fn test_gdbstub_unix() {
+    let prog = assemble(LOOP_PROG).unwrap();
+    let vm = rbpf::EbpfVmNoData::new(&prog).unwrap();
+    let mut session = vm.debug();
+
+    let path = std::env::temp_dir().join(format!("rbpf-gdbstub-{}.sock", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+    let client_path = path.clone();
+    let client = std::thread::spawn(move || loop {
+        if let Ok(stream) = std::os::unix::net::UnixStream::connect(&client_path) {
+            return socket_client(stream);
+        }
+        std::thread::sleep(std::time::Duration::from_millis(10));
+    });
+    assert_eq!(gdbstub::serve_unix(&mut session, &path).unwrap(), Status::Exited(6));
+    assert_eq!(client.join().unwrap(), vec!["S05", "W06"]);
+    std::fs::remove_file(&path).unwrap();
+}
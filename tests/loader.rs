@@ -0,0 +1,154 @@
+// Copyright 2026 agent <agent@local>
+//
+// Licensed under the Apache License, Version 2.0 <http://www.apache.org/licenses/LICENSE-2.0> or
+// the MIT license <http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+
+// The object files used in these tests are in `tests/elfs`, along with their sources (LLVM IR).
+
+extern crate rbpf;
+use rbpf::error::{ElfError, MapError};
+use rbpf::loader::{MapDef, Object, BPF_MAP_TYPE_ARRAY, BPF_MAP_TYPE_HASH};
+
+fn load(filename: &str) -> Vec<u8> {
+    std::fs::read(format!("tests/elfs/{}", filename)).unwrap()
+}
+
+#[test]
+fn test_loader_sections() {
+    let object = Object::parse(&load("map_counter.o")).unwrap();
+    assert_eq!(object.license(), Some("GPL"));
+    assert_eq!(object.maps(), &[
+        MapDef {
+            name: "counters".to_string(), map_type: BPF_MAP_TYPE_ARRAY,
+            key_size: 4, value_size: 8, max_entries: 16, map_flags: 0,
+        },
+        MapDef {
+            name: "seen".to_string(), map_type: BPF_MAP_TYPE_HASH,
+            key_size: 8, value_size: 8, max_entries: 4, map_flags: 0,
+        },
+    ]);
+    let names = object.programs().iter().map(|p| p.name()).collect::<Vec<&str>>();
+    assert_eq!(names, vec![".text", "xdp", "socket"]);
+}
+
+#[test]
+fn test_loader_relocations() {
+    let object = Object::parse(&load("map_counter.o")).unwrap();
+
+    // The function in `.text` is appended to the program, the call points to it.
+    let prog = object.program("xdp").unwrap().code();
+    let text = object.program(".text").unwrap().code();
+    assert_eq!(prog.len(), 3 * 8 + text.len());
+    assert_eq!(&prog[8..16], &[0x85, 0x10, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00]);
+    assert_eq!(&prog[24..], text);
+
+    // The map is referenced by its index.
+    assert_eq!(&text[24..32], &[0x18, 0x11, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00]);
+    let prog = object.program("socket").unwrap().code();
+    assert_eq!(&prog[64..72], &[0x18, 0x11, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00]);
+}
+
+#[test]
+fn test_loader_run_with_maps() {
+    let object = Object::parse(&load("map_counter.o")).unwrap();
+    let maps = object.create_maps().unwrap();
+
+    let mut vm = rbpf::EbpfVmNoData::new(object.program("xdp").unwrap().code()).unwrap();
+    for (fd, map) in maps.iter().enumerate() {
+        vm.register_map(fd as u32, map);
+    }
+    assert_eq!(vm.prog_exec().unwrap(), 1);
+    vm.jit_compile().unwrap();
+    unsafe { assert_eq!(vm.prog_exec_jit().unwrap(), 2); }
+    assert_eq!(maps[0].lookup(&3u32.to_le_bytes()), Some(2u64.to_le_bytes().to_vec()));
+
+    let mut vm = rbpf::EbpfVmRaw::new(object.program("socket").unwrap().code()).unwrap();
+    for (fd, map) in maps.iter().enumerate() {
+        vm.register_map(fd as u32, map);
+    }
+    let mut packet = 0x1122u64.to_le_bytes();
+    assert_eq!(vm.prog_exec(&mut packet).unwrap(), 0);
+    assert_eq!(vm.prog_exec(&mut packet).unwrap(), -17i64 as u64);
+    assert_eq!(maps[1].lookup(&0x1122u64.to_le_bytes()), Some(1u64.to_le_bytes().to_vec()));
+}
+
+#[test]
+fn test_loader_err_not_elf() {
+    assert_eq!(Object::parse(&[0x7f, b'E', b'L', b'F']).err(), Some(ElfError::NotElf));
+    let mut file = load("map_counter.o");
+    file[0] = 0;
+    assert_eq!(Object::parse(&file).err(), Some(ElfError::NotElf));
+}
+
+#[test]
+fn test_loader_err_unsupported_elf() {
+    // Change machine to x86_64.
+    let mut file = load("map_counter.o");
+    file[0x12] = 62;
+    assert_eq!(Object::parse(&file).err(), Some(ElfError::UnsupportedElf));
+}
+
+#[test]
+fn test_loader_err_truncated() {
+    let file = load("map_counter.o");
+    assert_eq!(Object::parse(&file[..file.len() / 2]).err(), Some(ElfError::Truncated));
+}
+
+#[test]
+fn test_loader_err_rodata_relocation() {
+    assert_eq!(Object::parse(&load("rodata.o")).err(),
+               Some(ElfError::InvalidRelocation("socket".to_string(), 0)));
+}
+
+// Offsets in map_counter.o, see `readelf -a tests/elfs/map_counter.o`.
+const SYMTAB: usize = 0x160;
+const SYM_SIZE: usize = 24;
+
+#[test]
+fn test_loader_err_map_symbol_overflow() {
+    // Move `counters` (symbol 4) to an offset where its definition wraps around.
+    let mut file = load("map_counter.o");
+    let off = SYMTAB + 4 * SYM_SIZE + 8;
+    file[off..off + 8].copy_from_slice(&0xfffffffffffffffcu64.to_le_bytes());
+    assert_eq!(Object::parse(&file).err(),
+               Some(ElfError::InvalidMapDefinition("counters".to_string())));
+}
+
+#[test]
+fn test_loader_err_map_relocation_overflow() {
+    // Make symbol 2 a section symbol for `maps` with the largest value, point the relocation of
+    // `.text` to it, and set a non-zero offset in the immediate of the instruction: the offset of
+    // the map wraps around.
+    let mut file = load("map_counter.o");
+    let sym = SYMTAB + 2 * SYM_SIZE;
+    file[sym + 4] = 3;
+    file[sym + 6..sym + 8].copy_from_slice(&8u16.to_le_bytes());
+    file[sym + 8..sym + 16].copy_from_slice(&u64::MAX.to_le_bytes());
+    file[0x244..0x248].copy_from_slice(&2u32.to_le_bytes());
+    file[0x40 + 0x18 + 4] = 1;
+    assert_eq!(Object::parse(&file).err(),
+               Some(ElfError::InvalidRelocation(".text".to_string(), 0x18)));
+}
+
+#[test]
+fn test_loader_err_partial_instruction() {
+    // Cut the last instruction of section `xdp` (header 4) in half.
+    let mut file = load("map_counter.o");
+    let off = 0x2d8 + 4 * 64 + 32;
+    file[off..off + 8].copy_from_slice(&0x14u64.to_le_bytes());
+    assert_eq!(Object::parse(&file).err(), Some(ElfError::InvalidProgram("xdp".to_string())));
+}
+
+#[test]
+fn test_loader_err_map_definition() {
+    let def = MapDef {
+        name: "m".to_string(), map_type: 27, key_size: 4, value_size: 8, max_entries: 1,
+        map_flags: 0,
+    };
+    assert_eq!(def.create().err(), Some(ElfError::UnsupportedMapType("m".to_string(), 27)));
+    let def = MapDef { map_type: BPF_MAP_TYPE_HASH, value_size: 0, ..def };
+    assert_eq!(def.create().err(),
+               Some(ElfError::InvalidMap("m".to_string(), MapError::InvalidValueSize(0))));
+}
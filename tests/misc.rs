@@ -20,7 +20,11 @@
 // use std::path::PathBuf;
 
 extern crate rbpf;
+use rbpf::assembler::assemble;
+use rbpf::debugger::Status;
+use rbpf::error::{EbpfError, ExecError, ExecErrorKind, JitError, MapError};
 use rbpf::helpers;
+use rbpf::maps::{Map, MapType, BPF_ANY, BPF_EXIST, BPF_NOEXIST, MAX_KEY_SIZE, MAX_VALUE_SIZE};
 
 // The following two examples have been compiled from C with the following command:
 //
@@ -161,10 +165,10 @@ fn test_vm_block_port() {
         0x64, 0x66, 0x0au8
     ];
 
-    let mut vm = rbpf::EbpfVmFixedMbuff::new(prog, 0x40, 0x50);
+    let mut vm = rbpf::EbpfVmFixedMbuff::new(prog, 0x40, 0x50).unwrap();
     vm.register_helper(helpers::BPF_TRACE_PRINTK_IDX, helpers::bpf_trace_printf);
 
-    let res = vm.prog_exec(packet);
+    let res = vm.prog_exec(packet).unwrap();
     println!("Program returned: {:?} ({:#x})", res, res);
     assert_eq!(res, 0xffffffff);
 }
@@ -242,12 +246,12 @@ fn test_jit_block_port() {
         0x64, 0x66, 0x0au8
     ];
 
-    let mut vm = rbpf::EbpfVmFixedMbuff::new(prog, 0x40, 0x50);
+    let mut vm = rbpf::EbpfVmFixedMbuff::new(prog, 0x40, 0x50).unwrap();
     vm.register_helper(helpers::BPF_TRACE_PRINTK_IDX, helpers::bpf_trace_printf);
-    vm.jit_compile();
+    vm.jit_compile().unwrap();
 
     unsafe {
-        let res = vm.prog_exec_jit(packet);
+        let res = vm.prog_exec_jit(packet).unwrap();
         println!("Program returned: {:?} ({:#x})", res, res);
         assert_eq!(res, 0xffffffff);
     }
@@ -267,16 +271,16 @@ fn test_vm_mbuff() {
         0xaa, 0xbb, 0x11, 0x22, 0xcc, 0xdd
     ];
 
-    let mbuff = [0u8; 32];
+    let mut mbuff = [0u8; 32];
     unsafe {
-        let mut data     = mbuff.as_ptr().offset(8)  as *mut u64;
-        let mut data_end = mbuff.as_ptr().offset(24) as *mut u64;
-        *data     = mem.as_ptr() as u64;
-        *data_end = mem.as_ptr() as u64 + mem.len() as u64;
+        let data     = mbuff.as_mut_ptr().offset(8)  as *mut u64;
+        let data_end = mbuff.as_mut_ptr().offset(24) as *mut u64;
+        data.write_unaligned(mem.as_ptr() as u64);
+        data_end.write_unaligned(mem.as_ptr() as u64 + mem.len() as u64);
     }
 
-    let vm = rbpf::EbpfVmMbuff::new(prog);
-    assert_eq!(vm.prog_exec(mem, &mbuff), 0x2211);
+    let vm = rbpf::EbpfVmMbuff::new(prog).unwrap();
+    assert_eq!(vm.prog_exec(mem, &mbuff).unwrap(), 0x2211);
 }
 
 // Program and memory come from uBPF test ldxh.
@@ -295,15 +299,1660 @@ fn test_jit_mbuff() {
 
     let mut mbuff = [0u8; 32];
     unsafe {
-        let mut data     = mbuff.as_ptr().offset(8)  as *mut u64;
-        let mut data_end = mbuff.as_ptr().offset(24) as *mut u64;
-        *data     = mem.as_ptr() as u64;
-        *data_end = mem.as_ptr() as u64 + mem.len() as u64;
+        let data     = mbuff.as_mut_ptr().offset(8)  as *mut u64;
+        let data_end = mbuff.as_mut_ptr().offset(24) as *mut u64;
+        data.write_unaligned(mem.as_ptr() as u64);
+        data_end.write_unaligned(mem.as_ptr() as u64 + mem.len() as u64);
     }
 
     unsafe {
-        let mut vm = rbpf::EbpfVmMbuff::new(prog);
-        vm.jit_compile();
-        assert_eq!(vm.prog_exec_jit(mem, &mut mbuff), 0x2211);
+        let mut vm = rbpf::EbpfVmMbuff::new(prog).unwrap();
+        vm.jit_compile().unwrap();
+        assert_eq!(vm.prog_exec_jit(mem, &mut mbuff).unwrap(), 0x2211);
+    }
+}
+
+#[test]
+fn test_jit_mem_check_mbuff() {
+    let prog = assemble("
+        ldxdw r2, [r1+0x8]
+        ldxh r0, [r2+0x2]
+        stdw [r1+0x18], 0x0
+        stdw [r1+0x20], 0x0
+        exit").unwrap();
+    let mem = &mut [
+        0xaa, 0xbb, 0x11, 0x22, 0xcc, 0xdd
+    ];
+
+    let mut mbuff = [0u8; 32];
+    unsafe {
+        let data = mbuff.as_mut_ptr().offset(8) as *mut u64;
+        data.write_unaligned(mem.as_ptr() as u64);
+    }
+
+    // Accesses to packet data and to the metadata buffer pass, the store past its end fails.
+    let mut vm = rbpf::EbpfVmMbuff::new(&prog).unwrap();
+    vm.set_jit_mem_check(true);
+    vm.jit_compile().unwrap();
+    let err = unsafe { vm.prog_exec_jit(mem, &mut mbuff).unwrap_err() };
+    assert_eq!(err.kind, ExecErrorKind::OutOfBoundsStore);
+    assert_eq!(err.insn_ptr, 3);
+    assert_eq!(err.addr, mbuff.as_ptr() as u64 + 0x20);
+    assert_eq!(err.size, 8);
+}
+
+#[test]
+fn test_verifier_err_set_prog_keeps_prog() {
+    let prog1 = &[
+        0xb7, 0x00, 0x00, 0x00, 0x11, 0x22, 0x00, 0x00, // mov r0, 0x2211
+        0x95, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00  // exit
+    ];
+    let prog2 = &[
+        0xb7, 0x0a, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // mov r10, 0
+        0x95, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00  // exit
+    ];
+
+    let mut vm = rbpf::EbpfVmFixedMbuff::new(prog1, 0x40, 0x50).unwrap();
+    assert_eq!(vm.set_prog(prog2, 0, 8), Err(EbpfError::CannotWriteR10(0)));
+    assert_eq!(vm.prog_exec(&mut []).unwrap(), 0x2211);
+}
+
+#[test]
+fn test_vm_err_packet_out_of_bound_recoverable() {
+    let prog = &[
+        0x71, 0x10, 0x04, 0x00, 0x00, 0x00, 0x00, 0x00, // ldxb r0, [r1+4]
+        0x95, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00  // exit
+    ];
+    let vm = rbpf::EbpfVmRaw::new(prog).unwrap();
+
+    // Packet too short: the load faults, but the VM can keep processing other packets.
+    let short = &mut [0xaa, 0xbb];
+    let err = vm.prog_exec(short).unwrap_err();
+    assert_eq!(err.kind, ExecErrorKind::OutOfBoundsLoad);
+    assert_eq!(err.insn_ptr, 0);
+    assert_eq!(err.addr, short.as_ptr() as u64 + 4);
+    assert_eq!(err.size, 1);
+
+    let long = &mut [0xaa, 0xbb, 0xcc, 0xdd, 0xee];
+    assert_eq!(vm.prog_exec(long).unwrap(), 0xee);
+}
+
+#[test]
+fn test_vm_err_div32_by_zero_upper_bits() {
+    // Only the lower 32 bits of the divisor are used by 32-bit divisions and modulos.
+    for opc in [0x3c, 0x9c].iter() {
+        let prog = &[
+            0x18, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // lddw r1, 0x100000000
+            0x00, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00,
+            0xb4, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, // mov32 r0, 0x1
+            *opc, 0x10, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // div32 (or mod32) r0, r1
+            0x95, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00  // exit
+        ];
+        let mut vm = rbpf::EbpfVmNoData::new(prog).unwrap();
+        let expected = ExecError::new(ExecErrorKind::DivisionByZero, 3);
+        assert_eq!(vm.prog_exec().unwrap_err(), expected);
+        vm.jit_compile().unwrap();
+        assert_eq!(unsafe { vm.prog_exec_jit().unwrap_err() }, expected);
+    }
+}
+
+#[test]
+fn test_vm_err_wrapping_address() {
+    // The end of the access wraps around the address space: it must not pass the bounds check.
+    let prog = &[
+        0x18, 0x01, 0x00, 0x00, 0xfc, 0xff, 0xff, 0xff, // lddw r1, 0xfffffffffffffffc
+        0x00, 0x00, 0x00, 0x00, 0xff, 0xff, 0xff, 0xff,
+        0x79, 0x10, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // ldxdw r0, [r1+0x0]
+        0x95, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00  // exit
+    ];
+    let vm = rbpf::EbpfVmNoData::new(prog).unwrap();
+    let err = vm.prog_exec().unwrap_err();
+    assert_eq!(err.kind, ExecErrorKind::OutOfBoundsLoad);
+    assert_eq!(err.insn_ptr, 2);
+    assert_eq!(err.addr, 0xfffffffffffffffc);
+    assert_eq!(err.size, 8);
+
+    let prog = &[
+        0x18, 0x01, 0x00, 0x00, 0xff, 0xff, 0xff, 0xff, // lddw r1, 0xffffffffffffffff
+        0x00, 0x00, 0x00, 0x00, 0xff, 0xff, 0xff, 0xff,
+        0x62, 0x01, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, // stw [r1+0x0], 0x1
+        0xb7, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // mov64 r0, 0x0
+        0x95, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00  // exit
+    ];
+    let vm = rbpf::EbpfVmNoData::new(prog).unwrap();
+    assert_eq!(vm.prog_exec().unwrap_err().kind, ExecErrorKind::OutOfBoundsStore);
+}
+
+#[test]
+fn test_jit_err_packet_out_of_bound_mem_check() {
+    let prog = &[
+        0x71, 0x10, 0x04, 0x00, 0x00, 0x00, 0x00, 0x00, // ldxb r0, [r1+4]
+        0x95, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00  // exit
+    ];
+    let mut vm = rbpf::EbpfVmRaw::new(prog).unwrap();
+    vm.set_jit_mem_check(true);
+    vm.jit_compile().unwrap();
+
+    // Same error as with the interpreter, and the program can run again.
+    let short = &mut [0xaa, 0xbb];
+    let err = unsafe { vm.prog_exec_jit(short).unwrap_err() };
+    assert_eq!(err, vm.prog_exec(short).unwrap_err());
+    assert_eq!(err.addr, short.as_ptr() as u64 + 4);
+
+    let long = &mut [0xaa, 0xbb, 0xcc, 0xdd, 0xee];
+    unsafe { assert_eq!(vm.prog_exec_jit(long).unwrap(), 0xee); }
+}
+
+// LD_ABS and LD_IND load from packet data, and convert the value from network byte order.
+fn ld_abs_ind_packet() -> [u8; 12] {
+    [0x00, 0x11, 0x22, 0x33, 0x44, 0x55, 0x66, 0x77, 0x88, 0x99, 0xaa, 0xbb]
+}
+
+#[test]
+fn test_vm_ldabs() {
+    let packet = &mut ld_abs_ind_packet();
+    for &(insn, res) in &[("ldabsb 0x3", 0x33), ("ldabsh 0x3", 0x3344),
+                          ("ldabsw 0x3", 0x33445566), ("ldabsdw 0x3", 0x33445566778899aa)] {
+        let prog = assemble(&format!("{}\nexit", insn)).unwrap();
+        let vm = rbpf::EbpfVmRaw::new(&prog).unwrap();
+        assert_eq!(vm.prog_exec(packet).unwrap(), res, "{}", insn);
+    }
+}
+
+#[test]
+fn test_vm_ldind() {
+    let packet = &mut ld_abs_ind_packet();
+    for &(insn, res) in &[("ldindb r2, 0x1", 0x44), ("ldindh r2, 0x1", 0x4455),
+                          ("ldindw r2, 0x1", 0x44556677), ("ldinddw r2, 0x1", 0x445566778899aabb)] {
+        let prog = assemble(&format!("mov64 r2, 0x3\n{}\nexit", insn)).unwrap();
+        let vm = rbpf::EbpfVmRaw::new(&prog).unwrap();
+        assert_eq!(vm.prog_exec(packet).unwrap(), res, "{}", insn);
+    }
+}
+
+#[test]
+fn test_vm_ldabs_ldind_out_of_bounds() {
+    let packet = &mut ld_abs_ind_packet();
+    // The program aborts and returns 0, without reaching the second exit.
+    for insn in &["ldabsw 0x9", "ldabsb 0xc", "ldabsb -1", "ldindb r2, 0x9", "ldindh r2, -0x4"] {
+        let prog = assemble(&format!("mov64 r0, 0x1\nmov64 r2, 0x3\n{}\nmov64 r0, 0x2\nexit", insn))
+            .unwrap();
+        let vm = rbpf::EbpfVmRaw::new(&prog).unwrap();
+        assert_eq!(vm.prog_exec(packet).unwrap(), 0, "{}", insn);
+    }
+}
+
+#[test]
+fn test_vm_ldabs_mbuff() {
+    // With a metadata buffer, the packet data is still the implicit base for LD_ABS.
+    let prog = assemble("ldabsh 0x4\nexit").unwrap();
+    let packet = &mut ld_abs_ind_packet();
+    let mut vm = rbpf::EbpfVmFixedMbuff::new(&prog, 0x40, 0x50).unwrap();
+    assert_eq!(vm.prog_exec(packet).unwrap(), 0x4455);
+}
+
+#[test]
+fn test_jit_ldabs() {
+    let packet = &mut ld_abs_ind_packet();
+    for &(insn, res) in &[("ldabsb 0x3", 0x33), ("ldabsh 0x3", 0x3344),
+                          ("ldabsw 0x3", 0x33445566), ("ldabsdw 0x3", 0x33445566778899aa)] {
+        let prog = assemble(&format!("{}\nexit", insn)).unwrap();
+        let mut vm = rbpf::EbpfVmRaw::new(&prog).unwrap();
+        vm.jit_compile().unwrap();
+        unsafe { assert_eq!(vm.prog_exec_jit(packet).unwrap(), res, "{}", insn); }
+    }
+}
+
+#[test]
+fn test_jit_ldind() {
+    let packet = &mut ld_abs_ind_packet();
+    for &(insn, res) in &[("ldindb r2, 0x1", 0x44), ("ldindh r2, 0x1", 0x4455),
+                          ("ldindw r2, 0x1", 0x44556677), ("ldinddw r2, 0x1", 0x445566778899aabb)] {
+        let prog = assemble(&format!("mov64 r2, 0x3\n{}\nexit", insn)).unwrap();
+        let mut vm = rbpf::EbpfVmRaw::new(&prog).unwrap();
+        vm.jit_compile().unwrap();
+        unsafe { assert_eq!(vm.prog_exec_jit(packet).unwrap(), res, "{}", insn); }
+    }
+}
+
+#[test]
+fn test_jit_ldabs_ldind_out_of_bounds() {
+    let packet = &mut ld_abs_ind_packet();
+    for insn in &["ldabsw 0x9", "ldabsb 0xc", "ldabsb -1", "ldindb r2, 0x9", "ldindh r2, -0x4"] {
+        let prog = assemble(&format!("mov64 r0, 0x1\nmov64 r2, 0x3\n{}\nmov64 r0, 0x2\nexit", insn))
+            .unwrap();
+        let mut vm = rbpf::EbpfVmRaw::new(&prog).unwrap();
+        vm.jit_compile().unwrap();
+        unsafe { assert_eq!(vm.prog_exec_jit(packet).unwrap(), 0, "{}", insn); }
+    }
+}
+
+#[test]
+fn test_jit_ldabs_mbuff() {
+    let prog = assemble("ldabsh 0x4\nexit").unwrap();
+    let packet = &mut ld_abs_ind_packet();
+    let mut vm = rbpf::EbpfVmFixedMbuff::new(&prog, 0x40, 0x50).unwrap();
+    vm.jit_compile().unwrap();
+    unsafe { assert_eq!(vm.prog_exec_jit(packet).unwrap(), 0x4455); }
+}
+
+#[test]
+fn test_vm_xadd() {
+    let prog = assemble("
+        mov64 r0, 0x0
+        stxdw [r10-0x8], r0
+        mov64 r2, 0x11
+        stxxadddw [r10-0x8], r2
+        stxxaddw [r10-0x8], r2
+        mov64 r2, -1
+        stxxaddw [r1+0x4], r2
+        ldxw r0, [r1+0x4]
+        ldxdw r1, [r10-0x8]
+        add64 r0, r1
+        exit").unwrap();
+    let mem = &mut [0x00, 0x00, 0x00, 0x00, 0x02, 0x00, 0x00, 0x00];
+    let vm = rbpf::EbpfVmRaw::new(&prog).unwrap();
+    assert_eq!(vm.prog_exec(mem).unwrap(), 0x23);
+    assert_eq!(mem, &[0x00, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00]);
+}
+
+#[test]
+fn test_vm_err_xadd_unaligned() {
+    let prog = assemble("
+        mov64 r2, 0x1
+        stxxaddw [r10-0x6], r2
+        exit").unwrap();
+    let vm = rbpf::EbpfVmNoData::new(&prog).unwrap();
+    let err = vm.prog_exec().unwrap_err();
+    assert_eq!(err.kind, ExecErrorKind::UnalignedAtomic);
+    assert_eq!(err.insn_ptr, 1);
+    assert_eq!(err.size, 4);
+}
+
+#[test]
+fn test_jit_err_xadd_unaligned() {
+    for &(src, size) in &[("stxxaddw [r10-0xd], r1", 4), ("stxxadddw [r10-0xc], r1", 8)] {
+        let prog = assemble(&format!("
+            mov64 r1, 0x1
+            {}
+            exit", src)).unwrap();
+        for &mem_check in &[false, true] {
+            let mut vm = rbpf::EbpfVmNoData::new(&prog).unwrap();
+            vm.set_jit_mem_check(mem_check);
+            vm.jit_compile().unwrap();
+            // Same error as with the interpreter, the stack is at a different address.
+            let err = unsafe { vm.prog_exec_jit().unwrap_err() };
+            assert_eq!(err.kind, ExecErrorKind::UnalignedAtomic);
+            assert_eq!(err.insn_ptr, 1);
+            assert_eq!(err.size, size);
+            assert_eq!(err.addr % 8, vm.prog_exec().unwrap_err().addr % 8);
+        }
+    }
+}
+
+#[test]
+fn test_jit_xadd() {
+    let prog = assemble("
+        mov64 r0, 0x0
+        stxdw [r10-0x8], r0
+        mov64 r2, 0x11
+        stxxadddw [r10-0x8], r2
+        stxxaddw [r10-0x8], r2
+        mov64 r2, -1
+        stxxaddw [r1+0x4], r2
+        ldxw r0, [r1+0x4]
+        ldxdw r1, [r10-0x8]
+        add64 r0, r1
+        exit").unwrap();
+    let mem = &mut [0x00, 0x00, 0x00, 0x00, 0x02, 0x00, 0x00, 0x00];
+    let mut vm = rbpf::EbpfVmRaw::new(&prog).unwrap();
+    vm.jit_compile().unwrap();
+    unsafe { assert_eq!(vm.prog_exec_jit(mem).unwrap(), 0x23); }
+    assert_eq!(mem, &[0x00, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00]);
+}
+
+// Several threads increment the same counter, no update must be lost.
+fn xadd_concurrent(jit: bool) {
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicU64, Ordering};
+    use std::thread;
+
+    const THREADS: u64 = 4;
+    const LOOPS: u64 = 10000;
+    let prog = assemble(&format!("
+        mov64 r2, 0x1
+        mov64 r3, {}
+    loop:
+        stxxadddw [r1+0x0], r2
+        add64 r3, -1
+        jne r3, 0x0, loop
+        mov64 r0, 0x0
+        exit", LOOPS)).unwrap();
+    let counter = Arc::new(AtomicU64::new(0));
+
+    let handles = (0..THREADS).map(|_| {
+        let prog = prog.clone();
+        let counter = counter.clone();
+        thread::spawn(move || {
+            // The shared counter is used as the packet data for each program.
+            let mem = unsafe {
+                std::slice::from_raw_parts_mut(&*counter as *const AtomicU64 as *mut u8, 8)
+            };
+            let mut vm = rbpf::EbpfVmRaw::new(&prog).unwrap();
+            if jit {
+                vm.jit_compile().unwrap();
+                unsafe { vm.prog_exec_jit(mem).unwrap(); }
+            } else {
+                vm.prog_exec(mem).unwrap();
+            }
+        })
+    }).collect::<Vec<_>>();
+    for handle in handles {
+        handle.join().unwrap();
+    }
+    assert_eq!(counter.load(Ordering::SeqCst), THREADS * LOOPS);
+}
+
+#[test]
+fn test_vm_xadd_concurrent() {
+    xadd_concurrent(false);
+}
+
+#[test]
+fn test_jit_xadd_concurrent() {
+    xadd_concurrent(true);
+}
+
+// Program of `ebpf::PROG_MAX_INSNS` instructions, incrementing r0 through the stack.
+fn max_size_prog() -> Vec<u8> {
+    let body = ["stxdw [r10-0x8], r0", "ldxdw r0, [r10-0x8]", "add64 r0, 0x1"];
+    let mut asm = "mov64 r0, 0x0\n".to_string();
+    for i in 0..rbpf::ebpf::PROG_MAX_INSNS - 2 {
+        asm.push_str(body[i % 3]);
+        asm.push('\n');
+    }
+    asm.push_str("exit");
+    let prog = assemble(&asm).unwrap();
+    assert_eq!(prog.len(), rbpf::ebpf::PROG_MAX_SIZE);
+    prog
+}
+
+#[test]
+fn test_jit_max_size_prog() {
+    // With memory checks, the code is much larger than one page.
+    let prog = max_size_prog();
+    let mut vm = rbpf::EbpfVmNoData::new(&prog).unwrap();
+    vm.set_jit_mem_check(true);
+    vm.jit_compile().unwrap();
+    unsafe { assert_eq!(vm.prog_exec_jit().unwrap(), 1364); }
+}
+
+#[test]
+fn test_jit_max_size_tail_calls() {
+    // Programs reachable by tail calls are compiled along with the main one, so the code can
+    // hold more than `ebpf::PROG_MAX_INSNS` instructions.
+    let prog = max_size_prog();
+    let mut prog_array = rbpf::ProgramArray::new(2);
+    prog_array.set(0, &prog).unwrap();
+    prog_array.set(1, &prog).unwrap();
+    let main = tail_call_prog(1);
+    let mut vm = rbpf::EbpfVmRaw::new(&main).unwrap();
+    vm.set_prog_array(&prog_array).unwrap();
+    vm.jit_compile().unwrap();
+    unsafe { assert_eq!(vm.prog_exec_jit(&mut []).unwrap(), 1364); }
+}
+
+// Memory mappings of the process, as listed in /proc/self/maps.
+fn memory_mappings() -> Vec<String> {
+    std::fs::read_to_string("/proc/self/maps").unwrap().lines().map(String::from).collect()
+}
+
+// Total size of the anonymous executable mappings, where JIT-compiled programs live.
+fn jit_memory_size() -> u64 {
+    memory_mappings().iter().filter(|m| {
+        let fields = m.split_whitespace().collect::<Vec<&str>>();
+        fields[1].contains('x') && fields.len() == 5
+    }).map(|m| {
+        let range = m.split_whitespace().next().unwrap();
+        let mut bounds = range.split('-').map(|b| u64::from_str_radix(b, 16).unwrap());
+        let start = bounds.next().unwrap();
+        bounds.next().unwrap() - start
+    }).sum()
+}
+
+#[test]
+fn test_jit_compile_releases_memory() {
+    // Compiled programs are unmapped when replaced, or when the VM is dropped. Otherwise, this
+    // would leak about 16 MB.
+    let prog = max_size_prog();
+    let before = jit_memory_size();
+    for _ in 0..200 {
+        let mut vm = rbpf::EbpfVmNoData::new(&prog).unwrap();
+        vm.jit_compile().unwrap();
+        vm.jit_compile().unwrap();
+    }
+    assert!(jit_memory_size() < before + 4 * 1024 * 1024);
+}
+
+#[test]
+fn test_jit_code_not_writable() {
+    let prog = max_size_prog();
+    let mut vm = rbpf::EbpfVmNoData::new(&prog).unwrap();
+    vm.jit_compile().unwrap();
+    assert!(memory_mappings().iter().all(|m| !m.contains("rwx")));
+    unsafe { assert_eq!(vm.prog_exec_jit().unwrap(), 1364); }
+}
+
+#[test]
+#[should_panic(expected = "Error: program has not been JIT-compiled")]
+fn test_jit_err_not_compiled() {
+    let prog = max_size_prog();
+    let vm = rbpf::EbpfVmNoData::new(&prog).unwrap();
+    unsafe { vm.prog_exec_jit().unwrap(); }
+}
+
+#[test]
+fn test_jit_reload_prog() {
+    let prog1 = assemble("
+        mov64 r0, 0x1
+        exit").unwrap();
+    let prog2 = assemble("
+        mov64 r0, 0x2
+        exit").unwrap();
+    let mut vm = rbpf::EbpfVmNoData::new(&prog1).unwrap();
+    vm.jit_compile().unwrap();
+    unsafe { assert_eq!(vm.prog_exec_jit().unwrap(), 0x1); }
+
+    // The new program runs once compiled, not the one compiled earlier.
+    vm.set_prog(&prog2).unwrap();
+    vm.jit_compile().unwrap();
+    unsafe { assert_eq!(vm.prog_exec_jit().unwrap(), 0x2); }
+}
+
+#[test]
+#[should_panic(expected = "Error: program has not been JIT-compiled")]
+fn test_jit_err_stale_prog() {
+    let prog1 = assemble("
+        mov64 r0, 0x1
+        exit").unwrap();
+    let prog2 = assemble("
+        mov64 r0, 0x2
+        exit").unwrap();
+    let mut vm = rbpf::EbpfVmNoData::new(&prog1).unwrap();
+    vm.jit_compile().unwrap();
+    vm.set_prog(&prog2).unwrap();
+    unsafe { vm.prog_exec_jit().unwrap(); }
+}
+
+#[test]
+#[should_panic(expected = "Error: program has not been JIT-compiled")]
+fn test_jit_err_stale_maps() {
+    let prog = assemble("
+        mov64 r0, 0x1
+        exit").unwrap();
+    let map = Map::new(MapType::Array, 4, 8, 1).unwrap();
+    let mut vm = rbpf::EbpfVmRaw::new(&prog).unwrap();
+    vm.set_insn_budget(Some(10));
+    vm.jit_compile().unwrap();
+    vm.register_map(0, &map);
+    unsafe { vm.prog_exec_jit(&mut [0u8; 4]).unwrap(); }
+}
+
+// Values to compare with the less-than jumps, in both signed and unsigned flavours.
+const LT_JUMP_VALUES: [i64; 6] = [0, 1, -1, -2, i64::MIN, i64::MAX];
+
+type JumpCondition = fn(i64, i64) -> bool;
+
+// Check each less-than jump against the expected result, for all pairs of values and with both
+// the immediate and the register forms of the instruction.
+fn lt_jumps_check(exec: &dyn Fn(&[u8]) -> u64) {
+    let ops: [(&str, JumpCondition); 4] = [
+        ("jlt",  |a, b| (a as u64) <  (b as u64)),
+        ("jle",  |a, b| (a as u64) <= (b as u64)),
+        ("jslt", |a, b| a <  b),
+        ("jsle", |a, b| a <= b),
+    ];
+    for &(name, op) in &ops {
+        for &a in &LT_JUMP_VALUES {
+            for &b in &LT_JUMP_VALUES {
+                let expected = op(a, b) as u64;
+                let src_reg = format!("
+                    lddw r1, {:#x}
+                    lddw r2, {:#x}
+                    mov64 r0, 0x1
+                    {} r1, r2, +1
+                    mov64 r0, 0x0
+                    exit", a, b, name);
+                let prog = assemble(&src_reg).unwrap();
+                assert_eq!(exec(&prog), expected, "{} {:#x}, {:#x}", name, a, b);
+
+                // Immediates are sign-extended 32-bit values.
+                if b as i32 as i64 == b {
+                    let src_imm = format!("
+                        lddw r1, {:#x}
+                        mov64 r0, 0x1
+                        {} r1, {}, +1
+                        mov64 r0, 0x0
+                        exit", a, name, b);
+                    let prog = assemble(&src_imm).unwrap();
+                    assert_eq!(exec(&prog), expected, "{} {:#x}, {}", name, a, b);
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn test_vm_lt_jumps() {
+    lt_jumps_check(&|prog| {
+        let vm = rbpf::EbpfVmNoData::new(prog).unwrap();
+        vm.prog_exec().unwrap()
+    });
+}
+
+#[test]
+fn test_jit_lt_jumps() {
+    lt_jumps_check(&|prog| {
+        let mut vm = rbpf::EbpfVmNoData::new(prog).unwrap();
+        vm.jit_compile().unwrap();
+        unsafe { vm.prog_exec_jit().unwrap() }
+    });
+}
+
+// Values for 32-bit jumps: the upper 32 bits must be ignored by the comparisons.
+const JMP32_VALUES: [i64; 7] = [0, 1, -1, 0x1_0000_0000, 0x7fff_ffff_ffff_ffff,
+                                0x1234_5678_8000_0000, -0x8000_0001];
+
+// Check each 32-bit jump against the expected result, for all pairs of values and with both the
+// immediate and the register forms of the instruction.
+fn jmp32_check(exec: &dyn Fn(&[u8]) -> u64) {
+    let ops: [(&str, JumpCondition); 11] = [
+        ("jeq32",  |a, b| a as u32 == b as u32),
+        ("jgt32",  |a, b| a as u32 >  b as u32),
+        ("jge32",  |a, b| a as u32 >= b as u32),
+        ("jset32", |a, b| a as u32 &  b as u32 != 0),
+        ("jne32",  |a, b| a as u32 != b as u32),
+        ("jsgt32", |a, b| a as i32 >  b as i32),
+        ("jsge32", |a, b| a as i32 >= b as i32),
+        ("jlt32",  |a, b| (a as u32) <  b as u32),
+        ("jle32",  |a, b| (a as u32) <= b as u32),
+        ("jslt32", |a, b| (a as i32) <  b as i32),
+        ("jsle32", |a, b| (a as i32) <= b as i32),
+    ];
+    for &(name, op) in &ops {
+        for &a in &JMP32_VALUES {
+            for &b in &JMP32_VALUES {
+                let expected = op(a, b) as u64;
+                let src_reg = format!("
+                    lddw r1, {:#x}
+                    lddw r2, {:#x}
+                    mov64 r0, 0x1
+                    {} r1, r2, +1
+                    mov64 r0, 0x0
+                    exit", a, b, name);
+                let prog = assemble(&src_reg).unwrap();
+                assert_eq!(exec(&prog), expected, "{} {:#x}, {:#x}", name, a, b);
+
+                // Only the lower 32 bits of the immediate are compared.
+                let src_imm = format!("
+                    lddw r1, {:#x}
+                    mov64 r0, 0x1
+                    {} r1, {}, +1
+                    mov64 r0, 0x0
+                    exit", a, name, b as i32);
+                let prog = assemble(&src_imm).unwrap();
+                assert_eq!(exec(&prog), expected, "{} {:#x}, {}", name, a, b as i32);
+            }
+        }
+    }
+}
+
+#[test]
+fn test_vm_jmp32() {
+    jmp32_check(&|prog| {
+        let vm = rbpf::EbpfVmNoData::new(prog).unwrap();
+        vm.prog_exec().unwrap()
+    });
+}
+
+#[test]
+fn test_jit_jmp32() {
+    jmp32_check(&|prog| {
+        let mut vm = rbpf::EbpfVmNoData::new(prog).unwrap();
+        vm.jit_compile().unwrap();
+        unsafe { vm.prog_exec_jit().unwrap() }
+    });
+}
+
+// Division and modulo by an immediate do not depend on the value of r0.
+#[test]
+fn test_jit_div_mod_imm_r0_zero() {
+    for op in &["div64", "mod64", "div32", "mod32"] {
+        let prog = assemble(&format!("
+            mov64 r0, 0x0
+            mov64 r1, 0x7
+            {} r1, 0x2
+            mov64 r0, r1
+            exit", op)).unwrap();
+        let mut vm = rbpf::EbpfVmNoData::new(&prog).unwrap();
+        vm.jit_compile().unwrap();
+        let expected = vm.prog_exec().unwrap();
+        assert_eq!(unsafe { vm.prog_exec_jit().unwrap() }, expected, "{}", op);
+    }
+}
+
+#[test]
+fn test_jit_mov32_reg_zero_extends() {
+    let prog = assemble("
+        lddw r1, 0x100000003
+        mov32 r0, r1
+        exit").unwrap();
+    let mut vm = rbpf::EbpfVmNoData::new(&prog).unwrap();
+    vm.jit_compile().unwrap();
+    assert_eq!(vm.prog_exec().unwrap(), 0x3);
+    assert_eq!(unsafe { vm.prog_exec_jit().unwrap() }, 0x3);
+}
+
+// The function gets its own stack frame, and r6-r9 are preserved across the call.
+const LOCAL_CALL_PROG: &str = "
+        mov64 r6, 0x11
+        stdw [r10-0x8], 0x22
+        mov64 r1, 0x3
+        call_local fn
+        ldxdw r1, [r10-0x8]
+        add64 r0, r1
+        add64 r0, r6
+        exit
+    fn:
+        mov64 r6, 0x1000
+        stdw [r10-0x8], 0x2000
+        ldxdw r0, [r10-0x8]
+        add64 r0, r6
+        add64 r0, r1
+        exit";
+
+// Recursive factorial, called with n in r1. Computing n! takes n + 1 nested calls.
+fn factorial_prog(n: u32) -> Vec<u8> {
+    assemble(&format!("
+        mov64 r1, {}
+        call_local fact
+        exit
+    fact:
+        mov64 r0, 0x1
+        jeq r1, 0x0, done
+        mov64 r6, r1
+        add64 r1, -1
+        call_local fact
+        mul64 r0, r6
+    done:
+        exit", n)).unwrap()
+}
+
+#[test]
+fn test_vm_call_local() {
+    let prog = assemble(LOCAL_CALL_PROG).unwrap();
+    let vm = rbpf::EbpfVmNoData::new(&prog).unwrap();
+    assert_eq!(vm.prog_exec().unwrap(), 0x3036);
+}
+
+#[test]
+fn test_vm_call_local_recursive() {
+    let prog = factorial_prog(6);
+    let vm = rbpf::EbpfVmNoData::new(&prog).unwrap();
+    assert_eq!(vm.prog_exec().unwrap(), 720);
+}
+
+#[test]
+fn test_vm_err_call_depth_exceeded() {
+    let prog = factorial_prog(7);
+    let vm = rbpf::EbpfVmNoData::new(&prog).unwrap();
+    let err = vm.prog_exec().unwrap_err();
+    assert_eq!(err.kind, ExecErrorKind::CallDepthExceeded);
+    assert_eq!(err.to_string(), "Error: exceeded maximum call depth of 8 (insn #7)");
+}
+
+#[test]
+fn test_jit_call_local() {
+    let prog = assemble(LOCAL_CALL_PROG).unwrap();
+    let mut vm = rbpf::EbpfVmNoData::new(&prog).unwrap();
+    vm.jit_compile().unwrap();
+    unsafe { assert_eq!(vm.prog_exec_jit().unwrap(), 0x3036); }
+}
+
+#[test]
+fn test_jit_call_local_recursive() {
+    let prog = factorial_prog(6);
+    let mut vm = rbpf::EbpfVmNoData::new(&prog).unwrap();
+    vm.jit_compile().unwrap();
+    unsafe { assert_eq!(vm.prog_exec_jit().unwrap(), 720); }
+}
+
+#[test]
+fn test_jit_call_depth_exceeded() {
+    let prog = factorial_prog(7);
+    let mut vm = rbpf::EbpfVmNoData::new(&prog).unwrap();
+    vm.jit_compile().unwrap();
+    let err = unsafe { vm.prog_exec_jit().unwrap_err() };
+    assert_eq!(err.kind, ExecErrorKind::CallDepthExceeded);
+    assert_eq!(err.to_string(), "Error: exceeded maximum call depth of 8 (insn #7)");
+}
+
+#[test]
+fn test_jit_mem_check_call_local() {
+    // Each local function accesses its own stack frame.
+    let prog = assemble(LOCAL_CALL_PROG).unwrap();
+    let mut vm = rbpf::EbpfVmNoData::new(&prog).unwrap();
+    vm.set_jit_mem_check(true);
+    vm.jit_compile().unwrap();
+    unsafe { assert_eq!(vm.prog_exec_jit().unwrap(), 0x3036); }
+
+    // Frames are contiguous, but the stack has no room below the last one.
+    let prog = assemble("
+        call_local fn
+        exit
+    fn:
+        stdw [r10-0x208], 0x1
+        ldxdw r0, [r10-0x208]
+        stdw [r10-0x1000], 0x1
+        exit").unwrap();
+    let mut vm = rbpf::EbpfVmNoData::new(&prog).unwrap();
+    vm.set_jit_mem_check(true);
+    vm.jit_compile().unwrap();
+    let err = unsafe { vm.prog_exec_jit().unwrap_err() };
+    assert_eq!(err.kind, ExecErrorKind::OutOfBoundsStore);
+    assert_eq!(err.insn_ptr, 4);
+    assert_eq!(err.size, 8);
+    assert_eq!(vm.prog_exec().unwrap_err().insn_ptr, 4);
+}
+
+#[test]
+fn test_jit_call_local_helper() {
+    // Helpers called from a local function need an aligned stack too.
+    let prog = assemble("
+        mov64 r1, 0x9
+        call_local fn
+        exit
+    fn:
+        call 0x1
+        exit").unwrap();
+    let mut vm = rbpf::EbpfVmNoData::new(&prog).unwrap();
+    vm.register_helper(1, helpers::sqrti);
+    vm.jit_compile().unwrap();
+    unsafe { assert_eq!(vm.prog_exec_jit().unwrap(), 3); }
+}
+
+#[test]
+fn test_verifier_err_call_local_target() {
+    let prog = assemble("call_local +0x1\nexit").unwrap();
+    assert_eq!(rbpf::EbpfVmNoData::new(&prog).err().unwrap(), EbpfError::JumpOutOfCode(2, 0));
+
+    let prog = assemble("call_local +0x1\nlddw r0, 0x1\nexit").unwrap();
+    assert_eq!(rbpf::EbpfVmNoData::new(&prog).err().unwrap(), EbpfError::JumpToMiddleOfLdDw(2, 0));
+
+    let mut prog = assemble("call 0x1\nexit").unwrap();
+    prog[1] = 0x20; // src = 2
+    assert_eq!(rbpf::EbpfVmNoData::new(&prog).err().unwrap(), EbpfError::InvalidSrcRegister(0));
+}
+
+// Counts its own runs in the first eight bytes of packet data, and tail calls itself (index 0).
+const TAIL_CALL_COUNTER_PROG: &str = "
+        ldxdw r2, [r1+0x0]
+        add64 r2, 0x1
+        stxdw [r1+0x0], r2
+        mov64 r3, 0x0
+        tail_call
+        ldxdw r0, [r1+0x0]
+        exit";
+
+// Tail calls the program at `index` in the array, with the packet data in r1.
+fn tail_call_prog(index: i32) -> Vec<u8> {
+    assemble(&format!("
+        mov64 r0, 0x1
+        mov64 r3, {}
+        tail_call
+        mov64 r0, 0x2
+        exit", index)).unwrap()
+}
+
+// Returns the first byte of packet data, plus r0 from the caller.
+const TAIL_CALL_TARGET_PROG: &str = "
+        ldxb r2, [r1+0x0]
+        add64 r0, r2
+        exit";
+
+#[test]
+fn test_vm_tail_call() {
+    let prog = tail_call_prog(1);
+    let target = assemble(TAIL_CALL_TARGET_PROG).unwrap();
+    let mut prog_array = rbpf::ProgramArray::new(2);
+    prog_array.set(1, &target).unwrap();
+
+    let mut vm = rbpf::EbpfVmRaw::new(&prog).unwrap();
+    vm.set_prog_array(&prog_array).unwrap();
+    assert_eq!(vm.prog_exec(&mut [0x10]).unwrap(), 0x11);
+}
+
+#[test]
+fn test_vm_tail_call_fails() {
+    let target = assemble(TAIL_CALL_TARGET_PROG).unwrap();
+    let mut prog_array = rbpf::ProgramArray::new(2);
+    prog_array.set(1, &target).unwrap();
+
+    // Empty slot, out of the array, and no array at all: go on with next instruction.
+    for &(index, use_array) in &[(0, true), (2, true), (-1, true), (1, false)] {
+        let prog = tail_call_prog(index);
+        let mut vm = rbpf::EbpfVmRaw::new(&prog).unwrap();
+        if use_array {
+            vm.set_prog_array(&prog_array).unwrap();
+        }
+        assert_eq!(vm.prog_exec(&mut [0x10]).unwrap(), 0x2, "index {}", index);
     }
 }
+
+#[test]
+fn test_vm_tail_call_limit() {
+    let prog = assemble(TAIL_CALL_COUNTER_PROG).unwrap();
+    let mut prog_array = rbpf::ProgramArray::new(1);
+    prog_array.set(0, &prog).unwrap();
+
+    let mut vm = rbpf::EbpfVmRaw::new(&prog).unwrap();
+    vm.set_prog_array(&prog_array).unwrap();
+    assert_eq!(vm.prog_exec(&mut [0; 8]).unwrap(), 34);
+    vm.set_max_tail_calls(5);
+    assert_eq!(vm.prog_exec(&mut [0; 8]).unwrap(), 6);
+}
+
+#[test]
+fn test_jit_tail_call() {
+    let prog = tail_call_prog(1);
+    let target = assemble(TAIL_CALL_TARGET_PROG).unwrap();
+    let mut prog_array = rbpf::ProgramArray::new(2);
+    prog_array.set(1, &target).unwrap();
+
+    let mut vm = rbpf::EbpfVmRaw::new(&prog).unwrap();
+    vm.set_prog_array(&prog_array).unwrap();
+    vm.jit_compile().unwrap();
+    unsafe { assert_eq!(vm.prog_exec_jit(&mut [0x10]).unwrap(), 0x11); }
+}
+
+#[test]
+fn test_jit_tail_call_fails() {
+    let target = assemble(TAIL_CALL_TARGET_PROG).unwrap();
+    let mut prog_array = rbpf::ProgramArray::new(2);
+    prog_array.set(1, &target).unwrap();
+
+    for &(index, use_array) in &[(0, true), (2, true), (-1, true), (1, false)] {
+        let prog = tail_call_prog(index);
+        let mut vm = rbpf::EbpfVmRaw::new(&prog).unwrap();
+        if use_array {
+            vm.set_prog_array(&prog_array).unwrap();
+        }
+        vm.jit_compile().unwrap();
+        unsafe { assert_eq!(vm.prog_exec_jit(&mut [0x10]).unwrap(), 0x2, "index {}", index); }
+    }
+}
+
+#[test]
+fn test_jit_tail_call_limit() {
+    let prog = assemble(TAIL_CALL_COUNTER_PROG).unwrap();
+    let mut prog_array = rbpf::ProgramArray::new(1);
+    prog_array.set(0, &prog).unwrap();
+
+    let mut vm = rbpf::EbpfVmRaw::new(&prog).unwrap();
+    vm.set_prog_array(&prog_array).unwrap();
+    vm.jit_compile().unwrap();
+    unsafe { assert_eq!(vm.prog_exec_jit(&mut [0; 8]).unwrap(), 34); }
+    vm.set_max_tail_calls(5);
+    vm.jit_compile().unwrap();
+    unsafe { assert_eq!(vm.prog_exec_jit(&mut [0; 8]).unwrap(), 6); }
+}
+
+#[test]
+fn test_jit_tail_call_from_local_function() {
+    // The tail call leaves the local function, the target program returns to the caller of
+    // the main program.
+    let prog = assemble("
+        mov64 r6, r1
+        call_local fn
+        mov64 r0, 0x2
+        exit
+    fn:
+        mov64 r1, r6
+        mov64 r0, 0x1
+        mov64 r3, 0x0
+        tail_call
+        exit").unwrap();
+    let target = assemble(TAIL_CALL_TARGET_PROG).unwrap();
+    let mut prog_array = rbpf::ProgramArray::new(1);
+    prog_array.set(0, &target).unwrap();
+
+    let mut vm = rbpf::EbpfVmRaw::new(&prog).unwrap();
+    vm.set_prog_array(&prog_array).unwrap();
+    assert_eq!(vm.prog_exec(&mut [0x10]).unwrap(), 0x11);
+    vm.jit_compile().unwrap();
+    unsafe { assert_eq!(vm.prog_exec_jit(&mut [0x10]).unwrap(), 0x11); }
+}
+
+#[test]
+fn test_jit_err_tail_call_mem_check() {
+    // The faulting instruction is reported relatively to the start of the target program.
+    let prog = tail_call_prog(0);
+    let target = assemble(TAIL_CALL_TARGET_PROG).unwrap();
+    let mut prog_array = rbpf::ProgramArray::new(1);
+    prog_array.set(0, &target).unwrap();
+
+    let mut vm = rbpf::EbpfVmRaw::new(&prog).unwrap();
+    vm.set_prog_array(&prog_array).unwrap();
+    vm.set_jit_mem_check(true);
+    vm.jit_compile().unwrap();
+    let err = unsafe { vm.prog_exec_jit(&mut []).unwrap_err() };
+    assert_eq!(err.kind, ExecErrorKind::OutOfBoundsLoad);
+    assert_eq!(err.insn_ptr, 0);
+    assert_eq!(err, vm.prog_exec(&mut []).unwrap_err());
+}
+
+#[test]
+fn test_verifier_err_prog_array() {
+    let mut prog_array = rbpf::ProgramArray::new(1);
+    assert_eq!(prog_array.set(0, &[0x95, 0, 0, 0, 0, 0, 0]), Err(EbpfError::WrongLength(7)));
+    assert_eq!(prog_array.get(0), None);
+}
+
+// Count the packets per first byte of the packet, in a hash map with fd 1 and one-byte keys.
+// Returns the result of the update, or 1 if the lookup after the update fails.
+const MAP_COUNTER_PROG: &str = "
+    ldxb r2, [r1+0x0]
+    stxb [r10-0x1], r2
+    stdw [r10-0x10], 0x1
+    lddw r1, map_fd(1)
+    mov64 r2, r10
+    add64 r2, -1
+    call 0x1
+    jeq r0, 0x0, insert
+    ldxdw r1, [r0+0x0]
+    add64 r1, 0x1
+    stxdw [r0+0x0], r1
+    mov64 r0, 0x0
+    exit
+insert:
+    lddw r1, map_fd(1)
+    mov64 r2, r10
+    add64 r2, -1
+    mov64 r3, r10
+    add64 r3, -16
+    mov64 r4, 0x1
+    call 0x2
+    exit";
+
+#[test]
+fn test_vm_map_hash_counter() {
+    let prog = assemble(MAP_COUNTER_PROG).unwrap();
+    let map = Map::new(MapType::Hash, 1, 8, 2).unwrap();
+    let mut vm = rbpf::EbpfVmRaw::new(&prog).unwrap();
+    vm.register_map(1, &map);
+
+    for &byte in &[0x11, 0x22, 0x11, 0x11] {
+        assert_eq!(vm.prog_exec(&mut [byte]).unwrap(), 0);
+    }
+    assert_eq!(map.lookup(&[0x11]), Some(3u64.to_le_bytes().to_vec()));
+    assert_eq!(map.lookup(&[0x22]), Some(1u64.to_le_bytes().to_vec()));
+
+    // The map is full, the update fails with -E2BIG.
+    assert_eq!(vm.prog_exec(&mut [0x33]).unwrap(), -7i64 as u64);
+    assert_eq!(map.lookup(&[0x33]), None);
+}
+
+#[test]
+fn test_jit_map_hash_counter() {
+    let prog = assemble(MAP_COUNTER_PROG).unwrap();
+    let map = Map::new(MapType::Hash, 1, 8, 2).unwrap();
+    let mut vm = rbpf::EbpfVmRaw::new(&prog).unwrap();
+    vm.register_map(1, &map);
+    vm.jit_compile().unwrap();
+
+    for &byte in &[0x11, 0x22, 0x11, 0x11] {
+        unsafe { assert_eq!(vm.prog_exec_jit(&mut [byte]).unwrap(), 0); }
+    }
+    assert_eq!(map.lookup(&[0x11]), Some(3u64.to_le_bytes().to_vec()));
+    assert_eq!(map.lookup(&[0x22]), Some(1u64.to_le_bytes().to_vec()));
+    unsafe { assert_eq!(vm.prog_exec_jit(&mut [0x33]).unwrap(), -7i64 as u64); }
+}
+
+#[test]
+fn test_vm_map_array_delete() {
+    // Elements of array maps cannot be deleted, and keys out of the array are not found.
+    let prog = assemble("
+        stw [r10-0x4], 0x0
+        lddw r1, map_fd(2)
+        mov64 r2, r10
+        add64 r2, -4
+        call 0x3
+        mov64 r6, r0
+        stw [r10-0x4], 0x4
+        lddw r1, map_fd(2)
+        mov64 r2, r10
+        add64 r2, -4
+        call 0x1
+        add64 r0, r6
+        exit").unwrap();
+    let map = Map::new(MapType::Array, 4, 4, 4).unwrap();
+    let mut vm = rbpf::EbpfVmNoData::new(&prog).unwrap();
+    vm.register_map(2, &map);
+    assert_eq!(vm.prog_exec().unwrap(), -22i64 as u64);
+}
+
+#[test]
+fn test_vm_map_value_out_of_bounds() {
+    // Values can be accessed through the pointer returned by the lookup, but not past their end.
+    let prog = assemble("
+        stw [r10-0x4], 0x3
+        lddw r1, map_fd(0)
+        mov64 r2, r10
+        add64 r2, -4
+        call 0x1
+        stw [r0+0x0], 0x1
+        stw [r0+0x4], 0x1
+        exit").unwrap();
+    let map = Map::new(MapType::Array, 4, 4, 4).unwrap();
+    let mut vm = rbpf::EbpfVmNoData::new(&prog).unwrap();
+    vm.register_map(0, &map);
+    let err = vm.prog_exec().unwrap_err();
+    assert_eq!(err.kind, ExecErrorKind::OutOfBoundsStore);
+    assert_eq!(err.insn_ptr, 7);
+    assert_eq!(map.lookup(&3u32.to_le_bytes()), Some(vec![1, 0, 0, 0]));
+}
+
+#[test]
+fn test_jit_map_value_out_of_bounds() {
+    let prog = assemble("
+        stw [r10-0x4], 0x3
+        lddw r1, map_fd(0)
+        mov64 r2, r10
+        add64 r2, -4
+        call 0x1
+        stw [r0+0x0], 0x1
+        stw [r0+0x4], 0x1
+        exit").unwrap();
+    let map = Map::new(MapType::Array, 4, 4, 4).unwrap();
+    let mut vm = rbpf::EbpfVmNoData::new(&prog).unwrap();
+    vm.register_map(0, &map);
+    vm.set_jit_mem_check(true);
+    vm.jit_compile().unwrap();
+    let err = unsafe { vm.prog_exec_jit().unwrap_err() };
+    assert_eq!(err.kind, ExecErrorKind::OutOfBoundsStore);
+    assert_eq!(err.insn_ptr, 7);
+    assert_eq!(map.lookup(&3u32.to_le_bytes()), Some(vec![1, 0, 0, 0]));
+}
+
+#[test]
+fn test_vm_map_unknown_fd() {
+    let prog = assemble("
+        mov64 r0, 0x0
+        lddw r1, map_fd(5)
+        exit").unwrap();
+    let vm = rbpf::EbpfVmNoData::new(&prog).unwrap();
+    let err = vm.prog_exec().unwrap_err();
+    assert_eq!(err.kind, ExecErrorKind::UnknownMap(5));
+    assert_eq!(err.insn_ptr, 1);
+}
+
+#[test]
+fn test_jit_map_unknown_fd() {
+    let prog = assemble("
+        mov64 r0, 0x0
+        lddw r1, map_fd(5)
+        exit").unwrap();
+    let mut vm = rbpf::EbpfVmNoData::new(&prog).unwrap();
+    let err = vm.jit_compile().unwrap_err();
+    assert_eq!(err, JitError::UnknownMap(5, 1));
+    assert_eq!(err.to_string(), "[JIT] Error: unknown map (fd: 5) (insn #1)");
+}
+
+// Map helpers called with a handle that does not come from a registered map.
+const MAP_INVALID_HANDLE_PROG: &str = "
+    mov64 r0, 0x0
+    mov64 r1, 0x1234
+    mov64 r2, r10
+    add64 r2, -4
+    call 0x1
+    exit";
+
+#[test]
+fn test_vm_map_helper_invalid_handle() {
+    let prog = assemble(MAP_INVALID_HANDLE_PROG).unwrap();
+    let map = Map::new(MapType::Array, 4, 4, 4).unwrap();
+    let mut vm = rbpf::EbpfVmNoData::new(&prog).unwrap();
+    vm.register_map(0, &map);
+    let err = vm.prog_exec().unwrap_err();
+    assert_eq!(err, ExecError::mem(ExecErrorKind::InvalidMapPointer, 4, 0x1234, 0));
+    assert_eq!(err.to_string(), "Error: invalid map handle 0x1234 passed to helper (insn #4)");
+}
+
+#[test]
+fn test_jit_map_helper_invalid_handle() {
+    let prog = assemble(MAP_INVALID_HANDLE_PROG).unwrap();
+    let map0 = Map::new(MapType::Array, 4, 4, 4).unwrap();
+    let map1 = Map::new(MapType::Hash, 4, 4, 4).unwrap();
+    let mut vm = rbpf::EbpfVmNoData::new(&prog).unwrap();
+    vm.register_map(0, &map0);
+    vm.register_map(1, &map1);
+    vm.jit_compile().unwrap();
+    let err = unsafe { vm.prog_exec_jit().unwrap_err() };
+    assert_eq!(err, ExecError::mem(ExecErrorKind::InvalidMapPointer, 4, 0x1234, 0));
+}
+
+// Map helpers called with a key, and with a value, out of bounds of the memory of the program. The
+// 8-byte value starts 4 bytes below the top of the stack.
+const MAP_KEY_OUT_OF_BOUNDS_PROG: &str = "
+    lddw r1, map_fd(0)
+    mov64 r2, 0x1000
+    call 0x1
+    exit";
+const MAP_VALUE_OUT_OF_BOUNDS_PROG: &str = "
+    stw [r10-0x4], 0x0
+    lddw r1, map_fd(0)
+    mov64 r2, r10
+    add64 r2, -4
+    mov64 r3, r10
+    add64 r3, -4
+    mov64 r4, 0x0
+    call 0x2
+    exit";
+
+#[test]
+fn test_vm_map_helper_args_out_of_bounds() {
+    let map = Map::new(MapType::Array, 4, 8, 4).unwrap();
+
+    let prog = assemble(MAP_KEY_OUT_OF_BOUNDS_PROG).unwrap();
+    let mut vm = rbpf::EbpfVmNoData::new(&prog).unwrap();
+    vm.register_map(0, &map);
+    assert_eq!(vm.prog_exec().unwrap_err(),
+               ExecError::mem(ExecErrorKind::OutOfBoundsLoad, 3, 0x1000, 4));
+
+    let prog = assemble(MAP_VALUE_OUT_OF_BOUNDS_PROG).unwrap();
+    let mut vm = rbpf::EbpfVmNoData::new(&prog).unwrap();
+    vm.register_map(0, &map);
+    let err = vm.prog_exec().unwrap_err();
+    assert_eq!((err.kind, err.insn_ptr, err.size), (ExecErrorKind::OutOfBoundsLoad, 8, 8));
+    assert_eq!(map.lookup(&[0, 0, 0, 0]), Some(vec![0; 8]));
+}
+
+#[test]
+fn test_jit_map_helper_args_out_of_bounds() {
+    let map = Map::new(MapType::Array, 4, 8, 4).unwrap();
+
+    let prog = assemble(MAP_KEY_OUT_OF_BOUNDS_PROG).unwrap();
+    let mut vm = rbpf::EbpfVmNoData::new(&prog).unwrap();
+    vm.register_map(0, &map);
+    vm.set_jit_mem_check(true);
+    vm.jit_compile().unwrap();
+    let err = unsafe { vm.prog_exec_jit().unwrap_err() };
+    assert_eq!(err, ExecError::mem(ExecErrorKind::OutOfBoundsLoad, 3, 0x1000, 4));
+
+    let prog = assemble(MAP_VALUE_OUT_OF_BOUNDS_PROG).unwrap();
+    let mut vm = rbpf::EbpfVmNoData::new(&prog).unwrap();
+    vm.register_map(0, &map);
+    vm.set_jit_mem_check(true);
+    vm.jit_compile().unwrap();
+    let err = unsafe { vm.prog_exec_jit().unwrap_err() };
+    assert_eq!((err.kind, err.insn_ptr, err.size), (ExecErrorKind::OutOfBoundsLoad, 8, 8));
+    assert_eq!(map.lookup(&[0, 0, 0, 0]), Some(vec![0; 8]));
+}
+
+#[test]
+fn test_verifier_err_lddw_invalid_src() {
+    let prog = &[
+        0x18, 0x20, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, // lddw r0 with src 2
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x95, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00  // exit
+    ];
+    assert_eq!(rbpf::EbpfVmNoData::new(prog).err().unwrap(), EbpfError::InvalidSrcRegister(0));
+}
+
+#[test]
+fn test_map_update_flags() {
+    let map = Map::new(MapType::Hash, 2, 1, 1).unwrap();
+    assert_eq!(map.update(&[1, 0], &[1], BPF_EXIST), Err(MapError::KeyNotFound));
+    assert_eq!(map.update(&[1, 0], &[1], BPF_NOEXIST), Ok(()));
+    assert_eq!(map.update(&[1, 0], &[2], BPF_NOEXIST), Err(MapError::KeyExists));
+    assert_eq!(map.update(&[1, 0], &[3], BPF_EXIST), Ok(()));
+    assert_eq!(map.update(&[2, 0], &[4], BPF_ANY), Err(MapError::MapFull));
+    assert_eq!(map.update(&[1, 0], &[5], 4), Err(MapError::InvalidFlags(4)));
+    assert_eq!(map.update(&[1], &[5], BPF_ANY), Err(MapError::InvalidKeySize(1)));
+    assert_eq!(map.lookup(&[1, 0]), Some(vec![3]));
+
+    // Deleting an element makes room for another one.
+    map.delete(&[1, 0]).unwrap();
+    assert_eq!(map.update(&[2, 0], &[4], BPF_ANY), Ok(()));
+    assert_eq!(map.lookup(&[1, 0]), None);
+    assert_eq!(map.lookup(&[2, 0]), Some(vec![4]));
+
+    assert_eq!(Map::new(MapType::Array, 8, 1, 1).err(), Some(MapError::InvalidKeySize(8)));
+}
+
+#[test]
+fn test_map_err_sizes() {
+    assert!(Map::new(MapType::Hash, MAX_KEY_SIZE, MAX_VALUE_SIZE, 1).is_ok());
+    assert_eq!(Map::new(MapType::Hash, MAX_KEY_SIZE + 1, 8, 1).err(),
+               Some(MapError::InvalidKeySize(MAX_KEY_SIZE + 1)));
+    assert_eq!(Map::new(MapType::Hash, 8, MAX_VALUE_SIZE + 1, 1).err(),
+               Some(MapError::InvalidValueSize(MAX_VALUE_SIZE + 1)));
+    assert_eq!(Map::new(MapType::Array, 4, 8, 0).err(), Some(MapError::InvalidMaxEntries(0)));
+
+    // The storage for the values is too large, or its size overflows.
+    assert_eq!(Map::new(MapType::Array, 4, 8, 1 << 30).err(),
+               Some(MapError::InvalidMaxEntries(1 << 30)));
+    assert_eq!(Map::new(MapType::Hash, 4, 8, usize::MAX).err(),
+               Some(MapError::InvalidMaxEntries(usize::MAX)));
+}
+
+// Adds 2 to r0 ten times: 2 + 3 * 10 + 1 = 33 instructions are executed, lddw counting as one.
+const INSN_BUDGET_LOOP_PROG: &str = "
+        lddw r0, 0x0
+        mov64 r1, 0xa
+    loop:
+        add64 r0, 0x2
+        add64 r1, -1
+        jne r1, 0x0, loop
+        exit";
+
+// Loops forever: the verifier only rejects jumps to the same instruction.
+const INSN_BUDGET_INFINITE_PROG: &str = "
+        mov64 r0, 0x0
+    loop:
+        add64 r0, 0x1
+        ja loop
+        exit";
+
+#[test]
+fn test_vm_insn_budget() {
+    let prog = assemble(INSN_BUDGET_LOOP_PROG).unwrap();
+    let mut vm = rbpf::EbpfVmNoData::new(&prog).unwrap();
+    assert_eq!(vm.prog_exec_metered().unwrap(), (20, 33));
+
+    vm.set_insn_budget(Some(33));
+    assert_eq!(vm.prog_exec_metered().unwrap(), (20, 33));
+
+    vm.set_insn_budget(Some(32));
+    let err = vm.prog_exec_metered().unwrap_err();
+    assert_eq!(err.kind, ExecErrorKind::InstructionBudgetExceeded);
+    assert_eq!(err.insn_ptr, 6);
+}
+
+#[test]
+fn test_jit_insn_budget() {
+    let prog = assemble(INSN_BUDGET_LOOP_PROG).unwrap();
+    let mut vm = rbpf::EbpfVmNoData::new(&prog).unwrap();
+    vm.jit_compile().unwrap();
+    // Instructions are not counted without a budget.
+    unsafe { assert_eq!(vm.prog_exec_jit_metered().unwrap(), (20, None)); }
+
+    vm.set_insn_budget(Some(33));
+    vm.jit_compile().unwrap();
+    unsafe { assert_eq!(vm.prog_exec_jit_metered().unwrap(), (20, Some(33))); }
+
+    vm.set_insn_budget(Some(32));
+    vm.jit_compile().unwrap();
+    let err = unsafe { vm.prog_exec_jit_metered().unwrap_err() };
+    assert_eq!(err.kind, ExecErrorKind::InstructionBudgetExceeded);
+    assert_eq!(err.insn_ptr, 6);
+}
+
+#[test]
+fn test_vm_insn_budget_infinite_loop() {
+    let prog = assemble(INSN_BUDGET_INFINITE_PROG).unwrap();
+    let mut vm = rbpf::EbpfVmRaw::new(&prog).unwrap();
+    vm.set_insn_budget(Some(1000));
+    let err = vm.prog_exec(&mut []).unwrap_err();
+    assert_eq!(err.kind, ExecErrorKind::InstructionBudgetExceeded);
+    assert_eq!(err.insn_ptr, 2);
+}
+
+#[test]
+fn test_jit_insn_budget_infinite_loop() {
+    let prog = assemble(INSN_BUDGET_INFINITE_PROG).unwrap();
+    let mut vm = rbpf::EbpfVmRaw::new(&prog).unwrap();
+    vm.set_insn_budget(Some(1000));
+    vm.jit_compile().unwrap();
+    let err = unsafe { vm.prog_exec_jit(&mut []).unwrap_err() };
+    assert_eq!(err.kind, ExecErrorKind::InstructionBudgetExceeded);
+    assert_eq!(err.insn_ptr, 2);
+}
+
+#[test]
+fn test_jit_insn_budget_local_and_tail_calls() {
+    // Instructions of local functions and of the target of the tail call are counted, and the
+    // faulting instruction is reported relatively to the start of the program it belongs to.
+    let prog = assemble("
+        mov64 r6, r1
+        call_local fn
+        mov64 r0, 0x2
+        exit
+    fn:
+        mov64 r1, r6
+        mov64 r0, 0x1
+        mov64 r3, 0x0
+        tail_call
+        exit").unwrap();
+    let target = assemble(TAIL_CALL_TARGET_PROG).unwrap();
+    let mut prog_array = rbpf::ProgramArray::new(1);
+    prog_array.set(0, &target).unwrap();
+
+    let mut vm = rbpf::EbpfVmRaw::new(&prog).unwrap();
+    vm.set_prog_array(&prog_array).unwrap();
+    vm.set_insn_budget(Some(9));
+    assert_eq!(vm.prog_exec_metered(&mut [0x10]).unwrap(), (0x11, 9));
+    vm.jit_compile().unwrap();
+    unsafe { assert_eq!(vm.prog_exec_jit_metered(&mut [0x10]).unwrap(), (0x11, Some(9))); }
+
+    vm.set_insn_budget(Some(8));
+    assert_eq!(vm.prog_exec(&mut [0x10]).unwrap_err().insn_ptr, 2);
+    vm.jit_compile().unwrap();
+    unsafe { assert_eq!(vm.prog_exec_jit(&mut [0x10]).unwrap_err().insn_ptr, 2); }
+}
+
+#[test]
+fn test_jit_insn_budget_fixed_mbuff() {
+    let prog = assemble(INSN_BUDGET_LOOP_PROG).unwrap();
+    let mem = &mut [0; 16];
+    let mut vm = rbpf::EbpfVmFixedMbuff::new(&prog, 0x40, 0x50).unwrap();
+    vm.set_insn_budget(Some(40));
+    vm.jit_compile().unwrap();
+    unsafe { assert_eq!(vm.prog_exec_jit_metered(mem).unwrap(), (20, Some(33))); }
+}
+
+#[test]
+fn test_vm_sync() {
+    fn assert_sync<T: Send + Sync>() {}
+    assert_sync::<rbpf::EbpfVmMbuff>();
+    assert_sync::<rbpf::EbpfVmFixedMbuff>();
+    assert_sync::<rbpf::EbpfVmRaw>();
+    assert_sync::<rbpf::EbpfVmNoData>();
+    assert_sync::<Map>();
+}
+
+#[test]
+fn test_vm_trace() {
+    let prog = assemble(INSN_BUDGET_LOOP_PROG).unwrap();
+    let div_prog = assemble("
+        mov32 r0, 0x1
+        mov32 r1, 0x0
+        div32 r0, r1
+        exit").unwrap();
+    let mut vm = rbpf::EbpfVmRaw::new(&prog).unwrap();
+    let mut trace = vec![];
+    assert_eq!(vm.prog_exec_traced(&mut [0; 4], &mut |entry| trace.push(entry.clone())).unwrap(),
+               20);
+    assert_eq!(trace.len() as u64, vm.prog_exec_metered(&mut [0; 4]).unwrap().1);
+    // lddw is traced once, and the loop body is executed with decreasing r1.
+    assert_eq!(trace[0].insn_ptr, 0);
+    assert_eq!(trace[0].insn.opc, rbpf::ebpf::LD_DW_IMM);
+    assert_eq!(trace[1].insn_ptr, 2);
+    assert_eq!(trace[2].insn_ptr, 3);
+    assert_eq!(trace[2].regs[0], 0);
+    assert_eq!(trace[2].regs[1], 10);
+    assert_eq!(trace[5].insn_ptr, 3);
+    assert_eq!(trace[5].regs[0], 2);
+    assert_eq!(trace[5].regs[1], 9);
+    assert!(trace.iter().all(|entry| entry.prog_index.is_none()));
+    assert_eq!(trace.last().unwrap().insn.opc, rbpf::ebpf::EXIT);
+    assert_eq!(trace.last().unwrap().regs[0], 20);
+
+    // The faulting instruction is traced.
+    vm.set_prog(&div_prog).unwrap();
+    let mut executed = vec![];
+    let err = vm.prog_exec_traced(&mut [0; 4], &mut |entry| executed.push(entry.insn_ptr))
+        .unwrap_err();
+    assert_eq!(err.kind, ExecErrorKind::DivisionByZero);
+    assert_eq!(executed, vec![0, 1, 2]);
+}
+
+#[test]
+fn test_vm_tracer() {
+    let prog = assemble(INSN_BUDGET_LOOP_PROG).unwrap();
+    let mut sum = 0;
+    let vm = rbpf::EbpfVmNoData::new(&prog).unwrap();
+    assert_eq!(vm.prog_exec_traced(&mut |entry| sum += entry.regs[0]).unwrap(), 20);
+    // r0 is 0 for the first three instructions, then 2 * i for each of the ten iterations
+    // (before add, jne, and the next add), and 20 at exit.
+    assert_eq!(sum, (0..10).map(|i| 2 * i + 2 * (i + 1) * 2).sum::<u64>() + 20);
+}
+
+#[test]
+fn test_vm_tracer_reentrant() {
+    // The tracer can run the program again while it is being traced.
+    let prog = assemble("
+        mov64 r0, 0x1
+        exit").unwrap();
+    let vm = rbpf::EbpfVmNoData::new(&prog).unwrap();
+    let mut results = vec![];
+    vm.prog_exec_traced(&mut |_| results.push(vm.prog_exec_metered().unwrap())).unwrap();
+    assert_eq!(results, vec![(1, 2), (1, 2)]);
+}
+
+#[test]
+fn test_vm_trace_tail_call() {
+    let prog = tail_call_prog(1);
+    let target = assemble(TAIL_CALL_TARGET_PROG).unwrap();
+    let mut prog_array = rbpf::ProgramArray::new(2);
+    prog_array.set(1, &target).unwrap();
+
+    let mut vm = rbpf::EbpfVmRaw::new(&prog).unwrap();
+    vm.set_prog_array(&prog_array).unwrap();
+    let mut trace = vec![];
+    assert_eq!(vm.prog_exec_traced(&mut [0x10], &mut |entry| trace.push(entry.clone())).unwrap(),
+               0x11);
+
+    assert_eq!(trace.iter().map(|entry| (entry.prog_index, entry.insn_ptr)).collect::<Vec<_>>(),
+               vec![(None, 0), (None, 1), (None, 2), (Some(1), 0), (Some(1), 1), (Some(1), 2)]);
+
+    let text = rbpf::trace::render(&prog, Some(&prog_array), &trace);
+    let insns = text.lines()
+        .map(|line| line.split("  r0=").next().unwrap().trim_end().to_string())
+        .collect::<Vec<_>>();
+    assert_eq!(insns, vec![
+        "    0  mov64 r0, 0x1",
+        "    1  mov64 r3, 0x1",
+        "    2  tail_call",
+        "    0  ldxb r2, [r1+0x0]",
+        "    1  add64 r0, r2",
+        "    2  exit",
+    ]);
+
+    // Without the program array, the target program cannot be disassembled.
+    let text = rbpf::trace::render(&prog, None, &trace);
+    assert!(text.lines().nth(5).unwrap().starts_with("    2  opcode 0x95"));
+}
+
+#[test]
+fn test_trace_render() {
+    let prog = assemble("
+        lddw r0, 0x100000000
+        add64 r0, 0x1
+        exit").unwrap();
+    let vm = rbpf::EbpfVmNoData::new(&prog).unwrap();
+    let mut trace = vec![];
+    vm.prog_exec_traced(&mut |entry| trace.push(entry.clone())).unwrap();
+
+    let text = rbpf::trace::render(&prog, None, &trace);
+    let r10 = trace[0].regs[10];
+    assert_eq!(text, format!("    0  lddw r0, 0x100000000  r0=0x0 r1=0x0 r2=0x0 r3=0x0 r4=0x0 r5=0x0 r6=0x0 r7=0x0 r8=0x0 r9=0x0 r10={:#x}
+    2  add64 r0, 0x1         r0=0x100000000 r1=0x0 r2=0x0 r3=0x0 r4=0x0 r5=0x0 r6=0x0 r7=0x0 r8=0x0 r9=0x0 r10={:#x}
+    3  exit                  r0=0x100000001 r1=0x0 r2=0x0 r3=0x0 r4=0x0 r5=0x0 r6=0x0 r7=0x0 r8=0x0 r9=0x0 r10={:#x}
+", r10, r10, r10));
+}
+
+#[test]
+fn test_debugger_breakpoints() {
+    let prog = assemble(INSN_BUDGET_LOOP_PROG).unwrap();
+    let vm = rbpf::EbpfVmNoData::new(&prog).unwrap();
+    let mut session = vm.debug();
+    assert_eq!(session.status(), Status::Stopped);
+    assert_eq!(session.insn_ptr(), 0);
+
+    // lddw is executed in a single step.
+    assert_eq!(session.step(), Status::Stopped);
+    assert_eq!(session.insn_ptr(), 2);
+    assert_eq!(session.next_insn().opc, rbpf::ebpf::MOV64_IMM);
+
+    session.set_breakpoint(3);
+    session.set_breakpoint(6);
+    assert_eq!(session.breakpoints(), vec![3, 6]);
+    for i in 0..3 {
+        assert_eq!(session.cont(), Status::Breakpoint(3));
+        assert_eq!(session.reg(0), 2 * i);
+        assert_eq!(session.reg(1), 10 - i);
+    }
+
+    // Skip the remaining iterations.
+    session.set_reg(1, 1);
+    assert!(session.clear_breakpoint(3));
+    assert!(!session.clear_breakpoint(3));
+    assert_eq!(session.cont(), Status::Breakpoint(6));
+    assert_eq!(session.regs()[0], 6);
+    assert_eq!(session.insn_count(), 11);
+
+    assert_eq!(session.cont(), Status::Exited(6));
+    assert_eq!(session.insn_ptr(), 6);
+    assert_eq!(session.step(), Status::Exited(6));
+    assert_eq!(session.insn_count(), 12);
+}
+
+#[test]
+fn test_debugger_fault() {
+    let prog = assemble("
+        mov32 r0, 0x1
+        mov32 r1, 0x0
+        div32 r0, r1
+        exit").unwrap();
+    let vm = rbpf::EbpfVmNoData::new(&prog).unwrap();
+
+    let mut session = vm.debug();
+    match session.cont() {
+        Status::Faulted(err) => {
+            assert_eq!(err.kind, ExecErrorKind::DivisionByZero);
+            assert_eq!(err.insn_ptr, 2);
+        },
+        status => panic!("unexpected status {:?}", status),
+    }
+    assert_eq!(session.insn_ptr(), 2);
+    assert_eq!(session.insn_count(), 3);
+
+    // The fault can be avoided by fixing the divisor before the division.
+    let mut session = vm.debug();
+    session.set_breakpoint(2);
+    assert_eq!(session.cont(), Status::Breakpoint(2));
+    session.set_reg(1, 1);
+    assert_eq!(session.cont(), Status::Exited(1));
+}
+
+#[test]
+fn test_debugger_insn_budget() {
+    let prog = assemble(INSN_BUDGET_LOOP_PROG).unwrap();
+    let mut vm = rbpf::EbpfVmNoData::new(&prog).unwrap();
+    vm.set_insn_budget(Some(32));
+    let mut session = vm.debug();
+    match session.cont() {
+        Status::Faulted(err) => {
+            assert_eq!(err.kind, ExecErrorKind::InstructionBudgetExceeded);
+            assert_eq!(err.insn_ptr, 6);
+        },
+        status => panic!("unexpected status {:?}", status),
+    }
+    assert_eq!(session.insn_count(), 32);
+}
+
+#[test]
+fn test_debugger_memory() {
+    let prog = assemble("
+        stdw [r10-0x8], 0x11
+        ldxdw r0, [r10-0x8]
+        ldxb r2, [r1+0x1]
+        add64 r0, r2
+        exit").unwrap();
+    let mem = &mut [0x01, 0x02, 0x03, 0x04];
+    let vm = rbpf::EbpfVmRaw::new(&prog).unwrap();
+    let mut session = vm.debug(mem);
+
+    assert_eq!(session.step(), Status::Stopped);
+    let stack_len = session.stack().len();
+    assert_eq!(session.stack()[stack_len - 8], 0x11);
+    let fp = session.reg(10);
+    assert_eq!(session.read_memory(fp - 8, 8).unwrap(), vec![0x11, 0, 0, 0, 0, 0, 0, 0]);
+    session.write_memory(fp - 8, &[0x20]).unwrap();
+    session.stack_mut()[stack_len - 7] = 0x01;
+
+    let addr = session.reg(1);
+    assert_eq!(session.read_memory(addr, 4).unwrap(), vec![0x01, 0x02, 0x03, 0x04]);
+    session.mem_mut()[1] = 0x05;
+    assert!(session.mbuff().is_empty());
+
+    // Accesses out of the memory regions are rejected.
+    let err = session.read_memory(addr + 2, 4).unwrap_err();
+    assert_eq!(err.kind, ExecErrorKind::OutOfBoundsLoad);
+    assert_eq!(err.addr, addr + 2);
+    assert_eq!(err.size, 4);
+    let err = session.write_memory(fp, &[0]).unwrap_err();
+    assert_eq!(err.kind, ExecErrorKind::OutOfBoundsStore);
+
+    assert_eq!(session.cont(), Status::Exited(0x125));
+    assert_eq!(session.mem(), &[0x01, 0x05, 0x03, 0x04]);
+}
+
+#[test]
+fn test_debugger_map_memory() {
+    let map = Map::new(MapType::Array, 4, 8, 1).unwrap();
+    map.update(&0u32.to_le_bytes(), &0x42u64.to_le_bytes(), BPF_ANY).unwrap();
+    let prog = assemble("
+        mov64 r0, 0x0
+        exit").unwrap();
+    let mut vm = rbpf::EbpfVmNoData::new(&prog).unwrap();
+    vm.register_map(1, &map);
+
+    let mut session = vm.debug();
+    let (addr, end) = map.value_region();
+    assert_eq!(session.read_memory(addr, 8).unwrap(), 0x42u64.to_le_bytes().to_vec());
+    session.write_memory(addr, &[0x43]).unwrap();
+    assert!(session.read_memory(end - 4, 8).is_err());
+    assert_eq!(session.cont(), Status::Exited(0));
+    assert_eq!(map.lookup(&0u32.to_le_bytes()).unwrap(), 0x43u64.to_le_bytes().to_vec());
+}
+
+#[test]
+fn test_debugger_tail_call() {
+    let prog = tail_call_prog(1);
+    let target = assemble(TAIL_CALL_TARGET_PROG).unwrap();
+    let mut prog_array = rbpf::ProgramArray::new(2);
+    prog_array.set(1, &target).unwrap();
+
+    let mut vm = rbpf::EbpfVmRaw::new(&prog).unwrap();
+    vm.set_prog_array(&prog_array).unwrap();
+    let mem = &mut [0x10];
+    let mut session = vm.debug(mem);
+
+    // Breakpoints only stop the program loaded into the VM.
+    session.set_breakpoint(1);
+    session.set_breakpoint(2);
+    assert_eq!(session.cont(), Status::Breakpoint(1));
+    assert_eq!(session.prog_index(), None);
+    assert_eq!(session.cont(), Status::Breakpoint(2));
+    assert_eq!(session.step(), Status::Stopped);
+    assert_eq!(session.prog_index(), Some(1));
+    assert_eq!(session.insn_ptr(), 0);
+    assert_eq!(session.cont(), Status::Exited(0x11));
+}
+
+#[test]
+fn test_debugger_fixed_mbuff() {
+    let prog = assemble("
+        ldxdw r2, [r1+0x40]
+        ldxdw r3, [r1+0x50]
+        sub64 r3, r2
+        mov64 r0, r3
+        exit").unwrap();
+    let mem = &mut [0; 6];
+    let mut vm = rbpf::EbpfVmFixedMbuff::new(&prog, 0x40, 0x50).unwrap();
+    let mut session = vm.debug(mem).unwrap();
+    assert_eq!(session.reg(1), session.mbuff().as_ptr() as u64);
+    assert_eq!(session.cont(), Status::Exited(6));
+}
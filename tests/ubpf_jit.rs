@@ -18,11 +18,13 @@
 // available at <https://github.com/iovisor/ubpf/tree/master/ubpf>.
 // The very few modifications that have been realized should be indicated.
 
-// These are unit tests for the eBPF JIT compiler.
+// These are unit tests for the eBPF JIT compiler. They run against the backend for the target
+// architecture (x86_64 or AArch64).
 
 
 extern crate rbpf;
 
+use rbpf::error::{ExecError, ExecErrorKind, JitError};
 use rbpf::helpers;
 
 #[test]
@@ -34,9 +36,9 @@ fn test_jit_add() {
         0x0c, 0x10, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
         0x95, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00
     ];
-    let mut vm = rbpf::EbpfVmNoData::new(prog);
-    vm.jit_compile();
-    unsafe { assert_eq!(vm.prog_exec_jit(), 0x3); }
+    let mut vm = rbpf::EbpfVmNoData::new(prog).unwrap();
+    vm.jit_compile().unwrap();
+    unsafe { assert_eq!(vm.prog_exec_jit().unwrap(), 0x3); }
 }
 
 #[test]
@@ -62,9 +64,9 @@ fn test_jit_alu64_arith() {
         0x3f, 0x40, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
         0x95, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00
     ];
-    let mut vm = rbpf::EbpfVmNoData::new(prog);
-    vm.jit_compile();
-    unsafe { assert_eq!(vm.prog_exec_jit(), 0x2a); }
+    let mut vm = rbpf::EbpfVmNoData::new(prog).unwrap();
+    vm.jit_compile().unwrap();
+    unsafe { assert_eq!(vm.prog_exec_jit().unwrap(), 0x2a); }
 }
 
 #[test]
@@ -94,9 +96,9 @@ fn test_jit_alu64_bit() {
         0xaf, 0x20, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
         0x95, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00
     ];
-    let mut vm = rbpf::EbpfVmNoData::new(prog);
-    vm.jit_compile();
-    unsafe { assert_eq!(vm.prog_exec_jit(), 0x11); }
+    let mut vm = rbpf::EbpfVmNoData::new(prog).unwrap();
+    vm.jit_compile().unwrap();
+    unsafe { assert_eq!(vm.prog_exec_jit().unwrap(), 0x11); }
 }
 
 #[test]
@@ -122,9 +124,9 @@ fn test_jit_alu_arith() {
         0x3c, 0x40, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
         0x95, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00
     ];
-    let mut vm = rbpf::EbpfVmNoData::new(prog);
-    vm.jit_compile();
-    unsafe { assert_eq!(vm.prog_exec_jit(), 0x2a); }
+    let mut vm = rbpf::EbpfVmNoData::new(prog).unwrap();
+    vm.jit_compile().unwrap();
+    unsafe { assert_eq!(vm.prog_exec_jit().unwrap(), 0x2a); }
 }
 
 #[test]
@@ -152,9 +154,9 @@ fn test_jit_alu_bit() {
         0xac, 0x20, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
         0x95, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00
     ];
-    let mut vm = rbpf::EbpfVmNoData::new(prog);
-    vm.jit_compile();
-    unsafe { assert_eq!(vm.prog_exec_jit(), 0x11); }
+    let mut vm = rbpf::EbpfVmNoData::new(prog).unwrap();
+    vm.jit_compile().unwrap();
+    unsafe { assert_eq!(vm.prog_exec_jit().unwrap(), 0x11); }
 }
 
 #[test]
@@ -166,9 +168,9 @@ fn test_jit_arsh32_high_shift() {
         0xcc, 0x10, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
         0x95, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00
     ];
-    let mut vm = rbpf::EbpfVmNoData::new(prog);
-    vm.jit_compile();
-    unsafe { assert_eq!(vm.prog_exec_jit(), 0x4); }
+    let mut vm = rbpf::EbpfVmNoData::new(prog).unwrap();
+    vm.jit_compile().unwrap();
+    unsafe { assert_eq!(vm.prog_exec_jit().unwrap(), 0x4); }
 }
 
 #[test]
@@ -179,9 +181,9 @@ fn test_jit_arsh() {
         0xc4, 0x00, 0x00, 0x00, 0x10, 0x00, 0x00, 0x00,
         0x95, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00
     ];
-    let mut vm = rbpf::EbpfVmNoData::new(prog);
-    vm.jit_compile();
-    unsafe { assert_eq!(vm.prog_exec_jit(), 0xffff8000); }
+    let mut vm = rbpf::EbpfVmNoData::new(prog).unwrap();
+    vm.jit_compile().unwrap();
+    unsafe { assert_eq!(vm.prog_exec_jit().unwrap(), 0xffff8000); }
 }
 
 #[test]
@@ -194,9 +196,9 @@ fn test_jit_arsh64() {
         0xcf, 0x10, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
         0x95, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00
     ];
-    let mut vm = rbpf::EbpfVmNoData::new(prog);
-    vm.jit_compile();
-    unsafe { assert_eq!(vm.prog_exec_jit(), 0xfffffffffffffff8); }
+    let mut vm = rbpf::EbpfVmNoData::new(prog).unwrap();
+    vm.jit_compile().unwrap();
+    unsafe { assert_eq!(vm.prog_exec_jit().unwrap(), 0xfffffffffffffff8); }
 }
 
 #[test]
@@ -208,9 +210,9 @@ fn test_jit_arsh_reg() {
         0xcc, 0x10, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
         0x95, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00
     ];
-    let mut vm = rbpf::EbpfVmNoData::new(prog);
-    vm.jit_compile();
-    unsafe { assert_eq!(vm.prog_exec_jit(), 0xffff8000); }
+    let mut vm = rbpf::EbpfVmNoData::new(prog).unwrap();
+    vm.jit_compile().unwrap();
+    unsafe { assert_eq!(vm.prog_exec_jit().unwrap(), 0xffff8000); }
 }
 
 #[test]
@@ -223,9 +225,9 @@ fn test_jit_be16() {
     let mem = &mut [
         0x11, 0x22
     ];
-    let mut vm = rbpf::EbpfVmRaw::new(prog);
-    vm.jit_compile();
-    unsafe { assert_eq!(vm.prog_exec_jit(mem), 0x1122); }
+    let mut vm = rbpf::EbpfVmRaw::new(prog).unwrap();
+    vm.jit_compile().unwrap();
+    unsafe { assert_eq!(vm.prog_exec_jit(mem).unwrap(), 0x1122); }
 }
 
 #[test]
@@ -238,9 +240,9 @@ fn test_jit_be16_high() {
     let mem = &mut [
         0x11, 0x22, 0x33, 0x44, 0x55, 0x66, 0x77, 0x88
     ];
-    let mut vm = rbpf::EbpfVmRaw::new(prog);
-    vm.jit_compile();
-    unsafe { assert_eq!(vm.prog_exec_jit(mem), 0x1122); }
+    let mut vm = rbpf::EbpfVmRaw::new(prog).unwrap();
+    vm.jit_compile().unwrap();
+    unsafe { assert_eq!(vm.prog_exec_jit(mem).unwrap(), 0x1122); }
 }
 
 #[test]
@@ -253,9 +255,9 @@ fn test_jit_be32() {
     let mem = &mut [
         0x11, 0x22, 0x33, 0x44
     ];
-    let mut vm = rbpf::EbpfVmRaw::new(prog);
-    vm.jit_compile();
-    unsafe { assert_eq!(vm.prog_exec_jit(mem), 0x11223344); }
+    let mut vm = rbpf::EbpfVmRaw::new(prog).unwrap();
+    vm.jit_compile().unwrap();
+    unsafe { assert_eq!(vm.prog_exec_jit(mem).unwrap(), 0x11223344); }
 }
 
 #[test]
@@ -268,9 +270,9 @@ fn test_jit_be32_high() {
     let mem = &mut [
         0x11, 0x22, 0x33, 0x44, 0x55, 0x66, 0x77, 0x88
     ];
-    let mut vm = rbpf::EbpfVmRaw::new(prog);
-    vm.jit_compile();
-    unsafe { assert_eq!(vm.prog_exec_jit(mem), 0x11223344); }
+    let mut vm = rbpf::EbpfVmRaw::new(prog).unwrap();
+    vm.jit_compile().unwrap();
+    unsafe { assert_eq!(vm.prog_exec_jit(mem).unwrap(), 0x11223344); }
 }
 
 #[test]
@@ -283,9 +285,9 @@ fn test_jit_be64() {
     let mem = &mut [
         0x11, 0x22, 0x33, 0x44, 0x55, 0x66, 0x77, 0x88
     ];
-    let mut vm = rbpf::EbpfVmRaw::new(prog);
-    vm.jit_compile();
-    unsafe { assert_eq!(vm.prog_exec_jit(mem), 0x1122334455667788); }
+    let mut vm = rbpf::EbpfVmRaw::new(prog).unwrap();
+    vm.jit_compile().unwrap();
+    unsafe { assert_eq!(vm.prog_exec_jit(mem).unwrap(), 0x1122334455667788); }
 }
 
 #[test]
@@ -299,10 +301,10 @@ fn test_jit_call() {
         0x85, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
         0x95, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00
     ];
-    let mut vm = rbpf::EbpfVmNoData::new(prog);
+    let mut vm = rbpf::EbpfVmNoData::new(prog).unwrap();
     vm.register_helper(0, helpers::gather_bytes);
-    vm.jit_compile();
-    unsafe { assert_eq!(vm.prog_exec_jit(), 0x0102030405); }
+    vm.jit_compile().unwrap();
+    unsafe { assert_eq!(vm.prog_exec_jit().unwrap(), 0x0102030405); }
 }
 
 #[test]
@@ -319,10 +321,10 @@ fn test_jit_call_memfrob() {
     let mem = &mut [
         0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07, 0x08
     ];
-    let mut vm = rbpf::EbpfVmRaw::new(prog);
+    let mut vm = rbpf::EbpfVmRaw::new(prog).unwrap();
     vm.register_helper(1, helpers::memfrob);
-    vm.jit_compile();
-    unsafe { assert_eq!(vm.prog_exec_jit(mem), 0x102292e2f2c0708); }
+    vm.jit_compile().unwrap();
+    unsafe { assert_eq!(vm.prog_exec_jit(mem).unwrap(), 0x102292e2f2c0708); }
 }
 
 // TODO: helpers::trash_registers needs asm!().
@@ -342,10 +344,10 @@ fn test_jit_call_memfrob() {
         //0x4f, 0x90, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
         //0x95, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00
     //];
-    //let mut vm = rbpf::EbpfVmNoData::new(prog);
+    //let mut vm = rbpf::EbpfVmNoData::new(prog).unwrap();
     //vm.register_helper(2, helpers::trash_registers);
-    //vm.jit_compile();
-    //unsafe { assert_eq!(vm.prog_exec_jit(), 0x4321); }
+    //vm.jit_compile().unwrap();
+    //unsafe { assert_eq!(vm.prog_exec_jit().unwrap(), 0x4321); }
 //}
 
 #[test]
@@ -357,9 +359,9 @@ fn test_jit_div32_high_divisor() {
         0x3c, 0x10, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
         0x95, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00
     ];
-    let mut vm = rbpf::EbpfVmNoData::new(prog);
-    vm.jit_compile();
-    unsafe { assert_eq!(vm.prog_exec_jit(), 0x3); }
+    let mut vm = rbpf::EbpfVmNoData::new(prog).unwrap();
+    vm.jit_compile().unwrap();
+    unsafe { assert_eq!(vm.prog_exec_jit().unwrap(), 0x3); }
 }
 
 #[test]
@@ -370,9 +372,9 @@ fn test_jit_div32_imm() {
         0x34, 0x00, 0x00, 0x00, 0x04, 0x00, 0x00, 0x00,
         0x95, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00
     ];
-    let mut vm = rbpf::EbpfVmNoData::new(prog);
-    vm.jit_compile();
-    unsafe { assert_eq!(vm.prog_exec_jit(), 0x3); }
+    let mut vm = rbpf::EbpfVmNoData::new(prog).unwrap();
+    vm.jit_compile().unwrap();
+    unsafe { assert_eq!(vm.prog_exec_jit().unwrap(), 0x3); }
 }
 
 #[test]
@@ -384,9 +386,9 @@ fn test_jit_div32_reg() {
         0x3c, 0x10, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
         0x95, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00
     ];
-    let mut vm = rbpf::EbpfVmNoData::new(prog);
-    vm.jit_compile();
-    unsafe { assert_eq!(vm.prog_exec_jit(), 0x3); }
+    let mut vm = rbpf::EbpfVmNoData::new(prog).unwrap();
+    vm.jit_compile().unwrap();
+    unsafe { assert_eq!(vm.prog_exec_jit().unwrap(), 0x3); }
 }
 
 #[test]
@@ -397,9 +399,9 @@ fn test_jit_div64_imm() {
         0x37, 0x00, 0x00, 0x00, 0x04, 0x00, 0x00, 0x00,
         0x95, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00
     ];
-    let mut vm = rbpf::EbpfVmNoData::new(prog);
-    vm.jit_compile();
-    unsafe { assert_eq!(vm.prog_exec_jit(), 0x300000000); }
+    let mut vm = rbpf::EbpfVmNoData::new(prog).unwrap();
+    vm.jit_compile().unwrap();
+    unsafe { assert_eq!(vm.prog_exec_jit().unwrap(), 0x300000000); }
 }
 
 #[test]
@@ -411,9 +413,9 @@ fn test_jit_div64_reg() {
         0x3f, 0x10, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
         0x95, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00
     ];
-    let mut vm = rbpf::EbpfVmNoData::new(prog);
-    vm.jit_compile();
-    unsafe { assert_eq!(vm.prog_exec_jit(), 0x300000000); }
+    let mut vm = rbpf::EbpfVmNoData::new(prog).unwrap();
+    vm.jit_compile().unwrap();
+    unsafe { assert_eq!(vm.prog_exec_jit().unwrap(), 0x300000000); }
 }
 
 #[test]
@@ -424,9 +426,9 @@ fn test_jit_early_exit() {
         0xb7, 0x00, 0x00, 0x00, 0x04, 0x00, 0x00, 0x00,
         0x95, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00
     ];
-    let mut vm = rbpf::EbpfVmNoData::new(prog);
-    vm.jit_compile();
-    unsafe { assert_eq!(vm.prog_exec_jit(), 0x3); }
+    let mut vm = rbpf::EbpfVmNoData::new(prog).unwrap();
+    vm.jit_compile().unwrap();
+    unsafe { assert_eq!(vm.prog_exec_jit().unwrap(), 0x3); }
 }
 
 // uBPF limits the number of user functions at 64. We don't.
@@ -435,7 +437,6 @@ fn test_jit_early_exit() {
 //}
 
 #[test]
-#[should_panic(expected = "[JIT] Error: unknown helper function (id: 0x3f)")]
 fn test_jit_err_call_unreg() {
     let prog = &[
         0xb7, 0x01, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00,
@@ -446,15 +447,13 @@ fn test_jit_err_call_unreg() {
         0x85, 0x00, 0x00, 0x00, 0x3f, 0x00, 0x00, 0x00,
         0x95, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00
     ];
-    let mut vm = rbpf::EbpfVmNoData::new(prog);
-    vm.jit_compile();
-    unsafe { vm.prog_exec_jit(); }
+    let mut vm = rbpf::EbpfVmNoData::new(prog).unwrap();
+    let err = vm.jit_compile().unwrap_err();
+    assert_eq!(err, JitError::UnknownHelper(0x3f, 5));
+    assert_eq!(err.to_string(), "[JIT] Error: unknown helper function (id: 0x3f) (insn #5)");
 }
 
-// TODO: Should panic!() instead, but I could not make it panic in JIT-compiled code, so the
-// program returns -1 instead. We can make it write on stderr, though.
 #[test]
-//#[should_panic(expected = "[JIT] Error: division by 0")]
 fn test_jit_err_div64_by_zero_reg() {
     let prog = &[
         0xb4, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00,
@@ -462,14 +461,13 @@ fn test_jit_err_div64_by_zero_reg() {
         0x3f, 0x10, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
         0x95, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00
     ];
-    let mut vm = rbpf::EbpfVmNoData::new(prog);
-    vm.jit_compile();
-    unsafe { assert_eq!(vm.prog_exec_jit(), 0xffffffffffffffff); }
+    let mut vm = rbpf::EbpfVmNoData::new(prog).unwrap();
+    vm.jit_compile().unwrap();
+    let err = unsafe { vm.prog_exec_jit().unwrap_err() };
+    assert_eq!(err, ExecError::new(ExecErrorKind::DivisionByZero, 2));
 }
 
-// TODO: Same remark as above
 #[test]
-//#[should_panic(expected = "[JIT] Error: division by 0")]
 fn test_jit_err_div_by_zero_reg() {
     let prog = &[
         0xb4, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00,
@@ -477,14 +475,13 @@ fn test_jit_err_div_by_zero_reg() {
         0x3c, 0x10, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
         0x95, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00
     ];
-    let mut vm = rbpf::EbpfVmNoData::new(prog);
-    vm.jit_compile();
-    unsafe { assert_eq!(vm.prog_exec_jit(), 0xffffffffffffffff); }
+    let mut vm = rbpf::EbpfVmNoData::new(prog).unwrap();
+    vm.jit_compile().unwrap();
+    let err = unsafe { vm.prog_exec_jit().unwrap_err() };
+    assert_eq!(err, ExecError::new(ExecErrorKind::DivisionByZero, 2));
 }
 
-// TODO: Same remark as above
 #[test]
-//#[should_panic(expected = "[JIT] Error: division by 0")]
 fn test_jit_err_mod64_by_zero_reg() {
     let prog = &[
         0xb4, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00,
@@ -492,14 +489,13 @@ fn test_jit_err_mod64_by_zero_reg() {
         0x9f, 0x10, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
         0x95, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00
     ];
-    let mut vm = rbpf::EbpfVmNoData::new(prog);
-    vm.jit_compile();
-    unsafe { assert_eq!(vm.prog_exec_jit(), 0xffffffffffffffff); }
+    let mut vm = rbpf::EbpfVmNoData::new(prog).unwrap();
+    vm.jit_compile().unwrap();
+    let err = unsafe { vm.prog_exec_jit().unwrap_err() };
+    assert_eq!(err, ExecError::new(ExecErrorKind::DivisionByZero, 2));
 }
 
-// TODO: Same remark as above
 #[test]
-//#[should_panic(expected = "[JIT] Error: division by 0")]
 fn test_jit_err_mod_by_zero_reg() {
     let prog = &[
         0xb4, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00,
@@ -507,23 +503,27 @@ fn test_jit_err_mod_by_zero_reg() {
         0x9c, 0x10, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
         0x95, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00
     ];
-    let mut vm = rbpf::EbpfVmNoData::new(prog);
-    vm.jit_compile();
-    unsafe { assert_eq!(vm.prog_exec_jit(), 0xffffffffffffffff); }
+    let mut vm = rbpf::EbpfVmNoData::new(prog).unwrap();
+    vm.jit_compile().unwrap();
+    let err = unsafe { vm.prog_exec_jit().unwrap_err() };
+    assert_eq!(err, ExecError::new(ExecErrorKind::DivisionByZero, 2));
 }
 
-// TODO SKIP: JIT disabled for this testcase (stack oob check not implemented)
-// #[test]
-// #[should_panic(expected = "Error: out of bounds memory store (insn #1)")]
-// fn test_jit_err_stack_out_of_bound() {
-//     let prog = &[
-//         0x72, 0x0a, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
-//         0x95, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00
-//     ];
-//     let mut vm = rbpf::EbpfVmNoData::new(prog);
-//     vm.jit_compile();
-//     unsafe { vm.prog_exec_jit(); }
-// }
+#[test]
+fn test_jit_err_stack_out_of_bound() {
+    let prog = &[
+        0x72, 0x0a, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x95, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00
+    ];
+    let mut vm = rbpf::EbpfVmNoData::new(prog).unwrap();
+    vm.set_jit_mem_check(true);
+    vm.jit_compile().unwrap();
+    let err = unsafe { vm.prog_exec_jit().unwrap_err() };
+    assert_eq!(err.kind, ExecErrorKind::OutOfBoundsStore);
+    assert_eq!(err.insn_ptr, 0);
+    assert_eq!(err.size, 1);
+    assert!(err.to_string().starts_with("Error: out of bounds memory store (insn #0)"));
+}
 
 #[test]
 fn test_jit_exit() {
@@ -531,9 +531,9 @@ fn test_jit_exit() {
         0xb7, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
         0x95, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00
     ];
-    let mut vm = rbpf::EbpfVmNoData::new(prog);
-    vm.jit_compile();
-    unsafe { assert_eq!(vm.prog_exec_jit(), 0x0); }
+    let mut vm = rbpf::EbpfVmNoData::new(prog).unwrap();
+    vm.jit_compile().unwrap();
+    unsafe { assert_eq!(vm.prog_exec_jit().unwrap(), 0x0); }
 }
 
 #[test]
@@ -544,9 +544,9 @@ fn test_jit_ja() {
         0xb7, 0x00, 0x00, 0x00, 0x02, 0x00, 0x00, 0x00,
         0x95, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00
     ];
-    let mut vm = rbpf::EbpfVmNoData::new(prog);
-    vm.jit_compile();
-    unsafe { assert_eq!(vm.prog_exec_jit(), 0x1); }
+    let mut vm = rbpf::EbpfVmNoData::new(prog).unwrap();
+    vm.jit_compile().unwrap();
+    unsafe { assert_eq!(vm.prog_exec_jit().unwrap(), 0x1); }
 }
 
 #[test]
@@ -561,9 +561,9 @@ fn test_jit_jeq_imm() {
         0xb4, 0x00, 0x00, 0x00, 0x02, 0x00, 0x00, 0x00,
         0x95, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00
     ];
-    let mut vm = rbpf::EbpfVmNoData::new(prog);
-    vm.jit_compile();
-    unsafe { assert_eq!(vm.prog_exec_jit(), 0x1); }
+    let mut vm = rbpf::EbpfVmNoData::new(prog).unwrap();
+    vm.jit_compile().unwrap();
+    unsafe { assert_eq!(vm.prog_exec_jit().unwrap(), 0x1); }
 }
 
 #[test]
@@ -579,9 +579,9 @@ fn test_jit_jeq_reg() {
         0xb4, 0x00, 0x00, 0x00, 0x02, 0x00, 0x00, 0x00,
         0x95, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00
     ];
-    let mut vm = rbpf::EbpfVmNoData::new(prog);
-    vm.jit_compile();
-    unsafe { assert_eq!(vm.prog_exec_jit(), 0x1); }
+    let mut vm = rbpf::EbpfVmNoData::new(prog).unwrap();
+    vm.jit_compile().unwrap();
+    unsafe { assert_eq!(vm.prog_exec_jit().unwrap(), 0x1); }
 }
 
 #[test]
@@ -596,9 +596,9 @@ fn test_jit_jge_imm() {
         0xb4, 0x00, 0x00, 0x00, 0x02, 0x00, 0x00, 0x00,
         0x95, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00
     ];
-    let mut vm = rbpf::EbpfVmNoData::new(prog);
-    vm.jit_compile();
-    unsafe { assert_eq!(vm.prog_exec_jit(), 0x1); }
+    let mut vm = rbpf::EbpfVmNoData::new(prog).unwrap();
+    vm.jit_compile().unwrap();
+    unsafe { assert_eq!(vm.prog_exec_jit().unwrap(), 0x1); }
 }
 
 #[test]
@@ -613,9 +613,9 @@ fn test_jit_jgt_imm() {
         0xb4, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00,
         0x95, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00
     ];
-    let mut vm = rbpf::EbpfVmNoData::new(prog);
-    vm.jit_compile();
-    unsafe { assert_eq!(vm.prog_exec_jit(), 0x1); }
+    let mut vm = rbpf::EbpfVmNoData::new(prog).unwrap();
+    vm.jit_compile().unwrap();
+    unsafe { assert_eq!(vm.prog_exec_jit().unwrap(), 0x1); }
 }
 
 #[test]
@@ -632,9 +632,9 @@ fn test_jit_jgt_reg() {
         0xb7, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00,
         0x95, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00
     ];
-    let mut vm = rbpf::EbpfVmNoData::new(prog);
-    vm.jit_compile();
-    unsafe { assert_eq!(vm.prog_exec_jit(), 0x1); }
+    let mut vm = rbpf::EbpfVmNoData::new(prog).unwrap();
+    vm.jit_compile().unwrap();
+    unsafe { assert_eq!(vm.prog_exec_jit().unwrap(), 0x1); }
 }
 
 #[test]
@@ -648,9 +648,9 @@ fn test_jit_jit_bounce() {
         0xbf, 0x90, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
         0x95, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00
     ];
-    let mut vm = rbpf::EbpfVmNoData::new(prog);
-    vm.jit_compile();
-    unsafe { assert_eq!(vm.prog_exec_jit(), 0x1); }
+    let mut vm = rbpf::EbpfVmNoData::new(prog).unwrap();
+    vm.jit_compile().unwrap();
+    unsafe { assert_eq!(vm.prog_exec_jit().unwrap(), 0x1); }
 }
 
 #[test]
@@ -666,9 +666,9 @@ fn test_jit_jne_reg() {
         0xb4, 0x00, 0x00, 0x00, 0x02, 0x00, 0x00, 0x00,
         0x95, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00
     ];
-    let mut vm = rbpf::EbpfVmNoData::new(prog);
-    vm.jit_compile();
-    unsafe { assert_eq!(vm.prog_exec_jit(), 0x1); }
+    let mut vm = rbpf::EbpfVmNoData::new(prog).unwrap();
+    vm.jit_compile().unwrap();
+    unsafe { assert_eq!(vm.prog_exec_jit().unwrap(), 0x1); }
 }
 
 #[test]
@@ -683,9 +683,9 @@ fn test_jit_jset_imm() {
         0xb4, 0x00, 0x00, 0x00, 0x02, 0x00, 0x00, 0x00,
         0x95, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00
     ];
-    let mut vm = rbpf::EbpfVmNoData::new(prog);
-    vm.jit_compile();
-    unsafe { assert_eq!(vm.prog_exec_jit(), 0x1); }
+    let mut vm = rbpf::EbpfVmNoData::new(prog).unwrap();
+    vm.jit_compile().unwrap();
+    unsafe { assert_eq!(vm.prog_exec_jit().unwrap(), 0x1); }
 }
 
 #[test]
@@ -701,9 +701,9 @@ fn test_jit_jset_reg() {
         0xb4, 0x00, 0x00, 0x00, 0x02, 0x00, 0x00, 0x00,
         0x95, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00
     ];
-    let mut vm = rbpf::EbpfVmNoData::new(prog);
-    vm.jit_compile();
-    unsafe { assert_eq!(vm.prog_exec_jit(), 0x1); }
+    let mut vm = rbpf::EbpfVmNoData::new(prog).unwrap();
+    vm.jit_compile().unwrap();
+    unsafe { assert_eq!(vm.prog_exec_jit().unwrap(), 0x1); }
 }
 
 #[test]
@@ -719,9 +719,9 @@ fn test_jit_jsge_imm() {
         0xb4, 0x00, 0x00, 0x00, 0x02, 0x00, 0x00, 0x00,
         0x95, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00
     ];
-    let mut vm = rbpf::EbpfVmNoData::new(prog);
-    vm.jit_compile();
-    unsafe { assert_eq!(vm.prog_exec_jit(), 0x1); }
+    let mut vm = rbpf::EbpfVmNoData::new(prog).unwrap();
+    vm.jit_compile().unwrap();
+    unsafe { assert_eq!(vm.prog_exec_jit().unwrap(), 0x1); }
 }
 
 #[test]
@@ -739,9 +739,9 @@ fn test_jit_jsge_reg() {
         0xb4, 0x00, 0x00, 0x00, 0x02, 0x00, 0x00, 0x00,
         0x95, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00
     ];
-    let mut vm = rbpf::EbpfVmNoData::new(prog);
-    vm.jit_compile();
-    unsafe { assert_eq!(vm.prog_exec_jit(), 0x1); }
+    let mut vm = rbpf::EbpfVmNoData::new(prog).unwrap();
+    vm.jit_compile().unwrap();
+    unsafe { assert_eq!(vm.prog_exec_jit().unwrap(), 0x1); }
 }
 
 #[test]
@@ -756,9 +756,9 @@ fn test_jit_jsgt_imm() {
         0xb4, 0x00, 0x00, 0x00, 0x02, 0x00, 0x00, 0x00,
         0x95, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00
     ];
-    let mut vm = rbpf::EbpfVmNoData::new(prog);
-    vm.jit_compile();
-    unsafe { assert_eq!(vm.prog_exec_jit(), 0x1); }
+    let mut vm = rbpf::EbpfVmNoData::new(prog).unwrap();
+    vm.jit_compile().unwrap();
+    unsafe { assert_eq!(vm.prog_exec_jit().unwrap(), 0x1); }
 }
 
 #[test]
@@ -774,9 +774,9 @@ fn test_jit_jsgt_reg() {
         0xb4, 0x00, 0x00, 0x00, 0x02, 0x00, 0x00, 0x00,
         0x95, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00
     ];
-    let mut vm = rbpf::EbpfVmNoData::new(prog);
-    vm.jit_compile();
-    unsafe { assert_eq!(vm.prog_exec_jit(), 0x1); }
+    let mut vm = rbpf::EbpfVmNoData::new(prog).unwrap();
+    vm.jit_compile().unwrap();
+    unsafe { assert_eq!(vm.prog_exec_jit().unwrap(), 0x1); }
 }
 
 #[test]
@@ -786,9 +786,9 @@ fn test_jit_lddw() {
         0x00, 0x00, 0x00, 0x00, 0x44, 0x33, 0x22, 0x11,
         0x95, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00
     ];
-    let mut vm = rbpf::EbpfVmNoData::new(prog);
-    vm.jit_compile();
-    unsafe { assert_eq!(vm.prog_exec_jit(), 0x1122334455667788); }
+    let mut vm = rbpf::EbpfVmNoData::new(prog).unwrap();
+    vm.jit_compile().unwrap();
+    unsafe { assert_eq!(vm.prog_exec_jit().unwrap(), 0x1122334455667788); }
 }
 
 #[test]
@@ -798,9 +798,9 @@ fn test_jit_lddw2() {
         0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
         0x95, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00
     ];
-    let mut vm = rbpf::EbpfVmNoData::new(prog);
-    vm.jit_compile();
-    unsafe { assert_eq!(vm.prog_exec_jit(), 0x80000000); }
+    let mut vm = rbpf::EbpfVmNoData::new(prog).unwrap();
+    vm.jit_compile().unwrap();
+    unsafe { assert_eq!(vm.prog_exec_jit().unwrap(), 0x80000000); }
 }
 
 #[test]
@@ -842,9 +842,9 @@ fn test_jit_ldxb_all() {
         0x00, 0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07,
         0x08, 0x09
     ];
-    let mut vm = rbpf::EbpfVmRaw::new(prog);
-    vm.jit_compile();
-    unsafe { assert_eq!(vm.prog_exec_jit(mem), 0x9876543210); }
+    let mut vm = rbpf::EbpfVmRaw::new(prog).unwrap();
+    vm.jit_compile().unwrap();
+    unsafe { assert_eq!(vm.prog_exec_jit(mem).unwrap(), 0x9876543210); }
 }
 
 #[test]
@@ -856,9 +856,9 @@ fn test_jit_ldxb() {
     let mem = &mut [
         0xaa, 0xbb, 0x11, 0xcc, 0xdd
     ];
-    let mut vm = rbpf::EbpfVmRaw::new(prog);
-    vm.jit_compile();
-    unsafe { assert_eq!(vm.prog_exec_jit(mem), 0x11); }
+    let mut vm = rbpf::EbpfVmRaw::new(prog).unwrap();
+    vm.jit_compile().unwrap();
+    unsafe { assert_eq!(vm.prog_exec_jit(mem).unwrap(), 0x11); }
 }
 
 #[test]
@@ -871,9 +871,9 @@ fn test_jit_ldxdw() {
         0xaa, 0xbb, 0x11, 0x22, 0x33, 0x44, 0x55, 0x66,
         0x77, 0x88, 0xcc, 0xdd
     ];
-    let mut vm = rbpf::EbpfVmRaw::new(prog);
-    vm.jit_compile();
-    unsafe { assert_eq!(vm.prog_exec_jit(mem), 0x8877665544332211); }
+    let mut vm = rbpf::EbpfVmRaw::new(prog).unwrap();
+    vm.jit_compile().unwrap();
+    unsafe { assert_eq!(vm.prog_exec_jit(mem).unwrap(), 0x8877665544332211); }
 }
 
 #[test]
@@ -926,9 +926,9 @@ fn test_jit_ldxh_all() {
         0x00, 0x04, 0x00, 0x05, 0x00, 0x06, 0x00, 0x07,
         0x00, 0x08, 0x00, 0x09
     ];
-    let mut vm = rbpf::EbpfVmRaw::new(prog);
-    vm.jit_compile();
-    unsafe { assert_eq!(vm.prog_exec_jit(mem), 0x9876543210); }
+    let mut vm = rbpf::EbpfVmRaw::new(prog).unwrap();
+    vm.jit_compile().unwrap();
+    unsafe { assert_eq!(vm.prog_exec_jit(mem).unwrap(), 0x9876543210); }
 }
 
 #[test]
@@ -971,9 +971,9 @@ fn test_jit_ldxh_all2() {
         0x00, 0x10, 0x00, 0x20, 0x00, 0x40, 0x00, 0x80,
         0x01, 0x00, 0x02, 0x00
     ];
-    let mut vm = rbpf::EbpfVmRaw::new(prog);
-    vm.jit_compile();
-    unsafe { assert_eq!(vm.prog_exec_jit(mem), 0x3ff); }
+    let mut vm = rbpf::EbpfVmRaw::new(prog).unwrap();
+    vm.jit_compile().unwrap();
+    unsafe { assert_eq!(vm.prog_exec_jit(mem).unwrap(), 0x3ff); }
 }
 
 #[test]
@@ -985,9 +985,9 @@ fn test_jit_ldxh() {
     let mem = &mut [
         0xaa, 0xbb, 0x11, 0x22, 0xcc, 0xdd
     ];
-    let mut vm = rbpf::EbpfVmRaw::new(prog);
-    vm.jit_compile();
-    unsafe { assert_eq!(vm.prog_exec_jit(mem), 0x2211); }
+    let mut vm = rbpf::EbpfVmRaw::new(prog).unwrap();
+    vm.jit_compile().unwrap();
+    unsafe { assert_eq!(vm.prog_exec_jit(mem).unwrap(), 0x2211); }
 }
 
 #[test]
@@ -1001,9 +1001,9 @@ fn test_jit_ldxh_same_reg() {
     let mem = &mut [
         0xff, 0xff
     ];
-    let mut vm = rbpf::EbpfVmRaw::new(prog);
-    vm.jit_compile();
-    unsafe { assert_eq!(vm.prog_exec_jit(mem), 0x1234); }
+    let mut vm = rbpf::EbpfVmRaw::new(prog).unwrap();
+    vm.jit_compile().unwrap();
+    unsafe { assert_eq!(vm.prog_exec_jit(mem).unwrap(), 0x1234); }
 }
 
 #[test]
@@ -1048,9 +1048,9 @@ fn test_jit_ldxw_all() {
         0x00, 0x00, 0x04, 0x00, 0x00, 0x00, 0x08, 0x00,
         0x00, 0x01, 0x00, 0x00, 0x00, 0x02, 0x00, 0x00
     ];
-    let mut vm = rbpf::EbpfVmRaw::new(prog);
-    vm.jit_compile();
-    unsafe { assert_eq!(vm.prog_exec_jit(mem), 0x030f0f); }
+    let mut vm = rbpf::EbpfVmRaw::new(prog).unwrap();
+    vm.jit_compile().unwrap();
+    unsafe { assert_eq!(vm.prog_exec_jit(mem).unwrap(), 0x030f0f); }
 }
 
 #[test]
@@ -1062,9 +1062,9 @@ fn test_jit_ldxw() {
     let mem = &mut [
         0xaa, 0xbb, 0x11, 0x22, 0x33, 0x44, 0xcc, 0xdd
     ];
-    let mut vm = rbpf::EbpfVmRaw::new(prog);
-    vm.jit_compile();
-    unsafe { assert_eq!(vm.prog_exec_jit(mem), 0x44332211); }
+    let mut vm = rbpf::EbpfVmRaw::new(prog).unwrap();
+    vm.jit_compile().unwrap();
+    unsafe { assert_eq!(vm.prog_exec_jit(mem).unwrap(), 0x44332211); }
 }
 
 #[test]
@@ -1077,9 +1077,9 @@ fn test_jit_le16() {
     let mem = &mut [
         0x22, 0x11
     ];
-    let mut vm = rbpf::EbpfVmRaw::new(prog);
-    vm.jit_compile();
-    unsafe { assert_eq!(vm.prog_exec_jit(mem), 0x1122); }
+    let mut vm = rbpf::EbpfVmRaw::new(prog).unwrap();
+    vm.jit_compile().unwrap();
+    unsafe { assert_eq!(vm.prog_exec_jit(mem).unwrap(), 0x1122); }
 }
 
 #[test]
@@ -1092,9 +1092,9 @@ fn test_jit_le32() {
     let mem = &mut [
         0x44, 0x33, 0x22, 0x11
     ];
-    let mut vm = rbpf::EbpfVmRaw::new(prog);
-    vm.jit_compile();
-    unsafe { assert_eq!(vm.prog_exec_jit(mem), 0x11223344); }
+    let mut vm = rbpf::EbpfVmRaw::new(prog).unwrap();
+    vm.jit_compile().unwrap();
+    unsafe { assert_eq!(vm.prog_exec_jit(mem).unwrap(), 0x11223344); }
 }
 
 #[test]
@@ -1107,9 +1107,9 @@ fn test_jit_le64() {
     let mem = &mut [
         0x88, 0x77, 0x66, 0x55, 0x44, 0x33, 0x22, 0x11
     ];
-    let mut vm = rbpf::EbpfVmRaw::new(prog);
-    vm.jit_compile();
-    unsafe { assert_eq!(vm.prog_exec_jit(mem), 0x1122334455667788); }
+    let mut vm = rbpf::EbpfVmRaw::new(prog).unwrap();
+    vm.jit_compile().unwrap();
+    unsafe { assert_eq!(vm.prog_exec_jit(mem).unwrap(), 0x1122334455667788); }
 }
 
 #[test]
@@ -1120,9 +1120,9 @@ fn test_jit_lsh_reg() {
         0x6f, 0x70, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
         0x95, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00
     ];
-    let mut vm = rbpf::EbpfVmNoData::new(prog);
-    vm.jit_compile();
-    unsafe { assert_eq!(vm.prog_exec_jit(), 0x10); }
+    let mut vm = rbpf::EbpfVmNoData::new(prog).unwrap();
+    vm.jit_compile().unwrap();
+    unsafe { assert_eq!(vm.prog_exec_jit().unwrap(), 0x10); }
 }
 
 #[test]
@@ -1134,9 +1134,9 @@ fn test_jit_mod() {
         0x9c, 0x10, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
         0x95, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00
     ];
-    let mut vm = rbpf::EbpfVmNoData::new(prog);
-    vm.jit_compile();
-    unsafe { assert_eq!(vm.prog_exec_jit(), 0x5); }
+    let mut vm = rbpf::EbpfVmNoData::new(prog).unwrap();
+    vm.jit_compile().unwrap();
+    unsafe { assert_eq!(vm.prog_exec_jit().unwrap(), 0x5); }
 }
 
 #[test]
@@ -1147,9 +1147,9 @@ fn test_jit_mod32() {
         0x94, 0x00, 0x00, 0x00, 0x03, 0x00, 0x00, 0x00,
         0x95, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00
     ];
-    let mut vm = rbpf::EbpfVmNoData::new(prog);
-    vm.jit_compile();
-    unsafe { assert_eq!(vm.prog_exec_jit(), 0x0); }
+    let mut vm = rbpf::EbpfVmNoData::new(prog).unwrap();
+    vm.jit_compile().unwrap();
+    unsafe { assert_eq!(vm.prog_exec_jit().unwrap(), 0x0); }
 }
 
 #[test]
@@ -1165,9 +1165,9 @@ fn test_jit_mod64() {
         0x97, 0x00, 0x00, 0x00, 0x78, 0x17, 0x8f, 0x65,
         0x95, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00
     ];
-    let mut vm = rbpf::EbpfVmNoData::new(prog);
-    vm.jit_compile();
-    unsafe { assert_eq!(vm.prog_exec_jit(), 0x30ba5a04); }
+    let mut vm = rbpf::EbpfVmNoData::new(prog).unwrap();
+    vm.jit_compile().unwrap();
+    unsafe { assert_eq!(vm.prog_exec_jit().unwrap(), 0x30ba5a04); }
 }
 
 #[test]
@@ -1177,9 +1177,9 @@ fn test_jit_mov() {
         0xbc, 0x10, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
         0x95, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00
     ];
-    let mut vm = rbpf::EbpfVmNoData::new(prog);
-    vm.jit_compile();
-    unsafe { assert_eq!(vm.prog_exec_jit(), 0x1); }
+    let mut vm = rbpf::EbpfVmNoData::new(prog).unwrap();
+    vm.jit_compile().unwrap();
+    unsafe { assert_eq!(vm.prog_exec_jit().unwrap(), 0x1); }
 }
 
 #[test]
@@ -1189,9 +1189,9 @@ fn test_jit_mul32_imm() {
         0x24, 0x00, 0x00, 0x00, 0x04, 0x00, 0x00, 0x00,
         0x95, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00
     ];
-    let mut vm = rbpf::EbpfVmNoData::new(prog);
-    vm.jit_compile();
-    unsafe { assert_eq!(vm.prog_exec_jit(), 0xc); }
+    let mut vm = rbpf::EbpfVmNoData::new(prog).unwrap();
+    vm.jit_compile().unwrap();
+    unsafe { assert_eq!(vm.prog_exec_jit().unwrap(), 0xc); }
 }
 
 #[test]
@@ -1202,9 +1202,9 @@ fn test_jit_mul32_reg() {
         0x2c, 0x10, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
         0x95, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00
     ];
-    let mut vm = rbpf::EbpfVmNoData::new(prog);
-    vm.jit_compile();
-    unsafe { assert_eq!(vm.prog_exec_jit(), 0xc); }
+    let mut vm = rbpf::EbpfVmNoData::new(prog).unwrap();
+    vm.jit_compile().unwrap();
+    unsafe { assert_eq!(vm.prog_exec_jit().unwrap(), 0xc); }
 }
 
 #[test]
@@ -1215,9 +1215,9 @@ fn test_jit_mul32_reg_overflow() {
         0x2c, 0x10, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
         0x95, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00
     ];
-    let mut vm = rbpf::EbpfVmNoData::new(prog);
-    vm.jit_compile();
-    unsafe { assert_eq!(vm.prog_exec_jit(), 0x4); }
+    let mut vm = rbpf::EbpfVmNoData::new(prog).unwrap();
+    vm.jit_compile().unwrap();
+    unsafe { assert_eq!(vm.prog_exec_jit().unwrap(), 0x4); }
 }
 
 #[test]
@@ -1227,9 +1227,9 @@ fn test_jit_mul64_imm() {
         0x27, 0x00, 0x00, 0x00, 0x04, 0x00, 0x00, 0x00,
         0x95, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00
     ];
-    let mut vm = rbpf::EbpfVmNoData::new(prog);
-    vm.jit_compile();
-    unsafe { assert_eq!(vm.prog_exec_jit(), 0x100000004); }
+    let mut vm = rbpf::EbpfVmNoData::new(prog).unwrap();
+    vm.jit_compile().unwrap();
+    unsafe { assert_eq!(vm.prog_exec_jit().unwrap(), 0x100000004); }
 }
 
 #[test]
@@ -1240,9 +1240,9 @@ fn test_jit_mul64_reg() {
         0x2f, 0x10, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
         0x95, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00
     ];
-    let mut vm = rbpf::EbpfVmNoData::new(prog);
-    vm.jit_compile();
-    unsafe { assert_eq!(vm.prog_exec_jit(), 0x100000004); }
+    let mut vm = rbpf::EbpfVmNoData::new(prog).unwrap();
+    vm.jit_compile().unwrap();
+    unsafe { assert_eq!(vm.prog_exec_jit().unwrap(), 0x100000004); }
 }
 
 #[test]
@@ -1259,9 +1259,9 @@ fn test_jit_mul_loop() {
         0x55, 0x01, 0xfd, 0xff, 0x00, 0x00, 0x00, 0x00,
         0x95, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00
     ];
-    let mut vm = rbpf::EbpfVmNoData::new(prog);
-    vm.jit_compile();
-    unsafe { assert_eq!(vm.prog_exec_jit(), 0x75db9c97); }
+    let mut vm = rbpf::EbpfVmNoData::new(prog).unwrap();
+    vm.jit_compile().unwrap();
+    unsafe { assert_eq!(vm.prog_exec_jit().unwrap(), 0x75db9c97); }
 }
 
 #[test]
@@ -1271,9 +1271,9 @@ fn test_jit_neg64() {
         0x87, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
         0x95, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00
     ];
-    let mut vm = rbpf::EbpfVmNoData::new(prog);
-    vm.jit_compile();
-    unsafe { assert_eq!(vm.prog_exec_jit(), 0xfffffffffffffffe); }
+    let mut vm = rbpf::EbpfVmNoData::new(prog).unwrap();
+    vm.jit_compile().unwrap();
+    unsafe { assert_eq!(vm.prog_exec_jit().unwrap(), 0xfffffffffffffffe); }
 }
 
 #[test]
@@ -1283,9 +1283,9 @@ fn test_jit_neg() {
         0x84, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
         0x95, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00
     ];
-    let mut vm = rbpf::EbpfVmNoData::new(prog);
-    vm.jit_compile();
-    unsafe { assert_eq!(vm.prog_exec_jit(), 0xfffffffe); }
+    let mut vm = rbpf::EbpfVmNoData::new(prog).unwrap();
+    vm.jit_compile().unwrap();
+    unsafe { assert_eq!(vm.prog_exec_jit().unwrap(), 0xfffffffe); }
 }
 
 #[test]
@@ -1308,9 +1308,9 @@ fn test_jit_prime() {
         0x55, 0x04, 0xf6, 0xff, 0x00, 0x00, 0x00, 0x00,
         0x95, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00
     ];
-    let mut vm = rbpf::EbpfVmNoData::new(prog);
-    vm.jit_compile();
-    unsafe { assert_eq!(vm.prog_exec_jit(), 0x1); }
+    let mut vm = rbpf::EbpfVmNoData::new(prog).unwrap();
+    vm.jit_compile().unwrap();
+    unsafe { assert_eq!(vm.prog_exec_jit().unwrap(), 0x1); }
 }
 
 #[test]
@@ -1321,9 +1321,9 @@ fn test_jit_rhs32() {
         0x74, 0x00, 0x00, 0x00, 0x08, 0x00, 0x00, 0x00,
         0x95, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00
     ];
-    let mut vm = rbpf::EbpfVmNoData::new(prog);
-    vm.jit_compile();
-    unsafe { assert_eq!(vm.prog_exec_jit(), 0x00ffffff); }
+    let mut vm = rbpf::EbpfVmNoData::new(prog).unwrap();
+    vm.jit_compile().unwrap();
+    unsafe { assert_eq!(vm.prog_exec_jit().unwrap(), 0x00ffffff); }
 }
 
 #[test]
@@ -1334,9 +1334,9 @@ fn test_jit_rsh_reg() {
         0x7f, 0x70, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
         0x95, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00
     ];
-    let mut vm = rbpf::EbpfVmNoData::new(prog);
-    vm.jit_compile();
-    unsafe { assert_eq!(vm.prog_exec_jit(), 0x1); }
+    let mut vm = rbpf::EbpfVmNoData::new(prog).unwrap();
+    vm.jit_compile().unwrap();
+    unsafe { assert_eq!(vm.prog_exec_jit().unwrap(), 0x1); }
 }
 
 #[test]
@@ -1352,9 +1352,9 @@ fn test_jit_stack() {
         0x79, 0x20, 0xf0, 0xff, 0x00, 0x00, 0x00, 0x00,
         0x95, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00
     ];
-    let mut vm = rbpf::EbpfVmNoData::new(prog);
-    vm.jit_compile();
-    unsafe { assert_eq!(vm.prog_exec_jit(), 0xcd); }
+    let mut vm = rbpf::EbpfVmNoData::new(prog).unwrap();
+    vm.jit_compile().unwrap();
+    unsafe { assert_eq!(vm.prog_exec_jit().unwrap(), 0xcd); }
 }
 
 #[test]
@@ -1377,11 +1377,11 @@ fn test_jit_stack2() {
         0xa7, 0x00, 0x00, 0x00, 0x2a, 0x2a, 0x2a, 0x2a,
         0x95, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00
     ];
-    let mut vm = rbpf::EbpfVmNoData::new(prog);
+    let mut vm = rbpf::EbpfVmNoData::new(prog).unwrap();
     vm.register_helper(0, helpers::gather_bytes);
     vm.register_helper(1, helpers::memfrob);
-    vm.jit_compile();
-    unsafe { assert_eq!(vm.prog_exec_jit(), 0x01020304); }
+    vm.jit_compile().unwrap();
+    unsafe { assert_eq!(vm.prog_exec_jit().unwrap(), 0x01020304); }
 }
 
 #[test]
@@ -1394,9 +1394,9 @@ fn test_jit_stb() {
     let mem = &mut [
         0xaa, 0xbb, 0xff, 0xcc, 0xdd
     ];
-    let mut vm = rbpf::EbpfVmRaw::new(prog);
-    vm.jit_compile();
-    unsafe { assert_eq!(vm.prog_exec_jit(mem), 0x11); }
+    let mut vm = rbpf::EbpfVmRaw::new(prog).unwrap();
+    vm.jit_compile().unwrap();
+    unsafe { assert_eq!(vm.prog_exec_jit(mem).unwrap(), 0x11); }
 }
 
 #[test]
@@ -1410,9 +1410,9 @@ fn test_jit_stdw() {
         0xaa, 0xbb, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff,
         0xff, 0xff, 0xcc, 0xdd
     ];
-    let mut vm = rbpf::EbpfVmRaw::new(prog);
-    vm.jit_compile();
-    unsafe { assert_eq!(vm.prog_exec_jit(mem), 0x44332211); }
+    let mut vm = rbpf::EbpfVmRaw::new(prog).unwrap();
+    vm.jit_compile().unwrap();
+    unsafe { assert_eq!(vm.prog_exec_jit(mem).unwrap(), 0x44332211); }
 }
 
 #[test]
@@ -1425,9 +1425,9 @@ fn test_jit_sth() {
     let mem = &mut [
         0xaa, 0xbb, 0xff, 0xff, 0xcc, 0xdd
     ];
-    let mut vm = rbpf::EbpfVmRaw::new(prog);
-    vm.jit_compile();
-    unsafe { assert_eq!(vm.prog_exec_jit(mem), 0x2211); }
+    let mut vm = rbpf::EbpfVmRaw::new(prog).unwrap();
+    vm.jit_compile().unwrap();
+    unsafe { assert_eq!(vm.prog_exec_jit(mem).unwrap(), 0x2211); }
 }
 
 #[test]
@@ -1462,10 +1462,10 @@ fn test_jit_string_stack() {
         0xb7, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
         0x95, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00
     ];
-    let mut vm = rbpf::EbpfVmNoData::new(prog);
+    let mut vm = rbpf::EbpfVmNoData::new(prog).unwrap();
     vm.register_helper(4, helpers::strcmp);
-    vm.jit_compile();
-    unsafe { assert_eq!(vm.prog_exec_jit(), 0x0); }
+    vm.jit_compile().unwrap();
+    unsafe { assert_eq!(vm.prog_exec_jit().unwrap(), 0x0); }
 }
 
 #[test]
@@ -1478,9 +1478,9 @@ fn test_jit_stw() {
     let mem = &mut [
         0xaa, 0xbb, 0xff, 0xff, 0xff, 0xff, 0xcc, 0xdd
     ];
-    let mut vm = rbpf::EbpfVmRaw::new(prog);
-    vm.jit_compile();
-    unsafe { assert_eq!(vm.prog_exec_jit(mem), 0x44332211); }
+    let mut vm = rbpf::EbpfVmRaw::new(prog).unwrap();
+    vm.jit_compile().unwrap();
+    unsafe { assert_eq!(vm.prog_exec_jit(mem).unwrap(), 0x44332211); }
 }
 
 #[test]
@@ -1494,9 +1494,9 @@ fn test_jit_stxb() {
     let mem = &mut [
         0xaa, 0xbb, 0xff, 0xcc, 0xdd
     ];
-    let mut vm = rbpf::EbpfVmRaw::new(prog);
-    vm.jit_compile();
-    unsafe { assert_eq!(vm.prog_exec_jit(mem), 0x11); }
+    let mut vm = rbpf::EbpfVmRaw::new(prog).unwrap();
+    vm.jit_compile().unwrap();
+    unsafe { assert_eq!(vm.prog_exec_jit(mem).unwrap(), 0x11); }
 }
 
 #[test]
@@ -1525,9 +1525,9 @@ fn test_jit_stxb_all() {
     let mem = &mut [
         0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff
     ];
-    let mut vm = rbpf::EbpfVmRaw::new(prog);
-    vm.jit_compile();
-    unsafe { assert_eq!(vm.prog_exec_jit(mem), 0xf0f2f3f4f5f6f7f8); }
+    let mut vm = rbpf::EbpfVmRaw::new(prog).unwrap();
+    vm.jit_compile().unwrap();
+    unsafe { assert_eq!(vm.prog_exec_jit(mem).unwrap(), 0xf0f2f3f4f5f6f7f8); }
 }
 
 #[test]
@@ -1545,9 +1545,9 @@ fn test_jit_stxb_all2() {
     let mem = &mut [
         0xff, 0xff
     ];
-    let mut vm = rbpf::EbpfVmRaw::new(prog);
-    vm.jit_compile();
-    unsafe { assert_eq!(vm.prog_exec_jit(mem), 0xf1f9); }
+    let mut vm = rbpf::EbpfVmRaw::new(prog).unwrap();
+    vm.jit_compile().unwrap();
+    unsafe { assert_eq!(vm.prog_exec_jit(mem).unwrap(), 0xf1f9); }
 }
 
 #[test]
@@ -1579,9 +1579,9 @@ fn test_jit_stxb_chain() {
         0x2a, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
         0x00, 0x00
     ];
-    let mut vm = rbpf::EbpfVmRaw::new(prog);
-    vm.jit_compile();
-    unsafe { assert_eq!(vm.prog_exec_jit(mem), 0x2a); }
+    let mut vm = rbpf::EbpfVmRaw::new(prog).unwrap();
+    vm.jit_compile().unwrap();
+    unsafe { assert_eq!(vm.prog_exec_jit(mem).unwrap(), 0x2a); }
 }
 
 #[test]
@@ -1598,9 +1598,9 @@ fn test_jit_stxdw() {
         0xaa, 0xbb, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff,
         0xff, 0xff, 0xcc, 0xdd
     ];
-    let mut vm = rbpf::EbpfVmRaw::new(prog);
-    vm.jit_compile();
-    unsafe { assert_eq!(vm.prog_exec_jit(mem), 0x8877665544332211); }
+    let mut vm = rbpf::EbpfVmRaw::new(prog).unwrap();
+    vm.jit_compile().unwrap();
+    unsafe { assert_eq!(vm.prog_exec_jit(mem).unwrap(), 0x8877665544332211); }
 }
 
 #[test]
@@ -1614,9 +1614,9 @@ fn test_jit_stxh() {
     let mem = &mut [
         0xaa, 0xbb, 0xff, 0xff, 0xcc, 0xdd
     ];
-    let mut vm = rbpf::EbpfVmRaw::new(prog);
-    vm.jit_compile();
-    unsafe { assert_eq!(vm.prog_exec_jit(mem), 0x2211); }
+    let mut vm = rbpf::EbpfVmRaw::new(prog).unwrap();
+    vm.jit_compile().unwrap();
+    unsafe { assert_eq!(vm.prog_exec_jit(mem).unwrap(), 0x2211); }
 }
 
 #[test]
@@ -1630,9 +1630,9 @@ fn test_jit_stxw() {
     let mem = &mut [
         0xaa, 0xbb, 0xff, 0xff, 0xff, 0xff, 0xcc, 0xdd
     ];
-    let mut vm = rbpf::EbpfVmRaw::new(prog);
-    vm.jit_compile();
-    unsafe { assert_eq!(vm.prog_exec_jit(mem), 0x44332211); }
+    let mut vm = rbpf::EbpfVmRaw::new(prog).unwrap();
+    vm.jit_compile().unwrap();
+    unsafe { assert_eq!(vm.prog_exec_jit(mem).unwrap(), 0x44332211); }
 }
 
 #[test]
@@ -1665,9 +1665,9 @@ fn test_jit_subnet() {
         0x27, 0x24, 0x00, 0x00, 0x00, 0x00, 0x01, 0x03,
         0x03, 0x00
     ];
-    let mut vm = rbpf::EbpfVmRaw::new(prog);
-    vm.jit_compile();
-    unsafe { assert_eq!(vm.prog_exec_jit(mem), 0x1); }
+    let mut vm = rbpf::EbpfVmRaw::new(prog).unwrap();
+    vm.jit_compile().unwrap();
+    unsafe { assert_eq!(vm.prog_exec_jit(mem).unwrap(), 0x1); }
 }
 
 
@@ -1711,9 +1711,9 @@ fn test_jit_tcp_port80_match() {
         0x44, 0x44, 0x44, 0x44
     ];
     let prog = &PROG_TCP_PORT_80;
-    let mut vm = rbpf::EbpfVmRaw::new(prog);
-    vm.jit_compile();
-    unsafe { assert_eq!(vm.prog_exec_jit(mem), 0x1); }
+    let mut vm = rbpf::EbpfVmRaw::new(prog).unwrap();
+    vm.jit_compile().unwrap();
+    unsafe { assert_eq!(vm.prog_exec_jit(mem).unwrap(), 0x1); }
 }
 
 #[test]
@@ -1734,9 +1734,9 @@ fn test_jit_tcp_port80_nomatch() {
         0x44, 0x44, 0x44, 0x44
     ];
     let prog = &PROG_TCP_PORT_80;
-    let mut vm = rbpf::EbpfVmRaw::new(prog);
-    vm.jit_compile();
-    unsafe { assert_eq!(vm.prog_exec_jit(mem), 0x0); }
+    let mut vm = rbpf::EbpfVmRaw::new(prog).unwrap();
+    vm.jit_compile().unwrap();
+    unsafe { assert_eq!(vm.prog_exec_jit(mem).unwrap(), 0x0); }
 }
 
 #[test]
@@ -1757,9 +1757,9 @@ fn test_jit_tcp_port80_nomatch_ethertype() {
         0x44, 0x44, 0x44, 0x44
     ];
     let prog = &PROG_TCP_PORT_80;
-    let mut vm = rbpf::EbpfVmRaw::new(prog);
-    vm.jit_compile();
-    unsafe { assert_eq!(vm.prog_exec_jit(mem), 0x0); }
+    let mut vm = rbpf::EbpfVmRaw::new(prog).unwrap();
+    vm.jit_compile().unwrap();
+    unsafe { assert_eq!(vm.prog_exec_jit(mem).unwrap(), 0x0); }
 }
 
 #[test]
@@ -1780,9 +1780,9 @@ fn test_jit_tcp_port80_nomatch_proto() {
         0x44, 0x44, 0x44, 0x44
     ];
     let prog = &PROG_TCP_PORT_80;
-    let mut vm = rbpf::EbpfVmRaw::new(prog);
-    vm.jit_compile();
-    unsafe { assert_eq!(vm.prog_exec_jit(mem), 0x0); }
+    let mut vm = rbpf::EbpfVmRaw::new(prog).unwrap();
+    vm.jit_compile().unwrap();
+    unsafe { assert_eq!(vm.prog_exec_jit(mem).unwrap(), 0x0); }
 }
 
 const PROG_TCP_SACK: [u8;352] = [
@@ -1847,9 +1847,9 @@ fn test_jit_tcp_sack_match() {
         0xca, 0x28, 0xa3, 0xc4, 0xcf, 0xd0
     ];
     let prog = &PROG_TCP_SACK;
-    let mut vm = rbpf::EbpfVmRaw::new(prog);
-    vm.jit_compile();
-    unsafe { assert_eq!(vm.prog_exec_jit(mem), 0x1); }
+    let mut vm = rbpf::EbpfVmRaw::new(prog).unwrap();
+    vm.jit_compile().unwrap();
+    unsafe { assert_eq!(vm.prog_exec_jit(mem).unwrap(), 0x1); }
 }
 
 #[test]
@@ -1866,7 +1866,7 @@ fn test_jit_tcp_sack_nomatch() {
         0x9e, 0x27
     ];
     let prog = &PROG_TCP_SACK;
-    let mut vm = rbpf::EbpfVmRaw::new(prog);
-    vm.jit_compile();
-    unsafe { assert_eq!(vm.prog_exec_jit(mem), 0x0); }
+    let mut vm = rbpf::EbpfVmRaw::new(prog).unwrap();
+    vm.jit_compile().unwrap();
+    unsafe { assert_eq!(vm.prog_exec_jit(mem).unwrap(), 0x0); }
 }
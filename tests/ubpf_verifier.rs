@@ -24,77 +24,77 @@
 extern crate rbpf;
 
 use rbpf::ebpf;
+use rbpf::error::EbpfError;
 
 #[test]
-#[should_panic(expected = "[Verifier] Error: division by 0 (insn #1)")]
 fn test_verifier_err_div_by_zero_imm() {
     let prog = &[
         0xb4, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00,
         0x34, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
         0x95, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00
     ];
-    let vm = rbpf::EbpfVmNoData::new(prog);
-    vm.prog_exec();
+    let err = rbpf::EbpfVmNoData::new(prog).err().unwrap();
+    assert_eq!(err, EbpfError::DivisionByZero(1));
+    assert_eq!(err.to_string(), "[Verifier] Error: division by 0 (insn #1)");
 }
 
 #[test]
-#[should_panic(expected = "[Verifier] Error: unsupported argument for LE/BE (insn #0)")]
 fn test_verifier_err_endian_size() {
     let prog = &[
         0xdc, 0x01, 0x00, 0x00, 0x03, 0x00, 0x00, 0x00,
         0xb7, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
         0x95, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00
     ];
-    let vm = rbpf::EbpfVmNoData::new(prog);
-    vm.prog_exec();
+    let err = rbpf::EbpfVmNoData::new(prog).err().unwrap();
+    assert_eq!(err, EbpfError::UnsupportedEndianSize(0));
+    assert_eq!(err.to_string(), "[Verifier] Error: unsupported argument for LE/BE (insn #0)");
 }
 
 #[test]
-#[should_panic(expected = "[Verifier] Error: incomplete LD_DW instruction (insn #0)")]
 fn test_verifier_err_incomplete_lddw() { // Note: ubpf has test-err-incomplete-lddw2, which is the same
     let prog = &[
         0x18, 0x00, 0x00, 0x00, 0x88, 0x77, 0x66, 0x55,
         0x95, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00
     ];
-    let vm = rbpf::EbpfVmNoData::new(prog);
-    vm.prog_exec();
+    let err = rbpf::EbpfVmNoData::new(prog).err().unwrap();
+    assert_eq!(err, EbpfError::IncompleteLdDw(0));
+    assert_eq!(err.to_string(), "[Verifier] Error: incomplete LD_DW instruction (insn #0)");
 }
 
 #[test]
-#[should_panic(expected = "[Verifier] Error: infinite loop")]
 fn test_verifier_err_infinite_loop() {
     let prog = &[
         0x05, 0x00, 0xff, 0xff, 0x00, 0x00, 0x00, 0x00,
         0x95, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00
     ];
-    let vm = rbpf::EbpfVmNoData::new(prog);
-    vm.prog_exec();
+    let err = rbpf::EbpfVmNoData::new(prog).err().unwrap();
+    assert_eq!(err, EbpfError::InfiniteLoop(0));
+    assert_eq!(err.to_string(), "[Verifier] Error: infinite loop (insn #0)");
 }
 
 #[test]
-#[should_panic(expected = "[Verifier] Error: invalid destination register (insn #0)")]
 fn test_verifier_err_invalid_reg_dst() {
     let prog = &[
         0xb7, 0x0b, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00,
         0x95, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00
     ];
-    let vm = rbpf::EbpfVmNoData::new(prog);
-    vm.prog_exec();
+    let err = rbpf::EbpfVmNoData::new(prog).err().unwrap();
+    assert_eq!(err, EbpfError::InvalidDstRegister(0));
+    assert_eq!(err.to_string(), "[Verifier] Error: invalid destination register (insn #0)");
 }
 
 #[test]
-#[should_panic(expected = "[Verifier] Error: invalid source register (insn #0)")]
 fn test_verifier_err_invalid_reg_src() {
     let prog = &[
         0xbf, 0xb0, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
         0x95, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00
     ];
-    let vm = rbpf::EbpfVmNoData::new(prog);
-    vm.prog_exec();
+    let err = rbpf::EbpfVmNoData::new(prog).err().unwrap();
+    assert_eq!(err, EbpfError::InvalidSrcRegister(0));
+    assert_eq!(err.to_string(), "[Verifier] Error: invalid source register (insn #0)");
 }
 
 #[test]
-#[should_panic(expected = "[Verifier] Error: jump to middle of LD_DW at #2 (insn #0)")]
 fn test_verifier_err_jmp_lddw() {
     let prog = &[
         0x05, 0x00, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00,
@@ -102,33 +102,33 @@ fn test_verifier_err_jmp_lddw() {
         0x00, 0x00, 0x00, 0x00, 0x44, 0x33, 0x22, 0x11,
         0x95, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00
     ];
-    let vm = rbpf::EbpfVmNoData::new(prog);
-    vm.prog_exec();
+    let err = rbpf::EbpfVmNoData::new(prog).err().unwrap();
+    assert_eq!(err, EbpfError::JumpToMiddleOfLdDw(2, 0));
+    assert_eq!(err.to_string(), "[Verifier] Error: jump to middle of LD_DW at #2 (insn #0)");
 }
 
 #[test]
-#[should_panic(expected = "[Verifier] Error: jump out of code to #3 (insn #0)")]
 fn test_verifier_err_jmp_out() {
     let prog = &[
         0x05, 0x00, 0x02, 0x00, 0x00, 0x00, 0x00, 0x00,
         0x95, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00
     ];
-    let vm = rbpf::EbpfVmNoData::new(prog);
-    vm.prog_exec();
+    let err = rbpf::EbpfVmNoData::new(prog).err().unwrap();
+    assert_eq!(err, EbpfError::JumpOutOfCode(3, 0));
+    assert_eq!(err.to_string(), "[Verifier] Error: jump out of code to #3 (insn #0)");
 }
 
 #[test]
-#[should_panic(expected = "[Verifier] Error: program does not end with “EXIT” instruction")]
 fn test_verifier_err_no_exit() {
     let prog = &[
         0xb4, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00
     ];
-    let vm = rbpf::EbpfVmNoData::new(prog);
-    vm.prog_exec();
+    let err = rbpf::EbpfVmNoData::new(prog).err().unwrap();
+    assert_eq!(err, EbpfError::NoExit);
+    assert_eq!(err.to_string(), "[Verifier] Error: program does not end with “EXIT” instruction");
 }
 
 #[test]
-#[should_panic(expected = "[Verifier] Error: eBPF program length limited to 4096, here 4097")]
 fn test_verifier_err_too_many_instructions() {
     // uBPF uses 65637 instructions, because it sets its limit at 65636.
     // We use the classic 4096 limit from kernel, so no need to produce as many instructions.
@@ -139,28 +139,29 @@ fn test_verifier_err_too_many_instructions() {
     }).collect::<Vec<u8>>();
     prog.append(&mut vec![ 0x95, 0, 0, 0, 0, 0, 0, 0 ]);
 
-    let vm = rbpf::EbpfVmNoData::new(&prog);
-    vm.prog_exec();
+    let err = rbpf::EbpfVmNoData::new(&prog).err().unwrap();
+    assert_eq!(err, EbpfError::TooManyInstructions(4097));
+    assert_eq!(err.to_string(), "[Verifier] Error: eBPF program length limited to 4096, here 4097");
 }
 
 #[test]
-#[should_panic(expected = "[Verifier] Error: unknown eBPF opcode 0x6 (insn #0)")]
 fn test_verifier_err_unknown_opcode() {
     let prog = &[
         0x06, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
         0x95, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00
     ];
-    let vm = rbpf::EbpfVmNoData::new(prog);
-    vm.prog_exec();
+    let err = rbpf::EbpfVmNoData::new(prog).err().unwrap();
+    assert_eq!(err, EbpfError::UnknownOpcode(0x06, 0));
+    assert_eq!(err.to_string(), "[Verifier] Error: unknown eBPF opcode 0x6 (insn #0)");
 }
 
 #[test]
-#[should_panic(expected = "[Verifier] Error: cannot write into register r10 (insn #0)")]
 fn test_verifier_err_write_r10() {
     let prog = &[
         0xb7, 0x0a, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00,
         0x95, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00
     ];
-    let vm = rbpf::EbpfVmNoData::new(prog);
-    vm.prog_exec();
+    let err = rbpf::EbpfVmNoData::new(prog).err().unwrap();
+    assert_eq!(err, EbpfError::CannotWriteR10(0));
+    assert_eq!(err.to_string(), "[Verifier] Error: cannot write into register r10 (insn #0)");
 }
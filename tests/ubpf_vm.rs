@@ -23,6 +23,7 @@
 
 extern crate rbpf;
 
+use rbpf::error::{ExecError, ExecErrorKind};
 use rbpf::helpers;
 
 #[test]
@@ -34,8 +35,8 @@ fn test_vm_add() {
         0x0c, 0x10, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
         0x95, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00
     ];
-    let vm = rbpf::EbpfVmNoData::new(prog);
-    assert_eq!(vm.prog_exec(), 0x3);
+    let vm = rbpf::EbpfVmNoData::new(prog).unwrap();
+    assert_eq!(vm.prog_exec().unwrap(), 0x3);
 }
 
 #[test]
@@ -61,8 +62,8 @@ fn test_vm_alu64_arith() {
         0x3f, 0x40, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
         0x95, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00
     ];
-    let vm = rbpf::EbpfVmNoData::new(prog);
-    assert_eq!(vm.prog_exec(), 0x2a);
+    let vm = rbpf::EbpfVmNoData::new(prog).unwrap();
+    assert_eq!(vm.prog_exec().unwrap(), 0x2a);
 }
 
 #[test]
@@ -92,8 +93,8 @@ fn test_vm_alu64_bit() {
         0xaf, 0x20, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
         0x95, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00
     ];
-    let vm = rbpf::EbpfVmNoData::new(prog);
-    assert_eq!(vm.prog_exec(), 0x11);
+    let vm = rbpf::EbpfVmNoData::new(prog).unwrap();
+    assert_eq!(vm.prog_exec().unwrap(), 0x11);
 }
 
 #[test]
@@ -119,8 +120,8 @@ fn test_vm_alu_arith() {
         0x3c, 0x40, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
         0x95, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00
     ];
-    let vm = rbpf::EbpfVmNoData::new(prog);
-    assert_eq!(vm.prog_exec(), 0x2a);
+    let vm = rbpf::EbpfVmNoData::new(prog).unwrap();
+    assert_eq!(vm.prog_exec().unwrap(), 0x2a);
 }
 
 #[test]
@@ -148,8 +149,8 @@ fn test_vm_alu_bit() {
         0xac, 0x20, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
         0x95, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00
     ];
-    let vm = rbpf::EbpfVmNoData::new(prog);
-    assert_eq!(vm.prog_exec(), 0x11);
+    let vm = rbpf::EbpfVmNoData::new(prog).unwrap();
+    assert_eq!(vm.prog_exec().unwrap(), 0x11);
 }
 
 #[test]
@@ -161,8 +162,8 @@ fn test_vm_arsh32_high_shift() {
         0xcc, 0x10, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
         0x95, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00
     ];
-    let vm = rbpf::EbpfVmNoData::new(prog);
-    assert_eq!(vm.prog_exec(), 0x4);
+    let vm = rbpf::EbpfVmNoData::new(prog).unwrap();
+    assert_eq!(vm.prog_exec().unwrap(), 0x4);
 }
 
 #[test]
@@ -173,8 +174,8 @@ fn test_vm_arsh() {
         0xc4, 0x00, 0x00, 0x00, 0x10, 0x00, 0x00, 0x00,
         0x95, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00
     ];
-    let vm = rbpf::EbpfVmNoData::new(prog);
-    assert_eq!(vm.prog_exec(), 0xffff8000);
+    let vm = rbpf::EbpfVmNoData::new(prog).unwrap();
+    assert_eq!(vm.prog_exec().unwrap(), 0xffff8000);
 }
 
 #[test]
@@ -187,8 +188,8 @@ fn test_vm_arsh64() {
         0xcf, 0x10, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
         0x95, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00
     ];
-    let vm = rbpf::EbpfVmNoData::new(prog);
-    assert_eq!(vm.prog_exec(), 0xfffffffffffffff8);
+    let vm = rbpf::EbpfVmNoData::new(prog).unwrap();
+    assert_eq!(vm.prog_exec().unwrap(), 0xfffffffffffffff8);
 }
 
 #[test]
@@ -200,8 +201,8 @@ fn test_vm_arsh_reg() {
         0xcc, 0x10, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
         0x95, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00
     ];
-    let vm = rbpf::EbpfVmNoData::new(prog);
-    assert_eq!(vm.prog_exec(), 0xffff8000);
+    let vm = rbpf::EbpfVmNoData::new(prog).unwrap();
+    assert_eq!(vm.prog_exec().unwrap(), 0xffff8000);
 }
 
 #[test]
@@ -214,8 +215,8 @@ fn test_vm_be16() {
     let mem = &mut [
         0x11, 0x22
     ];
-    let vm = rbpf::EbpfVmRaw::new(prog);
-    assert_eq!(vm.prog_exec(mem), 0x1122);
+    let vm = rbpf::EbpfVmRaw::new(prog).unwrap();
+    assert_eq!(vm.prog_exec(mem).unwrap(), 0x1122);
 }
 
 #[test]
@@ -228,8 +229,8 @@ fn test_vm_be16_high() {
     let mem = &mut [
         0x11, 0x22, 0x33, 0x44, 0x55, 0x66, 0x77, 0x88
     ];
-    let vm = rbpf::EbpfVmRaw::new(prog);
-    assert_eq!(vm.prog_exec(mem), 0x1122);
+    let vm = rbpf::EbpfVmRaw::new(prog).unwrap();
+    assert_eq!(vm.prog_exec(mem).unwrap(), 0x1122);
 }
 
 #[test]
@@ -242,8 +243,8 @@ fn test_vm_be32() {
     let mem = &mut [
         0x11, 0x22, 0x33, 0x44
     ];
-    let vm = rbpf::EbpfVmRaw::new(prog);
-    assert_eq!(vm.prog_exec(mem), 0x11223344);
+    let vm = rbpf::EbpfVmRaw::new(prog).unwrap();
+    assert_eq!(vm.prog_exec(mem).unwrap(), 0x11223344);
 }
 
 #[test]
@@ -256,8 +257,8 @@ fn test_vm_be32_high() {
     let mem = &mut [
         0x11, 0x22, 0x33, 0x44, 0x55, 0x66, 0x77, 0x88
     ];
-    let vm = rbpf::EbpfVmRaw::new(prog);
-    assert_eq!(vm.prog_exec(mem), 0x11223344);
+    let vm = rbpf::EbpfVmRaw::new(prog).unwrap();
+    assert_eq!(vm.prog_exec(mem).unwrap(), 0x11223344);
 }
 
 #[test]
@@ -270,8 +271,8 @@ fn test_vm_be64() {
     let mem = &mut [
         0x11, 0x22, 0x33, 0x44, 0x55, 0x66, 0x77, 0x88
     ];
-    let vm = rbpf::EbpfVmRaw::new(prog);
-    assert_eq!(vm.prog_exec(mem), 0x1122334455667788);
+    let vm = rbpf::EbpfVmRaw::new(prog).unwrap();
+    assert_eq!(vm.prog_exec(mem).unwrap(), 0x1122334455667788);
 }
 
 #[test]
@@ -285,9 +286,9 @@ fn test_vm_call() {
         0x85, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
         0x95, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00
     ];
-    let mut vm = rbpf::EbpfVmNoData::new(prog);
+    let mut vm = rbpf::EbpfVmNoData::new(prog).unwrap();
     vm.register_helper(0, helpers::gather_bytes);
-    assert_eq!(vm.prog_exec(), 0x0102030405);
+    assert_eq!(vm.prog_exec().unwrap(), 0x0102030405);
 }
 
 #[test]
@@ -304,9 +305,9 @@ fn test_vm_call_memfrob() {
     let mem = &mut [
         0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07, 0x08
     ];
-    let mut vm = rbpf::EbpfVmRaw::new(prog);
+    let mut vm = rbpf::EbpfVmRaw::new(prog).unwrap();
     vm.register_helper(1, helpers::memfrob);
-    assert_eq!(vm.prog_exec(mem), 0x102292e2f2c0708);
+    assert_eq!(vm.prog_exec(mem).unwrap(), 0x102292e2f2c0708);
 }
 
 // TODO: helpers::trash_registers needs asm!().
@@ -326,9 +327,9 @@ fn test_vm_call_memfrob() {
         //0x4f, 0x90, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
         //0x95, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00
     //];
-    //let mut vm = rbpf::EbpfVmNoData::new(prog);
+    //let mut vm = rbpf::EbpfVmNoData::new(prog).unwrap();
     //vm.register_helper(2, helpers::trash_registers);
-    //assert_eq!(vm.prog_exec(), 0x4321);
+    //assert_eq!(vm.prog_exec().unwrap(), 0x4321);
 //}
 
 #[test]
@@ -340,8 +341,8 @@ fn test_vm_div32_high_divisor() {
         0x3c, 0x10, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
         0x95, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00
     ];
-    let vm = rbpf::EbpfVmNoData::new(prog);
-    assert_eq!(vm.prog_exec(), 0x3);
+    let vm = rbpf::EbpfVmNoData::new(prog).unwrap();
+    assert_eq!(vm.prog_exec().unwrap(), 0x3);
 }
 
 #[test]
@@ -352,8 +353,8 @@ fn test_vm_div32_imm() {
         0x34, 0x00, 0x00, 0x00, 0x04, 0x00, 0x00, 0x00,
         0x95, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00
     ];
-    let vm = rbpf::EbpfVmNoData::new(prog);
-    assert_eq!(vm.prog_exec(), 0x3);
+    let vm = rbpf::EbpfVmNoData::new(prog).unwrap();
+    assert_eq!(vm.prog_exec().unwrap(), 0x3);
 }
 
 #[test]
@@ -365,8 +366,8 @@ fn test_vm_div32_reg() {
         0x3c, 0x10, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
         0x95, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00
     ];
-    let vm = rbpf::EbpfVmNoData::new(prog);
-    assert_eq!(vm.prog_exec(), 0x3);
+    let vm = rbpf::EbpfVmNoData::new(prog).unwrap();
+    assert_eq!(vm.prog_exec().unwrap(), 0x3);
 }
 
 #[test]
@@ -377,8 +378,8 @@ fn test_vm_div64_imm() {
         0x37, 0x00, 0x00, 0x00, 0x04, 0x00, 0x00, 0x00,
         0x95, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00
     ];
-    let vm = rbpf::EbpfVmNoData::new(prog);
-    assert_eq!(vm.prog_exec(), 0x300000000);
+    let vm = rbpf::EbpfVmNoData::new(prog).unwrap();
+    assert_eq!(vm.prog_exec().unwrap(), 0x300000000);
 }
 
 #[test]
@@ -390,8 +391,8 @@ fn test_vm_div64_reg() {
         0x3f, 0x10, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
         0x95, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00
     ];
-    let vm = rbpf::EbpfVmNoData::new(prog);
-    assert_eq!(vm.prog_exec(), 0x300000000);
+    let vm = rbpf::EbpfVmNoData::new(prog).unwrap();
+    assert_eq!(vm.prog_exec().unwrap(), 0x300000000);
 }
 
 #[test]
@@ -402,8 +403,8 @@ fn test_vm_early_exit() {
         0xb7, 0x00, 0x00, 0x00, 0x04, 0x00, 0x00, 0x00,
         0x95, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00
     ];
-    let vm = rbpf::EbpfVmNoData::new(prog);
-    assert_eq!(vm.prog_exec(), 0x3);
+    let vm = rbpf::EbpfVmNoData::new(prog).unwrap();
+    assert_eq!(vm.prog_exec().unwrap(), 0x3);
 }
 
 // uBPF limits the number of user functions at 64. We don't.
@@ -412,7 +413,6 @@ fn test_vm_early_exit() {
 //}
 
 #[test]
-#[should_panic(expected = "Error: unknown helper function (id: 0x3f)")]
 fn test_vm_err_call_unreg() {
     let prog = &[
         0xb7, 0x01, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00,
@@ -423,12 +423,13 @@ fn test_vm_err_call_unreg() {
         0x85, 0x00, 0x00, 0x00, 0x3f, 0x00, 0x00, 0x00,
         0x95, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00
     ];
-    let vm = rbpf::EbpfVmNoData::new(prog);
-    vm.prog_exec();
+    let vm = rbpf::EbpfVmNoData::new(prog).unwrap();
+    let err = vm.prog_exec().unwrap_err();
+    assert_eq!(err, ExecError::new(ExecErrorKind::UnknownHelper(0x3f), 5));
+    assert_eq!(err.to_string(), "Error: unknown helper function (id: 0x3f) (insn #5)");
 }
 
 #[test]
-#[should_panic(expected = "Error: division by 0")]
 fn test_vm_err_div64_by_zero_reg() {
     let prog = &[
         0xb4, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00,
@@ -436,12 +437,13 @@ fn test_vm_err_div64_by_zero_reg() {
         0x3f, 0x10, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
         0x95, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00
     ];
-    let vm = rbpf::EbpfVmNoData::new(prog);
-    vm.prog_exec();
+    let vm = rbpf::EbpfVmNoData::new(prog).unwrap();
+    let err = vm.prog_exec().unwrap_err();
+    assert_eq!(err, ExecError::new(ExecErrorKind::DivisionByZero, 2));
+    assert_eq!(err.to_string(), "Error: division by 0 (insn #2)");
 }
 
 #[test]
-#[should_panic(expected = "Error: division by 0")]
 fn test_vm_err_div_by_zero_reg() {
     let prog = &[
         0xb4, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00,
@@ -449,12 +451,13 @@ fn test_vm_err_div_by_zero_reg() {
         0x3c, 0x10, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
         0x95, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00
     ];
-    let vm = rbpf::EbpfVmNoData::new(prog);
-    vm.prog_exec();
+    let vm = rbpf::EbpfVmNoData::new(prog).unwrap();
+    let err = vm.prog_exec().unwrap_err();
+    assert_eq!(err, ExecError::new(ExecErrorKind::DivisionByZero, 2));
+    assert_eq!(err.to_string(), "Error: division by 0 (insn #2)");
 }
 
 #[test]
-#[should_panic(expected = "Error: division by 0")]
 fn test_vm_err_mod64_by_zero_reg() {
     let prog = &[
         0xb4, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00,
@@ -462,12 +465,13 @@ fn test_vm_err_mod64_by_zero_reg() {
         0x9f, 0x10, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
         0x95, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00
     ];
-    let vm = rbpf::EbpfVmNoData::new(prog);
-    vm.prog_exec();
+    let vm = rbpf::EbpfVmNoData::new(prog).unwrap();
+    let err = vm.prog_exec().unwrap_err();
+    assert_eq!(err, ExecError::new(ExecErrorKind::DivisionByZero, 2));
+    assert_eq!(err.to_string(), "Error: division by 0 (insn #2)");
 }
 
 #[test]
-#[should_panic(expected = "Error: division by 0")]
 fn test_vm_err_mod_by_zero_reg() {
     let prog = &[
         0xb4, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00,
@@ -475,19 +479,24 @@ fn test_vm_err_mod_by_zero_reg() {
         0x9c, 0x10, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
         0x95, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00
     ];
-    let vm = rbpf::EbpfVmNoData::new(prog);
-    vm.prog_exec();
+    let vm = rbpf::EbpfVmNoData::new(prog).unwrap();
+    let err = vm.prog_exec().unwrap_err();
+    assert_eq!(err, ExecError::new(ExecErrorKind::DivisionByZero, 2));
+    assert_eq!(err.to_string(), "Error: division by 0 (insn #2)");
 }
 
 #[test]
-#[should_panic(expected = "Error: out of bounds memory store (insn #1)")]
 fn test_vm_err_stack_out_of_bound() {
     let prog = &[
         0x72, 0x0a, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
         0x95, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00
     ];
-    let vm = rbpf::EbpfVmNoData::new(prog);
-    vm.prog_exec();
+    let vm = rbpf::EbpfVmNoData::new(prog).unwrap();
+    let err = vm.prog_exec().unwrap_err();
+    assert_eq!(err.kind, ExecErrorKind::OutOfBoundsStore);
+    assert_eq!(err.insn_ptr, 0);
+    assert_eq!(err.size, 1);
+    assert!(err.to_string().starts_with("Error: out of bounds memory store (insn #0)"));
 }
 
 #[test]
@@ -496,8 +505,8 @@ fn test_vm_exit() {
         0xb7, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
         0x95, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00
     ];
-    let vm = rbpf::EbpfVmNoData::new(prog);
-    assert_eq!(vm.prog_exec(), 0x0);
+    let vm = rbpf::EbpfVmNoData::new(prog).unwrap();
+    assert_eq!(vm.prog_exec().unwrap(), 0x0);
 }
 
 #[test]
@@ -508,8 +517,8 @@ fn test_vm_ja() {
         0xb7, 0x00, 0x00, 0x00, 0x02, 0x00, 0x00, 0x00,
         0x95, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00
     ];
-    let vm = rbpf::EbpfVmNoData::new(prog);
-    assert_eq!(vm.prog_exec(), 0x1);
+    let vm = rbpf::EbpfVmNoData::new(prog).unwrap();
+    assert_eq!(vm.prog_exec().unwrap(), 0x1);
 }
 
 #[test]
@@ -524,8 +533,8 @@ fn test_vm_jeq_imm() {
         0xb4, 0x00, 0x00, 0x00, 0x02, 0x00, 0x00, 0x00,
         0x95, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00
     ];
-    let vm = rbpf::EbpfVmNoData::new(prog);
-    assert_eq!(vm.prog_exec(), 0x1);
+    let vm = rbpf::EbpfVmNoData::new(prog).unwrap();
+    assert_eq!(vm.prog_exec().unwrap(), 0x1);
 }
 
 #[test]
@@ -541,8 +550,8 @@ fn test_vm_jeq_reg() {
         0xb4, 0x00, 0x00, 0x00, 0x02, 0x00, 0x00, 0x00,
         0x95, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00
     ];
-    let vm = rbpf::EbpfVmNoData::new(prog);
-    assert_eq!(vm.prog_exec(), 0x1);
+    let vm = rbpf::EbpfVmNoData::new(prog).unwrap();
+    assert_eq!(vm.prog_exec().unwrap(), 0x1);
 }
 
 #[test]
@@ -557,8 +566,8 @@ fn test_vm_jge_imm() {
         0xb4, 0x00, 0x00, 0x00, 0x02, 0x00, 0x00, 0x00,
         0x95, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00
     ];
-    let vm = rbpf::EbpfVmNoData::new(prog);
-    assert_eq!(vm.prog_exec(), 0x1);
+    let vm = rbpf::EbpfVmNoData::new(prog).unwrap();
+    assert_eq!(vm.prog_exec().unwrap(), 0x1);
 }
 
 #[test]
@@ -573,8 +582,8 @@ fn test_vm_jgt_imm() {
         0xb4, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00,
         0x95, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00
     ];
-    let vm = rbpf::EbpfVmNoData::new(prog);
-    assert_eq!(vm.prog_exec(), 0x1);
+    let vm = rbpf::EbpfVmNoData::new(prog).unwrap();
+    assert_eq!(vm.prog_exec().unwrap(), 0x1);
 }
 
 #[test]
@@ -591,8 +600,8 @@ fn test_vm_jgt_reg() {
         0xb7, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00,
         0x95, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00
     ];
-    let vm = rbpf::EbpfVmNoData::new(prog);
-    assert_eq!(vm.prog_exec(), 0x1);
+    let vm = rbpf::EbpfVmNoData::new(prog).unwrap();
+    assert_eq!(vm.prog_exec().unwrap(), 0x1);
 }
 
 #[test]
@@ -606,8 +615,8 @@ fn test_vm_jit_bounce() {
         0xbf, 0x90, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
         0x95, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00
     ];
-    let vm = rbpf::EbpfVmNoData::new(prog);
-    assert_eq!(vm.prog_exec(), 0x1);
+    let vm = rbpf::EbpfVmNoData::new(prog).unwrap();
+    assert_eq!(vm.prog_exec().unwrap(), 0x1);
 }
 
 #[test]
@@ -623,8 +632,8 @@ fn test_vm_jne_reg() {
         0xb4, 0x00, 0x00, 0x00, 0x02, 0x00, 0x00, 0x00,
         0x95, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00
     ];
-    let vm = rbpf::EbpfVmNoData::new(prog);
-    assert_eq!(vm.prog_exec(), 0x1);
+    let vm = rbpf::EbpfVmNoData::new(prog).unwrap();
+    assert_eq!(vm.prog_exec().unwrap(), 0x1);
 }
 
 #[test]
@@ -639,8 +648,8 @@ fn test_vm_jset_imm() {
         0xb4, 0x00, 0x00, 0x00, 0x02, 0x00, 0x00, 0x00,
         0x95, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00
     ];
-    let vm = rbpf::EbpfVmNoData::new(prog);
-    assert_eq!(vm.prog_exec(), 0x1);
+    let vm = rbpf::EbpfVmNoData::new(prog).unwrap();
+    assert_eq!(vm.prog_exec().unwrap(), 0x1);
 }
 
 #[test]
@@ -656,8 +665,8 @@ fn test_vm_jset_reg() {
         0xb4, 0x00, 0x00, 0x00, 0x02, 0x00, 0x00, 0x00,
         0x95, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00
     ];
-    let vm = rbpf::EbpfVmNoData::new(prog);
-    assert_eq!(vm.prog_exec(), 0x1);
+    let vm = rbpf::EbpfVmNoData::new(prog).unwrap();
+    assert_eq!(vm.prog_exec().unwrap(), 0x1);
 }
 
 #[test]
@@ -673,8 +682,8 @@ fn test_vm_jsge_imm() {
         0xb4, 0x00, 0x00, 0x00, 0x02, 0x00, 0x00, 0x00,
         0x95, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00
     ];
-    let vm = rbpf::EbpfVmNoData::new(prog);
-    assert_eq!(vm.prog_exec(), 0x1);
+    let vm = rbpf::EbpfVmNoData::new(prog).unwrap();
+    assert_eq!(vm.prog_exec().unwrap(), 0x1);
 }
 
 #[test]
@@ -692,8 +701,8 @@ fn test_vm_jsge_reg() {
         0xb4, 0x00, 0x00, 0x00, 0x02, 0x00, 0x00, 0x00,
         0x95, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00
     ];
-    let vm = rbpf::EbpfVmNoData::new(prog);
-    assert_eq!(vm.prog_exec(), 0x1);
+    let vm = rbpf::EbpfVmNoData::new(prog).unwrap();
+    assert_eq!(vm.prog_exec().unwrap(), 0x1);
 }
 
 #[test]
@@ -708,8 +717,8 @@ fn test_vm_jsgt_imm() {
         0xb4, 0x00, 0x00, 0x00, 0x02, 0x00, 0x00, 0x00,
         0x95, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00
     ];
-    let vm = rbpf::EbpfVmNoData::new(prog);
-    assert_eq!(vm.prog_exec(), 0x1);
+    let vm = rbpf::EbpfVmNoData::new(prog).unwrap();
+    assert_eq!(vm.prog_exec().unwrap(), 0x1);
 }
 
 #[test]
@@ -725,8 +734,8 @@ fn test_vm_jsgt_reg() {
         0xb4, 0x00, 0x00, 0x00, 0x02, 0x00, 0x00, 0x00,
         0x95, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00
     ];
-    let vm = rbpf::EbpfVmNoData::new(prog);
-    assert_eq!(vm.prog_exec(), 0x1);
+    let vm = rbpf::EbpfVmNoData::new(prog).unwrap();
+    assert_eq!(vm.prog_exec().unwrap(), 0x1);
 }
 
 #[test]
@@ -736,8 +745,8 @@ fn test_vm_lddw() {
         0x00, 0x00, 0x00, 0x00, 0x44, 0x33, 0x22, 0x11,
         0x95, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00
     ];
-    let vm = rbpf::EbpfVmNoData::new(prog);
-    assert_eq!(vm.prog_exec(), 0x1122334455667788);
+    let vm = rbpf::EbpfVmNoData::new(prog).unwrap();
+    assert_eq!(vm.prog_exec().unwrap(), 0x1122334455667788);
 }
 
 #[test]
@@ -747,8 +756,8 @@ fn test_vm_lddw2() {
         0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
         0x95, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00
     ];
-    let vm = rbpf::EbpfVmNoData::new(prog);
-    assert_eq!(vm.prog_exec(), 0x80000000);
+    let vm = rbpf::EbpfVmNoData::new(prog).unwrap();
+    assert_eq!(vm.prog_exec().unwrap(), 0x80000000);
 }
 
 #[test]
@@ -790,8 +799,8 @@ fn test_vm_ldxb_all() {
         0x00, 0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07,
         0x08, 0x09
     ];
-    let vm = rbpf::EbpfVmRaw::new(prog);
-    assert_eq!(vm.prog_exec(mem), 0x9876543210);
+    let vm = rbpf::EbpfVmRaw::new(prog).unwrap();
+    assert_eq!(vm.prog_exec(mem).unwrap(), 0x9876543210);
 }
 
 #[test]
@@ -803,8 +812,8 @@ fn test_vm_ldxb() {
     let mem = &mut [
         0xaa, 0xbb, 0x11, 0xcc, 0xdd
     ];
-    let vm = rbpf::EbpfVmRaw::new(prog);
-    assert_eq!(vm.prog_exec(mem), 0x11);
+    let vm = rbpf::EbpfVmRaw::new(prog).unwrap();
+    assert_eq!(vm.prog_exec(mem).unwrap(), 0x11);
 }
 
 #[test]
@@ -817,8 +826,8 @@ fn test_vm_ldxdw() {
         0xaa, 0xbb, 0x11, 0x22, 0x33, 0x44, 0x55, 0x66,
         0x77, 0x88, 0xcc, 0xdd
     ];
-    let vm = rbpf::EbpfVmRaw::new(prog);
-    assert_eq!(vm.prog_exec(mem), 0x8877665544332211);
+    let vm = rbpf::EbpfVmRaw::new(prog).unwrap();
+    assert_eq!(vm.prog_exec(mem).unwrap(), 0x8877665544332211);
 }
 
 #[test]
@@ -871,8 +880,8 @@ fn test_vm_ldxh_all() {
         0x00, 0x04, 0x00, 0x05, 0x00, 0x06, 0x00, 0x07,
         0x00, 0x08, 0x00, 0x09
     ];
-    let vm = rbpf::EbpfVmRaw::new(prog);
-    assert_eq!(vm.prog_exec(mem), 0x9876543210);
+    let vm = rbpf::EbpfVmRaw::new(prog).unwrap();
+    assert_eq!(vm.prog_exec(mem).unwrap(), 0x9876543210);
 }
 
 #[test]
@@ -915,8 +924,8 @@ fn test_vm_ldxh_all2() {
         0x00, 0x10, 0x00, 0x20, 0x00, 0x40, 0x00, 0x80,
         0x01, 0x00, 0x02, 0x00
     ];
-    let vm = rbpf::EbpfVmRaw::new(prog);
-    assert_eq!(vm.prog_exec(mem), 0x3ff);
+    let vm = rbpf::EbpfVmRaw::new(prog).unwrap();
+    assert_eq!(vm.prog_exec(mem).unwrap(), 0x3ff);
 }
 
 #[test]
@@ -928,8 +937,8 @@ fn test_vm_ldxh() {
     let mem = &mut [
         0xaa, 0xbb, 0x11, 0x22, 0xcc, 0xdd
     ];
-    let vm = rbpf::EbpfVmRaw::new(prog);
-    assert_eq!(vm.prog_exec(mem), 0x2211);
+    let vm = rbpf::EbpfVmRaw::new(prog).unwrap();
+    assert_eq!(vm.prog_exec(mem).unwrap(), 0x2211);
 }
 
 #[test]
@@ -943,8 +952,8 @@ fn test_vm_ldxh_same_reg() {
     let mem = &mut [
         0xff, 0xff
     ];
-    let vm = rbpf::EbpfVmRaw::new(prog);
-    assert_eq!(vm.prog_exec(mem), 0x1234);
+    let vm = rbpf::EbpfVmRaw::new(prog).unwrap();
+    assert_eq!(vm.prog_exec(mem).unwrap(), 0x1234);
 }
 
 #[test]
@@ -989,8 +998,8 @@ fn test_vm_ldxw_all() {
         0x00, 0x00, 0x04, 0x00, 0x00, 0x00, 0x08, 0x00,
         0x00, 0x01, 0x00, 0x00, 0x00, 0x02, 0x00, 0x00
     ];
-    let vm = rbpf::EbpfVmRaw::new(prog);
-    assert_eq!(vm.prog_exec(mem), 0x030f0f);
+    let vm = rbpf::EbpfVmRaw::new(prog).unwrap();
+    assert_eq!(vm.prog_exec(mem).unwrap(), 0x030f0f);
 }
 
 #[test]
@@ -1002,8 +1011,8 @@ fn test_vm_ldxw() {
     let mem = &mut [
         0xaa, 0xbb, 0x11, 0x22, 0x33, 0x44, 0xcc, 0xdd
     ];
-    let vm = rbpf::EbpfVmRaw::new(prog);
-    assert_eq!(vm.prog_exec(mem), 0x44332211);
+    let vm = rbpf::EbpfVmRaw::new(prog).unwrap();
+    assert_eq!(vm.prog_exec(mem).unwrap(), 0x44332211);
 }
 
 #[test]
@@ -1016,8 +1025,8 @@ fn test_vm_le16() {
     let mem = &mut [
         0x22, 0x11
     ];
-    let vm = rbpf::EbpfVmRaw::new(prog);
-    assert_eq!(vm.prog_exec(mem), 0x1122);
+    let vm = rbpf::EbpfVmRaw::new(prog).unwrap();
+    assert_eq!(vm.prog_exec(mem).unwrap(), 0x1122);
 }
 
 #[test]
@@ -1030,8 +1039,8 @@ fn test_vm_le32() {
     let mem = &mut [
         0x44, 0x33, 0x22, 0x11
     ];
-    let vm = rbpf::EbpfVmRaw::new(prog);
-    assert_eq!(vm.prog_exec(mem), 0x11223344);
+    let vm = rbpf::EbpfVmRaw::new(prog).unwrap();
+    assert_eq!(vm.prog_exec(mem).unwrap(), 0x11223344);
 }
 
 #[test]
@@ -1044,8 +1053,8 @@ fn test_vm_le64() {
     let mem = &mut [
         0x88, 0x77, 0x66, 0x55, 0x44, 0x33, 0x22, 0x11
     ];
-    let vm = rbpf::EbpfVmRaw::new(prog);
-    assert_eq!(vm.prog_exec(mem), 0x1122334455667788);
+    let vm = rbpf::EbpfVmRaw::new(prog).unwrap();
+    assert_eq!(vm.prog_exec(mem).unwrap(), 0x1122334455667788);
 }
 
 #[test]
@@ -1056,8 +1065,8 @@ fn test_vm_lsh_reg() {
         0x6f, 0x70, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
         0x95, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00
     ];
-    let vm = rbpf::EbpfVmNoData::new(prog);
-    assert_eq!(vm.prog_exec(), 0x10);
+    let vm = rbpf::EbpfVmNoData::new(prog).unwrap();
+    assert_eq!(vm.prog_exec().unwrap(), 0x10);
 }
 
 #[test]
@@ -1069,8 +1078,8 @@ fn test_vm_mod() {
         0x9c, 0x10, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
         0x95, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00
     ];
-    let vm = rbpf::EbpfVmNoData::new(prog);
-    assert_eq!(vm.prog_exec(), 0x5);
+    let vm = rbpf::EbpfVmNoData::new(prog).unwrap();
+    assert_eq!(vm.prog_exec().unwrap(), 0x5);
 }
 
 #[test]
@@ -1081,8 +1090,8 @@ fn test_vm_mod32() {
         0x94, 0x00, 0x00, 0x00, 0x03, 0x00, 0x00, 0x00,
         0x95, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00
     ];
-    let vm = rbpf::EbpfVmNoData::new(prog);
-    assert_eq!(vm.prog_exec(), 0x0);
+    let vm = rbpf::EbpfVmNoData::new(prog).unwrap();
+    assert_eq!(vm.prog_exec().unwrap(), 0x0);
 }
 
 #[test]
@@ -1098,8 +1107,8 @@ fn test_vm_mod64() {
         0x97, 0x00, 0x00, 0x00, 0x78, 0x17, 0x8f, 0x65,
         0x95, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00
     ];
-    let vm = rbpf::EbpfVmNoData::new(prog);
-    assert_eq!(vm.prog_exec(), 0x30ba5a04);
+    let vm = rbpf::EbpfVmNoData::new(prog).unwrap();
+    assert_eq!(vm.prog_exec().unwrap(), 0x30ba5a04);
 }
 
 #[test]
@@ -1109,8 +1118,8 @@ fn test_vm_mov() {
         0xbc, 0x10, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
         0x95, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00
     ];
-    let vm = rbpf::EbpfVmNoData::new(prog);
-    assert_eq!(vm.prog_exec(), 0x1);
+    let vm = rbpf::EbpfVmNoData::new(prog).unwrap();
+    assert_eq!(vm.prog_exec().unwrap(), 0x1);
 }
 
 #[test]
@@ -1120,8 +1129,8 @@ fn test_vm_mul32_imm() {
         0x24, 0x00, 0x00, 0x00, 0x04, 0x00, 0x00, 0x00,
         0x95, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00
     ];
-    let vm = rbpf::EbpfVmNoData::new(prog);
-    assert_eq!(vm.prog_exec(), 0xc);
+    let vm = rbpf::EbpfVmNoData::new(prog).unwrap();
+    assert_eq!(vm.prog_exec().unwrap(), 0xc);
 }
 
 #[test]
@@ -1132,8 +1141,8 @@ fn test_vm_mul32_reg() {
         0x2c, 0x10, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
         0x95, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00
     ];
-    let vm = rbpf::EbpfVmNoData::new(prog);
-    assert_eq!(vm.prog_exec(), 0xc);
+    let vm = rbpf::EbpfVmNoData::new(prog).unwrap();
+    assert_eq!(vm.prog_exec().unwrap(), 0xc);
 }
 
 #[test]
@@ -1144,8 +1153,8 @@ fn test_vm_mul32_reg_overflow() {
         0x2c, 0x10, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
         0x95, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00
     ];
-    let vm = rbpf::EbpfVmNoData::new(prog);
-    assert_eq!(vm.prog_exec(), 0x4);
+    let vm = rbpf::EbpfVmNoData::new(prog).unwrap();
+    assert_eq!(vm.prog_exec().unwrap(), 0x4);
 }
 
 #[test]
@@ -1155,8 +1164,8 @@ fn test_vm_mul64_imm() {
         0x27, 0x00, 0x00, 0x00, 0x04, 0x00, 0x00, 0x00,
         0x95, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00
     ];
-    let vm = rbpf::EbpfVmNoData::new(prog);
-    assert_eq!(vm.prog_exec(), 0x100000004);
+    let vm = rbpf::EbpfVmNoData::new(prog).unwrap();
+    assert_eq!(vm.prog_exec().unwrap(), 0x100000004);
 }
 
 #[test]
@@ -1167,8 +1176,8 @@ fn test_vm_mul64_reg() {
         0x2f, 0x10, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
         0x95, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00
     ];
-    let vm = rbpf::EbpfVmNoData::new(prog);
-    assert_eq!(vm.prog_exec(), 0x100000004);
+    let vm = rbpf::EbpfVmNoData::new(prog).unwrap();
+    assert_eq!(vm.prog_exec().unwrap(), 0x100000004);
 }
 
 #[test]
@@ -1185,8 +1194,8 @@ fn test_vm_mul_loop() {
         0x55, 0x01, 0xfd, 0xff, 0x00, 0x00, 0x00, 0x00,
         0x95, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00
     ];
-    let vm = rbpf::EbpfVmNoData::new(prog);
-    assert_eq!(vm.prog_exec(), 0x75db9c97);
+    let vm = rbpf::EbpfVmNoData::new(prog).unwrap();
+    assert_eq!(vm.prog_exec().unwrap(), 0x75db9c97);
 }
 
 #[test]
@@ -1196,8 +1205,8 @@ fn test_vm_neg64() {
         0x87, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
         0x95, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00
     ];
-    let vm = rbpf::EbpfVmNoData::new(prog);
-    assert_eq!(vm.prog_exec(), 0xfffffffffffffffe);
+    let vm = rbpf::EbpfVmNoData::new(prog).unwrap();
+    assert_eq!(vm.prog_exec().unwrap(), 0xfffffffffffffffe);
 }
 
 #[test]
@@ -1207,8 +1216,8 @@ fn test_vm_neg() {
         0x84, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
         0x95, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00
     ];
-    let vm = rbpf::EbpfVmNoData::new(prog);
-    assert_eq!(vm.prog_exec(), 0xfffffffe);
+    let vm = rbpf::EbpfVmNoData::new(prog).unwrap();
+    assert_eq!(vm.prog_exec().unwrap(), 0xfffffffe);
 }
 
 #[test]
@@ -1231,8 +1240,8 @@ fn test_vm_prime() {
         0x55, 0x04, 0xf6, 0xff, 0x00, 0x00, 0x00, 0x00,
         0x95, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00
     ];
-    let vm = rbpf::EbpfVmNoData::new(prog);
-    assert_eq!(vm.prog_exec(), 0x1);
+    let vm = rbpf::EbpfVmNoData::new(prog).unwrap();
+    assert_eq!(vm.prog_exec().unwrap(), 0x1);
 }
 
 #[test]
@@ -1243,8 +1252,8 @@ fn test_vm_rhs32() {
         0x74, 0x00, 0x00, 0x00, 0x08, 0x00, 0x00, 0x00,
         0x95, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00
     ];
-    let vm = rbpf::EbpfVmNoData::new(prog);
-    assert_eq!(vm.prog_exec(), 0x00ffffff);
+    let vm = rbpf::EbpfVmNoData::new(prog).unwrap();
+    assert_eq!(vm.prog_exec().unwrap(), 0x00ffffff);
 }
 
 #[test]
@@ -1255,8 +1264,8 @@ fn test_vm_rsh_reg() {
         0x7f, 0x70, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
         0x95, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00
     ];
-    let vm = rbpf::EbpfVmNoData::new(prog);
-    assert_eq!(vm.prog_exec(), 0x1);
+    let vm = rbpf::EbpfVmNoData::new(prog).unwrap();
+    assert_eq!(vm.prog_exec().unwrap(), 0x1);
 }
 
 #[test]
@@ -1272,8 +1281,8 @@ fn test_vm_stack() {
         0x79, 0x20, 0xf0, 0xff, 0x00, 0x00, 0x00, 0x00,
         0x95, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00
     ];
-    let vm = rbpf::EbpfVmNoData::new(prog);
-    assert_eq!(vm.prog_exec(), 0xcd);
+    let vm = rbpf::EbpfVmNoData::new(prog).unwrap();
+    assert_eq!(vm.prog_exec().unwrap(), 0xcd);
 }
 
 #[test]
@@ -1296,10 +1305,10 @@ fn test_vm_stack2() {
         0xa7, 0x00, 0x00, 0x00, 0x2a, 0x2a, 0x2a, 0x2a,
         0x95, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00
     ];
-    let mut vm = rbpf::EbpfVmNoData::new(prog);
+    let mut vm = rbpf::EbpfVmNoData::new(prog).unwrap();
     vm.register_helper(0, helpers::gather_bytes);
     vm.register_helper(1, helpers::memfrob);
-    assert_eq!(vm.prog_exec(), 0x01020304);
+    assert_eq!(vm.prog_exec().unwrap(), 0x01020304);
 }
 
 #[test]
@@ -1312,8 +1321,8 @@ fn test_vm_stb() {
     let mem = &mut [
         0xaa, 0xbb, 0xff, 0xcc, 0xdd
     ];
-    let vm = rbpf::EbpfVmRaw::new(prog);
-    assert_eq!(vm.prog_exec(mem), 0x11);
+    let vm = rbpf::EbpfVmRaw::new(prog).unwrap();
+    assert_eq!(vm.prog_exec(mem).unwrap(), 0x11);
 }
 
 #[test]
@@ -1327,8 +1336,8 @@ fn test_vm_stdw() {
         0xaa, 0xbb, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff,
         0xff, 0xff, 0xcc, 0xdd
     ];
-    let vm = rbpf::EbpfVmRaw::new(prog);
-    assert_eq!(vm.prog_exec(mem), 0x44332211);
+    let vm = rbpf::EbpfVmRaw::new(prog).unwrap();
+    assert_eq!(vm.prog_exec(mem).unwrap(), 0x44332211);
 }
 
 #[test]
@@ -1341,8 +1350,8 @@ fn test_vm_sth() {
     let mem = &mut [
         0xaa, 0xbb, 0xff, 0xff, 0xcc, 0xdd
     ];
-    let vm = rbpf::EbpfVmRaw::new(prog);
-    assert_eq!(vm.prog_exec(mem), 0x2211);
+    let vm = rbpf::EbpfVmRaw::new(prog).unwrap();
+    assert_eq!(vm.prog_exec(mem).unwrap(), 0x2211);
 }
 
 #[test]
@@ -1377,9 +1386,9 @@ fn test_vm_string_stack() {
         0xb7, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
         0x95, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00
     ];
-    let mut vm = rbpf::EbpfVmNoData::new(prog);
+    let mut vm = rbpf::EbpfVmNoData::new(prog).unwrap();
     vm.register_helper(4, helpers::strcmp);
-    assert_eq!(vm.prog_exec(), 0x0);
+    assert_eq!(vm.prog_exec().unwrap(), 0x0);
 }
 
 #[test]
@@ -1392,8 +1401,8 @@ fn test_vm_stw() {
     let mem = &mut [
         0xaa, 0xbb, 0xff, 0xff, 0xff, 0xff, 0xcc, 0xdd
     ];
-    let vm = rbpf::EbpfVmRaw::new(prog);
-    assert_eq!(vm.prog_exec(mem), 0x44332211);
+    let vm = rbpf::EbpfVmRaw::new(prog).unwrap();
+    assert_eq!(vm.prog_exec(mem).unwrap(), 0x44332211);
 }
 
 #[test]
@@ -1407,8 +1416,8 @@ fn test_vm_stxb() {
     let mem = &mut [
         0xaa, 0xbb, 0xff, 0xcc, 0xdd
     ];
-    let vm = rbpf::EbpfVmRaw::new(prog);
-    assert_eq!(vm.prog_exec(mem), 0x11);
+    let vm = rbpf::EbpfVmRaw::new(prog).unwrap();
+    assert_eq!(vm.prog_exec(mem).unwrap(), 0x11);
 }
 
 #[test]
@@ -1437,8 +1446,8 @@ fn test_vm_stxb_all() {
     let mem = &mut [
         0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff
     ];
-    let vm = rbpf::EbpfVmRaw::new(prog);
-    assert_eq!(vm.prog_exec(mem), 0xf0f2f3f4f5f6f7f8);
+    let vm = rbpf::EbpfVmRaw::new(prog).unwrap();
+    assert_eq!(vm.prog_exec(mem).unwrap(), 0xf0f2f3f4f5f6f7f8);
 }
 
 #[test]
@@ -1456,8 +1465,8 @@ fn test_vm_stxb_all2() {
     let mem = &mut [
         0xff, 0xff
     ];
-    let vm = rbpf::EbpfVmRaw::new(prog);
-    assert_eq!(vm.prog_exec(mem), 0xf1f9);
+    let vm = rbpf::EbpfVmRaw::new(prog).unwrap();
+    assert_eq!(vm.prog_exec(mem).unwrap(), 0xf1f9);
 }
 
 #[test]
@@ -1489,8 +1498,8 @@ fn test_vm_stxb_chain() {
         0x2a, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
         0x00, 0x00
     ];
-    let vm = rbpf::EbpfVmRaw::new(prog);
-    assert_eq!(vm.prog_exec(mem), 0x2a);
+    let vm = rbpf::EbpfVmRaw::new(prog).unwrap();
+    assert_eq!(vm.prog_exec(mem).unwrap(), 0x2a);
 }
 
 #[test]
@@ -1507,8 +1516,8 @@ fn test_vm_stxdw() {
         0xaa, 0xbb, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff,
         0xff, 0xff, 0xcc, 0xdd
     ];
-    let vm = rbpf::EbpfVmRaw::new(prog);
-    assert_eq!(vm.prog_exec(mem), 0x8877665544332211);
+    let vm = rbpf::EbpfVmRaw::new(prog).unwrap();
+    assert_eq!(vm.prog_exec(mem).unwrap(), 0x8877665544332211);
 }
 
 #[test]
@@ -1522,8 +1531,8 @@ fn test_vm_stxh() {
     let mem = &mut [
         0xaa, 0xbb, 0xff, 0xff, 0xcc, 0xdd
     ];
-    let vm = rbpf::EbpfVmRaw::new(prog);
-    assert_eq!(vm.prog_exec(mem), 0x2211);
+    let vm = rbpf::EbpfVmRaw::new(prog).unwrap();
+    assert_eq!(vm.prog_exec(mem).unwrap(), 0x2211);
 }
 
 #[test]
@@ -1537,8 +1546,8 @@ fn test_vm_stxw() {
     let mem = &mut [
         0xaa, 0xbb, 0xff, 0xff, 0xff, 0xff, 0xcc, 0xdd
     ];
-    let vm = rbpf::EbpfVmRaw::new(prog);
-    assert_eq!(vm.prog_exec(mem), 0x44332211);
+    let vm = rbpf::EbpfVmRaw::new(prog).unwrap();
+    assert_eq!(vm.prog_exec(mem).unwrap(), 0x44332211);
 }
 
 #[test]
@@ -1571,8 +1580,8 @@ fn test_vm_subnet() {
         0x27, 0x24, 0x00, 0x00, 0x00, 0x00, 0x01, 0x03,
         0x03, 0x00
     ];
-    let vm = rbpf::EbpfVmRaw::new(prog);
-    assert_eq!(vm.prog_exec(mem), 0x1);
+    let vm = rbpf::EbpfVmRaw::new(prog).unwrap();
+    assert_eq!(vm.prog_exec(mem).unwrap(), 0x1);
 }
 
 
@@ -1616,8 +1625,8 @@ fn test_vm_tcp_port80_match() {
         0x44, 0x44, 0x44, 0x44
     ];
     let prog = &PROG_TCP_PORT_80;
-    let vm = rbpf::EbpfVmRaw::new(prog);
-    assert_eq!(vm.prog_exec(mem), 0x1);
+    let vm = rbpf::EbpfVmRaw::new(prog).unwrap();
+    assert_eq!(vm.prog_exec(mem).unwrap(), 0x1);
 }
 
 #[test]
@@ -1638,8 +1647,8 @@ fn test_vm_tcp_port80_nomatch() {
         0x44, 0x44, 0x44, 0x44
     ];
     let prog = &PROG_TCP_PORT_80;
-    let vm = rbpf::EbpfVmRaw::new(prog);
-    assert_eq!(vm.prog_exec(mem), 0x0);
+    let vm = rbpf::EbpfVmRaw::new(prog).unwrap();
+    assert_eq!(vm.prog_exec(mem).unwrap(), 0x0);
 }
 
 #[test]
@@ -1660,8 +1669,8 @@ fn test_vm_tcp_port80_nomatch_ethertype() {
         0x44, 0x44, 0x44, 0x44
     ];
     let prog = &PROG_TCP_PORT_80;
-    let vm = rbpf::EbpfVmRaw::new(prog);
-    assert_eq!(vm.prog_exec(mem), 0x0);
+    let vm = rbpf::EbpfVmRaw::new(prog).unwrap();
+    assert_eq!(vm.prog_exec(mem).unwrap(), 0x0);
 }
 
 #[test]
@@ -1682,8 +1691,8 @@ fn test_vm_tcp_port80_nomatch_proto() {
         0x44, 0x44, 0x44, 0x44
     ];
     let prog = &PROG_TCP_PORT_80;
-    let vm = rbpf::EbpfVmRaw::new(prog);
-    assert_eq!(vm.prog_exec(mem), 0x0);
+    let vm = rbpf::EbpfVmRaw::new(prog).unwrap();
+    assert_eq!(vm.prog_exec(mem).unwrap(), 0x0);
 }
 
 const PROG_TCP_SACK: [u8;352] = [
@@ -1748,8 +1757,8 @@ fn test_vm_tcp_sack_match() {
         0xca, 0x28, 0xa3, 0xc4, 0xcf, 0xd0
     ];
     let prog = &PROG_TCP_SACK;
-    let vm = rbpf::EbpfVmRaw::new(prog);
-    assert_eq!(vm.prog_exec(mem), 0x1);
+    let vm = rbpf::EbpfVmRaw::new(prog).unwrap();
+    assert_eq!(vm.prog_exec(mem).unwrap(), 0x1);
 }
 
 #[test]
@@ -1766,6 +1775,6 @@ fn test_vm_tcp_sack_nomatch() {
         0x9e, 0x27
     ];
     let prog = &PROG_TCP_SACK;
-    let vm = rbpf::EbpfVmRaw::new(prog);
-    assert_eq!(vm.prog_exec(mem), 0x0);
+    let vm = rbpf::EbpfVmRaw::new(prog).unwrap();
+    assert_eq!(vm.prog_exec(mem).unwrap(), 0x0);
 }
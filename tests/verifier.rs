@@ -0,0 +1,1151 @@
+// Copyright 2026 agent <agent@local>
+//
+// Licensed under the Apache License, Version 2.0 <http://www.apache.org/licenses/LICENSE-2.0> or
+// the MIT license <http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+
+// These are tests for the stricter checks of the verifier, not used by default by the VMs. The
+// tests for `verifier::check()` are in `ubpf_verifier.rs` and `misc.rs`.
+
+extern crate rbpf;
+use std::time::{Duration, Instant};
+use rbpf::analysis::{Context, Log, MapDef, RegType};
+use rbpf::assembler::assemble;
+use rbpf::cfg::Cfg;
+use rbpf::ebpf;
+use rbpf::error::EbpfError;
+use rbpf::verifier::{self, Diagnostic, Severity};
+
+// Maps used by the programs analyzed in these tests.
+const MAPS: &[MapDef] = &[
+    MapDef { fd: 0, key_size: 4, value_size: 8 },
+    MapDef { fd: 1, key_size: 1, value_size: 8 },
+];
+
+#[test]
+fn test_cfg_blocks() {
+    let prog = assemble("
+        lddw r0, 0x1
+        jeq r1, 0x0, skip
+        call_local fn
+        tail_call
+    skip:
+        exit
+    fn:
+        mov64 r0, 0x2
+        exit").unwrap();
+    let cfg = Cfg::new(&prog);
+    let blocks = cfg.blocks().iter()
+        .map(|block| (block.start, block.end, block.last, block.succs.clone()))
+        .collect::<Vec<_>>();
+    assert_eq!(blocks, vec![
+        (0, 3, 2, vec![3, 1]),
+        (3, 4, 3, vec![4, 2]),
+        (4, 5, 4, vec![3]),
+        (5, 6, 5, vec![]),
+        (6, 8, 7, vec![]),
+    ]);
+    assert_eq!(cfg.functions(), &[0, 6]);
+    assert_eq!(cfg.block_at(5), Some(3));
+    assert_eq!(cfg.block_at(1), None);
+}
+
+#[test]
+fn test_verifier_cfg_ok() {
+    let prog = assemble("
+        mov64 r0, 0x0
+        jeq r1, 0x0, skip
+        mov64 r1, 0x1
+        call_local fn
+        call_local fn
+    skip:
+        exit
+    fn:
+        jgt r1, 0x2, big
+        add64 r0, r1
+        exit
+    big:
+        ja end
+    end:
+        exit").unwrap();
+    assert_eq!(verifier::check_cfg(&prog), Ok(()));
+}
+
+#[test]
+fn test_verifier_cfg_loop() {
+    let prog = assemble("
+        mov64 r0, 0x0
+    loop:
+        add64 r0, 0x1
+        jne r0, 0xa, loop
+        exit").unwrap();
+    assert_eq!(verifier::check(&prog), Ok(()));
+    let err = verifier::check_cfg(&prog).unwrap_err();
+    assert_eq!(err, EbpfError::Loop(1, 2));
+    assert_eq!(err.to_string(), "[Verifier] Error: loop, back-edge to #1 (insn #2)");
+
+    // Backward jumps are fine as long as they do not close a loop.
+    let prog = assemble("
+        jeq r1, 0x0, first
+    second:
+        exit
+    first:
+        ja second
+        exit").unwrap();
+    assert_eq!(verifier::check_cfg(&prog), Err(EbpfError::UnreachableCode(3)));
+}
+
+#[test]
+fn test_verifier_cfg_recursion() {
+    let prog = assemble("
+        mov64 r1, 0x3
+        call_local fn
+        exit
+    fn:
+        jeq r1, 0x0, done
+        add64 r1, -1
+        call_local fn
+    done:
+        exit").unwrap();
+    assert_eq!(verifier::check_cfg(&prog), Err(EbpfError::Loop(3, 5)));
+}
+
+#[test]
+fn test_verifier_cfg_unreachable() {
+    let prog = assemble("
+        mov64 r0, 0x0
+        ja end
+        mov64 r0, 0x1
+    end:
+        exit").unwrap();
+    let err = verifier::check_cfg(&prog).unwrap_err();
+    assert_eq!(err, EbpfError::UnreachableCode(2));
+    assert_eq!(err.to_string(), "[Verifier] Error: unreachable instruction (insn #2)");
+
+    // Code after the last exit of the main program is only reachable through local calls.
+    let prog = assemble("
+        mov64 r0, 0x0
+        exit
+        mov64 r0, 0x1
+        exit").unwrap();
+    assert_eq!(verifier::check_cfg(&prog), Err(EbpfError::UnreachableCode(2)));
+}
+
+#[test]
+fn test_verifier_cfg_fall_off_end() {
+    // The main program runs into the local function.
+    let prog = assemble("
+        call_local fn
+        mov64 r0, 0x0
+    fn:
+        mov64 r0, 0x1
+        exit").unwrap();
+    let err = verifier::check_cfg(&prog).unwrap_err();
+    assert_eq!(err, EbpfError::FallOffEnd(1));
+    assert_eq!(err.to_string(),
+               "[Verifier] Error: falling off the end of the function (insn #1)");
+
+    // A conditional jump right before the next function.
+    let prog = assemble("
+        call_local fn
+        jeq r1, 0x0, fn
+    fn:
+        exit").unwrap();
+    assert_eq!(verifier::check_cfg(&prog), Err(EbpfError::FallOffEnd(1)));
+
+    // The checks of `check()` come first.
+    assert_eq!(verifier::check_cfg(&assemble("mov64 r0, 0x0").unwrap()), Err(EbpfError::NoExit));
+}
+
+#[test]
+fn test_vm_set_verifier() {
+    let prog = assemble("
+        mov64 r0, 0x0
+        exit").unwrap();
+    let loop_prog = assemble("
+        mov64 r0, 0x0
+        add64 r0, 0x1
+        jne r0, 0xa, -2
+        exit").unwrap();
+
+    let mut vm = rbpf::EbpfVmNoData::new(&loop_prog).unwrap();
+    assert_eq!(vm.set_verifier(verifier::check_cfg), Err(EbpfError::Loop(1, 2)));
+    // The default verifier is kept.
+    vm.set_prog(&loop_prog).unwrap();
+    assert_eq!(vm.prog_exec().unwrap(), 10);
+
+    vm.set_prog(&prog).unwrap();
+    vm.set_verifier(verifier::check_cfg).unwrap();
+    assert_eq!(vm.set_prog(&loop_prog), Err(EbpfError::Loop(1, 2)));
+    // The previous program is kept.
+    assert_eq!(vm.prog_exec().unwrap(), 0);
+}
+
+#[test]
+fn test_vm_set_verifier_prog_array() {
+    // The target of the tail call passes the simple verifier, but contains a loop.
+    let prog = assemble("
+        mov64 r3, 0x0
+        tail_call
+        mov64 r0, 0x0
+        exit").unwrap();
+    let loop_prog = assemble("
+        mov64 r0, 0x0
+        add64 r0, 0x1
+        jne r0, 0xa, -2
+        exit").unwrap();
+    let mut prog_array = rbpf::ProgramArray::new(1);
+    prog_array.set(0, &loop_prog).unwrap();
+
+    let mut vm = rbpf::EbpfVmNoData::new(&prog).unwrap();
+    vm.set_verifier(verifier::check_cfg).unwrap();
+    assert_eq!(vm.set_prog_array(&prog_array), Err(EbpfError::Loop(1, 2)));
+    // The program array is not attached.
+    assert_eq!(vm.prog_exec().unwrap(), 0);
+
+    let mut vm = rbpf::EbpfVmNoData::new(&prog).unwrap();
+    vm.set_prog_array(&prog_array).unwrap();
+    assert_eq!(vm.prog_exec().unwrap(), 10);
+    assert_eq!(vm.set_verifier(verifier::check_cfg), Err(EbpfError::Loop(1, 2)));
+}
+
+#[test]
+fn test_verifier_types_ok() {
+    // Spill and fill pointers, pass a pointer to the stack to a local function, and look up a map.
+    let prog = assemble("
+        stxdw [r10-0x8], r1
+        ldxdw r6, [r10-0x8]
+        ldxb r7, [r6+0x1]
+        mov64 r1, r10
+        add64 r1, -16
+        call_local fn
+        stxb [r10-0x10], r7
+        lddw r1, map_fd(1)
+        mov64 r2, r10
+        add64 r2, -16
+        call 0x1
+        mov64 r6, r0
+        mov64 r0, 0x0
+        jne r6, 0x0, found
+        exit
+    found:
+        ldxdw r0, [r6+0x0]
+        exit
+    fn:
+        stdw [r1+0x0], 0x0
+        mov64 r0, 0x0
+        exit").unwrap();
+    assert_eq!(verifier::check_types(&prog, Context::Memory, MAPS), Ok(()));
+    // The value spilled from r1 is a number without data.
+    assert_eq!(verifier::check_types(&prog, Context::NoData, MAPS),
+               Err(EbpfError::InvalidMemoryAccess(6, 2)));
+}
+
+#[test]
+fn test_verifier_types_uninitialized() {
+    let prog = assemble("
+        mov64 r0, r2
+        exit").unwrap();
+    let err = verifier::check_types(&prog, Context::Memory, MAPS).unwrap_err();
+    assert_eq!(err, EbpfError::UninitializedRegister(2, 0));
+    assert_eq!(err.to_string(),
+               "[Verifier] Error: read of uninitialized register r2 (insn #0)");
+
+    // r0 must be set before exiting, on all paths.
+    let prog = assemble("
+        jeq r1, 0x0, skip
+        mov64 r0, 0x1
+    skip:
+        exit").unwrap();
+    assert_eq!(verifier::check_types(&prog, Context::NoData, MAPS),
+               Err(EbpfError::UninitializedRegister(0, 2)));
+
+    // Helpers clobber r1 to r5.
+    let prog = assemble("
+        mov64 r6, r1
+        call 0x5
+        mov64 r0, r1
+        exit").unwrap();
+    assert_eq!(verifier::check_types(&prog, Context::Memory, MAPS),
+               Err(EbpfError::UninitializedRegister(1, 2)));
+
+    // Local functions do not get r6 to r9 from their caller, but get them back on return.
+    let prog = assemble("
+        mov64 r6, 0x1
+        call_local fn
+        mov64 r0, r6
+        exit
+    fn:
+        mov64 r0, r6
+        exit").unwrap();
+    assert_eq!(verifier::check_types(&prog, Context::Memory, MAPS),
+               Err(EbpfError::UninitializedRegister(6, 4)));
+}
+
+#[test]
+fn test_verifier_types_pointer_arithmetic() {
+    let check = |src: &str| verifier::check_types(&assemble(src).unwrap(), Context::Memory, MAPS);
+
+    // Moving pointers, and measuring the distance between two of them, are fine.
+    assert_eq!(check("
+        mov64 r2, r1
+        add64 r2, 0x8
+        mov64 r0, 0x4
+        add64 r0, r2
+        sub64 r0, r1
+        exit"), Ok(()));
+
+    let err = check("
+        mul64 r1, 0x2
+        mov64 r0, 0x0
+        exit").unwrap_err();
+    assert_eq!(err, EbpfError::PointerArithmetic(0));
+    assert_eq!(err.to_string(), "[Verifier] Error: invalid arithmetic on pointer (insn #0)");
+
+    // Adding two pointers, or subtracting a pointer from a number.
+    assert_eq!(check("
+        mov64 r0, r1
+        add64 r0, r10
+        exit"), Err(EbpfError::PointerArithmetic(1)));
+    assert_eq!(check("
+        mov64 r0, 0x10
+        sub64 r0, r1
+        exit"), Err(EbpfError::PointerArithmetic(1)));
+    // Pointers into different regions.
+    assert_eq!(check("
+        mov64 r0, r10
+        sub64 r0, r1
+        exit"), Err(EbpfError::PointerArithmetic(1)));
+    // 32-bit operations truncate pointers.
+    assert_eq!(check("
+        mov32 r0, r1
+        exit"), Err(EbpfError::PointerArithmetic(0)));
+    assert_eq!(check("
+        add32 r1, 0x1
+        mov64 r0, 0x0
+        exit"), Err(EbpfError::PointerArithmetic(0)));
+    // Map handles cannot be moved.
+    assert_eq!(check("
+        lddw r0, map_fd(1)
+        add64 r0, 0x8
+        exit"), Err(EbpfError::PointerArithmetic(2)));
+}
+
+#[test]
+fn test_verifier_types_invalid_access() {
+    let check = |src: &str| verifier::check_types(&assemble(src).unwrap(), Context::Memory, MAPS);
+
+    let err = check("
+        mov64 r1, 0x1000
+        ldxb r0, [r1+0x0]
+        exit").unwrap_err();
+    assert_eq!(err, EbpfError::InvalidMemoryAccess(1, 1));
+    assert_eq!(err.to_string(),
+               "[Verifier] Error: invalid memory access through r1 (insn #1)");
+
+    // The result of a map lookup must be compared to 0 first.
+    assert_eq!(check("
+        stw [r10-0x4], 0x3
+        lddw r1, map_fd(0)
+        mov64 r2, r10
+        add64 r2, -4
+        call 0x1
+        stw [r0+0x0], 0x1
+        exit"), Err(EbpfError::InvalidMemoryAccess(0, 6)));
+    assert_eq!(check("
+        mov64 r0, 0x0
+        lddw r1, map_fd(0)
+        stxdw [r1+0x0], r0
+        exit"), Err(EbpfError::InvalidMemoryAccess(1, 3)));
+
+    // Pointers to the stack need a known offset.
+    assert_eq!(check("
+        ldxb r2, [r1+0x0]
+        mov64 r3, r10
+        add64 r3, r2
+        stb [r3-0x8], 0x0
+        mov64 r0, 0x0
+        exit"), Err(EbpfError::InvalidMemoryAccess(3, 3)));
+
+    // Numbers read from the stack are not pointers, but spilled pointers remain pointers.
+    assert_eq!(check("
+        stdw [r10-0x8], 0x0
+        stxw [r10-0x8], r1
+        ldxdw r2, [r10-0x8]
+        ldxb r0, [r2+0x0]
+        exit"), Err(EbpfError::InvalidMemoryAccess(2, 3)));
+}
+
+// Look up the element with key 3 in map 0, then run `access` with the pointer to the value in r0.
+fn map_value_prog(access: &str) -> Vec<u8> {
+    assemble(&format!("
+        stw [r10-0x4], 0x3
+        lddw r1, map_fd(0)
+        mov64 r2, r10
+        add64 r2, -4
+        call 0x1
+        jeq r0, 0x0, out
+        {}
+    out:
+        exit", access)).unwrap()
+}
+
+#[test]
+fn test_verifier_types_map_value() {
+    let check = |access: &str| {
+        verifier::check_types(&map_value_prog(access), Context::Memory, MAPS)
+    };
+
+    // The 8 bytes of the value can be accessed, at known offsets.
+    assert_eq!(check("
+        ldxw r1, [r0+0x4]
+        add64 r0, 0x6
+        sth [r0+0x0], 0x1"), Ok(()));
+
+    let err = check("ldxdw r1, [r0+0x4]").unwrap_err();
+    assert_eq!(err, EbpfError::OutOfBoundsAccess(0, 7));
+    assert_eq!(err.to_string(),
+               "[Verifier] Error: memory access through r0 may be out of bounds (insn #7)");
+    assert_eq!(check("stb [r0+0x8], 0x1"), Err(EbpfError::OutOfBoundsAccess(0, 7)));
+    assert_eq!(check("
+        add64 r0, -1
+        ldxb r1, [r0+0x0]"), Err(EbpfError::OutOfBoundsAccess(0, 8)));
+
+    // Pointers to map values cannot be moved by unknown amounts.
+    assert_eq!(check("
+        ldxb r1, [r0+0x0]
+        add64 r0, r1
+        ldxdw r0, [r0+0x0]"), Err(EbpfError::PointerArithmetic(8)));
+
+    // Maps must be known to the analysis.
+    let prog = map_value_prog("ldxb r1, [r0+0x0]");
+    let err = verifier::check_types(&prog, Context::Memory, &[]).unwrap_err();
+    assert_eq!(err, EbpfError::UnknownMap(0, 1));
+    assert_eq!(err.to_string(), "[Verifier] Error: unknown map (fd: 0) (insn #1)");
+
+    let mut log = Log::default();
+    verifier::check_types_with_log(&prog, Context::Memory, MAPS, &mut log).unwrap();
+    assert_eq!(log.entries[5].regs[0], RegType::MapValueOrNull(0, 8, 1));
+    assert_eq!(log.entries[6].regs[0], RegType::MapValue(0, 8, Some(0)));
+    assert!(log.to_string().contains("r0=map_value(fd=0,size=8)+0"));
+}
+
+#[test]
+fn test_verifier_types_map_helper_args() {
+    let check = |src: &str| verifier::check_types(&assemble(src).unwrap(), Context::NoData, MAPS);
+
+    // Update the element with key 3 in map 0, then delete it.
+    assert_eq!(check("
+        stw [r10-0x4], 0x3
+        stdw [r10-0x10], 0x1
+        lddw r1, map_fd(0)
+        mov64 r2, r10
+        add64 r2, -4
+        mov64 r3, r10
+        add64 r3, -16
+        mov64 r4, 0x0
+        call 0x2
+        lddw r1, map_fd(0)
+        mov64 r2, r10
+        add64 r2, -4
+        call 0x3
+        exit"), Ok(()));
+
+    // r1 must hold a map handle.
+    let err = check("
+        mov64 r1, 0x1000
+        mov64 r2, r10
+        call 0x1
+        exit").unwrap_err();
+    assert_eq!(err, EbpfError::InvalidHelperArgument(1, 2));
+    assert_eq!(err.to_string(),
+               "[Verifier] Error: invalid argument r1 for helper function (insn #2)");
+
+    // The key must be on the stack, within its bounds, and initialized.
+    assert_eq!(check("
+        lddw r1, map_fd(0)
+        mov64 r2, 0x1000
+        call 0x1
+        exit"), Err(EbpfError::InvalidHelperArgument(2, 3)));
+    assert_eq!(check("
+        stb [r10-0x1], 0x3
+        lddw r1, map_fd(0)
+        mov64 r2, r10
+        add64 r2, -1
+        call 0x3
+        exit"), Err(EbpfError::OutOfBoundsAccess(2, 5)));
+    assert_eq!(check("
+        sth [r10-0x4], 0x3
+        lddw r1, map_fd(0)
+        mov64 r2, r10
+        add64 r2, -4
+        call 0x1
+        exit"), Err(EbpfError::UninitializedStackRead(-4, 5)));
+
+    // The value of an update must be on the stack and initialized, and the flags a number.
+    assert_eq!(check("
+        stb [r10-0x1], 0x3
+        lddw r1, map_fd(1)
+        mov64 r2, r10
+        add64 r2, -1
+        mov64 r3, r10
+        add64 r3, -16
+        mov64 r4, 0x0
+        call 0x2
+        exit"), Err(EbpfError::UninitializedStackRead(-16, 8)));
+    assert_eq!(check("
+        stdw [r10-0x8], 0x3
+        lddw r1, map_fd(1)
+        mov64 r2, r10
+        add64 r2, -1
+        mov64 r3, r10
+        add64 r3, -8
+        mov64 r4, r10
+        call 0x2
+        exit"), Err(EbpfError::InvalidHelperArgument(4, 8)));
+    assert_eq!(check("
+        stdw [r10-0x8], 0x3
+        lddw r1, map_fd(1)
+        mov64 r2, r10
+        add64 r2, -1
+        mov64 r3, 0x0
+        mov64 r4, 0x0
+        call 0x2
+        exit"), Err(EbpfError::InvalidHelperArgument(3, 7)));
+
+    // Without maps, helpers 1 to 3 are regular helpers.
+    assert_eq!(verifier::check_types(&assemble("
+        mov64 r1, 0x1000
+        call 0x1
+        exit").unwrap(), Context::NoData, &[]), Ok(()));
+}
+
+#[test]
+fn test_verifier_types_helper_stack() {
+    let check = |src: &str| verifier::check_types(&assemble(src).unwrap(), Context::Memory, &[]);
+
+    // A helper such as `memfrob` may write anything into the stack it gets a pointer to, so the
+    // number read back cannot be trusted to compute a known offset.
+    let prog = "
+        stdw [r10-0x8], 0x0
+        mov64 r1, r10
+        add64 r1, -8
+        mov64 r2, 0x8
+        {}
+        ldxdw r2, [r10-0x8]
+        mov64 r3, r10
+        add64 r3, -16
+        add64 r3, r2
+        stdw [r3+0x0], 0x0
+        mov64 r0, 0x0
+        exit";
+    assert_eq!(check(&prog.replace("{}", "mov64 r0, 0x0")), Ok(()));
+    assert_eq!(check(&prog.replace("{}", "call 0x6")),
+               Err(EbpfError::InvalidMemoryAccess(3, 9)));
+
+    // Pointers spilled to that stack are numbers after the call.
+    assert_eq!(check("
+        stxdw [r10-0x8], r1
+        mov64 r1, r10
+        add64 r1, -16
+        mov64 r2, 0x8
+        call 0x6
+        ldxdw r2, [r10-0x8]
+        ldxb r0, [r2+0x0]
+        exit"), Err(EbpfError::InvalidMemoryAccess(2, 6)));
+    assert_eq!(check("
+        stxdw [r10-0x8], r1
+        mov64 r1, 0x0
+        call 0x6
+        ldxdw r2, [r10-0x8]
+        ldxb r0, [r2+0x0]
+        exit"), Ok(()));
+}
+
+#[test]
+fn test_verifier_types_packet() {
+    let prog = assemble("
+        ldxdw r2, [r1+0x40]
+        ldxdw r3, [r1+0x50]
+        mov64 r0, 0x0
+        mov64 r4, r2
+        add64 r4, 0x1
+        jgt r4, r3, out
+        ldxb r0, [r2+0x0]
+    out:
+        exit").unwrap();
+    assert_eq!(verifier::check_types(&prog, Context::FixedMbuff(0x40, 0x50), MAPS), Ok(()));
+    // Without the layout of the metadata buffer, r2 is a number.
+    assert_eq!(verifier::check_types(&prog, Context::Memory, MAPS),
+               Err(EbpfError::InvalidMemoryAccess(2, 6)));
+
+    // The end of packet data cannot be dereferenced.
+    let prog = assemble("
+        ldxdw r3, [r1+0x50]
+        ldxb r0, [r3-0x1]
+        exit").unwrap();
+    assert_eq!(verifier::check_types(&prog, Context::FixedMbuff(0x40, 0x50), MAPS),
+               Err(EbpfError::InvalidMemoryAccess(3, 1)));
+}
+
+#[test]
+fn test_verifier_packet_bounds() {
+    let check = |src: &str| {
+        verifier::check_types(&assemble(src).unwrap(), Context::FixedMbuff(0x40, 0x50), MAPS)
+    };
+
+    // The access is not checked at all.
+    let err = check("
+        ldxdw r2, [r1+0x40]
+        ldxb r0, [r2+0x0]
+        exit").unwrap_err();
+    assert_eq!(err, EbpfError::OutOfBoundsAccess(2, 1));
+    assert_eq!(err.to_string(),
+               "[Verifier] Error: memory access through r2 may be out of bounds (insn #1)");
+
+    // Only 14 bytes are checked, the access reads bytes 12 to 15.
+    assert_eq!(check("
+        ldxdw r2, [r1+0x40]
+        ldxdw r3, [r1+0x50]
+        mov64 r0, 0x0
+        mov64 r4, r2
+        add64 r4, 0xe
+        jgt r4, r3, out
+        ldxw r0, [r2+0xc]
+    out:
+        exit"), Err(EbpfError::OutOfBoundsAccess(2, 6)));
+
+    // The access is on the wrong side of the check.
+    assert_eq!(check("
+        ldxdw r2, [r1+0x40]
+        ldxdw r3, [r1+0x50]
+        mov64 r0, 0x0
+        mov64 r4, r2
+        add64 r4, 0x8
+        jgt r4, r3, out
+        exit
+    out:
+        ldxb r0, [r2+0x0]
+        exit"), Err(EbpfError::OutOfBoundsAccess(2, 7)));
+
+    // The checks hold on the paths they dominate, with the end of packet data on either side, and
+    // accesses can go through pointers moved within the checked range. With `data + 32 < data_end`,
+    // 33 bytes are in the packet.
+    assert_eq!(check("
+        ldxdw r2, [r1+0x40]
+        ldxdw r3, [r1+0x50]
+        mov64 r0, 0x0
+        mov64 r4, r2
+        add64 r4, 0xe
+        jge r3, r4, ok
+        exit
+    ok:
+        add64 r2, 0xc
+        ldxh r0, [r2+0x0]
+        mov64 r4, r2
+        add64 r4, 0x14
+        jlt r4, r3, more
+        exit
+    more:
+        ldxdw r5, [r2+0xd]
+        exit"), Ok(()));
+    assert_eq!(check("
+        ldxdw r2, [r1+0x40]
+        ldxdw r3, [r1+0x50]
+        mov64 r0, 0x0
+        mov64 r4, r2
+        add64 r4, 0xe
+        jge r3, r4, ok
+        exit
+    ok:
+        add64 r2, 0xc
+        ldxh r0, [r2+0x0]
+        mov64 r4, r2
+        add64 r4, 0x14
+        jlt r4, r3, more
+        exit
+    more:
+        ldxdw r5, [r2+0xe]
+        exit"), Err(EbpfError::OutOfBoundsAccess(2, 13)));
+
+    // Pointers with an unknown offset cannot be checked.
+    assert_eq!(check("
+        ldxdw r2, [r1+0x40]
+        ldxdw r3, [r1+0x50]
+        mov64 r0, 0x0
+        mov64 r4, r2
+        add64 r4, 0x10
+        jgt r4, r3, out
+        ldxb r5, [r2+0x0]
+        add64 r2, r5
+        ldxb r0, [r2+0x0]
+    out:
+        exit"), Err(EbpfError::OutOfBoundsAccess(2, 8)));
+
+    // The metadata buffer is 0x58 bytes long, and its pointers to packet data are read-only.
+    assert_eq!(check("
+        ldxdw r0, [r1+0x51]
+        exit"), Err(EbpfError::OutOfBoundsAccess(1, 0)));
+    assert_eq!(check("
+        stdw [r1+0x44], 0x0
+        ldxdw r2, [r1+0x40]
+        mov64 r0, 0x0
+        exit"), Err(EbpfError::InvalidMemoryAccess(1, 0)));
+    assert_eq!(check("
+        stdw [r1+0x48], 0x0
+        ldxdw r0, [r1+0x0]
+        exit"), Ok(()));
+}
+
+#[test]
+fn test_vm_fixed_mbuff_packet_bounds() {
+    // The program of the documentation of `EbpfVmFixedMbuff` checks that `data + 5 <= data_end`,
+    // but reads the sixth byte of the packet.
+    let prog = assemble("
+        mov64 r0, 0x0
+        ldxdw r2, [r1+0x40]
+        add64 r2, 0x5
+        ldxdw r1, [r1+0x50]
+        jgt r2, r1, 0x1
+        ldxb r0, [r2+0x0]
+        exit").unwrap();
+    let fixed_prog = assemble("
+        mov64 r0, 0x0
+        ldxdw r2, [r1+0x40]
+        add64 r2, 0x6
+        ldxdw r1, [r1+0x50]
+        jgt r2, r1, 0x1
+        ldxb r0, [r2-0x1]
+        exit").unwrap();
+    // `prog_exec()` borrows the packet for the lifetime of the VM.
+    let short1 = &mut [0xaa, 0xbb, 0x11, 0x22, 0xcc];
+    let short2 = &mut [0xaa, 0xbb, 0x11, 0x22, 0xcc];
+    let long = &mut [0xaa, 0xbb, 0x11, 0x22, 0xcc, 0x27];
+    let verifier = |prog: &[u8]| verifier::check_types(prog, Context::FixedMbuff(0x40, 0x50), &[]);
+
+    let mut vm = rbpf::EbpfVmFixedMbuff::new(&prog, 0x40, 0x50).unwrap();
+    assert_eq!(vm.set_verifier(verifier), Err(EbpfError::OutOfBoundsAccess(2, 5)));
+    let err = vm.prog_exec(short1).unwrap_err();
+    assert_eq!(err.kind, rbpf::error::ExecErrorKind::OutOfBoundsLoad);
+
+    vm.set_prog(&fixed_prog, 0x40, 0x50).unwrap();
+    vm.set_verifier(verifier).unwrap();
+    assert_eq!(vm.prog_exec(short2).unwrap(), 0);
+    assert_eq!(vm.prog_exec(long).unwrap(), 0x27);
+}
+
+#[test]
+fn test_verifier_types_too_complex() {
+    // Each of the 17 branches adds a different power of 2 to one of r2 to r9 on one path only, so
+    // that all the paths reach the end with different states: 2^17 of them.
+    let mut src = (2..10).map(|reg| format!("mov64 r{}, 0x0\n", reg)).collect::<String>();
+    for bit in 0..17 {
+        src.push_str(&format!("jeq r1, 0x0, 0x1\nadd64 r{}, {:#x}\n", 2 + bit % 8, 1 << bit));
+    }
+    src.push_str("mov64 r0, 0x0\nexit");
+    let prog = assemble(&src).unwrap();
+    assert_eq!(prog.len() / ebpf::INSN_SIZE, 44);
+
+    // Only a few states are stored for each block, and each lookup is cheap, so that the analysis
+    // takes time proportional to the number of processed instructions.
+    let start = Instant::now();
+    assert_eq!(verifier::check_types(&prog, Context::NoData, &[]), Ok(()));
+    assert!(start.elapsed() < Duration::from_secs(60), "analysis took {:?}", start.elapsed());
+}
+
+#[test]
+fn test_vm_set_verifier_types() {
+    let prog = assemble("
+        ldxb r0, [r1+0x1]
+        exit").unwrap();
+    let mem = &mut [0x11, 0x22];
+
+    let mut vm = rbpf::EbpfVmRaw::new(&prog).unwrap();
+    vm.set_verifier(|prog| verifier::check_types(prog, Context::Memory, &[])).unwrap();
+    assert_eq!(vm.prog_exec(mem).unwrap(), 0x22);
+
+    let mut vm = rbpf::EbpfVmNoData::new(&prog).unwrap();
+    assert_eq!(vm.set_verifier(|prog| verifier::check_types(prog, Context::NoData, &[])),
+               Err(EbpfError::InvalidMemoryAccess(1, 0)));
+}
+
+#[test]
+fn test_vm_set_verifier_types_prog_array() {
+    // The target of the tail call passes the simple verifier, but reads packet data through r1 in
+    // a VM without packet data.
+    let prog = assemble("
+        mov64 r3, 0x0
+        tail_call
+        mov64 r0, 0x0
+        exit").unwrap();
+    let target = assemble("
+        ldxb r0, [r1+0x0]
+        exit").unwrap();
+    let mut prog_array = rbpf::ProgramArray::new(1);
+    prog_array.set(0, &target).unwrap();
+    let verifier: verifier::Verifier = |prog| verifier::check_types(prog, Context::NoData, &[]);
+
+    let mut vm = rbpf::EbpfVmNoData::new(&prog).unwrap();
+    vm.set_verifier(verifier).unwrap();
+    assert_eq!(vm.set_prog_array(&prog_array), Err(EbpfError::InvalidMemoryAccess(1, 0)));
+    // The program array is not attached.
+    assert_eq!(vm.prog_exec().unwrap(), 0);
+
+    let mut vm = rbpf::EbpfVmNoData::new(&prog).unwrap();
+    vm.set_prog_array(&prog_array).unwrap();
+    assert_eq!(vm.set_verifier(verifier), Err(EbpfError::InvalidMemoryAccess(1, 0)));
+}
+
+#[test]
+fn test_verifier_log() {
+    let prog = assemble("
+        ldxdw r2, [r1+0x40]
+        ldxdw r3, [r1+0x50]
+        mov64 r0, 0x0
+        stxdw [r10-0x8], r2
+        mov64 r4, r2
+        add64 r4, 0xe
+        jgt r4, r3, out
+        ldxb r0, [r2+0xd]
+        exit
+    out:
+        ldxb r0, [r2+0x0]
+        exit").unwrap();
+    let mut log = Log::default();
+    let err = verifier::check_types_with_log(&prog, Context::FixedMbuff(0x40, 0x50), &[], &mut log)
+        .unwrap_err();
+    assert_eq!(err, EbpfError::OutOfBoundsAccess(2, 9));
+    assert_eq!(log.error, Some(err));
+    assert_eq!(log.error_path(), vec![0, 1, 2, 3, 4, 5, 6, 9]);
+
+    // The path where the check succeeds is processed first.
+    let insns = log.entries.iter().map(|entry| entry.insn_ptr).collect::<Vec<_>>();
+    assert_eq!(insns, vec![0, 1, 2, 3, 4, 5, 6, 7, 8, 9]);
+    assert_eq!(log.entries[9].parent, Some(6));
+    let entry = &log.entries[7];
+    assert_eq!(entry.desc, "ldxb r0, [r2+0xd]");
+    assert_eq!(entry.regs[2], RegType::Packet(Some(0)));
+    assert_eq!(entry.regs[4], RegType::Packet(Some(14)));
+    assert_eq!(entry.regs[5], RegType::Uninit);
+    assert_eq!(entry.stack, vec![(-8, RegType::Packet(Some(0)))]);
+    assert_eq!(entry.packet_range, 14);
+    assert_eq!(log.entries[9].packet_range, 0);
+
+    let text = log.to_string();
+    let lines = text.lines().collect::<Vec<_>>();
+    assert_eq!(lines[0], "0: ldxdw r2, [r1+0x40]");
+    assert_eq!(lines[1], "    r1=ctx+0 r10=fp0+0");
+    assert_eq!(lines[15], "    r0=0x0 r1=ctx+0 r2=pkt+0 r3=pkt_end r4=pkt+14 r10=fp0+0 \
+                           fp0-8=pkt+0 pkt_range=14");
+    assert_eq!(&lines[18..], &[
+        "from 6 to 9:",
+        "9: ldxb r0, [r2+0x0]",
+        "    r0=0x0 r1=ctx+0 r2=pkt+0 r3=pkt_end r4=pkt+14 r10=fp0+0 fp0-8=pkt+0",
+        "[Verifier] Error: memory access through r2 may be out of bounds (insn #9)",
+        "path: 0 -> 1 -> 2 -> 3 -> 4 -> 5 -> 6 -> 9",
+        "processed 10 insns",
+    ]);
+}
+
+#[test]
+fn test_verifier_log_local_call() {
+    let prog = assemble("
+        mov64 r6, 0x2
+        call_local fn
+        add64 r0, r6
+        exit
+    fn:
+        mov64 r0, 0x1
+        exit").unwrap();
+    let mut log = Log::default();
+    verifier::check_types_with_log(&prog, Context::NoData, &[], &mut log).unwrap();
+    assert_eq!(log.error, None);
+    assert!(log.error_path().is_empty());
+    let frames = log.entries.iter().map(|entry| (entry.insn_ptr, entry.frame)).collect::<Vec<_>>();
+    assert_eq!(frames, vec![(0, 0), (1, 0), (4, 1), (5, 1), (2, 0), (3, 0)]);
+    assert_eq!(log.entries[2].regs[6], RegType::Uninit);
+    assert_eq!(log.entries[2].regs[10], RegType::Stack(1, Some(0)));
+    assert_eq!(log.entries[4].regs[6], RegType::Scalar(Some(2)));
+    assert!(log.to_string().ends_with("\nprocessed 6 insns\n"));
+
+    // The log is cleared, and has no entries when the program is rejected before the analysis.
+    let prog = assemble("
+        mov64 r0, 0x0
+    loop:
+        add64 r0, 0x1
+        jne r0, 0xa, loop
+        exit").unwrap();
+    assert_eq!(verifier::check_types_with_log(&prog, Context::NoData, &[], &mut log),
+               Err(EbpfError::Loop(1, 2)));
+    assert!(log.entries.is_empty());
+    assert_eq!(log.to_string(), "[Verifier] Error: loop, back-edge to #1 (insn #2)\n\
+                                 processed 0 insns\n");
+}
+
+#[test]
+fn test_verifier_diagnose() {
+    let prog = &[
+        0xb7, 0x0b, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // mov r11, 0
+        0x8f, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // (unknown opcode)
+        0xd4, 0x01, 0x00, 0x00, 0x07, 0x00, 0x00, 0x00, // le16 r1 (imm is 7)
+        0x15, 0x01, 0xf0, 0xff, 0x00, 0x00, 0x00, 0x00, // jeq r1, 0, -16
+        0x18, 0x0a, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, // lddw r10, 1 (allowed by check())
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0xbf, 0xa0, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // mov r0, r10
+        0x18, 0x0c, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, // lddw r12, 1 (truncated)
+    ];
+    let errors = [
+        EbpfError::NoExit,
+        EbpfError::InvalidDstRegister(0),
+        EbpfError::UnknownOpcode(0x8f, 1),
+        EbpfError::UnsupportedEndianSize(2),
+        EbpfError::JumpOutOfCode(-12, 3),
+        EbpfError::IncompleteLdDw(7),
+        EbpfError::InvalidDstRegister(7),
+    ];
+    let diags = verifier::diagnose(prog);
+    assert_eq!(diags, errors.iter().map(|err| Diagnostic {
+        insn_ptr: err.insn_ptr(),
+        severity: Severity::Error,
+        message:  err.to_string(),
+    }).collect::<Vec<_>>());
+    assert_eq!(verifier::check(prog), Err(EbpfError::NoExit));
+
+    // A valid program has no diagnostics.
+    let prog = assemble("
+        lddw r1, 0x100000000
+        mov64 r0, r1
+        exit").unwrap();
+    assert_eq!(verifier::diagnose(&prog), vec![]);
+}
+
+#[test]
+fn test_verifier_diagnose_warnings() {
+    let prog = &[
+        0x07, 0x21, 0x03, 0x00, 0x01, 0x00, 0x00, 0x00, // add r1, 1 (src is 2, off is 3)
+        0x87, 0x01, 0x00, 0x00, 0x02, 0x00, 0x00, 0x00, // neg r1 (imm is 2)
+        0x05, 0x00, 0x00, 0x00, 0x04, 0x00, 0x00, 0x00, // ja +0 (imm is 4)
+        0x95, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00  // exit (dst is 1)
+    ];
+    let diags = verifier::diagnose(prog);
+    let warnings = diags.iter().map(|diag| {
+        assert_eq!(diag.severity, Severity::Warning);
+        (diag.insn_ptr.unwrap(), diag.message.as_str())
+    }).collect::<Vec<_>>();
+    assert_eq!(warnings, vec![
+        (0, "[Verifier] Warning: unused field src is not zero (insn #0)"),
+        (0, "[Verifier] Warning: unused field off is not zero (insn #0)"),
+        (1, "[Verifier] Warning: unused field imm is not zero (insn #1)"),
+        (2, "[Verifier] Warning: unused field imm is not zero (insn #2)"),
+        (3, "[Verifier] Warning: unused field dst is not zero (insn #3)"),
+    ]);
+    // Warnings do not make the program invalid.
+    assert_eq!(verifier::check(prog), Ok(()));
+
+    // Errors and warnings are sorted by instruction.
+    let prog = &[
+        0x37, 0x00, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, // div r0, 0 (off is 1)
+        0x95, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00  // exit
+    ];
+    let severities = verifier::diagnose(prog).iter().map(|diag| (diag.insn_ptr, diag.severity))
+        .collect::<Vec<_>>();
+    assert_eq!(severities, vec![(Some(0), Severity::Error), (Some(0), Severity::Warning)]);
+}
+
+#[test]
+fn test_verifier_render_diagnostics() {
+    let prog = &[
+        0x18, 0x01, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, // lddw r1, 1
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0xb7, 0x0b, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // mov r11, 0
+        0x18, 0x01, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, // lddw r1, 1 (truncated)
+        0x95, 0x00, 0x00                                // (truncated)
+    ];
+    let diags = verifier::diagnose(prog);
+    assert_eq!(verifier::render_diagnostics(prog, &diags), "\
+[Verifier] Error: eBPF program length must be a multiple of 8 octets
+[Verifier] Error: program does not end with “EXIT” instruction
+    0  lddw r1, 0x1
+    2  mov64 r11, 0x0
+       ^ [Verifier] Error: invalid destination register (insn #2)
+    3  opcode 0x18
+       ^ [Verifier] Error: incomplete LD_DW instruction (insn #3)
+");
+    // Without diagnostics, this is the disassembled program.
+    assert_eq!(verifier::render_diagnostics(prog, &[]),
+               "    0  lddw r1, 0x1\n    2  mov64 r11, 0x0\n    3  opcode 0x18\n");
+}
+
+#[test]
+fn test_verifier_stack_bounds() {
+    let check = |src: &str| verifier::check_types(&assemble(src).unwrap(), Context::NoData, &[]);
+
+    assert_eq!(check("
+        stdw [r10-0x200], 0x1
+        ldxdw r0, [r10-0x200]
+        exit"), Ok(()));
+    assert_eq!(check("
+        stdw [r10-0x208], 0x1
+        mov64 r0, 0x0
+        exit"), Err(EbpfError::OutOfBoundsAccess(10, 0)));
+    // The access must end below r10.
+    assert_eq!(check("
+        stw [r10-0x4], 0x1
+        stw [r10-0x2], 0x1
+        mov64 r0, 0x0
+        exit"), Err(EbpfError::OutOfBoundsAccess(10, 1)));
+    assert_eq!(check("
+        mov64 r2, r10
+        add64 r2, 0x8
+        ldxb r0, [r2-0x8]
+        exit"), Err(EbpfError::OutOfBoundsAccess(2, 2)));
+    assert_eq!(check("
+        mov64 r2, r10
+        add64 r2, -504
+        stxdw [r2-0x10], r1
+        mov64 r0, 0x0
+        exit"), Err(EbpfError::OutOfBoundsAccess(2, 2)));
+
+    // A pointer to the stack of the caller gives access to its stack, not to the one of the
+    // function.
+    assert_eq!(check("
+        mov64 r1, r10
+        add64 r1, -8
+        call_local fn
+        exit
+    fn:
+        stdw [r1-0x200], 0x0
+        mov64 r0, 0x0
+        exit"), Err(EbpfError::OutOfBoundsAccess(1, 4)));
+
+    // The interpreter rejects such programs at run time only.
+    let prog = assemble("
+        stdw [r10+0x0], 0x1
+        mov64 r0, 0x0
+        exit").unwrap();
+    assert_eq!(verifier::check(&prog), Ok(()));
+    assert_eq!(verifier::check_types(&prog, Context::NoData, &[]),
+               Err(EbpfError::OutOfBoundsAccess(10, 0)));
+    let vm = rbpf::EbpfVmNoData::new(&prog).unwrap();
+    assert!(vm.prog_exec().is_err());
+}
+
+#[test]
+fn test_verifier_stack_uninitialized() {
+    let check = |src: &str| verifier::check_types(&assemble(src).unwrap(), Context::NoData, &[]);
+
+    let err = check("
+        ldxdw r0, [r10-0x8]
+        exit").unwrap_err();
+    assert_eq!(err, EbpfError::UninitializedStackRead(-8, 0));
+    assert_eq!(err.to_string(),
+               "[Verifier] Error: read of uninitialized stack at r10-8 (insn #0)");
+
+    // Each byte must have been written.
+    assert_eq!(check("
+        stw [r10-0x8], 0x1
+        ldxw r0, [r10-0x8]
+        exit"), Ok(()));
+    assert_eq!(check("
+        stw [r10-0x8], 0x1
+        ldxdw r0, [r10-0x8]
+        exit"), Err(EbpfError::UninitializedStackRead(-8, 1)));
+    assert_eq!(check("
+        sth [r10-0x4], 0x1
+        ldxw r0, [r10-0x6]
+        exit"), Err(EbpfError::UninitializedStackRead(-6, 1)));
+    assert_eq!(check("
+        mov64 r0, 0x1
+        stxw [r10-0x8], r0
+        stxw [r10-0x4], r0
+        ldxdw r0, [r10-0x8]
+        exit"), Ok(()));
+
+    // On all paths.
+    assert_eq!(check("
+        jeq r1, 0x0, skip
+        stdw [r10-0x8], 0x1
+    skip:
+        ldxdw r0, [r10-0x8]
+        exit"), Err(EbpfError::UninitializedStackRead(-8, 2)));
+
+    // Atomic operations read the stack too.
+    assert_eq!(check("
+        mov64 r0, 0x1
+        stxxaddw [r10-0x8], r0
+        exit"), Err(EbpfError::UninitializedStackRead(-8, 1)));
+
+    // Each function has its own stack.
+    assert_eq!(check("
+        stdw [r10-0x8], 0x1
+        call_local fn
+        exit
+    fn:
+        ldxdw r0, [r10-0x8]
+        exit"), Err(EbpfError::UninitializedStackRead(-8, 3)));
+}
+
+#[test]
+fn test_verifier_stack_depth() {
+    let depth = |src: &str| verifier::stack_depth(&assemble(src).unwrap(), Context::NoData, &[]);
+
+    assert_eq!(depth("
+        mov64 r0, 0x0
+        exit"), Ok(0));
+    assert_eq!(depth("
+        stb [r10-0x3], 0x1
+        ldxb r0, [r10-0x3]
+        exit"), Ok(3));
+    // The deepest access on any path counts.
+    assert_eq!(depth("
+        mov64 r0, 0x0
+        jeq r1, 0x0, skip
+        stxdw [r10-0x40], r0
+    skip:
+        stxdw [r10-0x8], r0
+        exit"), Ok(64));
+
+    // The stacks of nested functions add up, but not the ones of functions called one after the
+    // other.
+    let prog = "
+        stdw [r10-0x10], 0x0
+        call_local f
+        call_local g
+        exit
+    f:
+        stdw [r10-0x20], 0x0
+        call_local h
+        exit
+    g:
+        stdw [r10-0x100], 0x0
+        mov64 r0, 0x0
+        exit
+    h:
+        stdw [r10-0x8], 0x0
+        mov64 r0, 0x0
+        exit";
+    assert_eq!(depth(prog), Ok(16 + 256));
+    assert_eq!(depth(&prog.replace("[r10-0x100]", "[r10-0x10]")), Ok(16 + 32 + 8));
+
+    // Accesses through a pointer to the stack of the caller count for the caller.
+    assert_eq!(depth("
+        mov64 r1, r10
+        add64 r1, -8
+        call_local fn
+        exit
+    fn:
+        stdw [r1-0x18], 0x0
+        mov64 r0, 0x0
+        exit"), Ok(32));
+
+    // The log shows the depth of accepted programs.
+    let prog = assemble("
+        stdw [r10-0x18], 0x0
+        mov64 r0, 0x0
+        exit").unwrap();
+    let mut log = Log::default();
+    verifier::check_types_with_log(&prog, Context::NoData, &[], &mut log).unwrap();
+    assert_eq!(log.stack_depth, 24);
+    assert!(log.to_string().ends_with("\nprocessed 3 insns\nstack depth 24\n"));
+}